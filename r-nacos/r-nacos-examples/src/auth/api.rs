@@ -0,0 +1,367 @@
+use crate::auth::model::{AuthError, UserSession};
+use crate::auth::service::{AuthService, UserInfoParam};
+use crate::common::constant::{ACCESS_TOKEN_HEADER, AUTHORIZATION_HEADER};
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpRequest, HttpResponse, web};
+use serde::{Deserialize, Serialize};
+use std::future::{Ready, ready};
+use std::sync::Arc;
+
+///
+/// 依次从 accessToken 头、accessToken 查询参数、Authorization: Bearer 头中取 token
+fn request_token(req: &HttpRequest) -> Option<String> {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.is_empty())
+    };
+    if let Some(v) = header(ACCESS_TOKEN_HEADER) {
+        return Some(v.to_owned());
+    }
+    let query = req.query_string();
+    if let Some((_, v)) = form_urlencoded::parse(query.as_bytes())
+        .find(|(k, v)| k == ACCESS_TOKEN_HEADER && !v.is_empty())
+    {
+        return Some(v.into_owned());
+    }
+    header(AUTHORIZATION_HEADER)
+        .map(|v| v.strip_prefix("Bearer ").unwrap_or(v).to_owned())
+        .filter(|v| !v.is_empty())
+}
+
+///
+/// 登录限流使用的客户端 ip，只取连接的对端地址(不含端口);
+/// X-Forwarded-For 等请求头可由客户端任意伪造，不能用于限流
+fn request_ip(req: &HttpRequest) -> String {
+    req.peer_addr()
+        .map(|v| v.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+fn forbidden(err: impl std::fmt::Display) -> actix_web::Error {
+    actix_web::error::ErrorForbidden(err.to_string())
+}
+
+///
+/// openapi 的调用者; 未注入 AuthService 或未开启 openapi 认证时为 None，不做校验
+pub struct AuthUser(pub Option<Arc<UserSession>>);
+
+impl AuthUser {
+    pub fn check(&self, namespace: &str, write: bool) -> Result<(), AuthError> {
+        match &self.0 {
+            Some(session) => session.check(namespace, write),
+            None => Ok(()),
+        }
+    }
+
+    pub fn username(&self) -> Option<&str> {
+        self.0.as_ref().map(|v| v.username.as_str())
+    }
+}
+
+impl FromRequest for AuthUser {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(service) = req.app_data::<web::Data<AuthService>>() else {
+            return ready(Ok(Self(None)));
+        };
+        if !service.openapi_enable_auth() {
+            return ready(Ok(Self(None)));
+        }
+        ready(
+            request_token(req)
+                .and_then(|token| service.get_session(&token))
+                .map(|v| Self(Some(v)))
+                .ok_or_else(|| forbidden(AuthError::InvalidToken)),
+        )
+    }
+}
+
+///
+/// 控制台的登录用户，总是需要 token
+pub struct ConsoleUser {
+    pub token: String,
+    pub session: Arc<UserSession>,
+}
+
+impl FromRequest for ConsoleUser {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let result = req
+            .app_data::<web::Data<AuthService>>()
+            .zip(request_token(req))
+            .and_then(|(service, token)| {
+                let session = service.get_session(&token)?;
+                Some(Self { token, session })
+            })
+            .ok_or_else(|| {
+                actix_web::error::ErrorUnauthorized(AuthError::InvalidToken.to_string())
+            });
+        ready(result)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginParams {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsernameParams {
+    pub username: String,
+}
+
+///
+/// 控制台接口的返回结构
+#[derive(Debug, Serialize)]
+pub struct ConsoleResult<T> {
+    pub success: bool,
+    pub message: Option<String>,
+    pub data: Option<T>,
+}
+
+impl<T: Serialize> ConsoleResult<T> {
+    fn success(data: T) -> HttpResponse {
+        HttpResponse::Ok().json(Self {
+            success: true,
+            message: None,
+            data: Some(data),
+        })
+    }
+}
+
+fn console_error(err: anyhow::Error) -> HttpResponse {
+    let mut res = match err.downcast_ref::<AuthError>() {
+        Some(AuthError::NoPermission) => HttpResponse::Forbidden(),
+        Some(_) => HttpResponse::Unauthorized(),
+        None => HttpResponse::BadRequest(),
+    };
+    res.json(ConsoleResult::<()> {
+        success: false,
+        message: Some(err.to_string()),
+        data: None,
+    })
+}
+
+fn check_manager(user: &ConsoleUser) -> anyhow::Result<()> {
+    if !user.session.is_manager() {
+        return Err(AuthError::NoPermission.into());
+    }
+    Ok(())
+}
+
+///
+/// nacos 客户端登录
+async fn openapi_login(
+    req: HttpRequest,
+    service: web::Data<AuthService>,
+    params: web::Form<LoginParams>,
+) -> HttpResponse {
+    match service.openapi_login(&params.username, &params.password, &request_ip(&req)) {
+        Ok((token, session)) => HttpResponse::Ok().json(serde_json::json!({
+            "accessToken": token,
+            "tokenTtl": service.openapi_login_timeout(),
+            "globalAdmin": session.is_manager(),
+        })),
+        Err(e) => HttpResponse::Forbidden().body(e.to_string()),
+    }
+}
+
+async fn console_login(
+    req: HttpRequest,
+    service: web::Data<AuthService>,
+    params: web::Form<LoginParams>,
+) -> HttpResponse {
+    match service.console_login(&params.username, &params.password, &request_ip(&req)) {
+        Ok((token, session)) => ConsoleResult::success(serde_json::json!({
+            "token": token,
+            "username": session.username,
+            "roles": session.roles,
+        })),
+        Err(e) => console_error(e),
+    }
+}
+
+async fn console_logout(service: web::Data<AuthService>, user: ConsoleUser) -> HttpResponse {
+    service.logout(&user.token);
+    ConsoleResult::success(true)
+}
+
+async fn list_users(service: web::Data<AuthService>, user: ConsoleUser) -> HttpResponse {
+    match check_manager(&user).and_then(|_| service.list_users()) {
+        Ok(v) => ConsoleResult::success(v),
+        Err(e) => console_error(e),
+    }
+}
+
+async fn add_user(
+    service: web::Data<AuthService>,
+    user: ConsoleUser,
+    params: web::Json<UserInfoParam>,
+) -> HttpResponse {
+    match check_manager(&user).and_then(|_| service.add_user(&params)) {
+        Ok(_) => ConsoleResult::success(true),
+        Err(e) => console_error(e),
+    }
+}
+
+async fn update_user(
+    service: web::Data<AuthService>,
+    user: ConsoleUser,
+    params: web::Json<UserInfoParam>,
+) -> HttpResponse {
+    match check_manager(&user).and_then(|_| service.update_user(&params)) {
+        Ok(_) => ConsoleResult::success(true),
+        Err(e) => console_error(e),
+    }
+}
+
+async fn remove_user(
+    service: web::Data<AuthService>,
+    user: ConsoleUser,
+    params: web::Json<UsernameParams>,
+) -> HttpResponse {
+    match check_manager(&user).and_then(|_| service.remove_user(&params.username)) {
+        Ok(_) => ConsoleResult::success(true),
+        Err(e) => console_error(e),
+    }
+}
+
+///
+/// 注册登录与用户管理接口，需要先通过 app_data 注入 `web::Data<AuthService>`
+pub fn app_config(config: &mut web::ServiceConfig) {
+    config
+        .route("/nacos/v1/auth/login", web::post().to(openapi_login))
+        .route("/nacos/v1/auth/users/login", web::post().to(openapi_login))
+        .route("/rnacos/api/console/login", web::post().to(console_login))
+        .route("/rnacos/api/console/logout", web::post().to(console_logout))
+        .route("/rnacos/api/console/user/list", web::get().to(list_users))
+        .route("/rnacos/api/console/user/add", web::post().to(add_user))
+        .route(
+            "/rnacos/api/console/user/update",
+            web::post().to(update_user),
+        )
+        .route(
+            "/rnacos/api/console/user/remove",
+            web::post().to(remove_user),
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::service::AuthOption;
+    use crate::transfer::data_to_sqlite::open_init_db;
+    use actix_web::http::StatusCode;
+    use actix_web::{App, test};
+
+    #[actix_rt::test]
+    async fn test_request_ip() {
+        let req = test::TestRequest::default()
+            .peer_addr("10.0.0.1:52000".parse().unwrap())
+            .insert_header(("x-forwarded-for", "1.2.3.4"))
+            .insert_header(("forwarded", "for=1.2.3.5"))
+            .to_http_request();
+        assert_eq!(request_ip(&req), "10.0.0.1");
+        assert_eq!(
+            request_ip(&test::TestRequest::default().to_http_request()),
+            "unknown"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_auth_api() -> anyhow::Result<()> {
+        let db = std::env::temp_dir().join(format!("auth_api_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db);
+        let service = web::Data::new(AuthService::new(
+            open_init_db(&db.to_string_lossy()).await?,
+            AuthOption {
+                openapi_enable_auth: true,
+                console_login_timeout: 3600,
+                console_login_one_hour_limit: 5,
+                openapi_login_timeout: 3600,
+                openapi_login_one_minute_limit: 100,
+                init_admin_username: "admin".to_owned(),
+                init_admin_password: "admin123".to_owned(),
+            },
+        )?);
+        let app =
+            test::init_service(App::new().app_data(service.clone()).configure(app_config)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/rnacos/api/console/login")
+            .set_form([("username", "admin"), ("password", "admin123")])
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let token = res["data"]["token"].as_str().unwrap().to_owned();
+
+        let req = test::TestRequest::post()
+            .uri("/rnacos/api/console/user/add")
+            .insert_header((AUTHORIZATION_HEADER, format!("Bearer {}", token)))
+            .set_json(serde_json::json!({
+                "username": "guest",
+                "password": "guest123",
+                "roles": ["2"],
+            }))
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["success"], true);
+
+        let req = test::TestRequest::post()
+            .uri("/nacos/v1/auth/login")
+            .set_form([("username", "guest"), ("password", "guest123")])
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["globalAdmin"], false);
+        let guest_token = res["accessToken"].as_str().unwrap().to_owned();
+
+        // 非管理员不能管理用户
+        let req = test::TestRequest::get()
+            .uri("/rnacos/api/console/user/list")
+            .insert_header((ACCESS_TOKEN_HEADER, guest_token.as_str()))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::FORBIDDEN
+        );
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/rnacos/api/console/user/list?accessToken={}",
+                token
+            ))
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["data"].as_array().unwrap().len(), 2);
+
+        let req = test::TestRequest::post()
+            .uri("/nacos/v1/auth/login")
+            .set_form([("username", "guest"), ("password", "wrong")])
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::FORBIDDEN
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/rnacos/api/console/logout")
+            .insert_header((ACCESS_TOKEN_HEADER, token.as_str()))
+            .to_request();
+        test::call_service(&app, req).await;
+        let req = test::TestRequest::get()
+            .uri("/rnacos/api/console/user/list")
+            .insert_header((ACCESS_TOKEN_HEADER, token.as_str()))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+        std::fs::remove_file(&db)?;
+        Ok(())
+    }
+}
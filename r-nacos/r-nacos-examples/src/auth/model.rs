@@ -0,0 +1,156 @@
+use crate::common::constant::{USER_ROLE_DEVELOPER, USER_ROLE_MANAGER};
+use crate::transfer::sqlite::dao::user::UserDO;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+///
+/// namespace_privilege_flags 的标志位，未开启时不限制命名空间
+pub const PRIVILEGE_ENABLE: u32 = 0b0001;
+pub const PRIVILEGE_WHITE_LIST_ALL: u32 = 0b0010;
+pub const PRIVILEGE_BLACK_LIST_ALL: u32 = 0b0100;
+
+#[derive(Debug)]
+pub enum AuthError {
+    InvalidToken,
+    LoginFailed,
+    LoginLimited,
+    UserDisabled,
+    NoPermission,
+    NoNamespacePrivilege(String),
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidToken => write!(f, "invalid or expired token"),
+            Self::LoginFailed => write!(f, "username or password is incorrect"),
+            Self::LoginLimited => write!(f, "too many login attempts, try again later"),
+            Self::UserDisabled => write!(f, "user is disabled"),
+            Self::NoPermission => write!(f, "no permission"),
+            Self::NoNamespacePrivilege(ns) => {
+                write!(f, "no privilege for namespace '{}'", ns)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+///
+/// 用户可以访问的命名空间: 黑名单优先于白名单
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NamespacePrivilege {
+    pub flags: u32,
+    pub white_list: HashSet<String>,
+    pub black_list: HashSet<String>,
+}
+
+impl NamespacePrivilege {
+    pub fn check(&self, namespace: &str) -> bool {
+        if self.flags & PRIVILEGE_ENABLE == 0 {
+            return true;
+        }
+        // public 命名空间的 id 为空字符串
+        let namespace = if namespace == "public" { "" } else { namespace };
+        if self.flags & PRIVILEGE_BLACK_LIST_ALL != 0 || self.black_list.contains(namespace) {
+            return false;
+        }
+        self.flags & PRIVILEGE_WHITE_LIST_ALL != 0 || self.white_list.contains(namespace)
+    }
+}
+
+///
+/// 登录后的会话，token 失效前缓存用户的角色与命名空间权限
+#[derive(Debug, Clone)]
+pub struct UserSession {
+    pub username: String,
+    pub roles: Vec<String>,
+    pub privilege: NamespacePrivilege,
+    pub expire_at: i64,
+}
+
+impl UserSession {
+    pub fn from_user(user: &UserDO, expire_at: i64) -> Self {
+        let json_list = |v: &Option<String>| -> HashSet<String> {
+            v.as_deref()
+                .and_then(|s| serde_json::from_str(s).ok())
+                .unwrap_or_default()
+        };
+        Self {
+            username: user.username.clone().unwrap_or_default(),
+            roles: user
+                .roles
+                .as_deref()
+                .and_then(|s| serde_json::from_str(s).ok())
+                .unwrap_or_default(),
+            privilege: NamespacePrivilege {
+                flags: user.namespace_privilege_flags.unwrap_or_default() as u32,
+                white_list: json_list(&user.namespace_white_list),
+                black_list: json_list(&user.namespace_black_list),
+            },
+            expire_at,
+        }
+    }
+
+    pub fn is_manager(&self) -> bool {
+        self.roles.iter().any(|v| v == USER_ROLE_MANAGER)
+    }
+
+    ///
+    /// 管理员不受命名空间限制; 写操作需要开发者以上角色，访客只读
+    pub fn check(&self, namespace: &str, write: bool) -> Result<(), AuthError> {
+        if self.is_manager() {
+            return Ok(());
+        }
+        if write && !self.roles.iter().any(|v| v == USER_ROLE_DEVELOPER) {
+            return Err(AuthError::NoPermission);
+        }
+        if !self.privilege.check(namespace) {
+            return Err(AuthError::NoNamespacePrivilege(namespace.to_owned()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::constant::USER_ROLE_VISITOR;
+
+    #[test]
+    fn test_namespace_privilege() {
+        let session = |roles: &[&str], privilege: NamespacePrivilege| UserSession {
+            username: "u".to_owned(),
+            roles: roles.iter().map(|v| v.to_string()).collect(),
+            privilege,
+            expire_at: 0,
+        };
+        let privilege = NamespacePrivilege {
+            flags: PRIVILEGE_ENABLE,
+            white_list: HashSet::from(["dev".to_owned(), "".to_owned()]),
+            black_list: HashSet::from(["prod".to_owned()]),
+        };
+        let dev = session(&[USER_ROLE_DEVELOPER], privilege.clone());
+        assert!(dev.check("dev", true).is_ok());
+        assert!(dev.check("public", false).is_ok());
+        assert!(dev.check("test", false).is_err());
+        assert!(dev.check("prod", false).is_err());
+
+        let all_but_prod = NamespacePrivilege {
+            flags: PRIVILEGE_ENABLE | PRIVILEGE_WHITE_LIST_ALL,
+            ..privilege.clone()
+        };
+        assert!(all_but_prod.check("test"));
+        assert!(!all_but_prod.check("prod"));
+
+        let visitor = session(&[USER_ROLE_VISITOR], NamespacePrivilege::default());
+        assert!(visitor.check("prod", false).is_ok());
+        assert!(matches!(
+            visitor.check("prod", true),
+            Err(AuthError::NoPermission)
+        ));
+        let manager = session(&[USER_ROLE_MANAGER], privilege);
+        assert!(manager.check("prod", true).is_ok());
+    }
+}
@@ -0,0 +1,491 @@
+use crate::auth::model::{AuthError, NamespacePrivilege, UserSession};
+use crate::common::constant::USER_ROLE_MANAGER;
+use crate::common::{AppSysConfig, now_millis};
+use crate::transfer::sqlite::dao::user::{UserDO, UserDao, UserParam};
+use rusqlite::Connection;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+// 测试中使用最低的 cost，避免哈希耗时过长
+const HASH_COST: u32 = if cfg!(test) { 4 } else { bcrypt::DEFAULT_COST };
+const MAX_LIMIT_RECORDS: usize = 10000;
+
+#[derive(Debug, Clone)]
+pub struct AuthOption {
+    // 是否校验 openapi(配置、服务发现接口) 的 token
+    pub openapi_enable_auth: bool,
+    // 单位为秒
+    pub console_login_timeout: i32,
+    pub console_login_one_hour_limit: u32,
+    pub openapi_login_timeout: i32,
+    pub openapi_login_one_minute_limit: u32,
+    pub init_admin_username: String,
+    pub init_admin_password: String,
+}
+
+impl From<&AppSysConfig> for AuthOption {
+    fn from(v: &AppSysConfig) -> Self {
+        Self {
+            openapi_enable_auth: v.openapi_enable_auth,
+            console_login_timeout: v.console_login_timeout,
+            console_login_one_hour_limit: v.console_login_one_hour_limit,
+            openapi_login_timeout: v.openapi_login_timeout,
+            openapi_login_one_minute_limit: v.openapi_login_one_minute_limit,
+            init_admin_username: v.init_admin_username.clone(),
+            init_admin_password: v.init_admin_password.clone(),
+        }
+    }
+}
+
+///
+/// 固定窗口的登录次数限制
+struct LoginLimiter {
+    window: i64,
+    limit: u32,
+    // key -> (窗口开始时间, 次数)
+    records: HashMap<String, (i64, u32)>,
+}
+
+impl LoginLimiter {
+    fn new(window: i64, limit: u32) -> Self {
+        Self {
+            window,
+            limit,
+            records: HashMap::new(),
+        }
+    }
+
+    fn is_limited(&self, key: &str, now: i64) -> bool {
+        match self.records.get(key) {
+            Some((start, count)) => now - start < self.window && *count >= self.limit,
+            None => false,
+        }
+    }
+
+    fn record(&mut self, key: &str, now: i64) {
+        if self.records.len() >= MAX_LIMIT_RECORDS {
+            let window = self.window;
+            self.records.retain(|_, (start, _)| now - *start < window);
+        }
+        let (start, count) = self.records.entry(key.to_owned()).or_insert((now, 0));
+        if now - *start >= self.window {
+            *start = now;
+            *count = 0;
+        }
+        *count += 1;
+    }
+
+    fn clear(&mut self, key: &str) {
+        self.records.remove(key);
+    }
+}
+
+///
+/// 新增或更新用户的参数，更新时为 None 的字段保持不变
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserInfoParam {
+    pub username: String,
+    pub password: Option<String>,
+    pub nickname: Option<String>,
+    pub roles: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+    pub namespace_privilege: Option<NamespacePrivilege>,
+}
+
+impl UserInfoParam {
+    fn to_do(&self) -> anyhow::Result<UserDO> {
+        let password_hash = match &self.password {
+            Some(v) => Some(bcrypt::hash(v, HASH_COST)?),
+            None => None,
+        };
+        let privilege = self.namespace_privilege.as_ref();
+        Ok(UserDO {
+            nickname: self.nickname.clone(),
+            password_hash,
+            gmt_modified: Some(now_millis() / 1000),
+            enabled: self.enabled.map(|v| v.to_string()),
+            roles: self.roles.as_ref().map(serde_json::to_string).transpose()?,
+            namespace_privilege_flags: privilege.map(|v| v.flags as i64),
+            namespace_white_list: privilege
+                .map(|v| serde_json::to_string(&v.white_list))
+                .transpose()?,
+            namespace_black_list: privilege
+                .map(|v| serde_json::to_string(&v.black_list))
+                .transpose()?,
+            ..Default::default()
+        })
+    }
+}
+
+///
+/// 用户认证服务: 用户存于 sqlite 的 tb_user，密码用 bcrypt 哈希;
+/// 登录后发放随机 token，token 与会话只保存在内存中
+pub struct AuthService {
+    conn: Mutex<Connection>,
+    option: AuthOption,
+    sessions: Mutex<HashMap<String, Arc<UserSession>>>,
+    // 控制台按用户与 ip 限制每小时失败次数
+    console_limiter: Mutex<LoginLimiter>,
+    // openapi 按用户与 ip 限制每分钟登录次数
+    openapi_limiter: Mutex<LoginLimiter>,
+}
+
+impl AuthService {
+    ///
+    /// 没有任何用户时按配置创建初始管理员
+    pub fn new(conn: Connection, option: AuthOption) -> anyhow::Result<Self> {
+        let service = Self {
+            conn: Mutex::new(conn),
+            console_limiter: Mutex::new(LoginLimiter::new(
+                3600 * 1000,
+                option.console_login_one_hour_limit,
+            )),
+            openapi_limiter: Mutex::new(LoginLimiter::new(
+                60 * 1000,
+                option.openapi_login_one_minute_limit,
+            )),
+            option,
+            sessions: Mutex::new(HashMap::new()),
+        };
+        service.init_admin()?;
+        Ok(service)
+    }
+
+    fn init_admin(&self) -> anyhow::Result<()> {
+        let count = {
+            let conn = self.conn.lock().unwrap();
+            UserDao::new(&conn).query_count(&UserParam::default())?
+        };
+        if count == 0 {
+            log::info!("init admin user: {}", self.option.init_admin_username);
+            self.add_user(&UserInfoParam {
+                username: self.option.init_admin_username.clone(),
+                password: Some(self.option.init_admin_password.clone()),
+                nickname: Some(self.option.init_admin_username.clone()),
+                roles: Some(vec![USER_ROLE_MANAGER.to_owned()]),
+                enabled: Some(true),
+                namespace_privilege: None,
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn openapi_enable_auth(&self) -> bool {
+        self.option.openapi_enable_auth
+    }
+
+    pub fn openapi_login_timeout(&self) -> i32 {
+        self.option.openapi_login_timeout
+    }
+
+    fn get_user(&self, username: &str) -> anyhow::Result<Option<UserDO>> {
+        let conn = self.conn.lock().unwrap();
+        let list = UserDao::new(&conn).query(&UserParam {
+            username: Some(username.to_owned()),
+            ..Default::default()
+        })?;
+        Ok(list.into_iter().next())
+    }
+
+    ///
+    /// 校验用户名密码并创建会话，timeout 单位为秒
+    fn login(
+        &self,
+        username: &str,
+        password: &str,
+        timeout: i32,
+    ) -> anyhow::Result<(String, Arc<UserSession>)> {
+        let Some(user) = self.get_user(username)? else {
+            return Err(AuthError::LoginFailed.into());
+        };
+        let verified = match &user.password_hash {
+            Some(hash) => bcrypt::verify(password, hash).unwrap_or(false),
+            None => false,
+        };
+        if !verified {
+            return Err(AuthError::LoginFailed.into());
+        }
+        if user.enabled.as_deref() != Some("true") {
+            return Err(AuthError::UserDisabled.into());
+        }
+        let now = now_millis();
+        let session = Arc::new(UserSession::from_user(&user, now + timeout as i64 * 1000));
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, v| v.expire_at > now);
+        sessions.insert(token.clone(), session.clone());
+        Ok((token, session))
+    }
+
+    pub fn console_login(
+        &self,
+        username: &str,
+        password: &str,
+        ip: &str,
+    ) -> anyhow::Result<(String, Arc<UserSession>)> {
+        let user_key = format!("user:{}", username);
+        let ip_key = format!("ip:{}", ip);
+        let now = now_millis();
+        {
+            let limiter = self.console_limiter.lock().unwrap();
+            if limiter.is_limited(&user_key, now) || limiter.is_limited(&ip_key, now) {
+                return Err(AuthError::LoginLimited.into());
+            }
+        }
+        let result = self.login(username, password, self.option.console_login_timeout);
+        let mut limiter = self.console_limiter.lock().unwrap();
+        match &result {
+            Ok(_) => limiter.clear(&user_key),
+            Err(_) => {
+                limiter.record(&user_key, now);
+                limiter.record(&ip_key, now);
+            }
+        }
+        result
+    }
+
+    pub fn openapi_login(
+        &self,
+        username: &str,
+        password: &str,
+        ip: &str,
+    ) -> anyhow::Result<(String, Arc<UserSession>)> {
+        {
+            let user_key = format!("user:{}", username);
+            let ip_key = format!("ip:{}", ip);
+            let now = now_millis();
+            let mut limiter = self.openapi_limiter.lock().unwrap();
+            if limiter.is_limited(&user_key, now) || limiter.is_limited(&ip_key, now) {
+                return Err(AuthError::LoginLimited.into());
+            }
+            limiter.record(&user_key, now);
+            limiter.record(&ip_key, now);
+        }
+        self.login(username, password, self.option.openapi_login_timeout)
+    }
+
+    ///
+    /// 查询未过期的会话
+    pub fn get_session(&self, token: &str) -> Option<Arc<UserSession>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get(token)?;
+        if session.expire_at > now_millis() {
+            return Some(session.clone());
+        }
+        sessions.remove(token);
+        None
+    }
+
+    pub fn logout(&self, token: &str) {
+        self.sessions.lock().unwrap().remove(token);
+    }
+
+    ///
+    /// 用户信息变化后移除其会话，需要重新登录
+    fn remove_user_sessions(&self, username: &str) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|_, v| v.username != username);
+    }
+
+    ///
+    /// 用户表被直接改写(如从备份恢复)后移除这些用户的会话
+    pub fn remove_sessions(&self, usernames: &HashSet<String>) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|_, v| !usernames.contains(&v.username));
+    }
+
+    ///
+    /// 用户列表，不返回密码哈希
+    pub fn list_users(&self) -> anyhow::Result<Vec<UserDO>> {
+        let conn = self.conn.lock().unwrap();
+        let list = UserDao::new(&conn).query(&UserParam::default())?;
+        Ok(list
+            .into_iter()
+            .map(|v| UserDO {
+                password_hash: None,
+                ..v
+            })
+            .collect())
+    }
+
+    pub fn add_user(&self, param: &UserInfoParam) -> anyhow::Result<()> {
+        if param.username.is_empty() || param.password.as_deref().unwrap_or_default().is_empty() {
+            return Err(anyhow::anyhow!("username and password are required"));
+        }
+        if self.get_user(&param.username)?.is_some() {
+            return Err(anyhow::anyhow!("user '{}' already exists", param.username));
+        }
+        let mut record = param.to_do()?;
+        record.username = Some(param.username.clone());
+        record.gmt_create = record.gmt_modified;
+        record.enabled.get_or_insert_with(|| true.to_string());
+        record.roles.get_or_insert_with(|| "[]".to_owned());
+        let conn = self.conn.lock().unwrap();
+        UserDao::new(&conn).insert(&record)?;
+        Ok(())
+    }
+
+    pub fn update_user(&self, param: &UserInfoParam) -> anyhow::Result<()> {
+        let record = param.to_do()?;
+        {
+            let conn = self.conn.lock().unwrap();
+            let count = UserDao::new(&conn).update(
+                &record,
+                &UserParam {
+                    username: Some(param.username.clone()),
+                    ..Default::default()
+                },
+            )?;
+            if count == 0 {
+                return Err(anyhow::anyhow!("user '{}' does not exist", param.username));
+            }
+        }
+        self.remove_user_sessions(&param.username);
+        Ok(())
+    }
+
+    pub fn remove_user(&self, username: &str) -> anyhow::Result<()> {
+        {
+            let conn = self.conn.lock().unwrap();
+            UserDao::new(&conn).delete(&UserParam {
+                username: Some(username.to_owned()),
+                ..Default::default()
+            })?;
+        }
+        self.remove_user_sessions(username);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::model::PRIVILEGE_ENABLE;
+    use crate::common::constant::USER_ROLE_DEVELOPER;
+    use crate::transfer::data_to_sqlite::open_init_db;
+    use std::collections::HashSet;
+
+    fn test_option() -> AuthOption {
+        AuthOption {
+            openapi_enable_auth: true,
+            console_login_timeout: 3600,
+            console_login_one_hour_limit: 3,
+            openapi_login_timeout: 3600,
+            openapi_login_one_minute_limit: 100,
+            init_admin_username: "admin".to_owned(),
+            init_admin_password: "admin123".to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auth_service() -> anyhow::Result<()> {
+        let db = std::env::temp_dir().join(format!("auth_service_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db);
+        let service = AuthService::new(open_init_db(&db.to_string_lossy()).await?, test_option())?;
+
+        let (token, session) = service.console_login("admin", "admin123", "127.0.0.1")?;
+        assert!(session.is_manager());
+        assert_eq!(service.get_session(&token).unwrap().username, "admin");
+        let hash = service.get_user("admin")?.unwrap().password_hash.unwrap();
+        assert!(hash.starts_with("$2"));
+        assert!(service.list_users()?[0].password_hash.is_none());
+
+        service.add_user(&UserInfoParam {
+            username: "dev".to_owned(),
+            password: Some("dev123".to_owned()),
+            roles: Some(vec![USER_ROLE_DEVELOPER.to_owned()]),
+            namespace_privilege: Some(NamespacePrivilege {
+                flags: PRIVILEGE_ENABLE,
+                white_list: HashSet::from(["dev".to_owned()]),
+                black_list: HashSet::new(),
+            }),
+            ..Default::default()
+        })?;
+        let (token, session) = service.openapi_login("dev", "dev123", "10.0.0.1")?;
+        assert!(session.check("dev", true).is_ok());
+        assert!(session.check("prod", false).is_err());
+
+        // 失败次数达到上限后，正确的密码也会被拒绝
+        for _ in 0..3 {
+            assert!(service.console_login("dev", "wrong", "10.0.0.2").is_err());
+        }
+        let err = service
+            .console_login("dev", "dev123", "10.0.0.3")
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err.downcast_ref::<AuthError>(),
+            Some(AuthError::LoginLimited)
+        ));
+
+        // 禁用用户后会话失效且无法登录
+        service.update_user(&UserInfoParam {
+            username: "dev".to_owned(),
+            enabled: Some(false),
+            ..Default::default()
+        })?;
+        assert!(service.get_session(&token).is_none());
+        let err = service
+            .openapi_login("dev", "dev123", "10.0.0.1")
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err.downcast_ref::<AuthError>(),
+            Some(AuthError::UserDisabled)
+        ));
+        service.remove_user("dev")?;
+        assert!(service.get_user("dev")?.is_none());
+
+        // 已有用户时不再创建初始管理员
+        drop(service);
+        let service = AuthService::new(
+            open_init_db(&db.to_string_lossy()).await?,
+            AuthOption {
+                init_admin_username: "root".to_owned(),
+                ..test_option()
+            },
+        )?;
+        assert!(service.get_user("root")?.is_none());
+
+        // openapi 按用户限制，换 ip 也不能继续尝试
+        let service = AuthService::new(
+            open_init_db(&db.to_string_lossy()).await?,
+            AuthOption {
+                openapi_login_one_minute_limit: 2,
+                ..test_option()
+            },
+        )?;
+        assert!(service.openapi_login("admin", "wrong", "10.0.0.1").is_err());
+        assert!(service.openapi_login("admin", "wrong", "10.0.0.2").is_err());
+        let err = service
+            .openapi_login("admin", "admin123", "10.0.0.3")
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err.downcast_ref::<AuthError>(),
+            Some(AuthError::LoginLimited)
+        ));
+        std::fs::remove_file(&db)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_login_limiter() {
+        let mut limiter = LoginLimiter::new(1000, 2);
+        limiter.record("a", 0);
+        assert!(!limiter.is_limited("a", 10));
+        limiter.record("a", 10);
+        assert!(limiter.is_limited("a", 20));
+        assert!(!limiter.is_limited("b", 20));
+        // 窗口过后重新计数
+        assert!(!limiter.is_limited("a", 1000));
+        limiter.record("a", 1000);
+        assert!(!limiter.is_limited("a", 1001));
+    }
+}
@@ -0,0 +1,432 @@
+use crate::auth::service::AuthService;
+use crate::backup::model::{BACKUP_TOKEN_HEADER, RestoreParam};
+use crate::backup::service::BackupService;
+use crate::raft::node::RaftNode;
+use actix_web::{HttpRequest, HttpResponse, web};
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+pub const BACKUP_API_PREFIX: &str = "/rnacos/backup";
+
+// 恢复时上传整个传输文件
+const RESTORE_PAYLOAD_LIMIT: usize = 256 * 1024 * 1024;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+fn check_request(req: &HttpRequest, service: &BackupService) -> Option<HttpResponse> {
+    if !service.enabled() {
+        return Some(
+            HttpResponse::Forbidden().body("backup api is disabled, backup_token is not set"),
+        );
+    }
+    let token = req
+        .headers()
+        .get(BACKUP_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !service.check_token(token) {
+        return Some(HttpResponse::Forbidden().body("invalid backup token"));
+    }
+    None
+}
+
+fn temp_data_file() -> String {
+    std::env::temp_dir()
+        .join(format!(
+            "rnacos_backup_{}.data",
+            uuid::Uuid::new_v4().simple()
+        ))
+        .to_string_lossy()
+        .to_string()
+}
+
+///
+/// 按块读出文件，读完后删除
+fn file_stream(path: String) -> ReceiverStream<std::io::Result<web::Bytes>> {
+    let (tx, rx) = mpsc::channel(4);
+    actix_web::rt::spawn(async move {
+        if let Err(e) = send_file(&path, &tx).await {
+            let _ = tx.send(Err(e)).await;
+        }
+        let _ = tokio::fs::remove_file(&path).await;
+    });
+    ReceiverStream::new(rx)
+}
+
+async fn send_file(
+    path: &str,
+    tx: &mpsc::Sender<std::io::Result<web::Bytes>>,
+) -> std::io::Result<()> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let len = file.read(&mut buf).await?;
+        // 客户端断开时不再继续读
+        if len == 0
+            || tx
+                .send(Ok(web::Bytes::copy_from_slice(&buf[..len])))
+                .await
+                .is_err()
+        {
+            return Ok(());
+        }
+    }
+}
+
+///
+/// 导出全量备份，先写到临时文件再流式返回
+async fn export(req: HttpRequest, service: web::Data<BackupService>) -> HttpResponse {
+    if let Some(res) = check_request(&req, &service) {
+        return res;
+    }
+    let data_file = temp_data_file();
+    let result = {
+        let data_file = data_file.clone();
+        web::block(move || service.backup(&data_file))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|v| v)
+    };
+    match result {
+        Ok(count) => {
+            log::info!(
+                "backup exported, config count:{}, tenant count:{}, user count:{}",
+                count.config,
+                count.tenant,
+                count.user
+            );
+            let file_name = format!("rnacos_backup_{}.data", crate::common::now_millis());
+            HttpResponse::Ok()
+                .content_type("application/octet-stream")
+                .insert_header((
+                    "Content-Disposition",
+                    format!("attachment; filename=\"{}\"", file_name),
+                ))
+                .streaming(file_stream(data_file))
+        }
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&data_file).await;
+            log::error!("backup export error: {}", e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+///
+/// 请求体为传输文件，返回各表的新增、更新、删除数量
+async fn restore(
+    req: HttpRequest,
+    service: web::Data<BackupService>,
+    raft: Option<web::Data<RaftNode>>,
+    auth: Option<web::Data<AuthService>>,
+    param: web::Query<RestoreParam>,
+    body: web::Bytes,
+) -> HttpResponse {
+    if let Some(res) = check_request(&req, &service) {
+        return res;
+    }
+    // 恢复只写本节点的数据，多节点集群中会造成节点间数据不一致
+    if !param.dry_run
+        && let Some(raft) = &raft
+        && raft.metrics().members.len() > 1
+    {
+        return HttpResponse::Conflict().body("restore is not supported in a multi-node cluster");
+    }
+    let data_file = temp_data_file();
+    if let Err(e) = tokio::fs::write(&data_file, &body).await {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+    let result = service
+        .restore(
+            &data_file,
+            param.mode,
+            param.dry_run,
+            auth.as_ref().map(|v| v.get_ref()),
+            raft.as_ref().map(|v| v.get_ref()),
+        )
+        .await;
+    let _ = tokio::fs::remove_file(&data_file).await;
+    match result {
+        Ok(report) => {
+            log::info!("backup restored: {:?}", report);
+            HttpResponse::Ok().json(report)
+        }
+        Err(e) => {
+            log::error!("backup restore error: {}", e);
+            HttpResponse::BadRequest().body(e.to_string())
+        }
+    }
+}
+
+///
+/// 注册备份与恢复接口，需要先通过 app_data 注入 `web::Data<BackupService>`
+pub fn app_config(config: &mut web::ServiceConfig) {
+    config.service(
+        web::scope(BACKUP_API_PREFIX)
+            .app_data(web::PayloadConfig::new(RESTORE_PAYLOAD_LIMIT))
+            .route("/export", web::get().to(export))
+            .route("/restore", web::post().to(restore)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::service::AuthOption;
+    use crate::backup::model::{RestoreReport, TableReport};
+    use crate::common::constant::{CONFIG_TREE_NAME, USER_TREE_NAME};
+    use crate::config::service::{ConfigService, ConfigWrite};
+    use crate::raft::model::RaftOption;
+    use crate::raft::store::RaftStore;
+    use crate::transfer::data_to_sqlite::open_init_db;
+    use crate::transfer::model::{ConfigKey, ConfigValueDo, TransferHeaderDto, UserDo};
+    use crate::transfer::writer::TransferFileWriter;
+    use actix_web::{App, test};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const TOKEN: &str = "0123456789abcdef0123456789abcdef";
+
+    #[actix_rt::test]
+    async fn test_backup_and_restore() -> anyhow::Result<()> {
+        let db = std::env::temp_dir().join(format!("backup_api_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db);
+        let db_path = db.to_string_lossy().to_string();
+        let config_service = Arc::new(ConfigService::new(open_init_db(&db_path).await?, 1024));
+        let key = ConfigKey::new("app.yaml", "DEFAULT_GROUP", "");
+        let other = ConfigKey::new("other.yaml", "DEFAULT_GROUP", "");
+        config_service.apply(&ConfigWrite::publish(
+            &key,
+            "a: 1",
+            None,
+            None,
+            Some("admin"),
+        ))?;
+        // 没有用户时创建初始管理员
+        let auth_service = web::Data::new(AuthService::new(
+            open_init_db(&db_path).await?,
+            AuthOption {
+                openapi_enable_auth: true,
+                console_login_timeout: 3600,
+                console_login_one_hour_limit: 5,
+                openapi_login_timeout: 3600,
+                openapi_login_one_minute_limit: 100,
+                init_admin_username: "admin".to_owned(),
+                init_admin_password: "admin123".to_owned(),
+            },
+        )?);
+
+        let service = web::Data::new(BackupService::new(
+            &db_path,
+            Arc::new(TOKEN.to_owned()),
+            config_service.clone(),
+        ));
+        let app = test::init_service(
+            App::new()
+                .app_data(service)
+                .app_data(auth_service.clone())
+                .configure(app_config),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/rnacos/backup/export")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 403);
+        let req = test::TestRequest::get()
+            .uri("/rnacos/backup/export")
+            .insert_header((BACKUP_TOKEN_HEADER, TOKEN.replace('0', "1")))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 403);
+        let req = test::TestRequest::get()
+            .uri("/rnacos/backup/export")
+            .insert_header((BACKUP_TOKEN_HEADER, TOKEN))
+            .to_request();
+        let data = test::call_and_read_body(&app, req).await;
+        assert!(!data.is_empty());
+
+        config_service.apply(&ConfigWrite::delete(&key, None))?;
+        config_service.apply(&ConfigWrite::publish(&other, "b: 1", None, None, None))?;
+        let mut subscription = config_service.listener().subscribe(vec![key.clone()]);
+
+        // dry run 只返回统计，数据不变
+        let req = test::TestRequest::post()
+            .uri("/rnacos/backup/restore?mode=overwrite&dryRun=true")
+            .insert_header((BACKUP_TOKEN_HEADER, TOKEN))
+            .set_payload(data.clone())
+            .to_request();
+        let report: RestoreReport = test::call_and_read_body_json(&app, req).await;
+        let expected = TableReport {
+            insert: 1,
+            update: 0,
+            delete: 1,
+        };
+        assert!(report.dry_run);
+        assert_eq!(report.config, expected);
+        assert_eq!(report.user.update, 1);
+        assert!(config_service.get(&key)?.is_none());
+
+        // 恢复提交后文件中用户的会话失效
+        let (token, _) = auth_service.console_login("admin", "admin123", "127.0.0.1")?;
+        assert!(auth_service.get_session(&token).is_some());
+
+        // merge 保留文件中没有的配置
+        let req = test::TestRequest::post()
+            .uri("/rnacos/backup/restore")
+            .insert_header((BACKUP_TOKEN_HEADER, TOKEN))
+            .set_payload(data.clone())
+            .to_request();
+        let report: RestoreReport = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report.config.insert, 1);
+        assert_eq!(report.config.delete, 0);
+        assert_eq!(config_service.get(&key)?.unwrap().content, "a: 1");
+        assert!(config_service.get(&other)?.is_some());
+        assert!(auth_service.get_session(&token).is_none());
+        let changed = subscription
+            .wait_changed(std::time::Duration::from_secs(1))
+            .await;
+        assert_eq!(changed, vec![key.clone()]);
+
+        let req = test::TestRequest::post()
+            .uri("/rnacos/backup/restore?mode=overwrite")
+            .insert_header((BACKUP_TOKEN_HEADER, TOKEN))
+            .set_payload(data)
+            .to_request();
+        let report: RestoreReport = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            report.config,
+            TableReport {
+                insert: 0,
+                update: 1,
+                delete: 1,
+            }
+        );
+        assert!(config_service.get(&other)?.is_none());
+        let (total, histories) = config_service.history_page(&key, 1, 10)?;
+        assert_eq!(total, 1);
+        assert_eq!(histories[0].op_user.as_deref(), Some("admin"));
+
+        // 文件中没有用户时拒绝 overwrite，否则恢复后无法登录
+        let empty_db =
+            std::env::temp_dir().join(format!("backup_api_empty_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&empty_db);
+        let empty_path = empty_db.to_string_lossy().to_string();
+        let empty_file = std::env::temp_dir()
+            .join(format!("backup_api_empty_{}.data", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        drop(open_init_db(&empty_path).await?);
+        BackupService::new(
+            &empty_path,
+            Arc::new(TOKEN.to_owned()),
+            config_service.clone(),
+        )
+        .backup(&empty_file)?;
+        let req = test::TestRequest::post()
+            .uri("/rnacos/backup/restore?mode=overwrite")
+            .insert_header((BACKUP_TOKEN_HEADER, TOKEN))
+            .set_payload(std::fs::read(&empty_file)?)
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 400);
+        let body = test::read_body(res).await;
+        assert!(String::from_utf8_lossy(&body).contains("remove all users"));
+        assert_eq!(auth_service.list_users()?.len(), 1);
+        assert!(config_service.get(&key)?.is_some());
+        std::fs::remove_file(&empty_file)?;
+        std::fs::remove_file(&empty_db)?;
+
+        let req = test::TestRequest::post()
+            .uri("/rnacos/backup/restore")
+            .insert_header((BACKUP_TOKEN_HEADER, TOKEN))
+            .set_payload("not a transfer file")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+        std::fs::remove_file(&db)?;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn test_restore_rebuilds_raft_snapshot() -> anyhow::Result<()> {
+        let db = std::env::temp_dir().join(format!("backup_raft_{}.db", std::process::id()));
+        let data_file = std::env::temp_dir()
+            .join(format!("backup_raft_{}.data", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let _ = std::fs::remove_file(&db);
+        let db_path = db.to_string_lossy().to_string();
+        let config_service = Arc::new(ConfigService::new(open_init_db(&db_path).await?, 1024));
+        let raft_node = web::Data::new(RaftNode::new(
+            RaftOption {
+                node_id: 1,
+                node_addr: "127.0.0.1:1".to_owned(),
+                auto_init: true,
+                join_addr: String::new(),
+                snapshot_log_size: 100,
+                cluster_token: Arc::new(String::new()),
+                heartbeat_interval: Duration::from_millis(50),
+                election_timeout: Duration::from_millis(300),
+            },
+            config_service.clone(),
+            RaftStore::new(open_init_db(&db_path).await?)?,
+        )?);
+        raft_node.clone().into_inner().start();
+
+        // 同一个配置出现两次，以最后一条为准
+        let key = ConfigKey::new("app.yaml", "DEFAULT_GROUP", "");
+        let mut header = TransferHeaderDto::new(1, 1700000000000, Some("test".to_owned()));
+        header.add_name(CONFIG_TREE_NAME.clone());
+        header.add_name(USER_TREE_NAME.clone());
+        let mut writer = TransferFileWriter::create(&data_file, header)?;
+        for content in ["a: 1", "a: 2"] {
+            let value = ConfigValueDo {
+                content: content.to_owned(),
+                ..Default::default()
+            };
+            writer.write_record(
+                &CONFIG_TREE_NAME,
+                key.build_key().into_bytes(),
+                value.to_bytes(),
+            )?;
+        }
+        let user = UserDo {
+            username: "admin".to_owned(),
+            ..Default::default()
+        };
+        writer.write_record(&USER_TREE_NAME, b"admin".to_vec(), user.to_bytes())?;
+        writer.finish()?;
+
+        let service = web::Data::new(BackupService::new(
+            &db_path,
+            Arc::new(TOKEN.to_owned()),
+            config_service.clone(),
+        ));
+        let app = test::init_service(
+            App::new()
+                .app_data(service)
+                .app_data(raft_node.clone())
+                .configure(app_config),
+        )
+        .await;
+        let req = test::TestRequest::post()
+            .uri("/rnacos/backup/restore?mode=overwrite")
+            .insert_header((BACKUP_TOKEN_HEADER, TOKEN))
+            .set_payload(std::fs::read(&data_file)?)
+            .to_request();
+        let report: RestoreReport = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report.config.insert, 1);
+        let configs = config_service.export_configs()?;
+        assert_eq!(configs.len(), 1);
+        assert_eq!(configs[0].content.as_deref(), Some("a: 2"));
+
+        // 快照包含恢复后的数据，之后加入的节点安装快照即可得到
+        let snapshot = RaftStore::new(open_init_db(&db_path).await?)?.load_snapshot()?;
+        assert_eq!(snapshot.configs.len(), 1);
+        assert_eq!(snapshot.configs[0].content.as_deref(), Some("a: 2"));
+        assert_eq!(snapshot.members.len(), 1);
+        raft_node.shutdown();
+        std::fs::remove_file(&data_file)?;
+        std::fs::remove_file(&db)?;
+        Ok(())
+    }
+}
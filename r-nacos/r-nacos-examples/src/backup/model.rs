@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::hash::Hash;
+
+pub const BACKUP_TOKEN_HEADER: &str = "Backup-Token";
+
+///
+/// 恢复方式: merge 时文件中的记录覆盖同 key 的记录，其它记录保留;
+/// overwrite 时先清空配置、历史记录、命名空间与用户，再写入文件中的记录，文件中没有用户时拒绝恢复
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RestoreMode {
+    #[default]
+    Merge,
+    Overwrite,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableReport {
+    pub insert: usize,
+    pub update: usize,
+    pub delete: usize,
+}
+
+impl TableReport {
+    ///
+    /// 按恢复前后的 key 计算新增、更新与删除的数量
+    pub fn diff<K: Eq + Hash>(old: &HashSet<K>, new: &HashSet<K>, mode: RestoreMode) -> Self {
+        let update = new.iter().filter(|v| old.contains(*v)).count();
+        let delete = match mode {
+            RestoreMode::Merge => 0,
+            RestoreMode::Overwrite => old.len() - update,
+        };
+        Self {
+            insert: new.len() - update,
+            update,
+            delete,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreReport {
+    pub mode: RestoreMode,
+    pub dry_run: bool,
+    pub config: TableReport,
+    pub namespace: TableReport,
+    pub user: TableReport,
+    // 文件中不支持恢复的表的记录数
+    pub ignore: usize,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreParam {
+    #[serde(default)]
+    pub mode: RestoreMode,
+    #[serde(default)]
+    pub dry_run: bool,
+}
@@ -0,0 +1,284 @@
+use crate::auth::service::AuthService;
+use crate::backup::model::{RestoreMode, RestoreReport, TableReport};
+use crate::common::constant::{CONFIG_TREE_NAME, NAMESPACE_TREE_NAME, USER_TREE_NAME};
+use crate::common::string_utils::StringUtils;
+use crate::config::service::ConfigService;
+use crate::raft::node::RaftNode;
+use crate::transfer::data_to_sqlite::open_init_db;
+use crate::transfer::model::{ConfigKey, ConfigValueDo, NamespaceDo, UserDo};
+use crate::transfer::reader::TransferFileReader;
+use crate::transfer::sqlite::dao::ConfigDao;
+use crate::transfer::sqlite::dao::config::{ConfigDO, ConfigParam};
+use crate::transfer::sqlite::dao::config_history::{
+    ConfigHistoryDO, ConfigHistoryDao, ConfigHistoryParam,
+};
+use crate::transfer::sqlite::dao::tenant::{TenantDO, TenantDao, TenantParam};
+use crate::transfer::sqlite::dao::user::{UserDO, UserDao, UserParam};
+use crate::transfer::sqlite_to_data::{ExportCount, export_to_file};
+use rusqlite::{Connection, OpenFlags};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::Arc;
+
+///
+/// 传输文件中可恢复的记录
+#[derive(Default)]
+struct RestoreData {
+    configs: Vec<(ConfigKey, ConfigValueDo)>,
+    namespaces: Vec<NamespaceDo>,
+    users: Vec<UserDo>,
+    ignore: usize,
+}
+
+impl RestoreData {
+    ///
+    /// 先读出并解码全部记录，文件有问题时不改动数据库
+    async fn read(data_file: &str) -> anyhow::Result<Self> {
+        let mut reader = TransferFileReader::new(data_file).await?;
+        let mut data = Self::default();
+        while let Some(v) = reader.read_record_vec().await? {
+            let record = reader.decode_record(&v)?;
+            if record.table_name.as_str() == CONFIG_TREE_NAME.as_str() {
+                let key: ConfigKey = String::from_utf8_lossy(&record.key).as_ref().into();
+                data.configs
+                    .push((key, ConfigValueDo::from_bytes(&record.value)?));
+            } else if record.table_name.as_str() == NAMESPACE_TREE_NAME.as_str() {
+                data.namespaces
+                    .push(NamespaceDo::from_bytes(&record.value)?);
+            } else if record.table_name.as_str() == USER_TREE_NAME.as_str() {
+                data.users.push(UserDo::from_bytes(&record.value)?);
+            } else {
+                data.ignore += 1;
+            }
+        }
+        // 同一条记录出现多次时以最后一条为准，避免 overwrite 时插入重复的行
+        data.configs = dedup_last(data.configs, |v| v.0.clone());
+        data.namespaces = dedup_last(data.namespaces, |v| v.namespace_id.clone());
+        data.users = dedup_last(data.users, |v| v.username.clone());
+        Ok(data)
+    }
+}
+
+///
+/// 按 key 去重，保留每个 key 最后出现的记录，记录之间的顺序不变
+fn dedup_last<T, K: Hash + Eq>(list: Vec<T>, key: impl Fn(&T) -> K) -> Vec<T> {
+    let last: HashMap<K, usize> = list.iter().enumerate().map(|(i, v)| (key(v), i)).collect();
+    list.into_iter()
+        .enumerate()
+        .filter(|(i, v)| last[&key(v)] == *i)
+        .map(|(_, v)| v)
+        .collect()
+}
+
+///
+/// 基于传输文件的备份与恢复，直接读写配置中心使用的 sqlite 文件
+pub struct BackupService {
+    db_path: String,
+    token: Arc<String>,
+    config_service: Arc<ConfigService>,
+}
+
+impl BackupService {
+    pub fn new(db_path: &str, token: Arc<String>, config_service: Arc<ConfigService>) -> Self {
+        Self {
+            db_path: db_path.to_owned(),
+            token,
+            config_service,
+        }
+    }
+
+    ///
+    /// 没有配置 backup_token 时不开放备份接口
+    pub fn enabled(&self) -> bool {
+        !self.token.is_empty()
+    }
+
+    pub fn check_token(&self, token: &str) -> bool {
+        self.enabled() && StringUtils::constant_time_eq(self.token.as_str(), token)
+    }
+
+    ///
+    /// 在一个读事务中导出配置(带历史记录)、命名空间与用户
+    pub fn backup(&self, data_file: &str) -> anyhow::Result<ExportCount> {
+        let conn = Connection::open_with_flags(&self.db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let tx = conn.unchecked_transaction()?;
+        export_to_file(&tx, data_file)
+    }
+
+    ///
+    /// 在一个事务中恢复传输文件，dry_run 时只返回统计并回滚
+    /// 提交后移除被改写用户的会话，权限与密码以恢复后的数据为准;
+    /// 恢复不经过 raft 日志，提交后重新生成 raft 快照，之后加入的节点从快照得到恢复后的数据
+    pub async fn restore(
+        &self,
+        data_file: &str,
+        mode: RestoreMode,
+        dry_run: bool,
+        auth_service: Option<&AuthService>,
+        raft_node: Option<&RaftNode>,
+    ) -> anyhow::Result<RestoreReport> {
+        let data = RestoreData::read(data_file).await?;
+        let mut conn = open_init_db(&self.db_path).await?;
+        let tx = conn.transaction()?;
+        let (mut report, changes) = restore_tables(&tx, &data, mode)?;
+        report.dry_run = dry_run;
+        if dry_run {
+            return Ok(report);
+        }
+        tx.commit()?;
+        if let Some(raft_node) = raft_node {
+            raft_node.rebuild_snapshot();
+        }
+        for key in &changes.configs {
+            self.config_service.listener().notify(key);
+        }
+        if let Some(auth_service) = auth_service {
+            auth_service.remove_sessions(&changes.users);
+        }
+        Ok(report)
+    }
+}
+
+///
+/// 恢复改动的记录: 需要通知监听者的配置与需要重新登录的用户
+struct RestoreChanges {
+    configs: HashSet<ConfigKey>,
+    users: HashSet<String>,
+}
+
+fn config_key(record: &ConfigDO) -> ConfigKey {
+    ConfigKey::new(
+        record.data_id.as_deref().unwrap_or_default(),
+        record.group_id.as_deref().unwrap_or_default(),
+        record.tenant_id.as_deref().unwrap_or_default(),
+    )
+}
+
+///
+/// 写入文件中的记录，返回统计与改动的记录
+fn restore_tables(
+    conn: &Connection,
+    data: &RestoreData,
+    mode: RestoreMode,
+) -> anyhow::Result<(RestoreReport, RestoreChanges)> {
+    let config_dao = ConfigDao::new(conn);
+    let config_history_dao = ConfigHistoryDao::new(conn);
+    let tenant_dao = TenantDao::new(conn);
+    let user_dao = UserDao::new(conn);
+
+    let old_configs: HashSet<ConfigKey> = config_dao
+        .query(&ConfigParam::default())?
+        .iter()
+        .map(config_key)
+        .collect();
+    let old_namespaces: HashSet<String> = tenant_dao
+        .query(&TenantParam::default())?
+        .into_iter()
+        .filter_map(|v| v.tenant_id)
+        .collect();
+    let old_users: HashSet<String> = user_dao
+        .query(&UserParam::default())?
+        .into_iter()
+        .filter_map(|v| v.username)
+        .collect();
+
+    let new_configs: HashSet<ConfigKey> = data.configs.iter().map(|(k, _)| k.clone()).collect();
+    let new_namespaces: HashSet<String> = data
+        .namespaces
+        .iter()
+        .map(|v| v.namespace_id.clone())
+        .collect();
+    let new_users: HashSet<String> = data.users.iter().map(|v| v.username.clone()).collect();
+    // 清空用户后无法再登录控制台
+    if mode == RestoreMode::Overwrite && new_users.is_empty() {
+        return Err(anyhow::anyhow!(
+            "overwrite restore would remove all users, the data file has no user"
+        ));
+    }
+
+    let report = RestoreReport {
+        mode,
+        dry_run: false,
+        config: TableReport::diff(&old_configs, &new_configs, mode),
+        namespace: TableReport::diff(&old_namespaces, &new_namespaces, mode),
+        user: TableReport::diff(&old_users, &new_users, mode),
+        ignore: data.ignore,
+    };
+
+    if mode == RestoreMode::Overwrite {
+        config_dao.execute("delete from tb_config", &[])?;
+        config_history_dao.execute("delete from tb_config_history", &[])?;
+        tenant_dao.execute("delete from tb_tenant", &[])?;
+        user_dao.execute("delete from tb_user", &[])?;
+    }
+
+    for (key, value_do) in &data.configs {
+        if mode == RestoreMode::Merge {
+            config_dao.delete(&ConfigParam {
+                data_id: Some(key.data_id.clone()),
+                group_id: Some(key.group.clone()),
+                tenant_id: Some(key.tenant.clone()),
+                ..Default::default()
+            })?;
+            config_history_dao.delete(&ConfigHistoryParam {
+                data_id: Some(key.data_id.clone()),
+                group_id: Some(key.group.clone()),
+                tenant_id: Some(key.tenant.clone()),
+                ..Default::default()
+            })?;
+        }
+        config_dao.insert(&ConfigDO {
+            id: None,
+            data_id: Some(key.data_id.clone()),
+            group_id: Some(key.group.clone()),
+            tenant_id: Some(key.tenant.clone()),
+            content: Some(value_do.content.clone()),
+            config_type: value_do.config_type.clone(),
+            config_desc: value_do.desc.clone(),
+            last_time: Some(value_do.last_time),
+        })?;
+        for item in &value_do.histories {
+            config_history_dao.insert(&ConfigHistoryDO::from_item(key, item.clone()))?;
+        }
+    }
+
+    for namespace in &data.namespaces {
+        if mode == RestoreMode::Merge {
+            tenant_dao.delete(&TenantParam {
+                tenant_id: Some(namespace.namespace_id.clone()),
+                ..Default::default()
+            })?;
+        }
+        let tenant_do: TenantDO = namespace.clone().into();
+        tenant_dao.insert(&TenantDO {
+            id: None,
+            ..tenant_do
+        })?;
+    }
+
+    for user in &data.users {
+        if mode == RestoreMode::Merge {
+            user_dao.delete(&UserParam {
+                username: Some(user.username.clone()),
+                ..Default::default()
+            })?;
+        }
+        let user_do: UserDO = user.clone().into();
+        user_dao.insert(&UserDO {
+            id: None,
+            ..user_do
+        })?;
+    }
+
+    let changes = match mode {
+        RestoreMode::Merge => RestoreChanges {
+            configs: new_configs,
+            users: new_users,
+        },
+        RestoreMode::Overwrite => RestoreChanges {
+            configs: old_configs.union(&new_configs).cloned().collect(),
+            users: old_users.union(&new_users).cloned().collect(),
+        },
+    };
+    Ok((report, changes))
+}
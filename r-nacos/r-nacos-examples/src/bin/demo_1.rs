@@ -1,3 +1,4 @@
+use actix_web::middleware::{Condition, from_fn};
 use actix_web::web::Data;
 use actix_web::{App, HttpResponse, HttpServer, Responder, delete, get, post, put, web};
 
@@ -7,12 +8,34 @@ use env_logger_timezone_fmt::{TimeZoneFormat, TimeZoneFormatEnv};
 // parse() 是 clap::Parser trait 提供的方法，而 Rust 要求 trait 在调用其他方法时必须在作用域内
 // 不引入，rust 编译器找不到 parse() 方法，使用 uer::Parser 让编译器知道 可以调用 parse 方法
 use clap::Parser;
+use r_nacos_examples::auth;
+use r_nacos_examples::auth::service::{AuthOption, AuthService};
+use r_nacos_examples::backup;
+use r_nacos_examples::backup::service::BackupService;
 use r_nacos_examples::cli;
 use r_nacos_examples::cli::Commands;
 use r_nacos_examples::common::AppSysConfig;
-use r_nacos_examples::transfer::data_to_sqlite::data_to_sqlite;
+use r_nacos_examples::config::api::app_config;
+use r_nacos_examples::config::service::ConfigService;
+use r_nacos_examples::grpc;
+use r_nacos_examples::grpc::handler::GrpcContext;
+use r_nacos_examples::metrics;
+use r_nacos_examples::metrics::manager::{MetricsCollector, MetricsOption};
+use r_nacos_examples::naming;
+use r_nacos_examples::naming::registry::NamingRegistry;
+use r_nacos_examples::raft;
+use r_nacos_examples::raft::model::RaftOption;
+use r_nacos_examples::raft::node::RaftNode;
+use r_nacos_examples::raft::store::RaftStore;
+use r_nacos_examples::transfer::data_to_sqlite::{data_to_sqlite, open_init_db};
+use r_nacos_examples::transfer::inspect;
+use r_nacos_examples::transfer::inspect::ExtractFilter;
+use r_nacos_examples::transfer::mysql_to_data::mysql_to_data;
+use r_nacos_examples::transfer::openapi_to_data::openapi_to_data;
+use r_nacos_examples::transfer::sqlite_to_data::sqlite_to_data;
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
 // actix_web::main 是 Actix Web 提供的一个属性宏，主要作用是将一个普通的异步async函数
@@ -23,7 +46,7 @@ use std::sync::{Arc, Mutex};
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("version: {}", get_app_version());
 
-    for (key, value) in env::vars() {
+    for (_key, _value) in env::vars() {
         // println!("key = {}: version = {}", key, value);
     }
 
@@ -63,13 +86,108 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         users: Mutex::new(Vec::new()),
     });
 
+    // 配置中心使用本地 sqlite 存储
+    std::fs::create_dir_all(&sys_config.local_db_dir)?;
+    let config_db = Path::new(&sys_config.local_db_dir).join(&sys_config.config_db_file);
+    let config_service = Arc::new(ConfigService::new(
+        open_init_db(&config_db.to_string_lossy()).await?,
+        sys_config.config_max_content,
+    ));
+
+    // 配置变更通过 raft 在集群节点间复制，节点间接口单独监听 raft_node_addr
+    // raft 的任期、投票与日志保存在同一个 sqlite 文件，重启后从本地状态恢复
+    sys_config.check_raft_addr()?;
+    let raft_node = Data::new(RaftNode::new(
+        RaftOption::from(sys_config.as_ref()),
+        config_service.clone(),
+        RaftStore::new(open_init_db(&config_db.to_string_lossy()).await?)?,
+    )?);
+    raft_node.clone().into_inner().start();
+    let raft_server = {
+        let raft_node = raft_node.clone();
+        HttpServer::new(move || {
+            App::new()
+                .app_data(raft_node.clone())
+                .configure(raft::api::app_config)
+        })
+        .bind(&sys_config.raft_node_addr)?
+        .run()
+    };
+    actix_web::rt::spawn(raft_server);
+
+    // 备份与恢复直接读写配置中心的 sqlite 文件，未配置 backup_token 时接口拒绝访问
+    let backup_service = Data::new(BackupService::new(
+        &config_db.to_string_lossy(),
+        sys_config.backup_token.clone(),
+        config_service.clone(),
+    ));
+    let config_service = Data::from(config_service);
+
+    let auth_service = Data::new(AuthService::new(
+        open_init_db(&config_db.to_string_lossy()).await?,
+        AuthOption::from(sys_config.as_ref()),
+    )?);
+
+    let naming_registry = Data::new(NamingRegistry::new(
+        sys_config.naming_health_timeout,
+        sys_config.naming_instance_timeout,
+    ));
+    naming_registry
+        .clone()
+        .into_inner()
+        .spawn_health_check(std::time::Duration::from_secs(1));
+
+    // nacos 2.x 客户端通过 grpc 端口访问配置与服务发现
+    let grpc_context = Arc::new(GrpcContext {
+        raft_node: Some(raft_node.clone().into_inner()),
+        auth_service: Some(auth_service.clone().into_inner()),
+        ..GrpcContext::new(
+            config_service.clone().into_inner(),
+            naming_registry.clone().into_inner(),
+        )
+    });
+    let grpc_listener = tokio::net::TcpListener::bind(("0.0.0.0", sys_config.grpc_port)).await?;
+    actix_web::rt::spawn(async move {
+        if let Err(e) = grpc::server::serve(grpc_context, grpc_listener).await {
+            log::error!("grpc server error: {}", e);
+        }
+    });
+
+    // 开启后定时采集指标，并通过 /metrics 提供 prometheus 拉取
+    let metrics_enable = sys_config.metrics_enable;
+    if metrics_enable {
+        MetricsCollector::new(
+            config_service.clone().into_inner(),
+            naming_registry.clone().into_inner(),
+        )
+        .spawn(MetricsOption::from(sys_config.as_ref()));
+    }
+
     HttpServer::new(move || {
         // 创建 http 服务器实例
         // 配置应用程序的路由和逻辑
         App::new() // 配置 根路由的 get 请求
             .route("/", web::get().to(root))
             .route("/hello", web::get().to(hello))
+            .wrap(Condition::new(
+                metrics_enable,
+                from_fn(metrics::api::http_metrics),
+            ))
+            .configure(|config| {
+                if metrics_enable {
+                    metrics::api::app_config(config);
+                }
+            })
             .app_data(app_state.clone())
+            .app_data(auth_service.clone())
+            .configure(auth::api::app_config)
+            .app_data(config_service.clone())
+            .app_data(raft_node.clone())
+            .configure(app_config)
+            .app_data(naming_registry.clone())
+            .configure(naming::api::app_config)
+            .app_data(backup_service.clone())
+            .configure(backup::api::app_config)
             .service(get_users)
             .service(get_user)
             .service(create_user)
@@ -77,6 +195,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .service(delete_user)
     })
     .bind("127.0.0.1:6789")? // 绑定到本地 5678 端口
+    .bind(("0.0.0.0", sys_config.http_port))?
     .run() // 启动服务器
     .await?; // 等待服务器运行完成
 
@@ -175,11 +294,49 @@ async fn run_subcommand(commands: Commands) -> Result<(), Box<dyn std::error::Er
             log::info!("middle data to sqlite, from:{file} to:{out}");
             data_to_sqlite(&file, &out).await?;
         }
-        Commands::SqliteToData { .. } => {}
+        Commands::SqliteToData { file, out } => {
+            log::info!("sqlite to middle data, from:{file} to:{out}");
+            sqlite_to_data(&file, &out).await?;
+        }
 
-        Commands::MysqlToData { .. } => {}
+        Commands::MysqlToData { file, out } => {
+            log::info!("nacos mysql dump to middle data, from:{file} to:{out}");
+            mysql_to_data(&file, &out).await?;
+        }
+
+        Commands::OpenapiToData {
+            username,
+            password,
+            host,
+            out,
+        } => {
+            log::info!("nacos openapi to middle data, from:{host} to:{out}");
+            openapi_to_data(&username, &password, &host, &out).await?;
+        }
 
-        Commands::OpenapiToData { .. } => {}
+        Commands::Inspect { file } => {
+            inspect::inspect(&file).await?;
+        }
+
+        Commands::Diff { file, other } => {
+            inspect::diff(&file, &other).await?;
+        }
+
+        Commands::Extract {
+            file,
+            out,
+            table,
+            tenant,
+            data_id,
+        } => {
+            let filter = ExtractFilter {
+                tables: table,
+                tenant,
+                data_id,
+            };
+            let count = inspect::extract(&file, &out, &filter).await?;
+            log::info!("extract transfer file, from:{file} to:{out}, record count:{count}");
+        }
     }
     Ok(())
 }
@@ -10,8 +10,6 @@
 // Builder API 手动控制来构建命令行
 // Derive API 通过 rust 宏来自动生成解析代码，只需要定义一个结构体，clap 会为你处理解析逻辑
 
-use clap::Subcommand;
-
 #[derive(Debug, clap::Parser)] // 通过 clap::Parser 引入，用于自动解析命令行参数
 #[command(name = "rnacos")] // 设置应用的元信息
 #[command(version, about = "rnacos cli", long_about = None)]
@@ -47,4 +45,30 @@ pub enum Commands {
         host: String,
         out: String,
     },
+
+    /// 打印传输文件的 header 与各表记录数
+    #[command(arg_required_else_help(true))]
+    Inspect { file: String },
+
+    /// 按表与 key 比较两个传输文件
+    #[command(arg_required_else_help(true))]
+    Diff { file: String, other: String },
+
+    /// 按条件从传输文件中导出部分记录
+    #[command(arg_required_else_help(true))]
+    Extract {
+        file: String,
+        out: String,
+
+        /// 只保留这些表，多个表用逗号分隔
+        #[arg(long, value_delimiter = ',')]
+        table: Option<Vec<String>>,
+
+        #[arg(long)]
+        tenant: Option<String>,
+
+        /// data_id 通配符，支持 * 与 ?
+        #[arg(long)]
+        data_id: Option<String>,
+    },
 }
@@ -18,6 +18,10 @@ pub const GRPC_HEAD_KEY_TRACE_ID: &str = "trace_id";
 
 pub const GRPC_HEAD_KEY_SUB_NAME: &str = "sub_name";
 
+pub const USER_ROLE_MANAGER: &str = "0";
+pub const USER_ROLE_DEVELOPER: &str = "1";
+pub const USER_ROLE_VISITOR: &str = "2";
+
 lazy_static::lazy_static! {
     pub static ref CONFIG_TREE_NAME: Arc<String> =  Arc::new("T_CONFIG".to_string());
     pub static ref SEQUENCE_TREE_NAME: Arc<String> =  Arc::new("T_SEQUENCE".to_string());
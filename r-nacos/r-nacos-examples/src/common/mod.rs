@@ -7,6 +7,7 @@ pub mod rusqlite_utils;
 pub mod string_utils;
 
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub const EMPTY_STR: &str = "";
 const DEFAULT_DB_PATH: &str = "nacos_db";
@@ -79,8 +80,13 @@ impl AppSysConfig {
             .unwrap_or("1".to_owned())
             .parse()
             .unwrap_or(1);
+        // raft 节点间接口单独监听，默认端口不能与 grpc 相同
+        let raft_port: u16 = std::env::var("RNACOS_RAFT_PORT")
+            .unwrap_or("".to_owned())
+            .parse()
+            .unwrap_or(grpc_port + 1);
         let raft_node_addr =
-            std::env::var("RNACOS_RAFT_NODE_ADDR").unwrap_or(format!("127.0.0.1:{}", &grpc_port));
+            std::env::var("RNACOS_RAFT_NODE_ADDR").unwrap_or(format!("127.0.0.1:{}", &raft_port));
         let raft_auto_init = std::env::var("RNACOS_RAFT_AUTO_INIT")
             .unwrap_or("".to_owned())
             .parse()
@@ -212,6 +218,27 @@ impl AppSysConfig {
         }
     }
 
+    ///
+    /// raft 节点地址的端口不能与本进程监听的 http、grpc 端口相同，否则启动时监听失败
+    pub fn check_raft_addr(&self) -> anyhow::Result<()> {
+        let port = self
+            .raft_node_addr
+            .rsplit_once(':')
+            .and_then(|(_, port)| port.parse::<u16>().ok())
+            .ok_or_else(|| anyhow::anyhow!("invalid raft node addr: {}", self.raft_node_addr))?;
+        for (name, v) in [("http", self.http_port), ("grpc", self.grpc_port)] {
+            if port == v {
+                return Err(anyhow::anyhow!(
+                    "raft node addr {} uses the {} port {}, set RNACOS_RAFT_PORT or RNACOS_RAFT_NODE_ADDR to another port",
+                    self.raft_node_addr,
+                    name,
+                    v
+                ));
+            }
+        }
+        Ok(())
+    }
+
     fn get_data_dir(run_in_docker: bool) -> String {
         if let Ok(v) = std::env::var("RNACOS_DATA_DIR") {
             v
@@ -236,3 +263,35 @@ impl AppSysConfig {
         }
     }
 }
+
+///
+/// 当前时间的毫秒时间戳
+pub fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_raft_addr() {
+        let config = AppSysConfig {
+            http_port: 8848,
+            grpc_port: 9848,
+            raft_node_addr: "127.0.0.1:9849".to_owned(),
+            ..Default::default()
+        };
+        assert!(config.check_raft_addr().is_ok());
+        for addr in ["127.0.0.1:9848", "0.0.0.0:8848", "127.0.0.1"] {
+            let config = AppSysConfig {
+                raft_node_addr: addr.to_owned(),
+                ..config.clone()
+            };
+            assert!(config.check_raft_addr().is_err());
+        }
+    }
+}
@@ -16,6 +16,7 @@ mod tests {
     // 灵活性：如果后续需要修改数据，可以把 Borrowed 变为 Owned，通过 clone 数据
     // 'a 告诉编译器，我这里的结构休整的 from_sys extend 借用了某些数据，这些数据至少得活到 'a 这么久，不然我没法用，
 
+    #[allow(dead_code)]
     #[derive(Debug)]
     struct User<'a> {
         name: Cow<'a, str>,
@@ -23,6 +24,7 @@ mod tests {
         age: u32,
     }
 
+    #[allow(dead_code)]
     impl<'a> User<'a> {
         pub fn new(name: impl Into<Cow<'a, str>>, addr: impl Into<Cow<'a, str>>, age: u32) -> Self {
             User {
@@ -45,7 +47,7 @@ mod tests {
         }
     }
 
-    #[cfg(not)]
+    #[cfg(any())] // 永不编译，仅作演示
     fn some_function(user: &mut User) {
         let temp_str = String::from("Hello"); // temp_str 在函数结束时销毁
         // borrowed value does not live long enough
@@ -69,13 +71,14 @@ mod tests {
     }
 
     // 'a 告诉编译器，结构体内部包含引用，并且这个引用的生命周期和 'a 有关
+    #[allow(dead_code)]
     struct Book<'a> {
         // 'a 是一个生命周期参数，表示 author 引用的生命周期
         author: &'a str, // 'a 确保 author 指向的字符串数据至少在 Book 实例存在期间是有效的
         title: String,
     }
 
-    #[cfg(not)]
+    #[cfg(any())] // 永不编译，仅作演示
     fn create_book() -> Book<'static> {
         let author_name = String::from("Bob");
         Book {
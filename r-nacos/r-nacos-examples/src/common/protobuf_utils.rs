@@ -1,7 +1,7 @@
 use std::io::SeekFrom;
-use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct MessagePosition {
     pub position: u64,
     pub len: u64,
@@ -9,18 +9,39 @@ pub struct MessagePosition {
 
 impl MessagePosition {
     pub fn get_end_position(&self) -> u64 {
-        self.position + self.len
+        self.position.saturating_add(self.len)
     }
 }
 
+///
+/// 读取 varint 长度前缀的消息文件
+/// 顺序扫描时会记录每条消息的位置，之后可以按序号或位置直接读取
 pub struct FileMessageReader {
-    file: tokio::fs::File,
+    file: BufReader<tokio::fs::File>,
+    // 第一条消息前的位置，序号从这里开始计算
+    base: u64,
     start: u64,
+    // 从 base 开始连续扫描到的消息位置，position 为消息内容(不含长度)的位置
+    index: Vec<MessagePosition>,
+    // 消息不能超过的结束位置，未设置时为文件长度
+    end: Option<u64>,
 }
 
 impl FileMessageReader {
     pub fn new(file: tokio::fs::File, start: u64) -> Self {
-        Self { file, start }
+        Self {
+            file: BufReader::new(file),
+            base: start,
+            start,
+            index: Vec::new(),
+            end: None,
+        }
+    }
+
+    ///
+    /// 设置消息区域的结束位置，之后的内容(如 trailer)不会被当作消息读取
+    pub fn set_end(&mut self, end: u64) {
+        self.end = Some(end);
     }
 
     pub async fn seek_start(&mut self, start: u64) -> anyhow::Result<()> {
@@ -30,35 +51,420 @@ impl FileMessageReader {
     }
 
     pub async fn read_next(&mut self) -> anyhow::Result<Vec<u8>> {
-        let len = self.read_len().await?;
-        let mut data_buf = vec![0u8; len as usize];
-        let data_len = self.file.read(&mut data_buf).await?;
-
-        if data_len < data_buf.len() {
-            return Err(anyhow::anyhow!("read data not enough"));
+        let offset = self.start;
+        let position = self.read_position().await?;
+        let mut data_buf = vec![0u8; position.len as usize];
+        // 一次 read 不一定能读满，用 read_exact 直到读满或遇到文件结尾
+        if let Err(e) = self.file.read_exact(&mut data_buf).await {
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                return Err(anyhow::anyhow!(
+                    "read data not enough, expect len:{}",
+                    position.len
+                ));
+            }
+            return Err(e.into());
         }
 
-        self.start += data_len as u64;
+        self.start += position.len;
+        self.push_index(offset, position);
         Ok(data_buf)
     }
 
+    ///
+    /// 下一条消息的起始位置
+    pub fn position(&self) -> u64 {
+        self.start
+    }
+
+    ///
+    /// 是否已读到消息区域末尾
+    pub async fn is_end(&mut self) -> anyhow::Result<bool> {
+        Ok(self.start >= self.end().await?)
+    }
+
+    ///
+    /// 按 (消息内容位置, 长度) 读取，读完后从这条消息之后继续顺序读
     pub async fn read_by_position(&mut self, position: (u64, usize)) -> anyhow::Result<Vec<u8>> {
-        todo!()
+        let (position, len) = position;
+        let end = self.end().await?;
+        if position.saturating_add(len as u64) > end {
+            return Err(anyhow::anyhow!(
+                "read data not enough at position:{}, expect len:{}",
+                position,
+                len
+            ));
+        }
+        self.seek_start(position).await?;
+        let mut data_buf = vec![0u8; len];
+        if let Err(e) = self.file.read_exact(&mut data_buf).await {
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                return Err(anyhow::anyhow!(
+                    "read data not enough at position:{}, expect len:{}",
+                    position,
+                    len
+                ));
+            }
+            return Err(e.into());
+        }
+        self.start += len as u64;
+        Ok(data_buf)
     }
 
+    ///
+    /// 从当前位置扫描到消息区域末尾，返回扫描的消息数与最后一条消息的位置
     pub async fn read_to_end(&mut self) -> anyhow::Result<(u64, MessagePosition)> {
-        todo!()
+        let end = self.end().await?;
+        let mut count = 0;
+        let mut last = MessagePosition::default();
+        while self.start < end {
+            last = self.read_next_position().await?;
+            count += 1;
+        }
+        Ok((count, last))
     }
 
+    ///
+    /// 读取下一条消息的位置并跳过消息内容
     pub async fn read_next_position(&mut self) -> anyhow::Result<MessagePosition> {
-        todo!()
+        let offset = self.start;
+        let position = self.read_position().await?;
+        let skip = tokio::io::copy(
+            &mut (&mut self.file).take(position.len),
+            &mut tokio::io::sink(),
+        )
+        .await?;
+        if skip < position.len {
+            return Err(anyhow::anyhow!(
+                "read data not enough at position:{}, expect len:{}",
+                position.position,
+                position.len
+            ));
+        }
+        self.start += position.len;
+        self.push_index(offset, position);
+        Ok(position)
     }
 
+    ///
+    /// 第 index 条消息(从 0 开始)的位置，未扫描到的部分会先扫描并记录(会移动读取位置)
     pub async fn read_index_position(&mut self, index: usize) -> anyhow::Result<MessagePosition> {
-        todo!()
+        if let Some(v) = self.index.get(index) {
+            return Ok(*v);
+        }
+        self.seek_start(self.index_end()).await?;
+        let end = self.end().await?;
+        while self.index.len() <= index {
+            if self.start >= end {
+                return Err(anyhow::anyhow!(
+                    "message index {} out of range, message count:{}",
+                    index,
+                    self.index.len()
+                ));
+            }
+            self.read_next_position().await?;
+        }
+        Ok(self.index[index])
+    }
+
+    ///
+    /// 扫描到消息区域末尾建立索引，返回消息数
+    pub async fn build_index(&mut self) -> anyhow::Result<usize> {
+        self.seek_start(self.index_end()).await?;
+        self.read_to_end().await?;
+        Ok(self.index.len())
+    }
+
+    ///
+    /// 索引写入旁路文件，每条消息 16 字节: position | len，均为大端 u64
+    pub async fn save_index(&self, path: &str) -> anyhow::Result<()> {
+        let mut v = Vec::with_capacity(self.index.len() * 16);
+        for item in &self.index {
+            v.extend_from_slice(&item.position.to_be_bytes());
+            v.extend_from_slice(&item.len.to_be_bytes());
+        }
+        let mut file = tokio::fs::File::create(path).await?;
+        file.write_all(&v).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    ///
+    /// 加载 save_index 写入的索引，索引与文件不匹配时重新扫描建立索引(会移动读取位置)
+    pub async fn load_index(&mut self, path: &str) -> anyhow::Result<usize> {
+        let v = tokio::fs::read(path).await?;
+        let index: Vec<_> = v
+            .chunks_exact(16)
+            .map(|c| MessagePosition {
+                position: u64::from_be_bytes(c[..8].try_into().unwrap()),
+                len: u64::from_be_bytes(c[8..].try_into().unwrap()),
+            })
+            .collect();
+        if v.len() % 16 != 0 || !self.check_index(&index).await? {
+            log::warn!(
+                "message index file {} does not match the data file, rebuild index",
+                path
+            );
+            self.index.clear();
+            return self.build_index().await;
+        }
+        self.index = index;
+        Ok(self.index.len())
+    }
+
+    ///
+    /// 每条消息都要紧跟在上一条之后(中间只有长度前缀)，且不超过消息区域的结束位置
+    async fn check_index(&self, index: &[MessagePosition]) -> anyhow::Result<bool> {
+        let end = self.end().await?;
+        let mut prev_end = self.base;
+        for item in index {
+            let Some(item_end) = item.position.checked_add(item.len) else {
+                return Ok(false);
+            };
+            if item.position.checked_sub(prev_end) != Some(varint_len(item.len)) || item_end > end {
+                return Ok(false);
+            }
+            prev_end = item_end;
+        }
+        Ok(true)
+    }
+
+    async fn file_len(&self) -> anyhow::Result<u64> {
+        Ok(self.file.get_ref().metadata().await?.len())
+    }
+
+    async fn end(&self) -> anyhow::Result<u64> {
+        match self.end {
+            Some(end) => Ok(end),
+            None => self.file_len().await,
+        }
+    }
+
+    ///
+    /// 读取长度前缀，长度超过剩余内容时直接报错，避免按损坏的长度分配内存
+    async fn read_position(&mut self) -> anyhow::Result<MessagePosition> {
+        let len = self.read_len().await?;
+        let remaining = self.end().await?.saturating_sub(self.start);
+        if len > remaining {
+            return Err(anyhow::anyhow!("read data not enough, expect len:{}", len));
+        }
+        Ok(MessagePosition {
+            position: self.start,
+            len,
+        })
+    }
+
+    ///
+    /// 从索引末尾连续读到的完整消息追加到索引中，offset 为长度前缀的位置
+    fn push_index(&mut self, offset: u64, position: MessagePosition) {
+        if offset == self.index_end() {
+            self.index.push(position);
+        }
     }
 
+    fn index_end(&self) -> u64 {
+        self.index
+            .last()
+            .map(|v| v.get_end_position())
+            .unwrap_or(self.base)
+    }
+
+    ///
+    /// 读取消息前的 varint 长度
     async fn read_len(&mut self) -> anyhow::Result<u64> {
-        todo!()
+        let mut len = 0u64;
+        for i in 0..10 {
+            let b = self.file.read_u8().await?;
+            self.start += 1;
+            len |= ((b & 0x7f) as u64) << (7 * i);
+            if b & 0x80 == 0 {
+                return Ok(len);
+            }
+        }
+        Err(anyhow::anyhow!("message len varint overflow"))
+    }
+}
+
+///
+/// varint 编码 v 需要的字节数
+fn varint_len(v: u64) -> u64 {
+    (64 - v.max(1).leading_zeros() as u64).div_ceil(7)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quick_protobuf::Writer;
+
+    fn temp_file(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("message_reader_{}_{}", name, std::process::id()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// 8 字节前缀后写入 n 条消息，长度跨过 1 字节 varint
+    fn write_messages(path: &str, n: usize) -> anyhow::Result<Vec<Vec<u8>>> {
+        let mut data = b"prefix00".to_vec();
+        let messages: Vec<_> = (0..n).map(|i| vec![i as u8; i * 20]).collect();
+        for v in &messages {
+            Writer::new(&mut data).write_bytes(v)?;
+        }
+        std::fs::write(path, &data)?;
+        Ok(messages)
+    }
+
+    async fn open(path: &str) -> anyhow::Result<FileMessageReader> {
+        let mut reader = FileMessageReader::new(tokio::fs::File::open(path).await?, 8);
+        reader.seek_start(8).await?;
+        Ok(reader)
+    }
+
+    #[tokio::test]
+    async fn test_random_access() -> anyhow::Result<()> {
+        let path = temp_file("random");
+        let messages = write_messages(&path, 10)?;
+        let mut reader = open(&path).await?;
+
+        assert_eq!(reader.read_next().await?, messages[0]);
+        let p7 = reader.read_index_position(7).await?;
+        assert_eq!(p7.len, 140);
+        let v = reader
+            .read_by_position((p7.position, p7.len as usize))
+            .await?;
+        assert_eq!(v, messages[7]);
+        // 读完后从下一条继续顺序读
+        assert_eq!(reader.read_next().await?, messages[8]);
+
+        let p3 = reader.read_index_position(3).await?;
+        assert_eq!(
+            reader.read_by_position((p3.position, 60)).await?,
+            messages[3]
+        );
+        assert!(reader.read_index_position(10).await.is_err());
+
+        reader.seek_start(8).await?;
+        let (count, last) = reader.read_to_end().await?;
+        assert_eq!(count, 10);
+        assert_eq!(last, reader.read_index_position(9).await?);
+        assert!(reader.is_end().await?);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sidecar_index() -> anyhow::Result<()> {
+        let path = temp_file("sidecar");
+        let index_path = format!("{}.index", path);
+        let messages = write_messages(&path, 10)?;
+        let mut reader = open(&path).await?;
+        assert_eq!(reader.build_index().await?, 10);
+        reader.save_index(&index_path).await?;
+
+        let mut reader = open(&path).await?;
+        assert_eq!(reader.load_index(&index_path).await?, 10);
+        let p = reader.read_index_position(9).await?;
+        // 已加载索引，不需要再扫描
+        assert_eq!(reader.position(), 8);
+        assert_eq!(
+            reader
+                .read_by_position((p.position, p.len as usize))
+                .await?,
+            messages[9]
+        );
+
+        // 中间的索引项被修改或溢出时重新扫描
+        let data = std::fs::read(&index_path)?;
+        for (i, v) in [(5 * 16 + 15, 1u8), (5 * 16, 0xff)] {
+            let mut index = data.clone();
+            index[i] ^= v;
+            std::fs::write(&index_path, &index)?;
+            let mut reader = open(&path).await?;
+            assert_eq!(reader.load_index(&index_path).await?, 10);
+            let p = reader.read_index_position(5).await?;
+            assert_eq!(
+                reader
+                    .read_by_position((p.position, p.len as usize))
+                    .await?,
+                messages[5]
+            );
+        }
+        std::fs::write(&index_path, &data)?;
+
+        // 索引超出消息区域的结束位置
+        let mut reader = open(&path).await?;
+        let p = reader.read_index_position(8).await?;
+        reader.set_end(p.get_end_position());
+        reader.seek_start(8).await?;
+        assert_eq!(reader.load_index(&index_path).await?, 9);
+        assert!(reader.is_end().await?);
+
+        // 截断后的文件与索引不匹配
+        std::fs::write(&path, &std::fs::read(&path)?[..100])?;
+        let mut reader = open(&path).await?;
+        assert!(reader.load_index(&index_path).await.is_err());
+        std::fs::remove_file(&path)?;
+        std::fs::remove_file(&index_path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_truncated_message() -> anyhow::Result<()> {
+        let path = temp_file("truncated");
+        write_messages(&path, 3)?;
+        let len = std::fs::metadata(&path)?.len();
+        std::fs::write(&path, &std::fs::read(&path)?[..len as usize - 5])?;
+
+        let mut reader = open(&path).await?;
+        reader.read_next().await?;
+        reader.read_next().await?;
+        let err = reader.read_next().await.err().unwrap();
+        assert_eq!(err.to_string(), "read data not enough, expect len:40");
+
+        let mut reader = open(&path).await?;
+        assert!(reader.read_to_end().await.is_err());
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_len() -> anyhow::Result<()> {
+        let path = temp_file("corrupt_len");
+        let messages = write_messages(&path, 3)?;
+        // 长度前缀被改成接近 u64::MAX，读取时不能按它分配内存
+        let mut data = std::fs::read(&path)?;
+        data.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        data.extend_from_slice(b"tail");
+        std::fs::write(&path, &data)?;
+
+        let mut reader = open(&path).await?;
+        for v in &messages {
+            assert_eq!(&reader.read_next().await?, v);
+        }
+        let err = reader.read_next().await.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("read data not enough, expect len:{}", u64::MAX)
+        );
+
+        let mut reader = open(&path).await?;
+        assert!(reader.read_to_end().await.is_err());
+        assert!(reader.read_by_position((8, usize::MAX)).await.is_err());
+
+        // 结束位置之后的内容不属于消息
+        let mut reader = open(&path).await?;
+        let p = reader.read_index_position(2).await?;
+        reader.set_end(p.get_end_position() - 1);
+        assert!(
+            reader
+                .read_by_position((p.position, p.len as usize))
+                .await
+                .is_err()
+        );
+        reader.seek_start(8).await?;
+        reader.read_next().await?;
+        reader.read_next().await?;
+        assert!(reader.read_next().await.is_err());
+        std::fs::remove_file(&path)?;
+        Ok(())
     }
 }
@@ -1,10 +1,8 @@
+use rusqlite::types::{ToSqlOutput, Value, ValueRef};
 use rusqlite::{Connection, Row, params_from_iter};
 
 fn result2option<T>(r: rusqlite::Result<T>) -> Option<T> {
-    match r {
-        Ok(v) => Some(v),
-        _ => None,
-    }
+    r.ok()
 }
 
 pub fn get_row_value<T>(r: &Row, name: &str) -> Option<T>
@@ -17,8 +15,24 @@ where
     }
 }
 
-pub fn convert_json_param(val: &serde_json::Value) -> rusqlite::types::ToSqlOutput<'_> {
-    todo!()
+pub fn convert_json_param(val: &serde_json::Value) -> ToSqlOutput<'_> {
+    match val {
+        serde_json::Value::Null => ToSqlOutput::Owned(Value::Null),
+        serde_json::Value::Bool(v) => ToSqlOutput::Owned(Value::Integer(*v as i64)),
+        serde_json::Value::Number(v) => {
+            if let Some(i) = v.as_i64() {
+                ToSqlOutput::Owned(Value::Integer(i))
+            } else if v.is_f64() {
+                ToSqlOutput::Owned(Value::Real(v.as_f64().unwrap_or_default()))
+            } else {
+                // 超出 i64 的 u64
+                ToSqlOutput::Owned(Value::Text(v.to_string()))
+            }
+        }
+        serde_json::Value::String(v) => ToSqlOutput::Borrowed(ValueRef::Text(v.as_bytes())),
+        // 数组与对象按 json 字符串存储
+        _ => ToSqlOutput::Owned(Value::Text(val.to_string())),
+    }
 }
 
 pub fn convert_json_params(inputs: &[serde_json::Value]) -> Vec<rusqlite::types::ToSqlOutput<'_>> {
@@ -11,6 +11,16 @@ impl StringUtils {
         a == b
     }
 
+    ///
+    /// 耗时只与长度有关的比较，用于校验 token，避免按响应时间逐字节猜出内容
+    pub fn constant_time_eq(a: &str, b: &str) -> bool {
+        a.len() == b.len()
+            && a.bytes()
+                .zip(b.bytes())
+                .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+                == 0
+    }
+
     pub fn like(a: &str, b: &str) -> Option<usize> {
         a.rfind(b)
     }
@@ -26,10 +36,10 @@ impl StringUtils {
     ///
     /// 空字符串转为None
     pub fn map_not_empty(v: Option<String>) -> Option<String> {
-        if let Some(v) = &v {
-            if v.is_empty() {
-                return None;
-            }
+        if let Some(v) = &v
+            && v.is_empty()
+        {
+            return None;
         }
         v
     }
@@ -0,0 +1,765 @@
+use crate::auth::api::AuthUser;
+use crate::auth::model::AuthError;
+use crate::config::service::{
+    ConfigError, ConfigInfo, ConfigService, ConfigWrite, DEFAULT_GROUP, content_md5,
+};
+use crate::metrics::manager::METRICS;
+use crate::raft::node::RaftNode;
+use crate::transfer::model::ConfigKey;
+use crate::transfer::sqlite::dao::config_history::ConfigHistoryDO;
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, web};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const LISTENER_TIMEOUT_HEADER: &str = "Long-Pulling-Timeout";
+const LISTENER_NO_HANGUP_HEADER: &str = "Long-Pulling-Timeout-No-Hangup";
+// 比客户端超时提前返回，避免客户端先超时
+const LISTENER_DELAY_MS: u64 = 500;
+// 客户端指定的挂起时间上限，避免一个请求长期占用订阅
+const LISTENER_MAX_TIMEOUT_MS: u64 = 120_000;
+
+///
+/// v1 与 v2 接口共用的参数，v2 使用 namespaceId 代替 tenant
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigWebParams {
+    pub data_id: Option<String>,
+    pub group: Option<String>,
+    pub tenant: Option<String>,
+    pub namespace_id: Option<String>,
+    pub content: Option<String>,
+    #[serde(rename = "type")]
+    pub config_type: Option<String>,
+    pub desc: Option<String>,
+    pub src_user: Option<String>,
+    pub nid: Option<i64>,
+    pub page_no: Option<u64>,
+    pub page_size: Option<u64>,
+}
+
+impl ConfigWebParams {
+    ///
+    /// group 为空时使用默认分组，public 命名空间与空命名空间相同
+    pub fn to_key(&self) -> ConfigKey {
+        let group = self
+            .group
+            .as_deref()
+            .filter(|v| !v.is_empty())
+            .unwrap_or(DEFAULT_GROUP);
+        let tenant = self
+            .namespace_id
+            .as_deref()
+            .or(self.tenant.as_deref())
+            .unwrap_or_default();
+        ConfigKey::new(
+            self.data_id.as_deref().unwrap_or_default(),
+            group,
+            normalize_tenant(tenant),
+        )
+    }
+
+    fn page(&self) -> (u64, u64) {
+        (
+            self.page_no.unwrap_or(1).max(1),
+            self.page_size.unwrap_or(100).clamp(1, 500),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListenerParams {
+    #[serde(rename = "Listening-Configs")]
+    pub listening_configs: String,
+}
+
+fn normalize_tenant(tenant: &str) -> &str {
+    if tenant == "public" { "" } else { tenant }
+}
+
+///
+/// 解析客户端的监听列表，每项格式为 `dataId^2group^2md5[^2tenant]^1`
+pub fn parse_listening_configs(s: &str) -> Vec<(ConfigKey, String)> {
+    s.split('\x01')
+        .filter_map(|line| {
+            let items: Vec<&str> = line.split('\x02').collect();
+            let (key, md5) = match items.as_slice() {
+                [data_id, group, md5] => (ConfigKey::new(data_id, group, ""), md5),
+                [data_id, group, md5, tenant] => (
+                    ConfigKey::new(data_id, group, normalize_tenant(tenant)),
+                    md5,
+                ),
+                _ => return None,
+            };
+            (!key.data_id.is_empty()).then(|| (key, md5.to_string()))
+        })
+        .collect()
+}
+
+///
+/// 变更的 key 按 `dataId^2group[^2tenant]^1` 拼接后做 url 编码
+pub fn encode_changed_keys(keys: &[ConfigKey]) -> String {
+    let mut s = String::new();
+    for key in keys {
+        s.push_str(&key.build_key());
+        s.push('\x01');
+    }
+    form_urlencoded::byte_serialize(s.as_bytes()).collect()
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigHistoryInfo {
+    pub id: i64,
+    pub data_id: String,
+    pub group: String,
+    pub tenant: String,
+    pub content: String,
+    pub md5: String,
+    #[serde(rename = "type")]
+    pub config_type: Option<String>,
+    pub src_user: Option<String>,
+    pub last_modified_time: i64,
+}
+
+impl From<ConfigHistoryDO> for ConfigHistoryInfo {
+    fn from(v: ConfigHistoryDO) -> Self {
+        let content = v.content.unwrap_or_default();
+        Self {
+            id: v.id.unwrap_or_default(),
+            data_id: v.data_id.unwrap_or_default(),
+            group: v.group_id.unwrap_or_default(),
+            tenant: v.tenant_id.unwrap_or_default(),
+            md5: content_md5(&content),
+            content,
+            config_type: v.config_type,
+            src_user: v.op_user,
+            last_modified_time: v.last_time.unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageResult<T> {
+    pub total_count: u64,
+    pub page_number: u64,
+    pub pages_available: u64,
+    pub page_items: Vec<T>,
+}
+
+///
+/// v2 接口统一的返回结构
+#[derive(Debug, Serialize)]
+pub struct ApiResult<T> {
+    pub code: i32,
+    pub message: String,
+    pub data: Option<T>,
+}
+
+impl<T: Serialize> ApiResult<T> {
+    fn success(data: T) -> HttpResponse {
+        HttpResponse::Ok().json(Self {
+            code: 0,
+            message: "success".to_owned(),
+            data: Some(data),
+        })
+    }
+}
+
+fn error_status(err: &anyhow::Error) -> (StatusCode, i32) {
+    if err.downcast_ref::<AuthError>().is_some() {
+        return (StatusCode::FORBIDDEN, 40300);
+    }
+    match err.downcast_ref::<ConfigError>() {
+        Some(ConfigError::ParamMissing(_)) => (StatusCode::BAD_REQUEST, 10000),
+        Some(ConfigError::ContentTooLarge { .. }) => (StatusCode::BAD_REQUEST, 10001),
+        Some(ConfigError::NotFound) => (StatusCode::NOT_FOUND, 20004),
+        None => (StatusCode::INTERNAL_SERVER_ERROR, 30000),
+    }
+}
+
+fn v1_error(err: anyhow::Error) -> HttpResponse {
+    let (status, _) = error_status(&err);
+    HttpResponse::build(status).body(err.to_string())
+}
+
+fn v2_error(err: anyhow::Error) -> HttpResponse {
+    let (status, code) = error_status(&err);
+    HttpResponse::build(status).json(ApiResult::<()> {
+        code,
+        message: err.to_string(),
+        data: None,
+    })
+}
+
+///
+/// 校验参数与调用者对配置所在命名空间的权限
+fn check_access(auth: &AuthUser, key: &ConfigKey, write: bool) -> anyhow::Result<()> {
+    ConfigService::check_key(key)?;
+    auth.check(&key.tenant, write)?;
+    Ok(())
+}
+
+fn history_page(
+    service: &ConfigService,
+    auth: &AuthUser,
+    params: &ConfigWebParams,
+) -> anyhow::Result<PageResult<ConfigHistoryInfo>> {
+    let key = params.to_key();
+    check_access(auth, &key, false)?;
+    let (page_no, page_size) = params.page();
+    let (total_count, list) = service.history_page(&key, page_no, page_size)?;
+    Ok(PageResult {
+        total_count,
+        page_number: page_no,
+        pages_available: total_count.div_ceil(page_size),
+        page_items: list.into_iter().map(|v| v.into()).collect(),
+    })
+}
+
+fn history_detail(
+    service: &ConfigService,
+    auth: &AuthUser,
+    nid: i64,
+) -> anyhow::Result<ConfigHistoryInfo> {
+    let info: ConfigHistoryInfo = service
+        .history_detail(nid)?
+        .map(|v| v.into())
+        .ok_or(ConfigError::NotFound)?;
+    auth.check(&info.tenant, false)?;
+    Ok(info)
+}
+
+///
+/// 集群模式下变更写入 raft 日志，复制到多数节点后应用; 否则直接写本地
+pub(crate) async fn write_config(
+    service: &ConfigService,
+    raft: Option<&RaftNode>,
+    write: ConfigWrite,
+) -> anyhow::Result<bool> {
+    service.check_write(&write)?;
+    METRICS.config_publish_total.inc();
+    match raft {
+        Some(raft) => raft.write(write).await,
+        None => service.apply(&write),
+    }
+}
+
+async fn publish(
+    service: &ConfigService,
+    raft: Option<&RaftNode>,
+    auth: &AuthUser,
+    params: ConfigWebParams,
+) -> anyhow::Result<String> {
+    let key = params.to_key();
+    check_access(auth, &key, true)?;
+    let content = params.content.unwrap_or_default();
+    let md5 = content_md5(&content);
+    let op_user = params.src_user.as_deref().or(auth.username());
+    write_config(
+        service,
+        raft,
+        ConfigWrite::publish(&key, &content, params.config_type, params.desc, op_user),
+    )
+    .await?;
+    Ok(md5)
+}
+
+async fn delete(
+    service: &ConfigService,
+    raft: Option<&RaftNode>,
+    auth: &AuthUser,
+    params: &ConfigWebParams,
+) -> anyhow::Result<bool> {
+    let key = params.to_key();
+    check_access(auth, &key, true)?;
+    let op_user = params.src_user.as_deref().or(auth.username());
+    write_config(service, raft, ConfigWrite::delete(&key, op_user)).await
+}
+
+fn get(
+    service: &ConfigService,
+    auth: &AuthUser,
+    params: &ConfigWebParams,
+) -> anyhow::Result<ConfigInfo> {
+    let key = params.to_key();
+    check_access(auth, &key, false)?;
+    METRICS.config_query_total.inc();
+    Ok(service.get(&key)?.ok_or(ConfigError::NotFound)?)
+}
+
+async fn get_config(
+    service: web::Data<ConfigService>,
+    auth: AuthUser,
+    params: web::Query<ConfigWebParams>,
+) -> HttpResponse {
+    match get(&service, &auth, &params) {
+        Ok(info) => HttpResponse::Ok()
+            .insert_header(("Content-MD5", info.md5))
+            .insert_header((
+                "Config-Type",
+                info.config_type.unwrap_or_else(|| "text".to_owned()),
+            ))
+            .content_type("text/plain;charset=UTF-8")
+            .body(info.content),
+        Err(e) => v1_error(e),
+    }
+}
+
+async fn publish_config(
+    service: web::Data<ConfigService>,
+    raft: Option<web::Data<RaftNode>>,
+    auth: AuthUser,
+    params: web::Form<ConfigWebParams>,
+) -> HttpResponse {
+    match publish(
+        &service,
+        raft.as_ref().map(|v| v.get_ref()),
+        &auth,
+        params.into_inner(),
+    )
+    .await
+    {
+        Ok(_) => HttpResponse::Ok().body("true"),
+        Err(e) => v1_error(e),
+    }
+}
+
+async fn delete_config(
+    service: web::Data<ConfigService>,
+    raft: Option<web::Data<RaftNode>>,
+    auth: AuthUser,
+    params: web::Query<ConfigWebParams>,
+) -> HttpResponse {
+    match delete(&service, raft.as_ref().map(|v| v.get_ref()), &auth, &params).await {
+        Ok(_) => HttpResponse::Ok().body("true"),
+        Err(e) => v1_error(e),
+    }
+}
+
+///
+/// 带 nid 时查询单条历史记录，否则分页查询
+async fn get_history(
+    service: web::Data<ConfigService>,
+    auth: AuthUser,
+    params: web::Query<ConfigWebParams>,
+) -> HttpResponse {
+    let result = match params.nid {
+        Some(nid) => history_detail(&service, &auth, nid).map(|v| HttpResponse::Ok().json(v)),
+        None => history_page(&service, &auth, &params).map(|v| HttpResponse::Ok().json(v)),
+    };
+    result.unwrap_or_else(v1_error)
+}
+
+///
+/// 长轮询的挂起时间: 默认 30 秒，不超过 LISTENER_MAX_TIMEOUT_MS，并比客户端超时提前返回
+fn listener_timeout(header: Option<&str>) -> Duration {
+    let timeout = header
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(30000)
+        .min(LISTENER_MAX_TIMEOUT_MS);
+    Duration::from_millis(timeout.saturating_sub(LISTENER_DELAY_MS))
+}
+
+///
+/// 长轮询监听配置: 先订阅再比较 md5，避免比较后、订阅前的变更被漏掉;
+/// 没有变更时挂起到超时或任一监听的配置变更
+async fn listener(
+    req: HttpRequest,
+    service: web::Data<ConfigService>,
+    auth: AuthUser,
+    params: web::Form<ListenerParams>,
+) -> HttpResponse {
+    let configs = parse_listening_configs(&params.listening_configs);
+    if configs.is_empty() {
+        return v1_error(ConfigError::ParamMissing("Listening-Configs").into());
+    }
+    for (key, _) in &configs {
+        if let Err(e) = auth.check(&key.tenant, false) {
+            return v1_error(e.into());
+        }
+    }
+    let mut subscription = service
+        .listener()
+        .subscribe(configs.iter().map(|(key, _)| key.clone()).collect());
+    let mut changed = Vec::new();
+    for (key, md5) in &configs {
+        let current = match service.get(key) {
+            Ok(v) => v.map(|v| v.md5).unwrap_or_default(),
+            Err(e) => return v1_error(e),
+        };
+        if &current != md5 {
+            changed.push(key.clone());
+        }
+    }
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_owned())
+    };
+    let no_hangup = header(LISTENER_NO_HANGUP_HEADER).as_deref() == Some("true");
+    if changed.is_empty() && !no_hangup {
+        let timeout = listener_timeout(header(LISTENER_TIMEOUT_HEADER).as_deref());
+        changed = subscription.wait_changed(timeout).await;
+    }
+    HttpResponse::Ok()
+        .content_type("text/plain;charset=UTF-8")
+        .body(encode_changed_keys(&changed))
+}
+
+async fn get_config_v2(
+    service: web::Data<ConfigService>,
+    auth: AuthUser,
+    params: web::Query<ConfigWebParams>,
+) -> HttpResponse {
+    match get(&service, &auth, &params) {
+        Ok(info) => ApiResult::success(info.content),
+        Err(e) => v2_error(e),
+    }
+}
+
+async fn publish_config_v2(
+    service: web::Data<ConfigService>,
+    raft: Option<web::Data<RaftNode>>,
+    auth: AuthUser,
+    params: web::Form<ConfigWebParams>,
+) -> HttpResponse {
+    match publish(
+        &service,
+        raft.as_ref().map(|v| v.get_ref()),
+        &auth,
+        params.into_inner(),
+    )
+    .await
+    {
+        Ok(_) => ApiResult::success(true),
+        Err(e) => v2_error(e),
+    }
+}
+
+async fn delete_config_v2(
+    service: web::Data<ConfigService>,
+    raft: Option<web::Data<RaftNode>>,
+    auth: AuthUser,
+    params: web::Query<ConfigWebParams>,
+) -> HttpResponse {
+    match delete(&service, raft.as_ref().map(|v| v.get_ref()), &auth, &params).await {
+        Ok(_) => ApiResult::success(true),
+        Err(e) => v2_error(e),
+    }
+}
+
+async fn history_list_v2(
+    service: web::Data<ConfigService>,
+    auth: AuthUser,
+    params: web::Query<ConfigWebParams>,
+) -> HttpResponse {
+    match history_page(&service, &auth, &params) {
+        Ok(v) => ApiResult::success(v),
+        Err(e) => v2_error(e),
+    }
+}
+
+async fn history_v2(
+    service: web::Data<ConfigService>,
+    auth: AuthUser,
+    params: web::Query<ConfigWebParams>,
+) -> HttpResponse {
+    let Some(nid) = params.nid else {
+        return v2_error(ConfigError::ParamMissing("nid").into());
+    };
+    match history_detail(&service, &auth, nid) {
+        Ok(v) => ApiResult::success(v),
+        Err(e) => v2_error(e),
+    }
+}
+
+///
+/// 注册配置中心接口，需要先通过 app_data 注入 `web::Data<ConfigService>`;
+/// 注入 `web::Data<RaftNode>` 时配置变更通过 raft 复制
+pub fn app_config(config: &mut web::ServiceConfig) {
+    config
+        .service(
+            web::resource("/nacos/v1/cs/configs")
+                .route(web::get().to(get_config))
+                .route(web::post().to(publish_config))
+                .route(web::delete().to(delete_config)),
+        )
+        .service(web::resource("/nacos/v1/cs/configs/listener").route(web::post().to(listener)))
+        .service(web::resource("/nacos/v1/cs/history").route(web::get().to(get_history)))
+        .service(
+            web::resource("/nacos/v2/cs/config")
+                .route(web::get().to(get_config_v2))
+                .route(web::post().to(publish_config_v2))
+                .route(web::delete().to(delete_config_v2)),
+        )
+        .service(web::resource("/nacos/v2/cs/history").route(web::get().to(history_v2)))
+        .service(web::resource("/nacos/v2/cs/history/list").route(web::get().to(history_list_v2)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::data_to_sqlite::open_init_db;
+    use actix_web::{App, test};
+
+    #[actix_rt::test]
+    async fn test_config_api() -> anyhow::Result<()> {
+        let db = std::env::temp_dir().join(format!("config_api_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db);
+        let service = web::Data::new(ConfigService::new(
+            open_init_db(&db.to_string_lossy()).await?,
+            32,
+        ));
+        let app =
+            test::init_service(App::new().app_data(service.clone()).configure(app_config)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/nacos/v1/cs/configs")
+            .set_form([
+                ("dataId", "app.yaml"),
+                ("group", "DEFAULT_GROUP"),
+                ("tenant", "dev"),
+                ("content", "a: 1"),
+                ("type", "yaml"),
+            ])
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert_eq!(body.as_ref(), b"true");
+
+        let req = test::TestRequest::get()
+            .uri("/nacos/v1/cs/configs?dataId=app.yaml&group=DEFAULT_GROUP&tenant=dev")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get("Content-MD5").unwrap(),
+            content_md5("a: 1").as_str()
+        );
+        assert_eq!(res.headers().get("Config-Type").unwrap(), "yaml");
+        assert_eq!(test::read_body(res).await.as_ref(), b"a: 1");
+
+        // v2 使用 namespaceId
+        let req = test::TestRequest::post()
+            .uri("/nacos/v2/cs/config")
+            .set_form([
+                ("dataId", "app.yaml"),
+                ("group", "DEFAULT_GROUP"),
+                ("namespaceId", "dev"),
+                ("content", "a: 2"),
+            ])
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["data"], true);
+        let req = test::TestRequest::get()
+            .uri("/nacos/v2/cs/config?dataId=app.yaml&group=DEFAULT_GROUP&namespaceId=dev")
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["code"], 0);
+        assert_eq!(res["data"], "a: 2");
+
+        // 超过 config_max_content
+        let req = test::TestRequest::post()
+            .uri("/nacos/v1/cs/configs")
+            .set_form([
+                ("dataId", "app.yaml"),
+                ("group", "DEFAULT_GROUP"),
+                ("content", "0123456789012345678901234567890123456789"),
+            ])
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+        let req = test::TestRequest::post()
+            .uri("/nacos/v1/cs/configs")
+            .set_form([("group", "DEFAULT_GROUP"), ("content", "a")])
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/nacos/v1/cs/history?search=accurate&dataId=app.yaml&group=DEFAULT_GROUP&tenant=dev&pageNo=1&pageSize=10")
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["totalCount"], 2);
+        assert_eq!(res["pageItems"][0]["content"], "a: 2");
+        assert_eq!(res["pageItems"][1]["content"], "a: 1");
+        assert_eq!(res["pageItems"][1]["md5"], content_md5("a: 1"));
+        let nid = res["pageItems"][1]["id"].as_i64().unwrap();
+        let req = test::TestRequest::get()
+            .uri(&format!("/nacos/v2/cs/history?nid={}", nid))
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["data"]["content"], "a: 1");
+
+        let req = test::TestRequest::delete()
+            .uri("/nacos/v1/cs/configs?dataId=app.yaml&group=DEFAULT_GROUP&tenant=dev")
+            .to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await.as_ref(), b"true");
+        let req = test::TestRequest::get()
+            .uri("/nacos/v1/cs/configs?dataId=app.yaml&group=DEFAULT_GROUP&tenant=dev")
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::NOT_FOUND
+        );
+        let req = test::TestRequest::get()
+            .uri("/nacos/v2/cs/history/list?dataId=app.yaml&group=DEFAULT_GROUP&namespaceId=dev")
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["data"]["totalCount"], 3);
+
+        std::fs::remove_file(&db)?;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn test_config_listener() -> anyhow::Result<()> {
+        let db = std::env::temp_dir().join(format!("config_listener_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db);
+        let service = web::Data::new(ConfigService::new(
+            open_init_db(&db.to_string_lossy()).await?,
+            1024,
+        ));
+        let app =
+            test::init_service(App::new().app_data(service.clone()).configure(app_config)).await;
+        let a = ConfigKey::new("a.yaml", DEFAULT_GROUP, "");
+        let b = ConfigKey::new("b.yaml", DEFAULT_GROUP, "dev");
+        service.publish(&a, "a", None, None, None)?;
+        let listening = format!(
+            "a.yaml\x02DEFAULT_GROUP\x02{}\x01b.yaml\x02DEFAULT_GROUP\x02\x02dev\x01",
+            content_md5("a")
+        );
+        let listen_req = |timeout: &str| {
+            test::TestRequest::post()
+                .uri("/nacos/v1/cs/configs/listener")
+                .insert_header((LISTENER_TIMEOUT_HEADER, timeout))
+                .set_form([("Listening-Configs", listening.as_str())])
+                .to_request()
+        };
+
+        // md5 一致时挂起到超时
+        let start = std::time::Instant::now();
+        let body = test::call_and_read_body(&app, listen_req("800")).await;
+        assert!(body.is_empty());
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        assert_eq!(service.listener().listener_count(), 0);
+
+        // 挂起期间的变更立即返回
+        let start = std::time::Instant::now();
+        let (body, _) = tokio::join!(test::call_and_read_body(&app, listen_req("30000")), async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            service.publish(&b, "b", None, None, None)
+        });
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            body.as_ref(),
+            encode_changed_keys(std::slice::from_ref(&b)).as_bytes()
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&body),
+            "b.yaml%02DEFAULT_GROUP%02dev%01"
+        );
+
+        // 请求前已变更的直接返回
+        let body = test::call_and_read_body(&app, listen_req("30000")).await;
+        assert_eq!(body.as_ref(), encode_changed_keys(&[b]).as_bytes());
+        assert_eq!(service.listener().listener_count(), 0);
+
+        assert_eq!(
+            parse_listening_configs(&listening)[1].0,
+            ConfigKey::new("b.yaml", DEFAULT_GROUP, "dev")
+        );
+
+        assert_eq!(listener_timeout(None), Duration::from_millis(29500));
+        assert_eq!(listener_timeout(Some("x")), Duration::from_millis(29500));
+        assert_eq!(listener_timeout(Some("300")), Duration::ZERO);
+        assert_eq!(
+            listener_timeout(Some("86400000")),
+            Duration::from_millis(LISTENER_MAX_TIMEOUT_MS - LISTENER_DELAY_MS)
+        );
+        std::fs::remove_file(&db)?;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn test_config_auth() -> anyhow::Result<()> {
+        use crate::auth::model::{NamespacePrivilege, PRIVILEGE_ENABLE};
+        use crate::auth::service::{AuthOption, AuthService, UserInfoParam};
+        use crate::common::constant::USER_ROLE_DEVELOPER;
+        use std::collections::HashSet;
+
+        let db = std::env::temp_dir().join(format!("config_auth_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db);
+        let path = db.to_string_lossy();
+        let service = web::Data::new(ConfigService::new(open_init_db(&path).await?, 1024));
+        let auth_service = web::Data::new(AuthService::new(
+            open_init_db(&path).await?,
+            AuthOption {
+                openapi_enable_auth: true,
+                console_login_timeout: 3600,
+                console_login_one_hour_limit: 5,
+                openapi_login_timeout: 3600,
+                openapi_login_one_minute_limit: 100,
+                init_admin_username: "admin".to_owned(),
+                init_admin_password: "admin123".to_owned(),
+            },
+        )?);
+        auth_service.add_user(&UserInfoParam {
+            username: "dev".to_owned(),
+            password: Some("dev123".to_owned()),
+            roles: Some(vec![USER_ROLE_DEVELOPER.to_owned()]),
+            namespace_privilege: Some(NamespacePrivilege {
+                flags: PRIVILEGE_ENABLE,
+                white_list: HashSet::from(["dev".to_owned()]),
+                black_list: HashSet::new(),
+            }),
+            ..Default::default()
+        })?;
+        let (token, _) = auth_service.openapi_login("dev", "dev123", "127.0.0.1")?;
+        let app = test::init_service(
+            App::new()
+                .app_data(service.clone())
+                .app_data(auth_service.clone())
+                .configure(app_config),
+        )
+        .await;
+        let publish = |tenant: &str, token: &str| {
+            test::TestRequest::post()
+                .uri(&format!("/nacos/v1/cs/configs?accessToken={}", token))
+                .set_form([
+                    ("dataId", "app.yaml"),
+                    ("group", "DEFAULT_GROUP"),
+                    ("tenant", tenant),
+                    ("content", "a: 1"),
+                ])
+                .to_request()
+        };
+
+        let res = test::call_service(&app, publish("dev", "")).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        let res = test::call_service(&app, publish("dev", &token)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = test::call_service(&app, publish("prod", &token)).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        // 发布者记录为登录用户
+        let key = ConfigKey::new("app.yaml", DEFAULT_GROUP, "dev");
+        let (_, list) = service.history_page(&key, 1, 10)?;
+        assert_eq!(list[0].op_user.as_deref(), Some("dev"));
+
+        let req = test::TestRequest::get()
+            .uri("/nacos/v2/cs/config?dataId=app.yaml&group=DEFAULT_GROUP&namespaceId=prod")
+            .insert_header(("accessToken", token.as_str()))
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["code"], 40300);
+        std::fs::remove_file(&db)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use crate::transfer::model::ConfigKey;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+#[derive(Default)]
+struct ListenerInner {
+    key_listeners: HashMap<ConfigKey, HashSet<u64>>,
+    listeners: HashMap<u64, (Vec<ConfigKey>, mpsc::UnboundedSender<ConfigKey>)>,
+}
+
+impl ListenerInner {
+    fn remove_key_listeners(&mut self, id: u64, keys: Vec<ConfigKey>) {
+        for key in keys {
+            if let Some(ids) = self.key_listeners.get_mut(&key) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.key_listeners.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+///
+/// 配置变更的订阅表，配置发布或删除时直接通知订阅了该 key 的监听者
+#[derive(Default)]
+pub struct ConfigListener {
+    next_id: AtomicU64,
+    inner: Arc<Mutex<ListenerInner>>,
+}
+
+impl ConfigListener {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// 订阅一组 key，返回的 Subscription 被 drop 时自动取消订阅
+    pub fn subscribe(&self, keys: Vec<ConfigKey>) -> Subscription {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut inner = self.inner.lock().unwrap();
+        for key in &keys {
+            inner
+                .key_listeners
+                .entry(key.clone())
+                .or_default()
+                .insert(id);
+        }
+        inner.listeners.insert(id, (keys, tx));
+        Subscription {
+            id,
+            rx,
+            inner: self.inner.clone(),
+        }
+    }
+
+    ///
+    /// 替换订阅的 key 列表，grpc 连接在同一个订阅上持续增减监听的配置
+    pub fn update_keys(&self, id: u64, keys: Vec<ConfigKey>) {
+        let mut inner = self.inner.lock().unwrap();
+        let Some((old_keys, _)) = inner.listeners.get_mut(&id) else {
+            return;
+        };
+        let old_keys = std::mem::replace(old_keys, keys.clone());
+        inner.remove_key_listeners(id, old_keys);
+        for key in keys {
+            inner.key_listeners.entry(key).or_default().insert(id);
+        }
+    }
+
+    ///
+    /// 通知 key 的所有订阅者，返回通知的数量
+    pub fn notify(&self, key: &ConfigKey) -> usize {
+        let inner = self.inner.lock().unwrap();
+        let Some(ids) = inner.key_listeners.get(key) else {
+            return 0;
+        };
+        ids.iter()
+            .filter_map(|id| inner.listeners.get(id))
+            .filter(|(_, tx)| tx.send(key.clone()).is_ok())
+            .count()
+    }
+
+    pub fn listener_count(&self) -> usize {
+        self.inner.lock().unwrap().listeners.len()
+    }
+}
+
+pub struct Subscription {
+    id: u64,
+    rx: mpsc::UnboundedReceiver<ConfigKey>,
+    inner: Arc<Mutex<ListenerInner>>,
+}
+
+impl Subscription {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    ///
+    /// 等待第一个变更，再取出已经到达的其它变更; 超时返回空列表
+    pub async fn wait_changed(&mut self, timeout: std::time::Duration) -> Vec<ConfigKey> {
+        let mut changed = Vec::new();
+        if let Ok(Some(key)) = tokio::time::timeout(timeout, self.rx.recv()).await {
+            changed.push(key);
+            while let Ok(key) = self.rx.try_recv() {
+                if !changed.contains(&key) {
+                    changed.push(key);
+                }
+            }
+        }
+        changed
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some((keys, _)) = inner.listeners.remove(&self.id) {
+            inner.remove_key_listeners(self.id, keys);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_subscribe_notify() {
+        let listener = ConfigListener::new();
+        let a = ConfigKey::new("a", "DEFAULT_GROUP", "");
+        let b = ConfigKey::new("b", "DEFAULT_GROUP", "dev");
+        let mut sub1 = listener.subscribe(vec![a.clone(), b.clone()]);
+        let mut sub2 = listener.subscribe(vec![b.clone()]);
+        assert_eq!(listener.listener_count(), 2);
+
+        assert_eq!(listener.notify(&a), 1);
+        assert_eq!(listener.notify(&b), 2);
+        assert_eq!(listener.notify(&a), 1);
+        assert_eq!(
+            sub1.wait_changed(Duration::from_millis(10)).await,
+            vec![a.clone(), b.clone()]
+        );
+        assert_eq!(
+            sub2.wait_changed(Duration::from_millis(10)).await,
+            vec![b.clone()]
+        );
+        assert!(
+            sub2.wait_changed(Duration::from_millis(10))
+                .await
+                .is_empty()
+        );
+
+        listener.update_keys(sub2.id(), vec![a.clone()]);
+        assert_eq!(listener.notify(&b), 1);
+        assert_eq!(listener.notify(&a), 2);
+        assert_eq!(
+            sub2.wait_changed(Duration::from_millis(10)).await,
+            vec![a.clone()]
+        );
+
+        drop(sub1);
+        assert_eq!(listener.notify(&a), 1);
+        drop(sub2);
+        assert_eq!(listener.listener_count(), 0);
+        assert!(listener.inner.lock().unwrap().key_listeners.is_empty());
+    }
+}
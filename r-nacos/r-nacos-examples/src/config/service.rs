@@ -0,0 +1,449 @@
+use crate::common::now_millis;
+use crate::config::listener::ConfigListener;
+use crate::metrics::manager::METRICS;
+use crate::transfer::model::ConfigKey;
+use crate::transfer::sqlite::dao::config::{ConfigDO, ConfigDao, ConfigParam};
+use crate::transfer::sqlite::dao::config_history::{
+    ConfigHistoryDO, ConfigHistoryDao, ConfigHistoryParam,
+};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+pub const DEFAULT_GROUP: &str = "DEFAULT_GROUP";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    ContentTooLarge { len: usize, max: usize },
+    ParamMissing(&'static str),
+    NotFound,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ContentTooLarge { len, max } => {
+                write!(f, "content is too large, len:{}, max:{}", len, max)
+            }
+            Self::ParamMissing(name) => write!(f, "parameter '{}' is missing", name),
+            Self::NotFound => write!(f, "config data not exist"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+///
+/// 一次配置变更，集群模式下作为 raft 日志复制到各节点后再执行
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConfigWrite {
+    Publish {
+        key: ConfigKey,
+        content: String,
+        config_type: Option<String>,
+        desc: Option<String>,
+        op_user: Option<String>,
+        last_time: i64,
+    },
+    Delete {
+        key: ConfigKey,
+        op_user: Option<String>,
+        last_time: i64,
+    },
+}
+
+impl ConfigWrite {
+    pub fn publish(
+        key: &ConfigKey,
+        content: &str,
+        config_type: Option<String>,
+        desc: Option<String>,
+        op_user: Option<&str>,
+    ) -> Self {
+        Self::Publish {
+            key: key.clone(),
+            content: content.to_owned(),
+            config_type,
+            desc,
+            op_user: op_user.map(|v| v.to_owned()),
+            last_time: now_millis(),
+        }
+    }
+
+    pub fn delete(key: &ConfigKey, op_user: Option<&str>) -> Self {
+        Self::Delete {
+            key: key.clone(),
+            op_user: op_user.map(|v| v.to_owned()),
+            last_time: now_millis(),
+        }
+    }
+
+    pub fn key(&self) -> &ConfigKey {
+        match self {
+            Self::Publish { key, .. } | Self::Delete { key, .. } => key,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigInfo {
+    pub content: String,
+    pub md5: String,
+    pub config_type: Option<String>,
+    pub desc: Option<String>,
+    pub last_time: i64,
+}
+
+pub fn content_md5(content: &str) -> String {
+    format!("{:x}", md5::compute(content))
+}
+
+///
+/// 基于 sqlite 的配置服务，tb_config 存当前值，每次变更在 tb_config_history 写一条记录
+/// 发布时记录新内容，删除时记录删除前的内容; 变更提交后通知监听者
+pub struct ConfigService {
+    conn: Mutex<Connection>,
+    max_content: usize,
+    listener: ConfigListener,
+}
+
+impl ConfigService {
+    pub fn new(conn: Connection, max_content: usize) -> Self {
+        Self {
+            conn: Mutex::new(conn),
+            max_content,
+            listener: ConfigListener::new(),
+        }
+    }
+
+    pub fn listener(&self) -> &ConfigListener {
+        &self.listener
+    }
+
+    fn key_param(key: &ConfigKey) -> ConfigParam {
+        ConfigParam {
+            data_id: Some(key.data_id.clone()),
+            group_id: Some(key.group.clone()),
+            tenant_id: Some(key.tenant.clone()),
+            ..Default::default()
+        }
+    }
+
+    fn history(key: &ConfigKey, config: &ConfigDO, op_user: Option<&str>) -> ConfigHistoryDO {
+        ConfigHistoryDO {
+            data_id: Some(key.data_id.clone()),
+            group_id: Some(key.group.clone()),
+            tenant_id: Some(key.tenant.clone()),
+            content: config.content.clone(),
+            config_type: config.config_type.clone(),
+            config_desc: config.config_desc.clone(),
+            op_user: op_user.map(|v| v.to_owned()),
+            last_time: config.last_time,
+            ..Default::default()
+        }
+    }
+
+    pub fn check_key(key: &ConfigKey) -> Result<(), ConfigError> {
+        if key.data_id.is_empty() {
+            return Err(ConfigError::ParamMissing("dataId"));
+        }
+        if key.group.is_empty() {
+            return Err(ConfigError::ParamMissing("group"));
+        }
+        Ok(())
+    }
+
+    ///
+    /// 校验变更的参数与内容大小，集群模式下在写入 raft 日志前调用
+    pub fn check_write(&self, write: &ConfigWrite) -> Result<(), ConfigError> {
+        Self::check_key(write.key())?;
+        if let ConfigWrite::Publish { content, .. } = write
+            && content.len() > self.max_content
+        {
+            return Err(ConfigError::ContentTooLarge {
+                len: content.len(),
+                max: self.max_content,
+            });
+        }
+        Ok(())
+    }
+
+    ///
+    /// 执行一次变更并通知监听者，删除不存在的配置时返回 false
+    /// 发布的内容与原内容 md5 一致时只更新类型、描述并记录历史，不通知监听者
+    pub fn apply(&self, write: &ConfigWrite) -> anyhow::Result<bool> {
+        self.check_write(write)?;
+        let content_changed = match write {
+            ConfigWrite::Publish {
+                key,
+                content,
+                config_type,
+                desc,
+                op_user,
+                last_time,
+            } => {
+                let mut conn = self.conn.lock().unwrap();
+                let _timer = METRICS.sqlite_duration.start_timer();
+                let tx = conn.transaction()?;
+                let content_changed;
+                {
+                    let config_dao = ConfigDao::new(&tx);
+                    let param = Self::key_param(key);
+                    let old = config_dao.query(&param)?.into_iter().next();
+                    content_changed = old
+                        .as_ref()
+                        .map(|v| content_md5(v.content.as_deref().unwrap_or_default()))
+                        != Some(content_md5(content));
+                    let record = ConfigDO {
+                        content: Some(content.to_owned()),
+                        config_type: config_type
+                            .clone()
+                            .or_else(|| old.as_ref()?.config_type.clone()),
+                        config_desc: desc.clone().or_else(|| old.as_ref()?.config_desc.clone()),
+                        last_time: Some(*last_time),
+                        ..Default::default()
+                    };
+                    if old.is_some() {
+                        config_dao.update(&record, &param)?;
+                    } else {
+                        config_dao.insert(&ConfigDO {
+                            data_id: Some(key.data_id.clone()),
+                            group_id: Some(key.group.clone()),
+                            tenant_id: Some(key.tenant.clone()),
+                            ..record.clone()
+                        })?;
+                    }
+                    ConfigHistoryDao::new(&tx).insert(&Self::history(
+                        key,
+                        &record,
+                        op_user.as_deref(),
+                    ))?;
+                }
+                tx.commit()?;
+                content_changed
+            }
+            ConfigWrite::Delete {
+                key,
+                op_user,
+                last_time,
+            } => {
+                let mut conn = self.conn.lock().unwrap();
+                let _timer = METRICS.sqlite_duration.start_timer();
+                let tx = conn.transaction()?;
+                {
+                    let config_dao = ConfigDao::new(&tx);
+                    let param = Self::key_param(key);
+                    let Some(mut old) = config_dao.query(&param)?.into_iter().next() else {
+                        return Ok(false);
+                    };
+                    config_dao.delete(&param)?;
+                    old.last_time = Some(*last_time);
+                    ConfigHistoryDao::new(&tx).insert(&Self::history(
+                        key,
+                        &old,
+                        op_user.as_deref(),
+                    ))?;
+                }
+                tx.commit()?;
+                true
+            }
+        };
+        if content_changed {
+            self.listener.notify(write.key());
+        }
+        Ok(true)
+    }
+
+    ///
+    /// 发布配置，返回内容的 md5; config_type、desc 为 None 时保留原值
+    pub fn publish(
+        &self,
+        key: &ConfigKey,
+        content: &str,
+        config_type: Option<String>,
+        desc: Option<String>,
+        op_user: Option<&str>,
+    ) -> anyhow::Result<String> {
+        self.apply(&ConfigWrite::publish(
+            key,
+            content,
+            config_type,
+            desc,
+            op_user,
+        ))?;
+        Ok(content_md5(content))
+    }
+
+    pub fn get(&self, key: &ConfigKey) -> anyhow::Result<Option<ConfigInfo>> {
+        let conn = self.conn.lock().unwrap();
+        let _timer = METRICS.sqlite_duration.start_timer();
+        let config = ConfigDao::new(&conn)
+            .query(&Self::key_param(key))?
+            .into_iter()
+            .next();
+        Ok(config.map(|v| {
+            let content = v.content.unwrap_or_default();
+            ConfigInfo {
+                md5: content_md5(&content),
+                content,
+                config_type: v.config_type,
+                desc: v.config_desc,
+                last_time: v.last_time.unwrap_or_default(),
+            }
+        }))
+    }
+
+    ///
+    /// 删除配置，配置不存在时返回 false
+    pub fn delete(&self, key: &ConfigKey, op_user: Option<&str>) -> anyhow::Result<bool> {
+        self.apply(&ConfigWrite::delete(key, op_user))
+    }
+
+    ///
+    /// 导出全部配置，用于生成 raft 快照
+    pub fn export_configs(&self) -> anyhow::Result<Vec<ConfigDO>> {
+        let conn = self.conn.lock().unwrap();
+        let _timer = METRICS.sqlite_duration.start_timer();
+        ConfigDao::new(&conn).query(&ConfigParam::default())
+    }
+
+    ///
+    /// 用快照中的配置替换全部配置(不写历史记录)，并通知前后涉及的所有配置的监听者
+    pub fn import_configs(&self, list: &[ConfigDO]) -> anyhow::Result<()> {
+        let mut keys = HashSet::new();
+        {
+            let mut conn = self.conn.lock().unwrap();
+            let _timer = METRICS.sqlite_duration.start_timer();
+            let tx = conn.transaction()?;
+            {
+                let config_dao = ConfigDao::new(&tx);
+                for old in config_dao.query(&ConfigParam::default())? {
+                    keys.insert(Self::config_key(&old));
+                }
+                config_dao.execute("delete from tb_config", &[])?;
+                for record in list {
+                    keys.insert(Self::config_key(record));
+                    config_dao.insert(&ConfigDO {
+                        id: None,
+                        ..record.clone()
+                    })?;
+                }
+            }
+            tx.commit()?;
+        }
+        for key in &keys {
+            self.listener.notify(key);
+        }
+        Ok(())
+    }
+
+    fn config_key(record: &ConfigDO) -> ConfigKey {
+        ConfigKey::new(
+            record.data_id.as_deref().unwrap_or_default(),
+            record.group_id.as_deref().unwrap_or_default(),
+            record.tenant_id.as_deref().unwrap_or_default(),
+        )
+    }
+
+    ///
+    /// 分页查询配置的历史记录，按时间倒序，page_no 从 1 开始
+    pub fn history_page(
+        &self,
+        key: &ConfigKey,
+        page_no: u64,
+        page_size: u64,
+    ) -> anyhow::Result<(u64, Vec<ConfigHistoryDO>)> {
+        let conn = self.conn.lock().unwrap();
+        let _timer = METRICS.sqlite_duration.start_timer();
+        ConfigHistoryDao::new(&conn).query_page(&ConfigHistoryParam {
+            data_id: Some(key.data_id.clone()),
+            group_id: Some(key.group.clone()),
+            tenant_id: Some(key.tenant.clone()),
+            limit: Some(page_size.max(1) as i64),
+            offset: Some((page_no.max(1) - 1) as i64 * page_size as i64),
+            order_desc: true,
+            ..Default::default()
+        })
+    }
+
+    pub fn history_detail(&self, id: i64) -> anyhow::Result<Option<ConfigHistoryDO>> {
+        let conn = self.conn.lock().unwrap();
+        let _timer = METRICS.sqlite_duration.start_timer();
+        let list = ConfigHistoryDao::new(&conn).query(&ConfigHistoryParam {
+            id: Some(id),
+            ..Default::default()
+        })?;
+        Ok(list.into_iter().next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::data_to_sqlite::open_init_db;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_config_service() -> anyhow::Result<()> {
+        let db = std::env::temp_dir().join(format!("config_service_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db);
+        let service = ConfigService::new(open_init_db(&db.to_string_lossy()).await?, 16);
+        let key = ConfigKey::new("app.yaml", DEFAULT_GROUP, "dev");
+
+        let mut subscription = service.listener().subscribe(vec![key.clone()]);
+        let md5 = service.publish(&key, "a: 1", Some("yaml".to_owned()), None, Some("admin"))?;
+        assert_eq!(md5, content_md5("a: 1"));
+        assert_eq!(
+            subscription.wait_changed(Duration::from_millis(10)).await,
+            vec![key.clone()]
+        );
+        // 内容不变时不通知监听者
+        service.publish(&key, "a: 1", None, None, None)?;
+        assert!(
+            subscription
+                .wait_changed(Duration::from_millis(10))
+                .await
+                .is_empty()
+        );
+        service.publish(&key, "a: 2", None, Some("app".to_owned()), None)?;
+        assert_eq!(
+            subscription.wait_changed(Duration::from_millis(10)).await,
+            vec![key.clone()]
+        );
+        let info = service.get(&key)?.unwrap();
+        assert_eq!(info.content, "a: 2");
+        assert_eq!(info.config_type.as_deref(), Some("yaml"));
+        assert_eq!(info.desc.as_deref(), Some("app"));
+        assert!(
+            service
+                .get(&ConfigKey::new("app.yaml", DEFAULT_GROUP, ""))?
+                .is_none()
+        );
+
+        let err = service
+            .publish(&key, "a: 1234567890123456", None, None, None)
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err.downcast_ref::<ConfigError>(),
+            Some(ConfigError::ContentTooLarge { .. })
+        ));
+
+        assert!(service.delete(&key, Some("admin"))?);
+        assert!(!service.delete(&key, None)?);
+        assert!(service.get(&key)?.is_none());
+
+        let (count, list) = service.history_page(&key, 1, 2)?;
+        assert_eq!(count, 4);
+        let contents: Vec<_> = list.iter().map(|v| v.content.as_deref().unwrap()).collect();
+        assert_eq!(contents, vec!["a: 2", "a: 2"]);
+        assert_eq!(list[0].op_user.as_deref(), Some("admin"));
+        let (_, list) = service.history_page(&key, 2, 2)?;
+        assert_eq!(list[0].content.as_deref(), Some("a: 1"));
+        let detail = service.history_detail(list[0].id.unwrap())?.unwrap();
+        assert_eq!(detail, list[0]);
+        std::fs::remove_file(&db)?;
+        Ok(())
+    }
+}
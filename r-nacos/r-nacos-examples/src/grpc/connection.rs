@@ -0,0 +1,142 @@
+use crate::grpc::nacos_grpc_service::Payload;
+use crate::naming::model::ServiceKey;
+use crate::naming::registry::NamingRegistry;
+use crate::transfer::model::ConfigKey;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tonic::Status;
+
+pub type PushSender = mpsc::UnboundedSender<Result<Payload, Status>>;
+
+///
+/// 通过连接注册的实例，连接断开时注销
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InstanceTarget {
+    pub key: ServiceKey,
+    pub ip: String,
+    pub port: u32,
+    pub cluster_name: String,
+}
+
+///
+/// 一个 BiRequestStream 长连接，同一 http2 连接上的 Request 请求通过对端地址找到它
+pub struct Connection {
+    pub client_ip: String,
+    sender: PushSender,
+    // 配置推送任务使用的 ConfigListener 订阅
+    pub config_subscription: u64,
+    pub config_keys: HashSet<ConfigKey>,
+    services: HashMap<ServiceKey, JoinHandle<()>>,
+    pub instances: HashSet<InstanceTarget>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Connection {
+    pub fn new(client_ip: String, sender: PushSender, config_subscription: u64) -> Self {
+        Self {
+            client_ip,
+            sender,
+            config_subscription,
+            config_keys: HashSet::new(),
+            services: HashMap::new(),
+            instances: HashSet::new(),
+            tasks: Vec::new(),
+        }
+    }
+
+    pub fn sender(&self) -> PushSender {
+        self.sender.clone()
+    }
+
+    ///
+    /// 连接断开时一起停止的后台任务
+    pub fn add_task(&mut self, task: JoinHandle<()>) {
+        self.tasks.push(task);
+    }
+
+    ///
+    /// 设置服务订阅的推送任务，替换之前的任务
+    pub fn subscribe_service(&mut self, key: ServiceKey, task: JoinHandle<()>) {
+        if let Some(old) = self.services.insert(key, task) {
+            old.abort();
+        }
+    }
+
+    pub fn unsubscribe_service(&mut self, key: &ServiceKey) {
+        if let Some(task) = self.services.remove(key) {
+            task.abort();
+        }
+    }
+
+    pub fn is_subscribed(&self, key: &ServiceKey) -> bool {
+        self.services.contains_key(key)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        for task in self.services.values().chain(self.tasks.iter()) {
+            task.abort();
+        }
+    }
+}
+
+///
+/// 客户端长连接表，key 为连接 id
+#[derive(Default)]
+pub struct ConnectionManager {
+    connections: Mutex<HashMap<String, Connection>>,
+    next_request_id: AtomicU64,
+}
+
+impl ConnectionManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&self, id: &str, connection: Connection) {
+        self.connections
+            .lock()
+            .unwrap()
+            .insert(id.to_owned(), connection);
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.connections.lock().unwrap().contains_key(id)
+    }
+
+    pub fn count(&self) -> usize {
+        self.connections.lock().unwrap().len()
+    }
+
+    ///
+    /// 在连接上执行 f，连接不存在时返回 None
+    pub fn with<R>(&self, id: &str, f: impl FnOnce(&mut Connection) -> R) -> Option<R> {
+        self.connections.lock().unwrap().get_mut(id).map(f)
+    }
+
+    ///
+    /// 移除连接，停止推送任务并注销通过该连接注册的实例
+    pub fn remove(&self, id: &str, registry: &NamingRegistry) {
+        let Some(connection) = self.connections.lock().unwrap().remove(id) else {
+            return;
+        };
+        for v in &connection.instances {
+            registry.deregister(&v.key, &v.ip, v.port, &v.cluster_name);
+        }
+        log::info!(
+            "grpc connection closed, id:{}, client_ip:{}",
+            id,
+            connection.client_ip
+        );
+    }
+
+    pub fn next_request_id(&self) -> String {
+        self.next_request_id
+            .fetch_add(1, Ordering::Relaxed)
+            .to_string()
+    }
+}
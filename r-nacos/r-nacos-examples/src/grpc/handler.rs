@@ -0,0 +1,477 @@
+use crate::auth::api::AuthUser;
+use crate::auth::model::AuthError;
+use crate::auth::service::AuthService;
+use crate::common::constant::{
+    ACCESS_TOKEN_HEADER, GRPC_HEAD_KEY_CLUSTER_ID, GRPC_HEAD_KEY_SUB_NAME, GRPC_HEAD_KEY_TRACE_ID,
+};
+use crate::config::api::{ConfigWebParams, write_config};
+use crate::config::service::{ConfigService, ConfigWrite};
+use crate::grpc::connection::{Connection, ConnectionManager, InstanceTarget, PushSender};
+use crate::grpc::model::*;
+use crate::grpc::nacos_grpc_service::Payload;
+use crate::metrics::manager::METRICS;
+use crate::naming::api::{InstanceWebParams, ServiceInstancesResult};
+use crate::naming::model::{DEFAULT_CLUSTER, Instance, ServiceKey};
+use crate::naming::registry::NamingRegistry;
+use crate::raft::node::RaftNode;
+use crate::transfer::model::ConfigKey;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+// 通过长连接注册的临时实例不发心跳，连接存活期间由服务端定时续约
+const INSTANCE_RENEW_INTERVAL: Duration = Duration::from_secs(5);
+const CONFIG_PUSH_WAIT: Duration = Duration::from_secs(30);
+
+type HandleResult = Result<BaseResponse, BaseResponse>;
+
+fn bad_request(err: impl ToString) -> BaseResponse {
+    BaseResponse::error(ERROR_BAD_REQUEST, err)
+}
+
+fn decode<T: DeserializeOwned>(payload: &Payload) -> Result<T, BaseResponse> {
+    decode_body(payload).map_err(bad_request)
+}
+
+fn forbidden(err: AuthError) -> BaseResponse {
+    BaseResponse::error(ERROR_NO_RIGHT, err)
+}
+
+fn unregistered() -> BaseResponse {
+    BaseResponse::error(ERROR_UNREGISTERED, "connection is unregistered")
+}
+
+fn parse_clusters(clusters: Option<&str>) -> Vec<String> {
+    clusters
+        .unwrap_or_default()
+        .split(',')
+        .filter(|v| !v.is_empty())
+        .map(|v| v.to_owned())
+        .collect()
+}
+
+///
+/// grpc 接口共用的服务; 注入 RaftNode 时配置变更通过 raft 复制，注入 AuthService 时按 accessToken 头校验权限
+pub struct GrpcContext {
+    pub config_service: Arc<ConfigService>,
+    pub naming_registry: Arc<NamingRegistry>,
+    pub raft_node: Option<Arc<RaftNode>>,
+    pub auth_service: Option<Arc<AuthService>>,
+    pub connections: ConnectionManager,
+}
+
+impl GrpcContext {
+    pub fn new(config_service: Arc<ConfigService>, naming_registry: Arc<NamingRegistry>) -> Self {
+        Self {
+            config_service,
+            naming_registry,
+            raft_node: None,
+            auth_service: None,
+            connections: ConnectionManager::new(),
+        }
+    }
+
+    fn auth_user(&self, payload: &Payload) -> Result<AuthUser, BaseResponse> {
+        let Some(service) = self
+            .auth_service
+            .as_ref()
+            .filter(|v| v.openapi_enable_auth())
+        else {
+            return Ok(AuthUser(None));
+        };
+        payload_header(payload, ACCESS_TOKEN_HEADER)
+            .and_then(|token| service.get_session(token))
+            .map(|v| AuthUser(Some(v)))
+            .ok_or_else(|| forbidden(AuthError::InvalidToken))
+    }
+
+    ///
+    /// 处理一次 Request 请求; 响应带回请求的 requestId 与 trace_id 头
+    pub async fn handle_request(
+        self: &Arc<Self>,
+        connection_id: &str,
+        payload: Payload,
+    ) -> Payload {
+        let request_type = payload_type(&payload).to_owned();
+        let base: BaseRequest = decode_body(&payload).unwrap_or_default();
+        let trace_id = payload_header(&payload, GRPC_HEAD_KEY_TRACE_ID).map(|v| v.to_owned());
+        log::debug!(
+            "grpc request, type:{}, connection:{}, trace_id:{:?}, cluster_id:{:?}, sub_name:{:?}",
+            request_type,
+            connection_id,
+            trace_id,
+            payload_header(&payload, GRPC_HEAD_KEY_CLUSTER_ID),
+            payload_header(&payload, GRPC_HEAD_KEY_SUB_NAME),
+        );
+        let mut response = self
+            .dispatch(connection_id, &request_type, &payload)
+            .await
+            .unwrap_or_else(|e| e);
+        response.request_id = base.request_id;
+        let response_type = if response.error_code == ERROR_NOT_SUPPORTED {
+            ERROR_RESPONSE.to_owned()
+        } else {
+            response_type(&request_type)
+        };
+        let headers = trace_id
+            .map(|v| HashMap::from([(GRPC_HEAD_KEY_TRACE_ID.to_owned(), v)]))
+            .unwrap_or_default();
+        build_payload(&response_type, &response, headers)
+    }
+
+    async fn dispatch(
+        self: &Arc<Self>,
+        connection_id: &str,
+        request_type: &str,
+        payload: &Payload,
+    ) -> HandleResult {
+        match request_type {
+            SERVER_CHECK_REQUEST => Ok(BaseResponse::success(serde_json::json!({
+                "connectionId": connection_id,
+            }))),
+            HEALTH_CHECK_REQUEST => Ok(BaseResponse::success(())),
+            CONFIG_QUERY_REQUEST => self.config_query(payload),
+            CONFIG_PUBLISH_REQUEST => self.config_publish(payload).await,
+            CONFIG_REMOVE_REQUEST => self.config_remove(payload).await,
+            CONFIG_BATCH_LISTEN_REQUEST => self.config_batch_listen(connection_id, payload),
+            INSTANCE_REQUEST => self.instance(connection_id, payload),
+            SUBSCRIBE_SERVICE_REQUEST => self.subscribe_service(connection_id, payload),
+            _ => Err(BaseResponse::error(
+                ERROR_NOT_SUPPORTED,
+                format!("unsupported request type: {}", request_type),
+            )),
+        }
+    }
+
+    fn config_key(request: &ConfigRequest) -> Result<ConfigKey, BaseResponse> {
+        let key = ConfigWebParams {
+            data_id: Some(request.data_id.clone()),
+            group: Some(request.group.clone()),
+            tenant: Some(request.tenant.clone()),
+            ..Default::default()
+        }
+        .to_key();
+        ConfigService::check_key(&key).map_err(bad_request)?;
+        Ok(key)
+    }
+
+    fn config_query(&self, payload: &Payload) -> HandleResult {
+        let request: ConfigRequest = decode(payload)?;
+        let key = Self::config_key(&request)?;
+        self.auth_user(payload)?
+            .check(&key.tenant, false)
+            .map_err(forbidden)?;
+        METRICS.config_query_total.inc();
+        let info = self
+            .config_service
+            .get(&key)
+            .map_err(|e| BaseResponse::error(RESULT_FAIL, e))?
+            .ok_or_else(|| BaseResponse::error(ERROR_CONFIG_NOT_FOUND, "config data not exist"))?;
+        Ok(BaseResponse::success(serde_json::json!({
+            "content": info.content,
+            "md5": info.md5,
+            "contentType": info.config_type.unwrap_or_else(|| "text".to_owned()),
+            "lastModified": info.last_time,
+            "encryptedDataKey": "",
+        })))
+    }
+
+    async fn config_publish(&self, payload: &Payload) -> HandleResult {
+        let request: ConfigRequest = decode(payload)?;
+        let key = Self::config_key(&request)?;
+        let auth = self.auth_user(payload)?;
+        auth.check(&key.tenant, true).map_err(forbidden)?;
+        // casMd5 不为空时只在当前内容的 md5 一致时发布
+        if let Some(cas_md5) = request.cas_md5.as_deref().filter(|v| !v.is_empty()) {
+            let current = self
+                .config_service
+                .get(&key)
+                .map_err(|e| BaseResponse::error(RESULT_FAIL, e))?
+                .map(|v| v.md5)
+                .unwrap_or_default();
+            if current != cas_md5 {
+                return Err(BaseResponse::error(RESULT_FAIL, "cas publish fail"));
+            }
+        }
+        let addition = |name: &str| request.addition_map.get(name).cloned();
+        let op_user = addition("src_user");
+        let write = ConfigWrite::publish(
+            &key,
+            &request.content,
+            addition("type"),
+            addition("desc"),
+            op_user.as_deref().or(auth.username()),
+        );
+        self.write_config(write).await
+    }
+
+    async fn config_remove(&self, payload: &Payload) -> HandleResult {
+        let request: ConfigRequest = decode(payload)?;
+        let key = Self::config_key(&request)?;
+        let auth = self.auth_user(payload)?;
+        auth.check(&key.tenant, true).map_err(forbidden)?;
+        self.write_config(ConfigWrite::delete(&key, auth.username()))
+            .await
+    }
+
+    async fn write_config(&self, write: ConfigWrite) -> HandleResult {
+        write_config(&self.config_service, self.raft_node.as_deref(), write)
+            .await
+            .map_err(|e| BaseResponse::error(RESULT_FAIL, e))?;
+        Ok(BaseResponse::success(()))
+    }
+
+    ///
+    /// 增减连接监听的配置，返回 md5 与客户端不一致的配置; 之后的变更通过长连接推送
+    fn config_batch_listen(&self, connection_id: &str, payload: &Payload) -> HandleResult {
+        let request: ConfigBatchListenRequest = decode(payload)?;
+        let auth = self.auth_user(payload)?;
+        let mut contexts = Vec::with_capacity(request.config_listen_contexts.len());
+        for context in request.config_listen_contexts {
+            let key = Self::config_key(&ConfigRequest {
+                data_id: context.data_id.clone(),
+                group: context.group.clone(),
+                tenant: context.tenant.clone(),
+                ..Default::default()
+            })?;
+            auth.check(&key.tenant, false).map_err(forbidden)?;
+            contexts.push((key, context));
+        }
+        let listener = self.config_service.listener();
+        self.connections
+            .with(connection_id, |connection| {
+                for (key, _) in &contexts {
+                    if request.listen {
+                        connection.config_keys.insert(key.clone());
+                    } else {
+                        connection.config_keys.remove(key);
+                    }
+                }
+                listener.update_keys(
+                    connection.config_subscription,
+                    connection.config_keys.iter().cloned().collect(),
+                );
+            })
+            .ok_or_else(unregistered)?;
+        let mut changed_configs = Vec::new();
+        if request.listen {
+            for (key, context) in contexts {
+                let md5 = self
+                    .config_service
+                    .get(&key)
+                    .map_err(|e| BaseResponse::error(RESULT_FAIL, e))?
+                    .map(|v| v.md5)
+                    .unwrap_or_default();
+                if md5 != context.md5 {
+                    changed_configs.push(context);
+                }
+            }
+        }
+        Ok(BaseResponse::success(serde_json::json!({
+            "changedConfigs": changed_configs,
+        })))
+    }
+
+    fn service_key(
+        namespace: &Option<String>,
+        service_name: &Option<String>,
+        group_name: &Option<String>,
+    ) -> Result<ServiceKey, BaseResponse> {
+        InstanceWebParams {
+            namespace_id: namespace.clone(),
+            service_name: service_name.clone(),
+            group_name: group_name.clone(),
+            ..Default::default()
+        }
+        .to_key()
+        .ok_or_else(|| bad_request("parameter 'serviceName' is missing"))
+    }
+
+    fn instance(&self, connection_id: &str, payload: &Payload) -> HandleResult {
+        let request: InstanceRequest = decode(payload)?;
+        let key = Self::service_key(
+            &request.namespace,
+            &request.service_name,
+            &request.group_name,
+        )?;
+        self.auth_user(payload)?
+            .check(&key.namespace_id, true)
+            .map_err(forbidden)?;
+        let v = request.instance;
+        let target = InstanceTarget {
+            key: key.clone(),
+            ip: v
+                .ip
+                .filter(|v| !v.is_empty())
+                .ok_or_else(|| bad_request("parameter 'ip' is missing"))?,
+            port: v
+                .port
+                .ok_or_else(|| bad_request("parameter 'port' is missing"))?,
+            cluster_name: v
+                .cluster_name
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| DEFAULT_CLUSTER.to_owned()),
+        };
+        match request.r#type.as_str() {
+            REGISTER_INSTANCE => {
+                let ephemeral = v.ephemeral.unwrap_or(true);
+                let instance = Instance {
+                    ip: target.ip.clone(),
+                    port: target.port,
+                    weight: v.weight.unwrap_or(1.0),
+                    healthy: v.healthy.unwrap_or(true),
+                    enabled: v.enabled.unwrap_or(true),
+                    ephemeral,
+                    cluster_name: target.cluster_name.clone(),
+                    metadata: v.metadata.unwrap_or_default(),
+                    ..Default::default()
+                };
+                // 临时实例的生命周期跟随连接
+                self.connections
+                    .with(connection_id, |connection| {
+                        if ephemeral {
+                            connection.instances.insert(target.clone());
+                        }
+                    })
+                    .ok_or_else(unregistered)?;
+                self.naming_registry.register(&key, instance);
+            }
+            DE_REGISTER_INSTANCE => {
+                self.connections.with(connection_id, |connection| {
+                    connection.instances.remove(&target);
+                });
+                self.naming_registry.deregister(
+                    &key,
+                    &target.ip,
+                    target.port,
+                    &target.cluster_name,
+                );
+            }
+            v => {
+                return Err(bad_request(format!(
+                    "unsupported instance request type: {}",
+                    v
+                )));
+            }
+        }
+        Ok(BaseResponse::success(serde_json::json!({
+            "type": request.r#type,
+        })))
+    }
+
+    fn service_info(&self, key: &ServiceKey, clusters: &[String]) -> ServiceInstancesResult {
+        let (revision, hosts) = self.naming_registry.list(key, clusters, false);
+        ServiceInstancesResult::new(key, clusters, revision, hosts)
+    }
+
+    ///
+    /// 订阅服务后实例变化时通过长连接推送完整的实例列表
+    fn subscribe_service(self: &Arc<Self>, connection_id: &str, payload: &Payload) -> HandleResult {
+        let request: SubscribeServiceRequest = decode(payload)?;
+        let key = Self::service_key(
+            &request.namespace,
+            &request.service_name,
+            &request.group_name,
+        )?;
+        self.auth_user(payload)?
+            .check(&key.namespace_id, false)
+            .map_err(forbidden)?;
+        let clusters = parse_clusters(request.clusters.as_deref());
+        if request.subscribe {
+            let mut receiver = self.naming_registry.subscribe(&key);
+            receiver.borrow_and_update();
+            let sender = self
+                .connections
+                .with(connection_id, |v| v.sender())
+                .ok_or_else(unregistered)?;
+            let context = self.clone();
+            let task_key = key.clone();
+            let task_clusters = clusters.clone();
+            let task = tokio::spawn(async move {
+                while receiver.changed().await.is_ok() {
+                    let request = NotifySubscriberRequest {
+                        request_id: context.connections.next_request_id(),
+                        module: "naming".to_owned(),
+                        headers: HashMap::new(),
+                        namespace: task_key.namespace_id.clone(),
+                        service_name: task_key.service_name.clone(),
+                        group_name: task_key.group_name.clone(),
+                        service_info: context.service_info(&task_key, &task_clusters),
+                    };
+                    if !push(&sender, NOTIFY_SUBSCRIBER_REQUEST, &request) {
+                        break;
+                    }
+                }
+            });
+            self.connections
+                .with(connection_id, |v| v.subscribe_service(key.clone(), task));
+        } else {
+            self.connections
+                .with(connection_id, |v| v.unsubscribe_service(&key));
+        }
+        Ok(BaseResponse::success(serde_json::json!({
+            "serviceInfo": self.service_info(&key, &clusters),
+        })))
+    }
+
+    ///
+    /// 注册长连接，并启动配置推送与实例续约任务
+    pub fn connect(self: &Arc<Self>, connection_id: &str, client_ip: String, sender: PushSender) {
+        let mut subscription = self.config_service.listener().subscribe(Vec::new());
+        let mut connection = Connection::new(client_ip, sender.clone(), subscription.id());
+        let context = self.clone();
+        connection.add_task(tokio::spawn(async move {
+            loop {
+                for key in subscription.wait_changed(CONFIG_PUSH_WAIT).await {
+                    let request = ConfigChangeNotifyRequest {
+                        request_id: context.connections.next_request_id(),
+                        module: "config".to_owned(),
+                        headers: HashMap::new(),
+                        data_id: key.data_id,
+                        group: key.group,
+                        tenant: key.tenant,
+                    };
+                    if !push(&sender, CONFIG_CHANGE_NOTIFY_REQUEST, &request) {
+                        return;
+                    }
+                }
+            }
+        }));
+        let context = self.clone();
+        let id = connection_id.to_owned();
+        connection.add_task(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(INSTANCE_RENEW_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let Some(targets) = context
+                    .connections
+                    .with(&id, |v| v.instances.iter().cloned().collect::<Vec<_>>())
+                else {
+                    return;
+                };
+                for v in targets {
+                    context
+                        .naming_registry
+                        .beat(&v.key, &v.ip, v.port, &v.cluster_name);
+                }
+            }
+        }));
+        self.connections.add(connection_id, connection);
+        log::info!("grpc connection setup, id:{}", connection_id);
+    }
+
+    pub fn disconnect(&self, connection_id: &str) {
+        self.connections
+            .remove(connection_id, &self.naming_registry);
+    }
+}
+
+///
+/// 通过长连接推送请求，连接已关闭时返回 false
+fn push<T: Serialize>(sender: &PushSender, payload_type: &str, body: &T) -> bool {
+    sender
+        .send(Ok(build_payload(payload_type, body, HashMap::new())))
+        .is_ok()
+}
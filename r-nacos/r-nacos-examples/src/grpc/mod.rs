@@ -0,0 +1,5 @@
+pub mod connection;
+pub mod handler;
+pub mod model;
+pub mod nacos_grpc_service;
+pub mod server;
@@ -0,0 +1,222 @@
+use crate::grpc::nacos_grpc_service::{Metadata, Payload};
+use crate::naming::api::ServiceInstancesResult;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// metadata.type 中的请求与响应类型，与 nacos 2.x 客户端的类名一致
+pub const SERVER_CHECK_REQUEST: &str = "ServerCheckRequest";
+pub const HEALTH_CHECK_REQUEST: &str = "HealthCheckRequest";
+pub const CONNECTION_SETUP_REQUEST: &str = "ConnectionSetupRequest";
+pub const CONFIG_QUERY_REQUEST: &str = "ConfigQueryRequest";
+pub const CONFIG_PUBLISH_REQUEST: &str = "ConfigPublishRequest";
+pub const CONFIG_REMOVE_REQUEST: &str = "ConfigRemoveRequest";
+pub const CONFIG_BATCH_LISTEN_REQUEST: &str = "ConfigBatchListenRequest";
+pub const CONFIG_CHANGE_NOTIFY_REQUEST: &str = "ConfigChangeNotifyRequest";
+pub const INSTANCE_REQUEST: &str = "InstanceRequest";
+pub const SUBSCRIBE_SERVICE_REQUEST: &str = "SubscribeServiceRequest";
+pub const NOTIFY_SUBSCRIBER_REQUEST: &str = "NotifySubscriberRequest";
+pub const ERROR_RESPONSE: &str = "ErrorResponse";
+
+pub const REGISTER_INSTANCE: &str = "registerInstance";
+pub const DE_REGISTER_INSTANCE: &str = "deRegisterInstance";
+
+pub const RESULT_SUCCESS: u16 = 200;
+pub const RESULT_FAIL: u16 = 500;
+// errorCode
+pub const ERROR_CONFIG_NOT_FOUND: u16 = 300;
+pub const ERROR_UNREGISTERED: u16 = 301;
+pub const ERROR_BAD_REQUEST: u16 = 400;
+pub const ERROR_NO_RIGHT: u16 = 403;
+pub const ERROR_NOT_SUPPORTED: u16 = 501;
+
+///
+/// 请求类型对应的响应类型
+pub fn response_type(request_type: &str) -> String {
+    match request_type {
+        CONFIG_BATCH_LISTEN_REQUEST => "ConfigChangeBatchListenResponse".to_owned(),
+        v => match v.strip_suffix("Request") {
+            Some(name) => format!("{}Response", name),
+            None => ERROR_RESPONSE.to_owned(),
+        },
+    }
+}
+
+pub fn payload_type(payload: &Payload) -> &str {
+    payload
+        .metadata
+        .as_ref()
+        .map(|v| v.r#type.as_str())
+        .unwrap_or_default()
+}
+
+pub fn payload_header<'a>(payload: &'a Payload, name: &str) -> Option<&'a str> {
+    payload
+        .metadata
+        .as_ref()
+        .and_then(|v| v.headers.get(name))
+        .map(|v| v.as_str())
+}
+
+///
+/// body 为 json，Any 的 type_url 不使用
+pub fn decode_body<T: DeserializeOwned>(payload: &Payload) -> serde_json::Result<T> {
+    let value = payload
+        .body
+        .as_ref()
+        .map(|v| v.value.as_slice())
+        .unwrap_or(b"{}");
+    serde_json::from_slice(value)
+}
+
+pub fn build_payload<T: Serialize>(
+    payload_type: &str,
+    body: &T,
+    headers: HashMap<String, String>,
+) -> Payload {
+    Payload {
+        metadata: Some(Metadata {
+            r#type: payload_type.to_owned(),
+            client_ip: String::new(),
+            headers,
+        }),
+        body: Some(prost_types::Any {
+            type_url: String::new(),
+            value: serde_json::to_vec(body).unwrap_or_default(),
+        }),
+    }
+}
+
+///
+/// 所有请求共有的字段
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BaseRequest {
+    pub request_id: Option<String>,
+    pub module: Option<String>,
+}
+
+///
+/// 所有响应共有的字段，其它字段放在 data 中平铺输出
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BaseResponse {
+    pub result_code: u16,
+    pub error_code: u16,
+    pub success: bool,
+    pub message: Option<String>,
+    pub request_id: Option<String>,
+    #[serde(flatten)]
+    pub data: serde_json::Map<String, serde_json::Value>,
+}
+
+impl BaseResponse {
+    pub fn success<T: Serialize>(data: T) -> Self {
+        let data = match serde_json::to_value(data) {
+            Ok(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+        Self {
+            result_code: RESULT_SUCCESS,
+            success: true,
+            data,
+            ..Default::default()
+        }
+    }
+
+    pub fn error(error_code: u16, message: impl ToString) -> Self {
+        Self {
+            result_code: RESULT_FAIL,
+            error_code,
+            message: Some(message.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConfigRequest {
+    pub data_id: String,
+    pub group: String,
+    pub tenant: String,
+    pub content: String,
+    pub cas_md5: Option<String>,
+    // 发布时的 type、desc、src_user 等附加字段
+    pub addition_map: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConfigListenContext {
+    pub data_id: String,
+    pub group: String,
+    pub tenant: String,
+    pub md5: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConfigBatchListenRequest {
+    pub listen: bool,
+    pub config_listen_contexts: Vec<ConfigListenContext>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigChangeNotifyRequest {
+    pub request_id: String,
+    pub module: String,
+    pub headers: HashMap<String, String>,
+    pub data_id: String,
+    pub group: String,
+    pub tenant: String,
+}
+
+///
+/// 客户端提交的实例，字段都可能为 null
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GrpcInstance {
+    pub ip: Option<String>,
+    pub port: Option<u32>,
+    pub weight: Option<f64>,
+    pub healthy: Option<bool>,
+    pub enabled: Option<bool>,
+    pub ephemeral: Option<bool>,
+    pub cluster_name: Option<String>,
+    pub metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InstanceRequest {
+    pub namespace: Option<String>,
+    pub service_name: Option<String>,
+    pub group_name: Option<String>,
+    pub r#type: String,
+    pub instance: GrpcInstance,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SubscribeServiceRequest {
+    pub namespace: Option<String>,
+    pub service_name: Option<String>,
+    pub group_name: Option<String>,
+    // 逗号分隔的集群列表
+    pub clusters: Option<String>,
+    pub subscribe: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotifySubscriberRequest {
+    pub request_id: String,
+    pub module: String,
+    pub headers: HashMap<String, String>,
+    pub namespace: String,
+    pub service_name: String,
+    pub group_name: String,
+    pub service_info: ServiceInstancesResult,
+}
@@ -0,0 +1,352 @@
+// Rust module for 'nacos_grpc_service.proto', in the layout of tonic-build 0.12 output
+
+#![allow(clippy::all)]
+#![cfg_attr(rustfmt, rustfmt_skip)]
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Metadata {
+    #[prost(string, tag = "3")]
+    pub r#type: ::prost::alloc::string::String,
+    #[prost(string, tag = "8")]
+    pub client_ip: ::prost::alloc::string::String,
+    #[prost(map = "string, string", tag = "7")]
+    pub headers: ::std::collections::HashMap<::prost::alloc::string::String, ::prost::alloc::string::String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Payload {
+    #[prost(message, optional, tag = "2")]
+    pub metadata: ::core::option::Option<Metadata>,
+    #[prost(message, optional, tag = "3")]
+    pub body: ::core::option::Option<::prost_types::Any>,
+}
+
+pub mod request_client {
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+
+    #[derive(Debug, Clone)]
+    pub struct RequestClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+
+    impl RequestClient<tonic::transport::Channel> {
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+
+    impl<T> RequestClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + std::marker::Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + std::marker::Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+
+        /// Sends a commonRequest
+        pub async fn request(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Payload>,
+        ) -> std::result::Result<tonic::Response<super::Payload>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/Request/request");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("Request", "request"));
+            self.inner.unary(req, path, codec).await
+        }
+    }
+}
+
+pub mod bi_request_stream_client {
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+
+    #[derive(Debug, Clone)]
+    pub struct BiRequestStreamClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+
+    impl BiRequestStreamClient<tonic::transport::Channel> {
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+
+    impl<T> BiRequestStreamClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + std::marker::Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + std::marker::Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+
+        /// Sends a biStreamRequest
+        pub async fn request_bi_stream(
+            &mut self,
+            request: impl tonic::IntoStreamingRequest<Message = super::Payload>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::Payload>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/BiRequestStream/requestBiStream",
+            );
+            let mut req = request.into_streaming_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("BiRequestStream", "requestBiStream"));
+            self.inner.streaming(req, path, codec).await
+        }
+    }
+}
+
+pub mod request_server {
+    use tonic::codegen::*;
+
+    #[async_trait]
+    pub trait Request: std::marker::Send + std::marker::Sync + 'static {
+        /// Sends a commonRequest
+        async fn request(
+            &self,
+            request: tonic::Request<super::Payload>,
+        ) -> std::result::Result<tonic::Response<super::Payload>, tonic::Status>;
+    }
+
+    #[derive(Debug)]
+    pub struct RequestServer<T> {
+        inner: Arc<T>,
+    }
+
+    impl<T> RequestServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            Self { inner }
+        }
+    }
+
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for RequestServer<T>
+    where
+        T: Request,
+        B: Body + std::marker::Send + 'static,
+        B::Error: Into<StdError> + std::marker::Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            match req.uri().path() {
+                "/Request/request" => {
+                    #[allow(non_camel_case_types)]
+                    struct requestSvc<T: Request>(pub Arc<T>);
+                    impl<T: Request> tonic::server::UnaryService<super::Payload>
+                    for requestSvc<T> {
+                        type Response = super::Payload;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Payload>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Request>::request(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = requestSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec);
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => {
+                    Box::pin(async move {
+                        Ok(tonic::Status::unimplemented("").into_http())
+                    })
+                }
+            }
+        }
+    }
+
+    impl<T> Clone for RequestServer<T> {
+        fn clone(&self) -> Self {
+            Self { inner: self.inner.clone() }
+        }
+    }
+
+    pub const SERVICE_NAME: &str = "Request";
+
+    impl<T> tonic::server::NamedService for RequestServer<T> {
+        const NAME: &'static str = SERVICE_NAME;
+    }
+}
+
+pub mod bi_request_stream_server {
+    use tonic::codegen::*;
+
+    #[async_trait]
+    pub trait BiRequestStream: std::marker::Send + std::marker::Sync + 'static {
+        /// Server streaming response type for the requestBiStream method.
+        type RequestBiStreamStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::Payload, tonic::Status>,
+            >
+            + std::marker::Send
+            + 'static;
+
+        /// Sends a biStreamRequest
+        async fn request_bi_stream(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::Payload>>,
+        ) -> std::result::Result<
+            tonic::Response<Self::RequestBiStreamStream>,
+            tonic::Status,
+        >;
+    }
+
+    #[derive(Debug)]
+    pub struct BiRequestStreamServer<T> {
+        inner: Arc<T>,
+    }
+
+    impl<T> BiRequestStreamServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            Self { inner }
+        }
+    }
+
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for BiRequestStreamServer<T>
+    where
+        T: BiRequestStream,
+        B: Body + std::marker::Send + 'static,
+        B::Error: Into<StdError> + std::marker::Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            match req.uri().path() {
+                "/BiRequestStream/requestBiStream" => {
+                    #[allow(non_camel_case_types)]
+                    struct requestBiStreamSvc<T: BiRequestStream>(pub Arc<T>);
+                    impl<T: BiRequestStream> tonic::server::StreamingService<super::Payload>
+                    for requestBiStreamSvc<T> {
+                        type Response = super::Payload;
+                        type ResponseStream = T::RequestBiStreamStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<tonic::Streaming<super::Payload>>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BiRequestStream>::request_bi_stream(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = requestBiStreamSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec);
+                        let res = grpc.streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => {
+                    Box::pin(async move {
+                        Ok(tonic::Status::unimplemented("").into_http())
+                    })
+                }
+            }
+        }
+    }
+
+    impl<T> Clone for BiRequestStreamServer<T> {
+        fn clone(&self) -> Self {
+            Self { inner: self.inner.clone() }
+        }
+    }
+
+    pub const SERVICE_NAME: &str = "BiRequestStream";
+
+    impl<T> tonic::server::NamedService for BiRequestStreamServer<T> {
+        const NAME: &'static str = SERVICE_NAME;
+    }
+}
@@ -0,0 +1,336 @@
+use crate::grpc::handler::GrpcContext;
+use crate::grpc::model::{CONNECTION_SETUP_REQUEST, payload_type};
+use crate::grpc::nacos_grpc_service::Payload;
+use crate::grpc::nacos_grpc_service::bi_request_stream_server::{
+    BiRequestStream, BiRequestStreamServer,
+};
+use crate::grpc::nacos_grpc_service::request_server::{Request, RequestServer};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::{TcpListenerStream, UnboundedReceiverStream};
+use tonic::{Response, Status, Streaming};
+
+///
+/// 同一 http2 连接上的 Request 与 BiRequestStream 请求对端地址相同，以此作为连接 id
+fn connection_id<T>(request: &tonic::Request<T>) -> Option<String> {
+    request.remote_addr().map(|v| v.to_string())
+}
+
+fn unknown_remote() -> Status {
+    Status::invalid_argument("remote address is unknown")
+}
+
+pub struct GrpcServer {
+    context: Arc<GrpcContext>,
+}
+
+impl GrpcServer {
+    pub fn new(context: Arc<GrpcContext>) -> Self {
+        Self { context }
+    }
+}
+
+#[tonic::async_trait]
+impl Request for GrpcServer {
+    async fn request(&self, request: tonic::Request<Payload>) -> Result<Response<Payload>, Status> {
+        let id = connection_id(&request).ok_or_else(unknown_remote)?;
+        let payload = self.context.handle_request(&id, request.into_inner()).await;
+        Ok(Response::new(payload))
+    }
+}
+
+#[tonic::async_trait]
+impl BiRequestStream for GrpcServer {
+    type RequestBiStreamStream = UnboundedReceiverStream<Result<Payload, Status>>;
+
+    ///
+    /// 服务端向客户端推送的请求走这个流; 客户端发来的推送响应只用于确认，不需要处理
+    async fn request_bi_stream(
+        &self,
+        request: tonic::Request<Streaming<Payload>>,
+    ) -> Result<Response<Self::RequestBiStreamStream>, Status> {
+        let id = connection_id(&request).ok_or_else(unknown_remote)?;
+        let client_ip = request
+            .remote_addr()
+            .map(|v| v.ip().to_string())
+            .unwrap_or_default();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.context.connect(&id, client_ip, sender);
+        let context = self.context.clone();
+        let mut inbound = request.into_inner();
+        tokio::spawn(async move {
+            while let Some(Ok(payload)) = inbound.next().await {
+                if payload_type(&payload) != CONNECTION_SETUP_REQUEST {
+                    continue;
+                }
+                let client_ip = payload
+                    .metadata
+                    .as_ref()
+                    .map(|v| v.client_ip.clone())
+                    .filter(|v| !v.is_empty());
+                if let Some(client_ip) = client_ip {
+                    context.connections.with(&id, |v| v.client_ip = client_ip);
+                }
+            }
+            context.disconnect(&id);
+        });
+        Ok(Response::new(UnboundedReceiverStream::new(receiver)))
+    }
+}
+
+///
+/// 在 listener 上启动 nacos 2.x 的 grpc 服务
+pub async fn serve(
+    context: Arc<GrpcContext>,
+    listener: tokio::net::TcpListener,
+) -> Result<(), tonic::transport::Error> {
+    let server = Arc::new(GrpcServer::new(context));
+    tonic::transport::Server::builder()
+        .add_service(RequestServer::from_arc(server.clone()))
+        .add_service(BiRequestStreamServer::from_arc(server))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::constant::GRPC_HEAD_KEY_TRACE_ID;
+    use crate::config::service::{ConfigService, content_md5};
+    use crate::grpc::model::*;
+    use crate::grpc::nacos_grpc_service::bi_request_stream_client::BiRequestStreamClient;
+    use crate::grpc::nacos_grpc_service::request_client::RequestClient;
+    use crate::naming::model::{Instance, ServiceKey};
+    use crate::naming::registry::NamingRegistry;
+    use crate::transfer::data_to_sqlite::open_init_db;
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use tonic::transport::Channel;
+
+    async fn start_server(
+        name: &str,
+    ) -> anyhow::Result<(Arc<GrpcContext>, Channel, std::path::PathBuf)> {
+        let db = std::env::temp_dir().join(format!("{}_{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&db);
+        let context = Arc::new(GrpcContext::new(
+            Arc::new(ConfigService::new(
+                open_init_db(&db.to_string_lossy()).await?,
+                1024,
+            )),
+            Arc::new(NamingRegistry::new(15000, 30000)),
+        ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(serve(context.clone(), listener));
+        let channel = tonic::transport::Endpoint::from_shared(format!("http://{}", addr))?
+            .connect()
+            .await?;
+        Ok((context, channel, db))
+    }
+
+    async fn call(
+        client: &mut RequestClient<Channel>,
+        request_type: &str,
+        body: serde_json::Value,
+    ) -> anyhow::Result<(String, serde_json::Value)> {
+        let payload = build_payload(request_type, &body, HashMap::new());
+        let payload = client.request(payload).await?.into_inner();
+        Ok((payload_type(&payload).to_owned(), decode_body(&payload)?))
+    }
+
+    async fn next_push(stream: &mut Streaming<Payload>) -> (String, serde_json::Value) {
+        let payload = tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        (
+            payload_type(&payload).to_owned(),
+            decode_body(&payload).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_grpc_server() -> anyhow::Result<()> {
+        let (context, channel, db) = start_server("grpc_server").await?;
+        let mut client = RequestClient::new(channel.clone());
+
+        let (res_type, res) = call(
+            &mut client,
+            SERVER_CHECK_REQUEST,
+            serde_json::json!({"requestId": "1"}),
+        )
+        .await?;
+        assert_eq!(res_type, "ServerCheckResponse");
+        assert_eq!(res["resultCode"], 200);
+        assert_eq!(res["requestId"], "1");
+        assert!(!res["connectionId"].as_str().unwrap().is_empty());
+
+        // 未建立长连接时不能监听
+        let listen = serde_json::json!({
+            "listen": true,
+            "configListenContexts": [{"dataId": "app.yaml", "group": "DEFAULT_GROUP", "md5": ""}],
+        });
+        let (_, res) = call(&mut client, CONFIG_BATCH_LISTEN_REQUEST, listen.clone()).await?;
+        assert_eq!(res["errorCode"], ERROR_UNREGISTERED);
+
+        let (outbound, receiver) = mpsc::unbounded_channel();
+        outbound.send(build_payload(
+            CONNECTION_SETUP_REQUEST,
+            &serde_json::json!({"clientVersion": "Nacos-Java-Client:v2.3.0"}),
+            HashMap::new(),
+        ))?;
+        let mut stream = BiRequestStreamClient::new(channel)
+            .request_bi_stream(UnboundedReceiverStream::new(receiver))
+            .await?
+            .into_inner();
+        for _ in 0..100 {
+            if context.connections.count() == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(context.connections.count(), 1);
+
+        let config = serde_json::json!({"dataId": "app.yaml", "group": "DEFAULT_GROUP"});
+        let (res_type, res) = call(&mut client, CONFIG_QUERY_REQUEST, config.clone()).await?;
+        assert_eq!(res_type, "ConfigQueryResponse");
+        assert_eq!(res["errorCode"], ERROR_CONFIG_NOT_FOUND);
+        let (_, res) = call(&mut client, CONFIG_BATCH_LISTEN_REQUEST, listen).await?;
+        assert!(res["changedConfigs"].as_array().unwrap().is_empty());
+
+        let (res_type, res) = call(
+            &mut client,
+            CONFIG_PUBLISH_REQUEST,
+            serde_json::json!({
+                "dataId": "app.yaml",
+                "group": "DEFAULT_GROUP",
+                "content": "a: 1",
+                "additionMap": {"type": "yaml"},
+            }),
+        )
+        .await?;
+        assert_eq!(res_type, "ConfigPublishResponse");
+        assert_eq!(res["success"], true);
+        let (push_type, push) = next_push(&mut stream).await;
+        assert_eq!(push_type, CONFIG_CHANGE_NOTIFY_REQUEST);
+        assert_eq!(push["dataId"], "app.yaml");
+
+        // 请求的 trace_id 头原样带回
+        let payload = build_payload(
+            CONFIG_QUERY_REQUEST,
+            &config,
+            HashMap::from([(GRPC_HEAD_KEY_TRACE_ID.to_owned(), "t-1".to_owned())]),
+        );
+        let payload = client.request(payload).await?.into_inner();
+        assert_eq!(
+            payload_header(&payload, GRPC_HEAD_KEY_TRACE_ID),
+            Some("t-1")
+        );
+        let res: serde_json::Value = decode_body(&payload)?;
+        assert_eq!(res["content"], "a: 1");
+        assert_eq!(res["md5"], content_md5("a: 1"));
+        assert_eq!(res["contentType"], "yaml");
+
+        let (_, res) = call(
+            &mut client,
+            CONFIG_PUBLISH_REQUEST,
+            serde_json::json!({"dataId": "app.yaml", "group": "DEFAULT_GROUP", "content": "a: 2", "casMd5": "x"}),
+        )
+        .await?;
+        assert_eq!(res["success"], false);
+
+        // 注册实例并订阅服务，实例变化时推送
+        let service = serde_json::json!({"serviceName": "demo", "groupName": "DEFAULT_GROUP"});
+        let mut register = service.clone();
+        register["type"] = REGISTER_INSTANCE.into();
+        register["instance"] =
+            serde_json::json!({"ip": "10.0.0.1", "port": 8080, "instanceId": null});
+        let (res_type, res) = call(&mut client, INSTANCE_REQUEST, register).await?;
+        assert_eq!(res_type, "InstanceResponse");
+        assert_eq!(res["type"], REGISTER_INSTANCE);
+        let mut subscribe = service.clone();
+        subscribe["subscribe"] = true.into();
+        let (res_type, res) = call(&mut client, SUBSCRIBE_SERVICE_REQUEST, subscribe).await?;
+        assert_eq!(res_type, "SubscribeServiceResponse");
+        assert_eq!(res["serviceInfo"]["hosts"].as_array().unwrap().len(), 1);
+
+        let key = ServiceKey::new("public", "DEFAULT_GROUP", "demo");
+        context.naming_registry.register(
+            &key,
+            Instance {
+                ip: "10.0.0.2".to_owned(),
+                port: 8080,
+                ..Default::default()
+            },
+        );
+        let (push_type, push) = next_push(&mut stream).await;
+        assert_eq!(push_type, NOTIFY_SUBSCRIBER_REQUEST);
+        assert_eq!(push["serviceInfo"]["hosts"].as_array().unwrap().len(), 2);
+
+        let (res_type, res) = call(&mut client, "UnknownRequest", serde_json::json!({})).await?;
+        assert_eq!(res_type, ERROR_RESPONSE);
+        assert_eq!(res["errorCode"], ERROR_NOT_SUPPORTED);
+
+        // 长连接断开后注销通过它注册的临时实例
+        drop(outbound);
+        for _ in 0..100 {
+            if context.connections.count() == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(context.connections.count(), 0);
+        let (_, hosts) = context.naming_registry.list(&key, &[], false);
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].ip, "10.0.0.2");
+        std::fs::remove_file(&db)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grpc_auth() -> anyhow::Result<()> {
+        use crate::auth::service::{AuthOption, AuthService};
+        use crate::common::constant::ACCESS_TOKEN_HEADER;
+
+        let db = std::env::temp_dir().join(format!("grpc_auth_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db);
+        let auth_service = AuthService::new(
+            open_init_db(&db.to_string_lossy()).await?,
+            AuthOption {
+                openapi_enable_auth: true,
+                console_login_timeout: 3600,
+                console_login_one_hour_limit: 5,
+                openapi_login_timeout: 3600,
+                openapi_login_one_minute_limit: 100,
+                init_admin_username: "admin".to_owned(),
+                init_admin_password: "admin123".to_owned(),
+            },
+        )?;
+        let (token, _) = auth_service.openapi_login("admin", "admin123", "127.0.0.1")?;
+        let context = GrpcContext {
+            auth_service: Some(Arc::new(auth_service)),
+            ..GrpcContext::new(
+                Arc::new(ConfigService::new(
+                    open_init_db(&db.to_string_lossy()).await?,
+                    1024,
+                )),
+                Arc::new(NamingRegistry::new(15000, 30000)),
+            )
+        };
+        let context = Arc::new(context);
+        let config =
+            serde_json::json!({"dataId": "app.yaml", "group": "DEFAULT_GROUP", "content": "a"});
+        let payload = build_payload(CONFIG_PUBLISH_REQUEST, &config, HashMap::new());
+        let res: serde_json::Value = decode_body(&context.handle_request("c1", payload).await)?;
+        assert_eq!(res["errorCode"], ERROR_NO_RIGHT);
+
+        let headers = HashMap::from([(ACCESS_TOKEN_HEADER.to_owned(), token)]);
+        let payload = build_payload(CONFIG_PUBLISH_REQUEST, &config, headers);
+        let res: serde_json::Value = decode_body(&context.handle_request("c1", payload).await)?;
+        assert_eq!(res["success"], true);
+        std::fs::remove_file(&db)?;
+        Ok(())
+    }
+}
@@ -1,3 +1,10 @@
+pub mod auth;
+pub mod backup;
 pub mod cli;
 pub mod common;
+pub mod config;
+pub mod grpc;
+pub mod metrics;
+pub mod naming;
+pub mod raft;
 pub mod transfer;
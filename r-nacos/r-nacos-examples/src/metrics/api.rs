@@ -0,0 +1,90 @@
+use crate::metrics::manager::METRICS;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{HttpResponse, web};
+
+///
+/// 记录 http 请求数与耗时，通过 `middleware::from_fn(http_metrics)` 挂到 App 上
+pub async fn http_metrics(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let _timer = METRICS.http_request_duration.start_timer();
+    METRICS.http_request_total.inc();
+    next.call(req).await
+}
+
+async fn prometheus_metrics() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(METRICS.to_prometheus())
+}
+
+///
+/// 注册 prometheus 拉取指标的接口
+pub fn app_config(config: &mut web::ServiceConfig) {
+    config.route("/metrics", web::get().to(prometheus_metrics));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::service::ConfigService;
+    use crate::metrics::manager::MetricsCollector;
+    use crate::naming::model::{Instance, ServiceKey};
+    use crate::naming::registry::NamingRegistry;
+    use crate::transfer::data_to_sqlite::open_init_db;
+    use actix_web::middleware::from_fn;
+    use actix_web::{App, test};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[actix_rt::test]
+    async fn test_metrics_api() -> anyhow::Result<()> {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(http_metrics))
+                .route("/hello", web::get().to(HttpResponse::Ok))
+                .configure(app_config),
+        )
+        .await;
+        let before = METRICS.http_request_total.get();
+        let req = test::TestRequest::get().uri("/hello").to_request();
+        test::call_service(&app, req).await;
+        assert!(METRICS.http_request_total.get() > before);
+
+        let db = std::env::temp_dir().join(format!("metrics_api_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db);
+        let config_service = Arc::new(ConfigService::new(
+            open_init_db(&db.to_string_lossy()).await?,
+            1024,
+        ));
+        let naming_registry = Arc::new(NamingRegistry::new(15000, 30000));
+        naming_registry.register(
+            &ServiceKey::new("metrics-test", "DEFAULT_GROUP", "demo"),
+            Instance {
+                ip: "10.0.0.1".to_owned(),
+                port: 8080,
+                ..Default::default()
+            },
+        );
+        let _subscription = config_service.listener().subscribe(Vec::new());
+        let mut collector = MetricsCollector::new(config_service.clone(), naming_registry);
+        METRICS.config_query_total.add(10);
+        collector.collect(Duration::from_secs(2));
+        assert!(METRICS.config_query_rate.get() >= 5.0);
+        assert_eq!(METRICS.naming_instance_count.get(), 1.0);
+        assert_eq!(METRICS.config_listener_count.get(), 1.0);
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let text = String::from_utf8(body.to_vec())?;
+        assert!(text.contains("# TYPE http_request_total counter\n"));
+        assert!(text.contains("# TYPE http_request_duration_seconds histogram\n"));
+        assert!(text.contains("naming_instance_count 1\n"));
+        assert!(text.contains("sqlite_duration_seconds_bucket{le=\"+Inf\"}"));
+        std::fs::remove_file(&db)?;
+        Ok(())
+    }
+}
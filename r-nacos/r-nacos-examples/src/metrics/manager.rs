@@ -0,0 +1,200 @@
+use crate::common::AppSysConfig;
+use crate::config::service::ConfigService;
+use crate::metrics::model::{Counter, Gauge, Histogram, MetricValue, write_prometheus};
+use crate::naming::registry::NamingRegistry;
+use std::sync::Arc;
+use std::time::Duration;
+
+lazy_static::lazy_static! {
+    ///
+    /// 进程内唯一的指标集合，各模块直接记录
+    pub static ref METRICS: AppMetrics = AppMetrics::default();
+}
+
+#[derive(Debug, Default)]
+pub struct AppMetrics {
+    pub http_request_total: Counter,
+    pub http_request_duration: Histogram,
+    pub config_publish_total: Counter,
+    pub config_query_total: Counter,
+    pub sqlite_duration: Histogram,
+    // 以下为定时采集的值
+    pub http_request_rate: Gauge,
+    pub config_publish_rate: Gauge,
+    pub config_query_rate: Gauge,
+    pub config_listener_count: Gauge,
+    pub naming_service_count: Gauge,
+    pub naming_instance_count: Gauge,
+}
+
+impl AppMetrics {
+    fn items(&self) -> Vec<(&'static str, &'static str, MetricValue<'_>)> {
+        vec![
+            (
+                "http_request_total",
+                "Total number of http requests",
+                MetricValue::Counter(&self.http_request_total),
+            ),
+            (
+                "http_request_duration_seconds",
+                "Http request latency in seconds",
+                MetricValue::Histogram(&self.http_request_duration),
+            ),
+            (
+                "http_request_rate",
+                "Http requests per second in the last collect interval",
+                MetricValue::Gauge(&self.http_request_rate),
+            ),
+            (
+                "config_publish_total",
+                "Total number of config publish and delete",
+                MetricValue::Counter(&self.config_publish_total),
+            ),
+            (
+                "config_publish_rate",
+                "Config publish per second in the last collect interval",
+                MetricValue::Gauge(&self.config_publish_rate),
+            ),
+            (
+                "config_query_total",
+                "Total number of config query",
+                MetricValue::Counter(&self.config_query_total),
+            ),
+            (
+                "config_query_rate",
+                "Config query per second in the last collect interval",
+                MetricValue::Gauge(&self.config_query_rate),
+            ),
+            (
+                "config_listener_count",
+                "Number of config listeners",
+                MetricValue::Gauge(&self.config_listener_count),
+            ),
+            (
+                "naming_service_count",
+                "Number of naming services",
+                MetricValue::Gauge(&self.naming_service_count),
+            ),
+            (
+                "naming_instance_count",
+                "Number of naming instances",
+                MetricValue::Gauge(&self.naming_instance_count),
+            ),
+            (
+                "sqlite_duration_seconds",
+                "Sqlite operation latency in seconds",
+                MetricValue::Histogram(&self.sqlite_duration),
+            ),
+        ]
+    }
+
+    ///
+    /// prometheus 文本格式
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        for (name, help, value) in self.items() {
+            write_prometheus(&mut out, name, help, &value);
+        }
+        out
+    }
+
+    ///
+    /// 用于日志的单行摘要，直方图输出次数与平均值
+    pub fn summary(&self) -> String {
+        self.items()
+            .into_iter()
+            .map(|(name, _, value)| match value {
+                MetricValue::Counter(v) => format!("{}={}", name, v.get()),
+                MetricValue::Gauge(v) => format!("{}={:.2}", name, v.get()),
+                MetricValue::Histogram(v) => {
+                    let snapshot = v.snapshot();
+                    let avg = snapshot.sum / snapshot.count.max(1) as f64;
+                    format!("{}_count={},avg={:.6}", name, snapshot.count, avg)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricsOption {
+    pub collect_interval: Duration,
+    pub log_interval: Duration,
+    pub log_enable: bool,
+}
+
+impl From<&AppSysConfig> for MetricsOption {
+    fn from(v: &AppSysConfig) -> Self {
+        Self {
+            collect_interval: Duration::from_secs(v.metrics_collect_interval_second.max(1)),
+            log_interval: Duration::from_secs(v.metrics_log_interval_second.max(1)),
+            log_enable: v.metrics_log_enable,
+        }
+    }
+}
+
+///
+/// 定时采集监听数、实例数等当前值，并按计数的增量计算每秒速率
+pub struct MetricsCollector {
+    config_service: Arc<ConfigService>,
+    naming_registry: Arc<NamingRegistry>,
+    // 上次采集时的 (http 请求数, 配置发布数, 配置查询数)
+    last_totals: (u64, u64, u64),
+}
+
+impl MetricsCollector {
+    pub fn new(config_service: Arc<ConfigService>, naming_registry: Arc<NamingRegistry>) -> Self {
+        Self {
+            config_service,
+            naming_registry,
+            last_totals: Self::totals(),
+        }
+    }
+
+    fn totals() -> (u64, u64, u64) {
+        (
+            METRICS.http_request_total.get(),
+            METRICS.config_publish_total.get(),
+            METRICS.config_query_total.get(),
+        )
+    }
+
+    pub fn collect(&mut self, interval: Duration) {
+        let seconds = interval.as_secs_f64().max(0.001);
+        let totals = Self::totals();
+        let rate = |now: u64, last: u64| now.saturating_sub(last) as f64 / seconds;
+        METRICS
+            .http_request_rate
+            .set(rate(totals.0, self.last_totals.0));
+        METRICS
+            .config_publish_rate
+            .set(rate(totals.1, self.last_totals.1));
+        METRICS
+            .config_query_rate
+            .set(rate(totals.2, self.last_totals.2));
+        self.last_totals = totals;
+
+        METRICS
+            .config_listener_count
+            .set(self.config_service.listener().listener_count() as f64);
+        let (service_count, instance_count) = self.naming_registry.counts();
+        METRICS.naming_service_count.set(service_count as f64);
+        METRICS.naming_instance_count.set(instance_count as f64);
+    }
+
+    pub fn spawn(mut self, option: MetricsOption) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(option.collect_interval);
+            let mut last_log = tokio::time::Instant::now();
+            loop {
+                ticker.tick().await;
+                self.collect(option.collect_interval);
+                if option.log_enable && last_log.elapsed() >= option.log_interval {
+                    last_log = tokio::time::Instant::now();
+                    log::info!("metrics: {}", METRICS.summary());
+                }
+            }
+        })
+    }
+}
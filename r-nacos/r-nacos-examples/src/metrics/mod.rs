@@ -0,0 +1,3 @@
+pub mod api;
+pub mod manager;
+pub mod model;
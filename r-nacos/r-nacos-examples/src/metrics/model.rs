@@ -0,0 +1,192 @@
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+///
+/// 单调递增的计数
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, v: u64) {
+        self.0.fetch_add(v, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+///
+/// 可增可减的当前值，f64 按位存在 AtomicU64 中
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicU64);
+
+impl Gauge {
+    pub fn set(&self, v: f64) {
+        self.0.store(v.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistogramSnapshot {
+    // 每个桶的累计数量，与 buckets 一一对应
+    pub bucket_counts: Vec<u64>,
+    pub sum: f64,
+    pub count: u64,
+}
+
+///
+/// 按上界分桶统计的分布，单位为秒
+#[derive(Debug)]
+pub struct Histogram {
+    buckets: Vec<f64>,
+    inner: Mutex<HistogramSnapshot>,
+}
+
+// 覆盖 1ms 到 10s 的耗时
+pub const DEFAULT_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUCKETS)
+    }
+}
+
+impl Histogram {
+    pub fn new(buckets: &[f64]) -> Self {
+        Self {
+            buckets: buckets.to_vec(),
+            inner: Mutex::new(HistogramSnapshot {
+                bucket_counts: vec![0; buckets.len()],
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn observe(&self, v: f64) {
+        let mut inner = self.inner.lock().unwrap();
+        for (bound, count) in self.buckets.iter().zip(inner.bucket_counts.iter_mut()) {
+            if v <= *bound {
+                *count += 1;
+            }
+        }
+        inner.sum += v;
+        inner.count += 1;
+    }
+
+    ///
+    /// 返回的计时器 drop 时记录经过的秒数
+    pub fn start_timer(&self) -> HistogramTimer<'_> {
+        HistogramTimer {
+            histogram: self,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        self.inner.lock().unwrap().clone()
+    }
+}
+
+pub struct HistogramTimer<'a> {
+    histogram: &'a Histogram,
+    start: Instant,
+}
+
+impl Drop for HistogramTimer<'_> {
+    fn drop(&mut self) {
+        self.histogram.observe(self.start.elapsed().as_secs_f64());
+    }
+}
+
+pub enum MetricValue<'a> {
+    Counter(&'a Counter),
+    Gauge(&'a Gauge),
+    Histogram(&'a Histogram),
+}
+
+impl MetricValue<'_> {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Counter(_) => "counter",
+            Self::Gauge(_) => "gauge",
+            Self::Histogram(_) => "histogram",
+        }
+    }
+}
+
+///
+/// 按 prometheus 文本格式输出一个指标
+pub fn write_prometheus(out: &mut String, name: &str, help: &str, value: &MetricValue) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, value.type_name());
+    match value {
+        MetricValue::Counter(v) => {
+            let _ = writeln!(out, "{} {}", name, v.get());
+        }
+        MetricValue::Gauge(v) => {
+            let _ = writeln!(out, "{} {}", name, v.get());
+        }
+        MetricValue::Histogram(v) => {
+            let snapshot = v.snapshot();
+            for (bound, count) in v.buckets.iter().zip(&snapshot.bucket_counts) {
+                let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+            }
+            let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, snapshot.count);
+            let _ = writeln!(out, "{}_sum {}", name, snapshot.sum);
+            let _ = writeln!(out, "{}_count {}", name, snapshot.count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prometheus_text() {
+        let histogram = Histogram::new(&[0.1, 1.0]);
+        histogram.observe(0.05);
+        histogram.observe(0.5);
+        histogram.observe(3.0);
+        let mut out = String::new();
+        write_prometheus(
+            &mut out,
+            "demo_seconds",
+            "demo latency",
+            &MetricValue::Histogram(&histogram),
+        );
+        assert_eq!(
+            out,
+            "# HELP demo_seconds demo latency\n\
+             # TYPE demo_seconds histogram\n\
+             demo_seconds_bucket{le=\"0.1\"} 1\n\
+             demo_seconds_bucket{le=\"1\"} 2\n\
+             demo_seconds_bucket{le=\"+Inf\"} 3\n\
+             demo_seconds_sum 3.55\n\
+             demo_seconds_count 3\n"
+        );
+
+        let counter = Counter::default();
+        counter.add(2);
+        let gauge = Gauge::default();
+        gauge.set(1.5);
+        let mut out = String::new();
+        write_prometheus(&mut out, "c", "c", &MetricValue::Counter(&counter));
+        write_prometheus(&mut out, "g", "g", &MetricValue::Gauge(&gauge));
+        assert!(out.contains("# TYPE c counter\nc 2\n"));
+        assert!(out.contains("# TYPE g gauge\ng 1.5\n"));
+    }
+}
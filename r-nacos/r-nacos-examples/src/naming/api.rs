@@ -0,0 +1,371 @@
+use crate::auth::api::AuthUser;
+use crate::naming::model::{
+    DEFAULT_CLUSTER, DEFAULT_GROUP, DEFAULT_NAMESPACE, GROUP_SPLIT, Instance, ServiceKey,
+};
+use crate::naming::registry::NamingRegistry;
+use actix_web::{HttpResponse, web};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+// 客户端心跳间隔
+const CLIENT_BEAT_INTERVAL: u64 = 5000;
+const BEAT_OK: i32 = 10200;
+const BEAT_NOT_FOUND: i32 = 20404;
+// 长轮询最长挂起时间
+const MAX_WAIT_MS: u64 = 30000;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceWebParams {
+    pub ip: Option<String>,
+    pub port: Option<u32>,
+    pub namespace_id: Option<String>,
+    pub service_name: Option<String>,
+    pub group_name: Option<String>,
+    pub cluster_name: Option<String>,
+    pub weight: Option<f64>,
+    pub enabled: Option<bool>,
+    pub healthy: Option<bool>,
+    pub ephemeral: Option<bool>,
+    // json 格式的元数据
+    pub metadata: Option<String>,
+    // json 格式的心跳信息
+    pub beat: Option<String>,
+    // 逗号分隔的集群列表
+    pub clusters: Option<String>,
+    pub healthy_only: Option<bool>,
+    // 长轮询: 客户端已知的版本号与等待的毫秒数
+    pub revision: Option<u64>,
+    pub timeout: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BeatInfo {
+    pub ip: String,
+    pub port: u32,
+    pub cluster: Option<String>,
+    pub service_name: Option<String>,
+    pub weight: Option<f64>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+impl InstanceWebParams {
+    ///
+    /// serviceName 带 group@@ 前缀时以前缀为分组
+    pub fn to_key(&self) -> Option<ServiceKey> {
+        let service_name = self.service_name.as_deref().filter(|v| !v.is_empty())?;
+        let (group_name, service_name) = match service_name.split_once(GROUP_SPLIT) {
+            Some((group, name)) => (group, name),
+            None => (
+                self.group_name
+                    .as_deref()
+                    .filter(|v| !v.is_empty())
+                    .unwrap_or(DEFAULT_GROUP),
+                service_name,
+            ),
+        };
+        let namespace_id = self
+            .namespace_id
+            .as_deref()
+            .filter(|v| !v.is_empty())
+            .unwrap_or(DEFAULT_NAMESPACE);
+        Some(ServiceKey::new(namespace_id, group_name, service_name))
+    }
+
+    fn cluster_name(&self) -> &str {
+        self.cluster_name
+            .as_deref()
+            .filter(|v| !v.is_empty())
+            .unwrap_or(DEFAULT_CLUSTER)
+    }
+
+    fn to_instance(&self) -> Result<Instance, String> {
+        let metadata = match self.metadata.as_deref().filter(|v| !v.is_empty()) {
+            Some(v) => serde_json::from_str(v).map_err(|e| format!("invalid metadata: {}", e))?,
+            None => HashMap::new(),
+        };
+        Ok(Instance {
+            ip: self.ip.clone().unwrap_or_default(),
+            port: self.port.unwrap_or_default(),
+            weight: self.weight.unwrap_or(1.0),
+            enabled: self.enabled.unwrap_or(true),
+            healthy: self.healthy.unwrap_or(true),
+            ephemeral: self.ephemeral.unwrap_or(true),
+            cluster_name: self.cluster_name().to_owned(),
+            metadata,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceInstancesResult {
+    pub name: String,
+    pub group_name: String,
+    pub clusters: String,
+    pub cache_millis: u64,
+    pub hosts: Vec<Instance>,
+    pub last_ref_time: i64,
+    pub checksum: String,
+    pub revision: u64,
+}
+
+impl ServiceInstancesResult {
+    pub fn new(key: &ServiceKey, clusters: &[String], revision: u64, hosts: Vec<Instance>) -> Self {
+        Self {
+            name: key.grouped_name(),
+            group_name: key.group_name.clone(),
+            clusters: clusters.join(","),
+            cache_millis: 10000,
+            hosts,
+            last_ref_time: crate::common::now_millis(),
+            checksum: revision.to_string(),
+            revision,
+        }
+    }
+}
+
+fn bad_request(msg: &str) -> HttpResponse {
+    HttpResponse::BadRequest().body(msg.to_owned())
+}
+
+fn forbidden(err: impl std::fmt::Display) -> HttpResponse {
+    HttpResponse::Forbidden().body(err.to_string())
+}
+
+///
+/// 注册、注销与心跳需要的 (服务, ip, port)
+fn instance_target(params: &InstanceWebParams) -> Result<(ServiceKey, String, u32), &'static str> {
+    let Some(key) = params.to_key() else {
+        return Err("parameter 'serviceName' is missing");
+    };
+    let Some(ip) = params.ip.clone().filter(|v| !v.is_empty()) else {
+        return Err("parameter 'ip' is missing");
+    };
+    let Some(port) = params.port else {
+        return Err("parameter 'port' is missing");
+    };
+    Ok((key, ip, port))
+}
+
+async fn register_instance(
+    registry: web::Data<NamingRegistry>,
+    auth: AuthUser,
+    params: web::Form<InstanceWebParams>,
+) -> HttpResponse {
+    let (key, _, _) = match instance_target(&params) {
+        Ok(v) => v,
+        Err(msg) => return bad_request(msg),
+    };
+    if let Err(e) = auth.check(&key.namespace_id, true) {
+        return forbidden(e);
+    }
+    match params.to_instance() {
+        Ok(instance) => {
+            registry.register(&key, instance);
+            HttpResponse::Ok().body("ok")
+        }
+        Err(msg) => bad_request(&msg),
+    }
+}
+
+async fn deregister_instance(
+    registry: web::Data<NamingRegistry>,
+    auth: AuthUser,
+    params: web::Query<InstanceWebParams>,
+) -> HttpResponse {
+    let (key, ip, port) = match instance_target(&params) {
+        Ok(v) => v,
+        Err(msg) => return bad_request(msg),
+    };
+    if let Err(e) = auth.check(&key.namespace_id, true) {
+        return forbidden(e);
+    }
+    registry.deregister(&key, &ip, port, params.cluster_name());
+    HttpResponse::Ok().body("ok")
+}
+
+///
+/// 心跳，带 beat 信息且实例不存在时按心跳信息重新注册
+async fn beat_instance(
+    registry: web::Data<NamingRegistry>,
+    auth: AuthUser,
+    params: web::Query<InstanceWebParams>,
+) -> HttpResponse {
+    let beat: Option<BeatInfo> = match params.beat.as_deref().filter(|v| !v.is_empty()) {
+        Some(v) => match serde_json::from_str(v) {
+            Ok(v) => Some(v),
+            Err(e) => return bad_request(&format!("invalid beat: {}", e)),
+        },
+        None => None,
+    };
+    let mut params = params.into_inner();
+    if let Some(beat) = &beat {
+        params.ip = Some(beat.ip.clone());
+        params.port = Some(beat.port);
+        params.cluster_name = beat.cluster.clone();
+        if let Some(service_name) = &beat.service_name {
+            params.service_name = Some(service_name.clone());
+        }
+    }
+    let (key, ip, port) = match instance_target(&params) {
+        Ok(v) => v,
+        Err(msg) => return bad_request(msg),
+    };
+    if let Err(e) = auth.check(&key.namespace_id, true) {
+        return forbidden(e);
+    }
+    let mut code = BEAT_OK;
+    if !registry.beat(&key, &ip, port, params.cluster_name()) {
+        match beat {
+            Some(beat) => registry.register(
+                &key,
+                Instance {
+                    ip,
+                    port,
+                    weight: beat.weight.unwrap_or(1.0),
+                    cluster_name: params.cluster_name().to_owned(),
+                    metadata: beat.metadata,
+                    ..Default::default()
+                },
+            ),
+            None => code = BEAT_NOT_FOUND,
+        }
+    }
+    HttpResponse::Ok().json(serde_json::json!({
+        "clientBeatInterval": CLIENT_BEAT_INTERVAL,
+        "code": code,
+        "lightBeatEnabled": true,
+    }))
+}
+
+///
+/// 查询实例列表; 带 revision 且与服务当前版本相同时挂起，直到服务变化或 timeout
+async fn list_instance(
+    registry: web::Data<NamingRegistry>,
+    auth: AuthUser,
+    params: web::Query<InstanceWebParams>,
+) -> HttpResponse {
+    let Some(key) = params.to_key() else {
+        return bad_request("parameter 'serviceName' is missing");
+    };
+    if let Err(e) = auth.check(&key.namespace_id, false) {
+        return forbidden(e);
+    }
+    if let Some(revision) = params.revision {
+        let timeout = params.timeout.unwrap_or(MAX_WAIT_MS).min(MAX_WAIT_MS);
+        registry
+            .wait_change(&key, revision, Duration::from_millis(timeout))
+            .await;
+    }
+    let clusters: Vec<String> = params
+        .clusters
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .filter(|v| !v.is_empty())
+        .map(|v| v.to_owned())
+        .collect();
+    let (revision, hosts) = registry.list(&key, &clusters, params.healthy_only.unwrap_or(false));
+    HttpResponse::Ok().json(ServiceInstancesResult::new(
+        &key, &clusters, revision, hosts,
+    ))
+}
+
+///
+/// 注册服务发现接口，需要先通过 app_data 注入 `web::Data<NamingRegistry>`
+pub fn app_config(config: &mut web::ServiceConfig) {
+    config
+        .service(
+            web::resource("/nacos/v1/ns/instance")
+                .route(web::post().to(register_instance))
+                .route(web::put().to(register_instance))
+                .route(web::delete().to(deregister_instance)),
+        )
+        .service(web::resource("/nacos/v1/ns/instance/beat").route(web::put().to(beat_instance)))
+        .service(web::resource("/nacos/v1/ns/instance/list").route(web::get().to(list_instance)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{App, test};
+
+    #[actix_rt::test]
+    async fn test_naming_api() {
+        let registry = web::Data::new(NamingRegistry::new(15000, 30000));
+        let app =
+            test::init_service(App::new().app_data(registry.clone()).configure(app_config)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/nacos/v1/ns/instance")
+            .set_form([
+                ("serviceName", "DEFAULT_GROUP@@demo"),
+                ("ip", "10.0.0.1"),
+                ("port", "8080"),
+                ("weight", "2"),
+                ("metadata", r#"{"version":"1"}"#),
+            ])
+            .to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await.as_ref(), b"ok");
+        let req = test::TestRequest::post()
+            .uri("/nacos/v1/ns/instance")
+            .set_form([("serviceName", "demo"), ("port", "8080")])
+            .to_request();
+        assert!(
+            test::call_service(&app, req)
+                .await
+                .status()
+                .is_client_error()
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/nacos/v1/ns/instance/list?serviceName=demo&namespaceId=public")
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["name"], "DEFAULT_GROUP@@demo");
+        assert_eq!(res["hosts"][0]["ip"], "10.0.0.1");
+        assert_eq!(res["hosts"][0]["weight"], 2.0);
+        assert_eq!(res["hosts"][0]["metadata"]["version"], "1");
+        let revision = res["revision"].as_u64().unwrap();
+
+        // 心跳: 实例不存在时返回 20404，带 beat 信息时重新注册
+        let req = test::TestRequest::put()
+            .uri("/nacos/v1/ns/instance/beat?serviceName=demo&ip=10.0.0.2&port=8080")
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["code"], BEAT_NOT_FOUND);
+        let beat =
+            r#"{"ip":"10.0.0.2","port":8080,"cluster":"sh","serviceName":"DEFAULT_GROUP@@demo"}"#;
+        let req = test::TestRequest::put()
+            .uri(&format!(
+                "/nacos/v1/ns/instance/beat?serviceName=demo&beat={}",
+                form_urlencoded::byte_serialize(beat.as_bytes()).collect::<String>()
+            ))
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["code"], BEAT_OK);
+
+        // 长轮询: 版本号变化后立即返回
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/nacos/v1/ns/instance/list?serviceName=demo&clusters=sh&revision={}",
+                revision
+            ))
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["hosts"].as_array().unwrap().len(), 1);
+        assert_eq!(res["hosts"][0]["clusterName"], "sh");
+
+        let req = test::TestRequest::delete()
+            .uri("/nacos/v1/ns/instance?serviceName=demo&ip=10.0.0.1&port=8080")
+            .to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await.as_ref(), b"ok");
+        let key = ServiceKey::new("public", "DEFAULT_GROUP", "demo");
+        assert_eq!(registry.list(&key, &[], false).1.len(), 1);
+    }
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const DEFAULT_GROUP: &str = "DEFAULT_GROUP";
+pub const DEFAULT_CLUSTER: &str = "DEFAULT";
+pub const DEFAULT_NAMESPACE: &str = "public";
+// serviceName 可能带分组，格式为 group@@service
+pub const GROUP_SPLIT: &str = "@@";
+
+///
+/// 服务的 key，同一命名空间、分组下服务名唯一
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ServiceKey {
+    pub namespace_id: String,
+    pub group_name: String,
+    pub service_name: String,
+}
+
+impl ServiceKey {
+    pub fn new(namespace_id: &str, group_name: &str, service_name: &str) -> Self {
+        Self {
+            namespace_id: namespace_id.to_owned(),
+            group_name: group_name.to_owned(),
+            service_name: service_name.to_owned(),
+        }
+    }
+
+    pub fn grouped_name(&self) -> String {
+        format!("{}{}{}", self.group_name, GROUP_SPLIT, self.service_name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Instance {
+    pub instance_id: String,
+    pub ip: String,
+    pub port: u32,
+    pub weight: f64,
+    pub healthy: bool,
+    pub enabled: bool,
+    pub ephemeral: bool,
+    pub cluster_name: String,
+    pub service_name: String,
+    pub metadata: HashMap<String, String>,
+    // 最后一次心跳的毫秒时间戳
+    #[serde(skip)]
+    pub last_heartbeat: i64,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            instance_id: String::new(),
+            ip: String::new(),
+            port: 0,
+            weight: 1.0,
+            healthy: true,
+            enabled: true,
+            ephemeral: true,
+            cluster_name: DEFAULT_CLUSTER.to_owned(),
+            service_name: String::new(),
+            metadata: HashMap::new(),
+            last_heartbeat: 0,
+        }
+    }
+}
+
+impl Instance {
+    pub fn build_id(ip: &str, port: u32, cluster_name: &str) -> String {
+        format!("{}#{}#{}", ip, port, cluster_name)
+    }
+
+    pub fn id(&self) -> String {
+        Self::build_id(&self.ip, self.port, &self.cluster_name)
+    }
+}
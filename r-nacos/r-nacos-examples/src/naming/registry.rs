@@ -0,0 +1,310 @@
+use crate::common::now_millis;
+use crate::naming::model::{Instance, ServiceKey};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+
+struct ServiceInfo {
+    // key 为 Instance::id()
+    instances: BTreeMap<String, Instance>,
+    // 服务的版本号，实例变化时更新并通知订阅者
+    revision: watch::Sender<u64>,
+}
+
+impl ServiceInfo {
+    fn new() -> Self {
+        Self {
+            instances: BTreeMap::new(),
+            revision: watch::channel(0).0,
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.instances.is_empty() && self.revision.receiver_count() == 0
+    }
+}
+
+///
+/// 内存中的服务注册表
+/// 临时实例超过 health_timeout 没有心跳标记为不健康，超过 instance_timeout 移除;
+/// 持久实例不受心跳影响，只能主动注销
+pub struct NamingRegistry {
+    services: Mutex<HashMap<ServiceKey, ServiceInfo>>,
+    // 全局递增，服务被移除后重建也不会出现重复的版本号
+    next_revision: AtomicU64,
+    health_timeout: i64,
+    instance_timeout: i64,
+}
+
+impl NamingRegistry {
+    ///
+    /// 超时时间单位为毫秒，与 AppSysConfig 中的 naming_*_timeout 一致
+    pub fn new(health_timeout: u64, instance_timeout: u64) -> Self {
+        Self {
+            services: Mutex::new(HashMap::new()),
+            next_revision: AtomicU64::new(1),
+            health_timeout: health_timeout as i64,
+            instance_timeout: instance_timeout as i64,
+        }
+    }
+
+    fn notify(&self, service: &ServiceInfo) {
+        let revision = self.next_revision.fetch_add(1, Ordering::Relaxed);
+        service.revision.send_replace(revision);
+    }
+
+    ///
+    /// 注册或更新实例，实例内容有变化时通知订阅者
+    pub fn register(&self, key: &ServiceKey, mut instance: Instance) {
+        instance.instance_id = instance.id();
+        instance.service_name = key.grouped_name();
+        instance.last_heartbeat = now_millis();
+        let mut services = self.services.lock().unwrap();
+        let service = services.entry(key.clone()).or_insert_with(ServiceInfo::new);
+        let changed = match service.instances.get(&instance.instance_id) {
+            Some(old) => {
+                old != &Instance {
+                    last_heartbeat: old.last_heartbeat,
+                    ..instance.clone()
+                }
+            }
+            None => true,
+        };
+        service
+            .instances
+            .insert(instance.instance_id.clone(), instance);
+        if changed {
+            self.notify(service);
+        }
+    }
+
+    ///
+    /// 注销实例，实例不存在时返回 false
+    pub fn deregister(&self, key: &ServiceKey, ip: &str, port: u32, cluster_name: &str) -> bool {
+        let mut services = self.services.lock().unwrap();
+        let Some(service) = services.get_mut(key) else {
+            return false;
+        };
+        if service
+            .instances
+            .remove(&Instance::build_id(ip, port, cluster_name))
+            .is_none()
+        {
+            return false;
+        }
+        self.notify(service);
+        if service.is_idle() {
+            services.remove(key);
+        }
+        true
+    }
+
+    ///
+    /// 更新实例心跳，实例不存在时返回 false，客户端需要重新注册
+    pub fn beat(&self, key: &ServiceKey, ip: &str, port: u32, cluster_name: &str) -> bool {
+        let mut services = self.services.lock().unwrap();
+        let Some(service) = services.get_mut(key) else {
+            return false;
+        };
+        let Some(instance) = service
+            .instances
+            .get_mut(&Instance::build_id(ip, port, cluster_name))
+        else {
+            return false;
+        };
+        instance.last_heartbeat = now_millis();
+        if !instance.healthy {
+            instance.healthy = true;
+            self.notify(service);
+        }
+        true
+    }
+
+    ///
+    /// 查询服务实例，clusters 为空时不过滤集群; 返回服务当前的版本号与实例列表
+    pub fn list(
+        &self,
+        key: &ServiceKey,
+        clusters: &[String],
+        healthy_only: bool,
+    ) -> (u64, Vec<Instance>) {
+        let services = self.services.lock().unwrap();
+        let Some(service) = services.get(key) else {
+            return (0, Vec::new());
+        };
+        let instances = service
+            .instances
+            .values()
+            .filter(|v| clusters.is_empty() || clusters.contains(&v.cluster_name))
+            .filter(|v| !healthy_only || (v.healthy && v.enabled))
+            .cloned()
+            .collect();
+        (*service.revision.borrow(), instances)
+    }
+
+    ///
+    /// 订阅服务的变化，接收到的值为服务的新版本号
+    pub fn subscribe(&self, key: &ServiceKey) -> watch::Receiver<u64> {
+        let mut services = self.services.lock().unwrap();
+        services
+            .entry(key.clone())
+            .or_insert_with(ServiceInfo::new)
+            .revision
+            .subscribe()
+    }
+
+    ///
+    /// 服务版本号与 revision 相同时等待变化，直到超时
+    pub async fn wait_change(&self, key: &ServiceKey, revision: u64, timeout: Duration) {
+        let mut receiver = self.subscribe(key);
+        if *receiver.borrow_and_update() != revision {
+            return;
+        }
+        let _ = tokio::time::timeout(timeout, receiver.changed()).await;
+    }
+
+    ///
+    /// 有实例的服务数与实例总数
+    pub fn counts(&self) -> (usize, usize) {
+        let services = self.services.lock().unwrap();
+        services
+            .values()
+            .filter(|v| !v.instances.is_empty())
+            .fold((0, 0), |(s, i), v| (s + 1, i + v.instances.len()))
+    }
+
+    ///
+    /// 按 now 检查临时实例的心跳，返回状态变化(标记不健康或移除)的实例数
+    pub fn check_health(&self, now: i64) -> usize {
+        let mut services = self.services.lock().unwrap();
+        let mut count = 0;
+        for service in services.values_mut() {
+            let before = service.instances.len();
+            service
+                .instances
+                .retain(|_, v| !v.ephemeral || now - v.last_heartbeat <= self.instance_timeout);
+            let mut changed = before - service.instances.len();
+            for instance in service.instances.values_mut() {
+                if instance.ephemeral
+                    && instance.healthy
+                    && now - instance.last_heartbeat > self.health_timeout
+                {
+                    instance.healthy = false;
+                    changed += 1;
+                }
+            }
+            if changed > 0 {
+                self.notify(service);
+                count += changed;
+            }
+        }
+        services.retain(|_, v| !v.is_idle());
+        count
+    }
+
+    ///
+    /// 定时检查实例心跳
+    pub fn spawn_health_check(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let count = self.check_health(now_millis());
+                if count > 0 {
+                    log::info!("naming health check, changed instance count:{}", count);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(ip: &str, cluster_name: &str, ephemeral: bool) -> Instance {
+        Instance {
+            ip: ip.to_owned(),
+            port: 8080,
+            cluster_name: cluster_name.to_owned(),
+            ephemeral,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry() {
+        let registry = NamingRegistry::new(15000, 30000);
+        let key = ServiceKey::new("public", "DEFAULT_GROUP", "demo");
+        let mut receiver = registry.subscribe(&key);
+
+        registry.register(&key, instance("10.0.0.1", "DEFAULT", true));
+        registry.register(&key, instance("10.0.0.2", "sh", true));
+        registry.register(&key, instance("10.0.0.3", "sh", false));
+        assert!(receiver.has_changed().unwrap());
+        let revision = *receiver.borrow_and_update();
+        // 内容没变的重复注册不通知
+        registry.register(&key, instance("10.0.0.1", "DEFAULT", true));
+        assert!(!receiver.has_changed().unwrap());
+
+        let (v, list) = registry.list(&key, &[], false);
+        assert_eq!(v, revision);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0].instance_id, "10.0.0.1#8080#DEFAULT");
+        assert_eq!(list[0].service_name, "DEFAULT_GROUP@@demo");
+        let (_, list) = registry.list(&key, &["sh".to_owned()], false);
+        assert_eq!(list.len(), 2);
+
+        // 心跳超时: 先标记不健康，再移除临时实例，持久实例保留
+        let now = now_millis();
+        assert_eq!(registry.check_health(now + 20000), 2);
+        assert!(receiver.has_changed().unwrap());
+        let (_, list) = registry.list(&key, &[], true);
+        assert_eq!(list.len(), 1);
+        assert!(registry.beat(&key, "10.0.0.1", 8080, "DEFAULT"));
+        assert_eq!(registry.list(&key, &[], true).1.len(), 2);
+        assert_eq!(registry.counts(), (1, 3));
+        assert_eq!(registry.check_health(now + 40000), 2);
+        let (_, list) = registry.list(&key, &[], false);
+        assert_eq!(list.len(), 1);
+        assert!(!list[0].ephemeral);
+        assert!(!registry.beat(&key, "10.0.0.2", 8080, "sh"));
+
+        assert!(registry.deregister(&key, "10.0.0.3", 8080, "sh"));
+        assert!(!registry.deregister(&key, "10.0.0.3", 8080, "sh"));
+        drop(receiver);
+        registry.check_health(now);
+        assert!(registry.services.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_wait_change() {
+        let registry = Arc::new(NamingRegistry::new(15000, 30000));
+        let key = ServiceKey::new("public", "DEFAULT_GROUP", "demo");
+        let (revision, _) = registry.list(&key, &[], false);
+
+        let waiter = {
+            let registry = registry.clone();
+            let key = key.clone();
+            tokio::spawn(async move {
+                let start = std::time::Instant::now();
+                registry
+                    .wait_change(&key, revision, Duration::from_secs(30))
+                    .await;
+                start.elapsed()
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        registry.register(&key, instance("10.0.0.1", "DEFAULT", true));
+        assert!(waiter.await.unwrap() < Duration::from_secs(5));
+
+        // 版本号不一致时立即返回
+        let start = std::time::Instant::now();
+        registry
+            .wait_change(&key, revision, Duration::from_secs(30))
+            .await;
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::raft::model::{
+    AppendRequest, CLUSTER_TOKEN_HEADER, InstallSnapshotRequest, JoinRequest, RaftRequest,
+    VoteRequest,
+};
+use crate::raft::node::{RAFT_API_PREFIX, RaftNode};
+use actix_web::{HttpRequest, HttpResponse, web};
+
+// 快照包含全部配置，放宽请求体的大小限制
+const RAFT_PAYLOAD_LIMIT: usize = 256 * 1024 * 1024;
+
+///
+/// 节点间的请求需要携带相同的 cluster_token，已停止的节点拒绝所有请求
+fn check_request(req: &HttpRequest, node: &RaftNode) -> Option<HttpResponse> {
+    if node.is_stopped() {
+        return Some(HttpResponse::ServiceUnavailable().body("raft node is stopped"));
+    }
+    let token = req
+        .headers()
+        .get(CLUSTER_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !node.check_token(token) {
+        return Some(HttpResponse::Forbidden().body("invalid cluster token"));
+    }
+    None
+}
+
+async fn vote(
+    req: HttpRequest,
+    node: web::Data<RaftNode>,
+    body: web::Json<VoteRequest>,
+) -> HttpResponse {
+    if let Some(res) = check_request(&req, &node) {
+        return res;
+    }
+    HttpResponse::Ok().json(node.handle_vote(&body))
+}
+
+async fn append(
+    req: HttpRequest,
+    node: web::Data<RaftNode>,
+    body: web::Json<AppendRequest>,
+) -> HttpResponse {
+    if let Some(res) = check_request(&req, &node) {
+        return res;
+    }
+    HttpResponse::Ok().json(node.handle_append(body.into_inner()))
+}
+
+async fn snapshot(
+    req: HttpRequest,
+    node: web::Data<RaftNode>,
+    body: web::Json<InstallSnapshotRequest>,
+) -> HttpResponse {
+    if let Some(res) = check_request(&req, &node) {
+        return res;
+    }
+    match node.handle_snapshot(body.into_inner()) {
+        Ok(v) => HttpResponse::Ok().json(v),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+///
+/// 新节点申请加入集群，非 leader 节点转发给 leader
+async fn join(
+    req: HttpRequest,
+    node: web::Data<RaftNode>,
+    body: web::Json<JoinRequest>,
+) -> HttpResponse {
+    if let Some(res) = check_request(&req, &node) {
+        return res;
+    }
+    let body = body.into_inner();
+    let result = node
+        .client_write(RaftRequest::AddMember {
+            node_id: body.node_id,
+            addr: body.addr,
+        })
+        .await
+        .map_err(|e| e.to_string());
+    HttpResponse::Ok().json(result)
+}
+
+///
+/// follower 转发过来的写入，只在 leader 上执行
+async fn write(
+    req: HttpRequest,
+    node: web::Data<RaftNode>,
+    body: web::Json<RaftRequest>,
+) -> HttpResponse {
+    if let Some(res) = check_request(&req, &node) {
+        return res;
+    }
+    HttpResponse::Ok().json(node.leader_write(body.into_inner()).await)
+}
+
+async fn metrics(req: HttpRequest, node: web::Data<RaftNode>) -> HttpResponse {
+    if let Some(res) = check_request(&req, &node) {
+        return res;
+    }
+    HttpResponse::Ok().json(node.metrics())
+}
+
+///
+/// 注册节点间的 raft 接口，需要先通过 app_data 注入 `web::Data<RaftNode>`
+pub fn app_config(config: &mut web::ServiceConfig) {
+    config.service(
+        web::scope(RAFT_API_PREFIX)
+            .app_data(web::JsonConfig::default().limit(RAFT_PAYLOAD_LIMIT))
+            .route("/vote", web::post().to(vote))
+            .route("/append", web::post().to(append))
+            .route("/snapshot", web::post().to(snapshot))
+            .route("/join", web::post().to(join))
+            .route("/write", web::post().to(write))
+            .route("/metrics", web::get().to(metrics)),
+    );
+}
@@ -0,0 +1,4 @@
+pub mod api;
+pub mod model;
+pub mod node;
+pub mod store;
@@ -0,0 +1,140 @@
+use crate::common::AppSysConfig;
+use crate::config::service::ConfigWrite;
+use crate::transfer::sqlite::dao::config::ConfigDO;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const CLUSTER_TOKEN_HEADER: &str = "Cluster-Token";
+
+#[derive(Debug, Clone)]
+pub struct RaftOption {
+    pub node_id: u64,
+    // 其它节点访问本节点 raft 接口的地址 ip:port
+    pub node_addr: String,
+    pub auto_init: bool,
+    pub join_addr: String,
+    // 应用多少条日志后生成快照并截断日志
+    pub snapshot_log_size: u64,
+    pub cluster_token: Arc<String>,
+    pub heartbeat_interval: Duration,
+    // 选举超时的下限，实际超时在 [election_timeout, 2 * election_timeout) 之间
+    pub election_timeout: Duration,
+}
+
+impl From<&AppSysConfig> for RaftOption {
+    fn from(v: &AppSysConfig) -> Self {
+        Self {
+            node_id: v.raft_node_id,
+            node_addr: v.raft_node_addr.clone(),
+            auto_init: v.raft_auto_init,
+            join_addr: v.raft_join_addr.clone(),
+            snapshot_log_size: v.raft_snapshot_log_size.max(1),
+            cluster_token: v.cluster_token.clone(),
+            heartbeat_interval: Duration::from_millis(100),
+            election_timeout: Duration::from_millis(1000),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RaftRole {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+///
+/// 日志中的请求，提交后按顺序在每个节点上执行
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RaftRequest {
+    // 仅用于新 leader 提交之前任期的日志
+    Blank,
+    Config(ConfigWrite),
+    AddMember { node_id: u64, addr: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub term: u64,
+    pub index: u64,
+    pub request: RaftRequest,
+}
+
+///
+/// 快照包含状态机的全部配置与集群成员
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub last_index: u64,
+    pub last_term: u64,
+    pub members: BTreeMap<u64, String>,
+    pub configs: Vec<ConfigDO>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoteRequest {
+    pub term: u64,
+    pub candidate_id: u64,
+    pub last_log_index: u64,
+    pub last_log_term: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoteResponse {
+    pub term: u64,
+    pub vote_granted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppendRequest {
+    pub term: u64,
+    pub leader_id: u64,
+    pub prev_log_index: u64,
+    pub prev_log_term: u64,
+    pub entries: Vec<LogEntry>,
+    pub leader_commit: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppendResponse {
+    pub term: u64,
+    pub success: bool,
+    // 成功时为已匹配的最后一条日志; 失败时为建议的下一次 prev_log_index + 1
+    pub next_index: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallSnapshotRequest {
+    pub term: u64,
+    pub leader_id: u64,
+    pub snapshot: Snapshot,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallSnapshotResponse {
+    pub term: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JoinRequest {
+    pub node_id: u64,
+    pub addr: String,
+}
+
+///
+/// 节点状态，用于查看集群与测试
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RaftMetrics {
+    pub node_id: u64,
+    pub role: RaftRole,
+    pub term: u64,
+    pub leader_id: Option<u64>,
+    pub members: BTreeMap<u64, String>,
+    pub last_log_index: u64,
+    pub commit_index: u64,
+    pub last_applied: u64,
+    pub snapshot_index: u64,
+    // 内存中保留的日志条数
+    pub log_len: usize,
+}
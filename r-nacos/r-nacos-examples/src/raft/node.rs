@@ -0,0 +1,1150 @@
+use crate::common::string_utils::StringUtils;
+use crate::config::service::{ConfigService, ConfigWrite};
+use crate::raft::model::{
+    AppendRequest, AppendResponse, CLUSTER_TOKEN_HEADER, InstallSnapshotRequest,
+    InstallSnapshotResponse, JoinRequest, LogEntry, RaftMetrics, RaftOption, RaftRequest, RaftRole,
+    Snapshot, VoteRequest, VoteResponse,
+};
+use crate::raft::store::{RaftHardState, RaftStore};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, oneshot};
+
+// 单次 append 请求最多携带的日志条数
+const MAX_APPEND_ENTRIES: usize = 256;
+// 客户端写入等待提交的超时时间
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub const RAFT_API_PREFIX: &str = "/rnacos/raft";
+
+type WriteResult = Result<bool, String>;
+
+#[derive(Debug, Clone, Copy)]
+struct Progress {
+    next_index: u64,
+    match_index: u64,
+}
+
+struct RaftState {
+    role: RaftRole,
+    term: u64,
+    voted_for: Option<u64>,
+    leader_id: Option<u64>,
+    // 已应用的集群成员，node_id -> raft 地址
+    members: BTreeMap<u64, String>,
+    // 快照之后的日志，log[0].index == snapshot.last_index + 1
+    log: Vec<LogEntry>,
+    snapshot: Snapshot,
+    commit_index: u64,
+    last_applied: u64,
+    election_deadline: Instant,
+    // leader 维护的各节点复制进度
+    progress: HashMap<u64, Progress>,
+    // 正在发送请求的节点，避免同一节点并发复制
+    inflight: HashSet<u64>,
+    // 等待日志应用的客户端写入，index -> (写入时的任期, 结果)
+    waiters: HashMap<u64, (u64, oneshot::Sender<WriteResult>)>,
+}
+
+impl RaftState {
+    fn hard_state(&self) -> RaftHardState {
+        RaftHardState {
+            term: self.term,
+            voted_for: self.voted_for,
+            last_applied: self.last_applied,
+        }
+    }
+
+    fn last_log_index(&self) -> u64 {
+        self.snapshot.last_index + self.log.len() as u64
+    }
+
+    fn last_log_term(&self) -> u64 {
+        self.log
+            .last()
+            .map(|v| v.term)
+            .unwrap_or(self.snapshot.last_term)
+    }
+
+    fn entry(&self, index: u64) -> Option<&LogEntry> {
+        if index <= self.snapshot.last_index {
+            return None;
+        }
+        self.log
+            .get((index - self.snapshot.last_index - 1) as usize)
+    }
+
+    ///
+    /// 日志的任期，index 为快照的最后一条时返回快照的任期，已被截断或不存在时返回 None
+    fn term_at(&self, index: u64) -> Option<u64> {
+        if index == self.snapshot.last_index {
+            return Some(self.snapshot.last_term);
+        }
+        self.entry(index).map(|v| v.term)
+    }
+
+    fn quorum(&self) -> usize {
+        self.members.len() / 2 + 1
+    }
+
+    // 被截断的日志不会再被应用，等待者收到 sender 被丢弃的错误
+    fn truncate_from(&mut self, index: u64) {
+        self.log
+            .truncate((index - self.snapshot.last_index - 1) as usize);
+        self.waiters.retain(|k, _| *k < index);
+    }
+}
+
+///
+/// raft 节点: 配置变更先写入 leader 的日志，复制到多数节点后在每个节点按顺序应用到 ConfigService
+/// 任期、投票与日志在回复其它节点前写入 RaftStore，每 snapshot_log_size 条生成一次快照并截断日志，落后太多的节点直接安装快照;
+/// 节点重启后从本地状态恢复，由 leader 补齐重启期间的日志
+pub struct RaftNode {
+    option: RaftOption,
+    config_service: Arc<ConfigService>,
+    store: RaftStore,
+    state: Mutex<RaftState>,
+    client: reqwest::Client,
+    // 唤醒 leader 立即复制日志
+    notify: Notify,
+    stopped: AtomicBool,
+}
+
+impl RaftNode {
+    ///
+    /// 从 store 恢复本地状态; 已应用的日志对应的配置已在 ConfigService 中，只恢复成员列表
+    pub fn new(
+        option: RaftOption,
+        config_service: Arc<ConfigService>,
+        store: RaftStore,
+    ) -> anyhow::Result<Self> {
+        let hard_state = store.load_state()?;
+        let snapshot = store.load_snapshot()?;
+        let log: Vec<_> = store
+            .load_logs()?
+            .into_iter()
+            .filter(|v| v.index > snapshot.last_index)
+            .collect();
+        let mut st = RaftState {
+            role: RaftRole::Follower,
+            term: hard_state.term,
+            voted_for: hard_state.voted_for,
+            leader_id: None,
+            members: snapshot.members.clone(),
+            log,
+            snapshot,
+            commit_index: 0,
+            last_applied: 0,
+            election_deadline: Instant::now() + option.election_timeout,
+            progress: HashMap::new(),
+            inflight: HashSet::new(),
+            waiters: HashMap::new(),
+        };
+        let last_applied = hard_state
+            .last_applied
+            .clamp(st.snapshot.last_index, st.last_log_index());
+        let members: Vec<_> = (st.snapshot.last_index + 1..=last_applied)
+            .filter_map(|i| match &st.entry(i)?.request {
+                RaftRequest::AddMember { node_id, addr } => Some((*node_id, addr.clone())),
+                _ => None,
+            })
+            .collect();
+        st.members.extend(members);
+        st.commit_index = last_applied;
+        st.last_applied = last_applied;
+        // 没有 cluster_token 时节点间接口拒绝所有请求，只能以单节点运行
+        if option.cluster_token.is_empty() && (!option.join_addr.is_empty() || st.members.len() > 1)
+        {
+            return Err(anyhow::anyhow!(
+                "raft cluster with more than one node requires RNACOS_CLUSTER_TOKEN, node_id:{}",
+                option.node_id
+            ));
+        }
+        if st.term > 0 {
+            log::info!(
+                "raft load state, node_id:{}, term:{}, last_applied:{}, last_log_index:{}",
+                option.node_id,
+                st.term,
+                last_applied,
+                st.last_log_index()
+            );
+        }
+        Ok(Self {
+            option,
+            config_service,
+            store,
+            state: Mutex::new(st),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .unwrap_or_default(),
+            notify: Notify::new(),
+            stopped: AtomicBool::new(false),
+        })
+    }
+
+    pub fn node_id(&self) -> u64 {
+        self.option.node_id
+    }
+
+    ///
+    /// 未配置 cluster_token 时不接受任何节点间请求，避免空 token 与缺失的请求头相等
+    pub fn check_token(&self, token: &str) -> bool {
+        !self.option.cluster_token.is_empty()
+            && StringUtils::constant_time_eq(self.option.cluster_token.as_str(), token)
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    ///
+    /// 停止节点，之后不再参与选举与复制，raft 接口返回错误
+    pub fn shutdown(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        self.notify.notify_one();
+    }
+
+    pub fn metrics(&self) -> RaftMetrics {
+        let st = self.state.lock().unwrap();
+        RaftMetrics {
+            node_id: self.option.node_id,
+            role: st.role,
+            term: st.term,
+            leader_id: st.leader_id,
+            members: st.members.clone(),
+            last_log_index: st.last_log_index(),
+            commit_index: st.commit_index,
+            last_applied: st.last_applied,
+            snapshot_index: st.snapshot.last_index,
+            log_len: st.log.len(),
+        }
+    }
+
+    ///
+    /// 启动后台任务: raft_auto_init 时以单节点集群初始化，否则通过 raft_join_addr 加入集群
+    /// 已有本地状态时不再初始化，已是成员时不再加入
+    pub fn start(self: Arc<Self>) {
+        if self.option.auto_init {
+            self.init_cluster();
+        } else if !self.option.join_addr.is_empty() {
+            tokio::spawn(self.clone().join_loop());
+        }
+        tokio::spawn(self.run());
+    }
+
+    fn init_cluster(&self) {
+        let mut st = self.state.lock().unwrap();
+        // 重启的节点可能已加入其它集群或已投票，不能再以单节点集群初始化
+        if st.term > 0 || !st.members.is_empty() || st.last_log_index() > 0 {
+            log::info!(
+                "raft local state exists, skip cluster init, node_id:{}",
+                self.option.node_id
+            );
+            return;
+        }
+        // 成员变更写入日志，后加入的节点回放日志时也能得到完整的成员列表
+        let entry = LogEntry {
+            term: 1,
+            index: 1,
+            request: RaftRequest::AddMember {
+                node_id: self.option.node_id,
+                addr: self.option.node_addr.clone(),
+            },
+        };
+        let state = RaftHardState {
+            term: 1,
+            voted_for: Some(self.option.node_id),
+            last_applied: 0,
+        };
+        let saved = self
+            .store
+            .append_logs(std::slice::from_ref(&entry))
+            .and_then(|_| self.store.save_state(&state));
+        if let Err(e) = saved {
+            log::error!("raft cluster init error, {}", e);
+            return;
+        }
+        st.term = 1;
+        st.voted_for = Some(self.option.node_id);
+        st.members
+            .insert(self.option.node_id, self.option.node_addr.clone());
+        st.log.push(entry);
+        self.become_leader(&mut st);
+        self.advance_commit(&mut st);
+        log::info!("raft cluster init, node_id:{}", self.option.node_id);
+    }
+
+    async fn join_loop(self: Arc<Self>) {
+        let request = JoinRequest {
+            node_id: self.option.node_id,
+            addr: self.option.node_addr.clone(),
+        };
+        while !self.is_stopped() {
+            if self
+                .state
+                .lock()
+                .unwrap()
+                .members
+                .contains_key(&self.option.node_id)
+            {
+                return;
+            }
+            let result: anyhow::Result<WriteResult> = self
+                .post(&self.option.join_addr, "/join", &request, WRITE_TIMEOUT)
+                .await;
+            match result {
+                Ok(Ok(_)) => log::info!("raft join cluster by {}", self.option.join_addr),
+                Ok(Err(e)) => log::warn!("raft join failed: {}", e),
+                Err(e) => log::warn!("raft join failed: {}", e),
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn run(self: Arc<Self>) {
+        while !self.is_stopped() {
+            tokio::select! {
+                _ = tokio::time::sleep(self.option.heartbeat_interval) => {}
+                _ = self.notify.notified() => {}
+            }
+            if self.is_stopped() {
+                break;
+            }
+            let (role, election) = {
+                let st = self.state.lock().unwrap();
+                let election = st.role != RaftRole::Leader
+                    && Instant::now() >= st.election_deadline
+                    && st.members.contains_key(&self.option.node_id);
+                (st.role, election)
+            };
+            if role == RaftRole::Leader {
+                self.replicate_all();
+            } else if election {
+                self.start_election();
+            }
+        }
+    }
+
+    fn election_timeout(&self) -> Duration {
+        let base = self.option.election_timeout.as_millis() as u64;
+        let jitter = (uuid::Uuid::new_v4().as_u128() as u64) % base.max(1);
+        Duration::from_millis(base + jitter)
+    }
+
+    fn reset_election(&self, st: &mut RaftState) {
+        st.election_deadline = Instant::now() + self.election_timeout();
+    }
+
+    fn become_follower(&self, st: &mut RaftState, term: u64, leader_id: Option<u64>) {
+        if term > st.term {
+            st.term = term;
+            st.voted_for = None;
+            self.save_state(st);
+        }
+        if st.role == RaftRole::Leader {
+            log::info!(
+                "raft step down, node_id:{}, term:{}",
+                self.option.node_id,
+                term
+            );
+        }
+        st.role = RaftRole::Follower;
+        st.leader_id = leader_id;
+        st.progress.clear();
+        self.reset_election(st);
+    }
+
+    fn save_state(&self, st: &RaftState) -> bool {
+        match self.store.save_state(&st.hard_state()) {
+            Ok(()) => true,
+            Err(e) => {
+                log::error!("raft save state error, {}", e);
+                false
+            }
+        }
+    }
+
+    fn become_leader(&self, st: &mut RaftState) {
+        st.role = RaftRole::Leader;
+        st.leader_id = Some(self.option.node_id);
+        let next_index = st.last_log_index() + 1;
+        st.progress = st
+            .members
+            .keys()
+            .filter(|id| **id != self.option.node_id)
+            .map(|id| {
+                (
+                    *id,
+                    Progress {
+                        next_index,
+                        match_index: 0,
+                    },
+                )
+            })
+            .collect();
+        log::info!(
+            "raft become leader, node_id:{}, term:{}",
+            self.option.node_id,
+            st.term
+        );
+    }
+
+    fn start_election(self: &Arc<Self>) {
+        let (request, peers) = {
+            let mut st = self.state.lock().unwrap();
+            st.term += 1;
+            st.voted_for = Some(self.option.node_id);
+            st.leader_id = None;
+            self.reset_election(&mut st);
+            // 任期与投票落盘后才发起投票，失败时等下一次选举超时
+            if !self.save_state(&st) {
+                return;
+            }
+            st.role = RaftRole::Candidate;
+            if st.quorum() <= 1 {
+                self.become_leader(&mut st);
+                // 新 leader 只能通过提交当前任期的日志来提交之前任期的日志
+                match self.append_local(&mut st, RaftRequest::Blank) {
+                    Ok(_) => self.advance_commit(&mut st),
+                    Err(e) => log::error!("raft append log error, {}", e),
+                }
+                return;
+            }
+            let request = VoteRequest {
+                term: st.term,
+                candidate_id: self.option.node_id,
+                last_log_index: st.last_log_index(),
+                last_log_term: st.last_log_term(),
+            };
+            (request, self.peers(&st))
+        };
+        log::info!(
+            "raft start election, node_id:{}, term:{}",
+            self.option.node_id,
+            request.term
+        );
+        let votes = Arc::new(Mutex::new(1usize));
+        for (_, addr) in peers {
+            let node = self.clone();
+            let request = request.clone();
+            let votes = votes.clone();
+            tokio::spawn(async move {
+                let result: anyhow::Result<VoteResponse> = node
+                    .post(&addr, "/vote", &request, node.option.election_timeout)
+                    .await;
+                let Ok(response) = result else {
+                    return;
+                };
+                let mut st = node.state.lock().unwrap();
+                if response.term > st.term {
+                    node.become_follower(&mut st, response.term, None);
+                    return;
+                }
+                if !response.vote_granted
+                    || st.term != request.term
+                    || st.role != RaftRole::Candidate
+                {
+                    return;
+                }
+                let mut votes = votes.lock().unwrap();
+                *votes += 1;
+                if *votes >= st.quorum() {
+                    node.become_leader(&mut st);
+                    if let Err(e) = node.append_local(&mut st, RaftRequest::Blank) {
+                        log::error!("raft append log error, {}", e);
+                    }
+                    node.notify.notify_one();
+                }
+            });
+        }
+    }
+
+    fn peers(&self, st: &RaftState) -> Vec<(u64, String)> {
+        st.members
+            .iter()
+            .filter(|(id, _)| **id != self.option.node_id)
+            .map(|(id, addr)| (*id, addr.clone()))
+            .collect()
+    }
+
+    ///
+    /// leader 追加日志，写入 store 后才计入自身的复制进度
+    fn append_local(&self, st: &mut RaftState, request: RaftRequest) -> anyhow::Result<u64> {
+        let entry = LogEntry {
+            term: st.term,
+            index: st.last_log_index() + 1,
+            request,
+        };
+        self.store.append_logs(std::slice::from_ref(&entry))?;
+        let index = entry.index;
+        st.log.push(entry);
+        Ok(index)
+    }
+
+    fn replicate_all(self: &Arc<Self>) {
+        let peers = {
+            let mut st = self.state.lock().unwrap();
+            if st.role != RaftRole::Leader {
+                return;
+            }
+            let peers: Vec<_> = self
+                .peers(&st)
+                .into_iter()
+                .filter(|(id, _)| !st.inflight.contains(id))
+                .collect();
+            for (id, _) in &peers {
+                st.inflight.insert(*id);
+            }
+            peers
+        };
+        for (id, addr) in peers {
+            let node = self.clone();
+            tokio::spawn(async move {
+                node.replicate(id, &addr).await;
+                node.state.lock().unwrap().inflight.remove(&id);
+            });
+        }
+    }
+
+    ///
+    /// 向一个节点发送日志或快照，日志已被快照截断时发送快照
+    async fn replicate(self: &Arc<Self>, id: u64, addr: &str) {
+        let (append, snapshot) = {
+            let mut st = self.state.lock().unwrap();
+            if st.role != RaftRole::Leader {
+                return;
+            }
+            let last_log_index = st.last_log_index();
+            let progress = *st.progress.entry(id).or_insert(Progress {
+                next_index: last_log_index + 1,
+                match_index: 0,
+            });
+            if progress.next_index <= st.snapshot.last_index {
+                let request = InstallSnapshotRequest {
+                    term: st.term,
+                    leader_id: self.option.node_id,
+                    snapshot: st.snapshot.clone(),
+                };
+                (None, Some(request))
+            } else {
+                let prev_log_index = progress.next_index - 1;
+                let entries: Vec<_> = (progress.next_index..=last_log_index)
+                    .take(MAX_APPEND_ENTRIES)
+                    .filter_map(|i| st.entry(i).cloned())
+                    .collect();
+                let request = AppendRequest {
+                    term: st.term,
+                    leader_id: self.option.node_id,
+                    prev_log_index,
+                    prev_log_term: st.term_at(prev_log_index).unwrap_or_default(),
+                    entries,
+                    leader_commit: st.commit_index,
+                };
+                (Some(request), None)
+            }
+        };
+        if let Some(request) = snapshot {
+            let result: anyhow::Result<InstallSnapshotResponse> =
+                self.post(addr, "/snapshot", &request, WRITE_TIMEOUT).await;
+            let Ok(response) = result else {
+                return;
+            };
+            let mut st = self.state.lock().unwrap();
+            if response.term > st.term {
+                self.become_follower(&mut st, response.term, None);
+            } else if st.role == RaftRole::Leader && st.term == request.term {
+                let match_index = request.snapshot.last_index;
+                st.progress.insert(
+                    id,
+                    Progress {
+                        next_index: match_index + 1,
+                        match_index,
+                    },
+                );
+                self.notify.notify_one();
+            }
+            return;
+        }
+        let Some(request) = append else {
+            return;
+        };
+        let result: anyhow::Result<AppendResponse> = self
+            .post(addr, "/append", &request, self.option.election_timeout)
+            .await;
+        let Ok(response) = result else {
+            return;
+        };
+        let mut st = self.state.lock().unwrap();
+        if response.term > st.term {
+            self.become_follower(&mut st, response.term, None);
+            return;
+        }
+        if st.role != RaftRole::Leader || st.term != request.term {
+            return;
+        }
+        let last_log_index = st.last_log_index();
+        let Some(progress) = st.progress.get_mut(&id) else {
+            return;
+        };
+        if response.success {
+            progress.match_index = progress.match_index.max(response.next_index);
+            progress.next_index = progress.match_index + 1;
+            let behind = progress.next_index <= last_log_index;
+            self.advance_commit(&mut st);
+            if behind {
+                self.notify.notify_one();
+            }
+        } else {
+            progress.next_index = response.next_index.clamp(1, request.prev_log_index.max(1));
+            self.notify.notify_one();
+        }
+    }
+
+    ///
+    /// leader 按多数节点的复制进度推进 commit_index，只直接提交当前任期的日志
+    fn advance_commit(&self, st: &mut RaftState) {
+        let mut matched: Vec<u64> = st
+            .members
+            .keys()
+            .map(|id| {
+                if *id == self.option.node_id {
+                    st.last_log_index()
+                } else {
+                    st.progress.get(id).map(|v| v.match_index).unwrap_or(0)
+                }
+            })
+            .collect();
+        if matched.is_empty() {
+            return;
+        }
+        matched.sort_unstable_by(|a, b| b.cmp(a));
+        let index = matched[st.quorum() - 1];
+        if index > st.commit_index && st.term_at(index) == Some(st.term) {
+            st.commit_index = index;
+            self.apply_committed(st);
+        }
+    }
+
+    fn apply_committed(&self, st: &mut RaftState) {
+        let last_applied = st.last_applied;
+        while st.last_applied < st.commit_index {
+            let index = st.last_applied + 1;
+            let Some(entry) = st.entry(index).cloned() else {
+                break;
+            };
+            let result = match &entry.request {
+                RaftRequest::Blank => Ok(true),
+                RaftRequest::Config(write) => {
+                    self.config_service.apply(write).map_err(|e| e.to_string())
+                }
+                RaftRequest::AddMember { node_id, addr } => {
+                    st.members.insert(*node_id, addr.clone());
+                    log::info!("raft add member, node_id:{}, addr:{}", node_id, addr);
+                    Ok(true)
+                }
+            };
+            if let Err(e) = &result {
+                log::error!("raft apply log error, index:{}, {}", index, e);
+            }
+            st.last_applied = index;
+            if let Some((term, sender)) = st.waiters.remove(&index) {
+                let result = if term == entry.term {
+                    result
+                } else {
+                    Err("leader changed".to_owned())
+                };
+                let _ = sender.send(result);
+            }
+        }
+        // 重启后从 last_applied 之后继续应用，崩溃在两次保存之间时会重复应用少量日志
+        if st.last_applied > last_applied {
+            self.save_state(st);
+        }
+        if st.last_applied - st.snapshot.last_index >= self.option.snapshot_log_size {
+            self.build_snapshot(st);
+        }
+    }
+
+    ///
+    /// 数据绕过 raft 日志被修改(如备份恢复)后，按当前数据重新生成快照
+    pub fn rebuild_snapshot(&self) {
+        let mut st = self.state.lock().unwrap();
+        self.build_snapshot(&mut st);
+    }
+
+    fn build_snapshot(&self, st: &mut RaftState) {
+        let configs = match self.config_service.export_configs() {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("raft build snapshot error, {}", e);
+                return;
+            }
+        };
+        let last_index = st.last_applied;
+        let Some(last_term) = st.term_at(last_index) else {
+            return;
+        };
+        let snapshot = Snapshot {
+            last_index,
+            last_term,
+            members: st.members.clone(),
+            configs,
+        };
+        if let Err(e) = self.store.save_snapshot(&snapshot) {
+            log::error!("raft save snapshot error, {}", e);
+            return;
+        }
+        let count = (last_index - st.snapshot.last_index) as usize;
+        st.log.drain(..count);
+        st.snapshot = snapshot;
+        log::info!(
+            "raft build snapshot, node_id:{}, last_index:{}",
+            self.option.node_id,
+            last_index
+        );
+    }
+
+    pub fn handle_vote(&self, request: &VoteRequest) -> VoteResponse {
+        let mut st = self.state.lock().unwrap();
+        if request.term > st.term {
+            self.become_follower(&mut st, request.term, None);
+        }
+        let up_to_date = (request.last_log_term, request.last_log_index)
+            >= (st.last_log_term(), st.last_log_index());
+        let mut vote_granted = request.term == st.term
+            && up_to_date
+            && st.voted_for.is_none_or(|v| v == request.candidate_id);
+        // 投票落盘后才回复，重启后同一任期不会再投给其它节点
+        if vote_granted && st.voted_for.is_none() {
+            st.voted_for = Some(request.candidate_id);
+            if !self.save_state(&st) {
+                st.voted_for = None;
+                vote_granted = false;
+            }
+        }
+        if vote_granted {
+            self.reset_election(&mut st);
+        }
+        VoteResponse {
+            term: st.term,
+            vote_granted,
+        }
+    }
+
+    pub fn handle_append(&self, request: AppendRequest) -> AppendResponse {
+        let mut st = self.state.lock().unwrap();
+        if request.term < st.term {
+            return AppendResponse {
+                term: st.term,
+                success: false,
+                next_index: st.last_log_index() + 1,
+            };
+        }
+        self.become_follower(&mut st, request.term, Some(request.leader_id));
+        if request.prev_log_index > st.last_log_index() {
+            return AppendResponse {
+                term: st.term,
+                success: false,
+                next_index: st.last_log_index() + 1,
+            };
+        }
+        // 已进入快照的日志一定已提交，与 leader 一致
+        if request.prev_log_index >= st.snapshot.last_index
+            && st.term_at(request.prev_log_index) != Some(request.prev_log_term)
+        {
+            return AppendResponse {
+                term: st.term,
+                success: false,
+                next_index: st.commit_index + 1,
+            };
+        }
+        let match_index = request.prev_log_index + request.entries.len() as u64;
+        // 从第一条本地没有或任期不同的日志开始写入，日志落盘后才回复成功
+        let entries: Vec<_> = request
+            .entries
+            .into_iter()
+            .skip_while(|v| {
+                v.index <= st.snapshot.last_index || st.term_at(v.index) == Some(v.term)
+            })
+            .collect();
+        if let Some(first_index) = entries.first().map(|v| v.index) {
+            if let Err(e) = self.store.append_logs(&entries) {
+                log::error!("raft append log error, {}", e);
+                return AppendResponse {
+                    term: st.term,
+                    success: false,
+                    next_index: st.last_log_index() + 1,
+                };
+            }
+            if first_index <= st.last_log_index() {
+                st.truncate_from(first_index);
+            }
+            st.log.extend(entries);
+        }
+        if request.leader_commit > st.commit_index {
+            st.commit_index = request.leader_commit.min(match_index);
+            self.apply_committed(&mut st);
+        }
+        AppendResponse {
+            term: st.term,
+            success: true,
+            next_index: match_index,
+        }
+    }
+
+    pub fn handle_snapshot(
+        &self,
+        request: InstallSnapshotRequest,
+    ) -> anyhow::Result<InstallSnapshotResponse> {
+        let mut st = self.state.lock().unwrap();
+        if request.term < st.term {
+            return Ok(InstallSnapshotResponse { term: st.term });
+        }
+        self.become_follower(&mut st, request.term, Some(request.leader_id));
+        let snapshot = request.snapshot;
+        if snapshot.last_index > st.commit_index {
+            self.config_service.import_configs(&snapshot.configs)?;
+            let state = RaftHardState {
+                last_applied: snapshot.last_index,
+                ..st.hard_state()
+            };
+            self.store.install_snapshot(&snapshot, &state)?;
+            st.log.clear();
+            st.waiters.clear();
+            st.members = snapshot.members.clone();
+            st.commit_index = snapshot.last_index;
+            st.last_applied = snapshot.last_index;
+            log::info!(
+                "raft install snapshot, node_id:{}, last_index:{}",
+                self.option.node_id,
+                snapshot.last_index
+            );
+            st.snapshot = snapshot;
+        }
+        Ok(InstallSnapshotResponse { term: st.term })
+    }
+
+    ///
+    /// 写入 raft 日志并等待应用，非 leader 节点转发给 leader
+    pub async fn write(&self, write: ConfigWrite) -> anyhow::Result<bool> {
+        self.client_write(RaftRequest::Config(write)).await
+    }
+
+    pub async fn client_write(&self, request: RaftRequest) -> anyhow::Result<bool> {
+        let leader_addr = {
+            let st = self.state.lock().unwrap();
+            match st.leader_id {
+                Some(id) if id == self.option.node_id => None,
+                Some(id) => st.members.get(&id).cloned(),
+                None => None,
+            }
+        };
+        let result = match leader_addr {
+            Some(addr) => self.post(&addr, "/write", &request, WRITE_TIMEOUT).await?,
+            None => self.leader_write(request).await,
+        };
+        result.map_err(anyhow::Error::msg)
+    }
+
+    ///
+    /// 只在 leader 上执行写入，由 /write 接口调用，避免请求在节点间循环转发
+    pub async fn leader_write(&self, request: RaftRequest) -> WriteResult {
+        let receiver = {
+            let mut st = self.state.lock().unwrap();
+            if st.role != RaftRole::Leader {
+                return Err("raft leader not found".to_owned());
+            }
+            let index = self
+                .append_local(&mut st, request)
+                .map_err(|e| e.to_string())?;
+            let (sender, receiver) = oneshot::channel();
+            let term = st.term;
+            st.waiters.insert(index, (term, sender));
+            // 单节点集群直接提交
+            self.advance_commit(&mut st);
+            receiver
+        };
+        self.notify.notify_one();
+        match tokio::time::timeout(WRITE_TIMEOUT, receiver).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err("raft log was truncated".to_owned()),
+            Err(_) => Err("raft write timeout".to_owned()),
+        }
+    }
+
+    async fn post<T: Serialize, R: DeserializeOwned>(
+        &self,
+        addr: &str,
+        path: &str,
+        body: &T,
+        timeout: Duration,
+    ) -> anyhow::Result<R> {
+        let response = self
+            .client
+            .post(format!("http://{}{}{}", addr, RAFT_API_PREFIX, path))
+            .header(CLUSTER_TOKEN_HEADER, self.option.cluster_token.as_str())
+            .timeout(timeout)
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raft::api::app_config;
+    use crate::transfer::data_to_sqlite::open_init_db;
+    use crate::transfer::model::ConfigKey;
+    use actix_web::dev::ServerHandle;
+    use actix_web::{App, HttpServer, web};
+
+    struct TestNode {
+        node: Arc<RaftNode>,
+        service: Arc<ConfigService>,
+        server: ServerHandle,
+        db: std::path::PathBuf,
+    }
+
+    async fn start_node(node_id: u64, join_addr: &str) -> anyhow::Result<TestNode> {
+        let db =
+            std::env::temp_dir().join(format!("raft_node_{}_{}.db", node_id, std::process::id()));
+        let _ = std::fs::remove_file(&db);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?.to_string();
+        run_node(node_id, join_addr, &addr, Some(listener), db).await
+    }
+
+    ///
+    /// 停止节点后用原来的数据库与地址重新启动
+    async fn restart_node(node: TestNode) -> anyhow::Result<TestNode> {
+        node.node.shutdown();
+        node.server.stop(false).await;
+        let option = node.node.option.clone();
+        run_node(
+            option.node_id,
+            &option.join_addr,
+            &option.node_addr,
+            None,
+            node.db,
+        )
+        .await
+    }
+
+    async fn run_node(
+        node_id: u64,
+        join_addr: &str,
+        addr: &str,
+        listener: Option<std::net::TcpListener>,
+        db: std::path::PathBuf,
+    ) -> anyhow::Result<TestNode> {
+        let service = Arc::new(ConfigService::new(
+            open_init_db(&db.to_string_lossy()).await?,
+            1024,
+        ));
+        let store = RaftStore::new(open_init_db(&db.to_string_lossy()).await?)?;
+        let option = RaftOption {
+            node_id,
+            node_addr: addr.to_owned(),
+            auto_init: join_addr.is_empty(),
+            join_addr: join_addr.to_owned(),
+            snapshot_log_size: 5,
+            cluster_token: Arc::new("test-token".to_owned()),
+            heartbeat_interval: Duration::from_millis(50),
+            election_timeout: Duration::from_millis(300),
+        };
+        let node = web::Data::new(RaftNode::new(option, service.clone(), store)?);
+        let server = {
+            let node = node.clone();
+            let server =
+                HttpServer::new(move || App::new().app_data(node.clone()).configure(app_config))
+                    .workers(1);
+            // 重启时 bind 会设置 SO_REUSEADDR，原端口的 TIME_WAIT 连接不影响监听
+            match listener {
+                Some(listener) => server.listen(listener)?,
+                None => server.bind(addr)?,
+            }
+            .run()
+        };
+        let handle = server.handle();
+        actix_rt::spawn(server);
+        let node = node.into_inner();
+        node.clone().start();
+        Ok(TestNode {
+            node,
+            service,
+            server: handle,
+            db,
+        })
+    }
+
+    async fn wait_until(mut f: impl FnMut() -> bool) {
+        for _ in 0..400 {
+            if f() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("wait timeout");
+    }
+
+    fn content(node: &TestNode, key: &ConfigKey) -> Option<String> {
+        node.service.get(key).unwrap().map(|v| v.content)
+    }
+
+    #[actix_rt::test]
+    async fn test_raft_cluster() -> anyhow::Result<()> {
+        let node1 = start_node(1, "").await?;
+        let addr1 = node1.node.metrics().members[&1].clone();
+        let node2 = start_node(2, &addr1).await?;
+        // 通过 follower 加入，由 follower 转发给 leader
+        let node3 = start_node(3, &node2.node.option.node_addr).await?;
+        let mut nodes = vec![node1, node2, node3];
+        wait_until(|| {
+            nodes.iter().all(|v| {
+                let metrics = v.node.metrics();
+                metrics.members.len() == 3 && metrics.leader_id == Some(1)
+            })
+        })
+        .await;
+
+        // 通过 follower 写入，复制到所有节点
+        let key = ConfigKey::new("app.yaml", "DEFAULT_GROUP", "");
+        let write = ConfigWrite::publish(&key, "a: 1", None, None, Some("admin"));
+        assert!(nodes[2].node.write(write).await?);
+        wait_until(|| {
+            nodes
+                .iter()
+                .all(|v| content(v, &key).as_deref() == Some("a: 1"))
+        })
+        .await;
+
+        // 超过 snapshot_log_size 后生成快照并截断日志
+        for i in 0..10 {
+            let key = ConfigKey::new(&format!("{}.yaml", i), "DEFAULT_GROUP", "dev");
+            nodes[0]
+                .node
+                .write(ConfigWrite::publish(&key, "v", None, None, None))
+                .await?;
+        }
+        let delete = ConfigWrite::delete(&ConfigKey::new("0.yaml", "DEFAULT_GROUP", "dev"), None);
+        assert!(nodes[1].node.write(delete).await?);
+        wait_until(|| {
+            nodes.iter().all(|v| {
+                let metrics = v.node.metrics();
+                metrics.last_applied == nodes[0].node.metrics().last_applied
+            })
+        })
+        .await;
+        for node in &nodes {
+            let metrics = node.node.metrics();
+            assert!(metrics.snapshot_index > 0);
+            assert!(metrics.log_len < 5);
+            assert_eq!(node.service.export_configs()?.len(), 10);
+        }
+
+        // 日志已截断，后加入的节点通过快照恢复数据
+        let node4 = start_node(4, &addr1).await?;
+        wait_until(|| node4.node.metrics().members.len() == 4 && content(&node4, &key).is_some())
+            .await;
+        assert!(node4.node.metrics().snapshot_index > 0);
+        assert_eq!(node4.service.export_configs()?.len(), 10);
+        nodes.push(node4);
+
+        // leader 停止后重新选举，剩下的节点仍可写入
+        nodes[0].node.shutdown();
+        wait_until(|| {
+            nodes[1..].iter().all(|v| {
+                let leader_id = v.node.metrics().leader_id;
+                leader_id.is_some() && leader_id != Some(1)
+            })
+        })
+        .await;
+        let write = ConfigWrite::publish(&key, "a: 2", None, None, None);
+        assert!(nodes[3].node.write(write).await?);
+        wait_until(|| {
+            nodes[1..]
+                .iter()
+                .all(|v| content(v, &key).as_deref() == Some("a: 2"))
+        })
+        .await;
+        assert_eq!(content(&nodes[0], &key).as_deref(), Some("a: 1"));
+
+        // 原 leader 重启: 从本地状态恢复而不是重新初始化集群，作为 follower 补齐停止期间的写入
+        let metrics = nodes[0].node.metrics();
+        let node1 = restart_node(nodes.remove(0)).await?;
+        let restarted = node1.node.metrics();
+        assert_eq!(restarted.role, RaftRole::Follower);
+        assert!(restarted.term >= metrics.term);
+        assert_eq!(restarted.members.len(), 4);
+        assert_eq!(restarted.snapshot_index, metrics.snapshot_index);
+        assert_eq!(restarted.last_applied, metrics.last_applied);
+        assert_eq!(restarted.last_log_index, metrics.last_log_index);
+        nodes.insert(0, node1);
+        wait_until(|| {
+            let metrics = nodes[0].node.metrics();
+            metrics.leader_id.is_some() && content(&nodes[0], &key).as_deref() == Some("a: 2")
+        })
+        .await;
+        let key2 = ConfigKey::new("b.yaml", "DEFAULT_GROUP", "");
+        let write = ConfigWrite::publish(&key2, "b: 1", None, None, None);
+        assert!(nodes[0].node.write(write).await?);
+        wait_until(|| {
+            nodes
+                .iter()
+                .all(|v| content(v, &key2).as_deref() == Some("b: 1"))
+        })
+        .await;
+
+        for node in nodes {
+            node.node.shutdown();
+            node.server.stop(false).await;
+            std::fs::remove_file(&node.db)?;
+        }
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn test_cluster_token() -> anyhow::Result<()> {
+        let node = start_node(9, "").await?;
+        let addr = node.node.option.node_addr.clone();
+        let client = reqwest::Client::new();
+        let url = format!("http://{}{}/metrics", addr, RAFT_API_PREFIX);
+        let res = client.get(&url).send().await?;
+        assert_eq!(res.status().as_u16(), 403);
+        let res = client
+            .get(&url)
+            .header(CLUSTER_TOKEN_HEADER, "test-token")
+            .send()
+            .await?;
+        let metrics: RaftMetrics = res.json().await?;
+        assert_eq!(metrics.role, RaftRole::Leader);
+        node.node.shutdown();
+        node.server.stop(false).await;
+
+        // 没有 cluster_token 时拒绝空的请求头，也不能加入或恢复多节点集群
+        let option = RaftOption {
+            cluster_token: Arc::new(String::new()),
+            ..node.node.option.clone()
+        };
+        let open = || async { RaftStore::new(open_init_db(&node.db.to_string_lossy()).await?) };
+        let single = RaftNode::new(option.clone(), node.service.clone(), open().await?)?;
+        assert!(!single.check_token(""));
+        let join = RaftOption {
+            auto_init: false,
+            join_addr: "127.0.0.1:1".to_owned(),
+            ..option
+        };
+        assert!(RaftNode::new(join, node.service.clone(), open().await?).is_err());
+        drop(single);
+        std::fs::remove_file(&node.db)?;
+        Ok(())
+    }
+}
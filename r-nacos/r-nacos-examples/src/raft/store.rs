@@ -0,0 +1,230 @@
+use crate::raft::model::{LogEntry, Snapshot};
+use rusqlite::{Connection, OptionalExtension, params};
+use std::sync::Mutex;
+use std::time::Duration;
+
+///
+/// 需要在回复其它节点前落盘的状态
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RaftHardState {
+    pub term: u64,
+    pub voted_for: Option<u64>,
+    pub last_applied: u64,
+}
+
+///
+/// raft 的本地存储: 任期、投票、快照与快照之后的日志，与配置数据保存在同一个 sqlite 文件
+/// 表由 open_init_db 创建
+pub struct RaftStore {
+    conn: Mutex<Connection>,
+}
+
+impl RaftStore {
+    pub fn new(conn: Connection) -> anyhow::Result<Self> {
+        // 与 ConfigService 使用不同的连接，写入时等待对方的读锁释放
+        conn.busy_timeout(Duration::from_secs(5))?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn load_state(&self) -> anyhow::Result<RaftHardState> {
+        let conn = self.conn.lock().unwrap();
+        let state = conn
+            .query_row(
+                "select term,voted_for,last_applied from tb_raft_state where id=1",
+                [],
+                |r| {
+                    Ok(RaftHardState {
+                        term: r.get::<_, i64>(0)? as u64,
+                        voted_for: r.get::<_, Option<i64>>(1)?.map(|v| v as u64),
+                        last_applied: r.get::<_, i64>(2)? as u64,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(state.unwrap_or_default())
+    }
+
+    pub fn save_state(&self, state: &RaftHardState) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        Self::write_state(&conn, state)?;
+        Ok(())
+    }
+
+    fn write_state(conn: &Connection, state: &RaftHardState) -> rusqlite::Result<()> {
+        conn.execute(
+            "insert or replace into tb_raft_state(id,term,voted_for,last_applied) values(1,?,?,?)",
+            params![
+                state.term as i64,
+                state.voted_for.map(|v| v as i64),
+                state.last_applied as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn load_snapshot(&self) -> anyhow::Result<Snapshot> {
+        let conn = self.conn.lock().unwrap();
+        let data = conn
+            .query_row("select data from tb_raft_snapshot where id=1", [], |r| {
+                r.get::<_, String>(0)
+            })
+            .optional()?;
+        match data {
+            Some(data) => Ok(serde_json::from_str(&data)?),
+            None => Ok(Snapshot::default()),
+        }
+    }
+
+    ///
+    /// 保存新生成的快照并删除快照包含的日志
+    pub fn save_snapshot(&self, snapshot: &Snapshot) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        Self::write_snapshot(&tx, snapshot)?;
+        tx.execute(
+            "delete from tb_raft_log where log_index<=?",
+            [snapshot.last_index as i64],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    ///
+    /// 安装 leader 发来的快照，本地日志全部丢弃
+    pub fn install_snapshot(
+        &self,
+        snapshot: &Snapshot,
+        state: &RaftHardState,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        Self::write_snapshot(&tx, snapshot)?;
+        tx.execute("delete from tb_raft_log", [])?;
+        Self::write_state(&tx, state)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn write_snapshot(conn: &Connection, snapshot: &Snapshot) -> anyhow::Result<()> {
+        conn.execute(
+            "insert or replace into tb_raft_snapshot(id,last_index,data) values(1,?,?)",
+            params![snapshot.last_index as i64, serde_json::to_string(snapshot)?],
+        )?;
+        Ok(())
+    }
+
+    pub fn load_logs(&self) -> anyhow::Result<Vec<LogEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("select log_index,term,request from tb_raft_log order by log_index")?;
+        let rows = stmt.query_map([], |r| {
+            Ok((
+                r.get::<_, i64>(0)? as u64,
+                r.get::<_, i64>(1)? as u64,
+                r.get::<_, String>(2)?,
+            ))
+        })?;
+        let mut list = Vec::new();
+        for row in rows {
+            let (index, term, request) = row?;
+            list.push(LogEntry {
+                term,
+                index,
+                request: serde_json::from_str(&request)?,
+            });
+        }
+        Ok(list)
+    }
+
+    ///
+    /// 追加连续的日志，先删除本地 index 不小于第一条的日志，冲突的日志因此被截断
+    pub fn append_logs(&self, entries: &[LogEntry]) -> anyhow::Result<()> {
+        let Some(first) = entries.first() else {
+            return Ok(());
+        };
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "delete from tb_raft_log where log_index>=?",
+            [first.index as i64],
+        )?;
+        {
+            let mut stmt =
+                tx.prepare("insert into tb_raft_log(log_index,term,request) values(?,?,?)")?;
+            for entry in entries {
+                stmt.execute(params![
+                    entry.index as i64,
+                    entry.term as i64,
+                    serde_json::to_string(&entry.request)?
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raft::model::RaftRequest;
+    use crate::transfer::data_to_sqlite::open_init_db;
+
+    fn entry(term: u64, index: u64) -> LogEntry {
+        LogEntry {
+            term,
+            index,
+            request: RaftRequest::Blank,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_raft_store() -> anyhow::Result<()> {
+        let db = std::env::temp_dir().join(format!("raft_store_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db);
+        let store = RaftStore::new(open_init_db(&db.to_string_lossy()).await?)?;
+        assert_eq!(store.load_state()?, RaftHardState::default());
+        assert!(store.load_logs()?.is_empty());
+
+        let state = RaftHardState {
+            term: 3,
+            voted_for: Some(2),
+            last_applied: 1,
+        };
+        store.save_state(&state)?;
+        store.append_logs(&[entry(1, 1), entry(1, 2), entry(1, 3)])?;
+        // 与 leader 冲突的日志被截断
+        store.append_logs(&[entry(2, 2)])?;
+        store.save_snapshot(&Snapshot {
+            last_index: 1,
+            last_term: 1,
+            ..Default::default()
+        })?;
+
+        // 重新打开后状态不变
+        let store = RaftStore::new(open_init_db(&db.to_string_lossy()).await?)?;
+        assert_eq!(store.load_state()?, state);
+        assert_eq!(store.load_snapshot()?.last_index, 1);
+        assert_eq!(store.load_logs()?, vec![entry(2, 2)]);
+
+        store.install_snapshot(
+            &Snapshot {
+                last_index: 5,
+                last_term: 2,
+                ..Default::default()
+            },
+            &RaftHardState {
+                last_applied: 5,
+                ..state
+            },
+        )?;
+        assert_eq!(store.load_state()?.last_applied, 5);
+        assert_eq!(store.load_snapshot()?.last_term, 2);
+        assert!(store.load_logs()?.is_empty());
+        drop(store);
+        std::fs::remove_file(&db)?;
+        Ok(())
+    }
+}
@@ -1,10 +1,11 @@
 use crate::common::constant::{CONFIG_TREE_NAME, NAMESPACE_TREE_NAME, USER_TREE_NAME};
-use crate::transfer::model::{TransferRecordRef, UserDo};
-use crate::transfer::reader::{TransferFileReader, reader_transfer_record};
+use crate::transfer::model::{ConfigKey, ConfigValueDo, NamespaceDo, TransferRecordRef, UserDo};
+use crate::transfer::reader::TransferFileReader;
 use crate::transfer::sqlite::TableSeq;
 use crate::transfer::sqlite::dao::ConfigDao;
-use crate::transfer::sqlite::dao::config_history::ConfigHistoryDao;
-use crate::transfer::sqlite::dao::tenant::TenantDao;
+use crate::transfer::sqlite::dao::config::ConfigDO;
+use crate::transfer::sqlite::dao::config_history::{ConfigHistoryDO, ConfigHistoryDao};
+use crate::transfer::sqlite::dao::tenant::{TenantDO, TenantDao};
 use crate::transfer::sqlite::dao::user::{UserDO, UserDao};
 use rusqlite::Connection;
 
@@ -24,8 +25,8 @@ pub async fn data_to_sqlite(data_file: &str, db_path: &str) -> anyhow::Result<()
     let user_dao = UserDao::new(&conn);
     let tenant_dao = TenantDao::new(&conn);
 
-    while let Ok(Some(vec)) = file_reader.read_record_vec().await {
-        let record = reader_transfer_record(&vec, &file_reader.header)?;
+    while let Some(vec) = file_reader.read_record_vec().await? {
+        let record = file_reader.decode_record(&vec)?;
         if record.table_name.as_str() == CONFIG_TREE_NAME.as_str() {
             config_count += 1;
             self::insert_config(&mut table_seq, &config_dao, &config_history_dao, record)?;
@@ -99,21 +100,96 @@ create table if not exists tb_user(
     gmt_modified integer,
     enabled text,
     roles text,
-    extend_info text
+    extend_info text,
+    namespace_privilege_flags integer,
+    namespace_white_list text,
+    namespace_black_list text
+);
+
+create table if not exists tb_raft_state(
+    id integer primary key,
+    term integer,
+    voted_for integer,
+    last_applied integer
+);
+
+create table if not exists tb_raft_snapshot(
+    id integer primary key,
+    last_index integer,
+    data text
+);
+
+create table if not exists tb_raft_log(
+    log_index integer primary key,
+    term integer,
+    request text
 );
     ";
 
     conn.execute_batch(create_table_sql)?;
+    self::add_missing_columns(
+        &conn,
+        "tb_user",
+        &[
+            ("namespace_privilege_flags", "integer"),
+            ("namespace_white_list", "text"),
+            ("namespace_black_list", "text"),
+        ],
+    )?;
     Ok(conn)
 }
 
+///
+/// 旧版本创建的表缺少新增的列，打开时补上
+fn add_missing_columns(
+    conn: &Connection,
+    table: &str,
+    columns: &[(&str, &str)],
+) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare(&format!("pragma table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |r| r.get::<_, String>("name"))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (name, column_type) in columns {
+        if !exists.iter().any(|v| v == name) {
+            conn.execute(
+                &format!("alter table {} add column {} {}", table, name, column_type),
+                [],
+            )?;
+        }
+    }
+    Ok(())
+}
+
 fn insert_config(
     table_seq: &mut TableSeq,
     config_dao: &ConfigDao<'_>,
     config_history_dao: &ConfigHistoryDao<'_>,
     record: TransferRecordRef<'_>,
 ) -> anyhow::Result<()> {
-    todo!()
+    let value_do = ConfigValueDo::from_bytes(&record.value)?;
+    let key = String::from_utf8_lossy(&record.key);
+    let key: ConfigKey = key.as_ref().into();
+
+    let config_do = ConfigDO {
+        id: Some(table_seq.next_config_id()),
+        data_id: Some(key.data_id.clone()),
+        group_id: Some(key.group.clone()),
+        tenant_id: Some(key.tenant.clone()),
+        content: Some(value_do.content),
+        config_type: value_do.config_type,
+        config_desc: value_do.desc,
+        last_time: Some(value_do.last_time),
+    };
+    config_dao.insert(&config_do)?;
+
+    for item in value_do.histories {
+        let mut history_do = ConfigHistoryDO::from_item(&key, item);
+        history_do.id = Some(table_seq.next_config_history_id());
+        config_history_dao.insert(&history_do)?;
+    }
+
+    Ok(())
 }
 
 fn insert_namespace(
@@ -121,7 +197,14 @@ fn insert_namespace(
     tenant_dao: &TenantDao<'_>,
     record: TransferRecordRef<'_>,
 ) -> anyhow::Result<()> {
-    todo!()
+    let value_do = NamespaceDo::from_bytes(&record.value)?;
+
+    let mut tenant_do: TenantDO = value_do.into();
+    tenant_do.id = Some(table_seq.next_tenant_id());
+
+    tenant_dao.insert(&tenant_do)?;
+
+    Ok(())
 }
 
 fn insert_user(
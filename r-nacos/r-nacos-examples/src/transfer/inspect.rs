@@ -0,0 +1,382 @@
+use crate::common::constant::{CONFIG_TREE_NAME, NAMESPACE_TREE_NAME};
+use crate::transfer::model::{ConfigKey, TransferHeaderDto};
+use crate::transfer::reader::TransferFileReader;
+use crate::transfer::writer::TransferFileWriter;
+use std::collections::{BTreeMap, HashMap};
+
+/// diff 时每类差异最多打印的 key 数
+const DIFF_PRINT_LIMIT: usize = 20;
+
+///
+/// 传输文件概况: header 与各表的记录数
+#[derive(Debug)]
+pub struct TransferStat {
+    pub header: TransferHeaderDto,
+    pub table_count: BTreeMap<String, u64>,
+}
+
+pub async fn stat_file(file: &str) -> anyhow::Result<TransferStat> {
+    let mut reader = TransferFileReader::new(file).await?;
+    let mut table_count = BTreeMap::new();
+    while let Some(v) = reader.read_record_vec().await? {
+        let record = reader.decode_record(&v)?;
+        *table_count
+            .entry(record.table_name.to_string())
+            .or_default() += 1;
+    }
+    Ok(TransferStat {
+        header: reader.header,
+        table_count,
+    })
+}
+
+///
+/// 打印传输文件的 header、表映射与各表记录数
+pub async fn inspect(file: &str) -> anyhow::Result<()> {
+    let stat = stat_file(file).await?;
+    let header = &stat.header;
+    println!("file: {}", file);
+    println!("version: {}", header.version);
+    println!("modify_time: {}", header.modify_time);
+    println!(
+        "from_sys: {}",
+        header.from_sys.as_deref().unwrap_or_default()
+    );
+    let mut extend_info: Vec<_> = header.extend_info.iter().collect();
+    extend_info.sort();
+    for (k, v) in extend_info {
+        println!("extend_info: {}={}", k, v);
+    }
+    let mut ids: Vec<_> = header.id_to_name.keys().collect();
+    ids.sort();
+    for id in ids {
+        println!("table map: {} -> {}", id, header.id_to_name[id]);
+    }
+    for (table, count) in &stat.table_count {
+        println!("table: {}, record count: {}", table, count);
+    }
+    println!(
+        "total record count: {}",
+        stat.table_count.values().sum::<u64>()
+    );
+    Ok(())
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct TableDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+    pub same: u64,
+}
+
+impl TableDiff {
+    pub fn is_same(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+///
+/// key 中的分隔符换成 `|` 便于显示
+fn display_key(key: &[u8]) -> String {
+    String::from_utf8_lossy(key).replace('\x02', "|")
+}
+
+///
+/// 读出文件中 (表名, key) 到 value md5 的映射，只保存摘要以控制内存
+async fn load_digests(file: &str) -> anyhow::Result<HashMap<(String, Vec<u8>), [u8; 16]>> {
+    let mut reader = TransferFileReader::new(file).await?;
+    let mut map = HashMap::new();
+    while let Some(v) = reader.read_record_vec().await? {
+        let record = reader.decode_record(&v)?;
+        map.insert(
+            (record.table_name.to_string(), record.key.to_vec()),
+            md5::compute(&record.value).0,
+        );
+    }
+    Ok(map)
+}
+
+///
+/// 按表与 key 比较两个传输文件，added 为只在 other 中存在的 key
+pub async fn diff_file(file: &str, other: &str) -> anyhow::Result<BTreeMap<String, TableDiff>> {
+    let left = load_digests(file).await?;
+    let mut right = load_digests(other).await?;
+    let mut result: BTreeMap<String, TableDiff> = BTreeMap::new();
+    for ((table, key), digest) in left {
+        let v = right.remove(&(table.clone(), key.clone()));
+        let diff = result.entry(table).or_default();
+        match v {
+            None => diff.removed.push(display_key(&key)),
+            Some(other_digest) if other_digest != digest => diff.changed.push(display_key(&key)),
+            Some(_) => diff.same += 1,
+        }
+    }
+    for (table, key) in right.into_keys() {
+        result
+            .entry(table)
+            .or_default()
+            .added
+            .push(display_key(&key));
+    }
+    for diff in result.values_mut() {
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort();
+    }
+    Ok(result)
+}
+
+pub async fn diff(file: &str, other: &str) -> anyhow::Result<()> {
+    let result = diff_file(file, other).await?;
+    for (table, diff) in &result {
+        println!(
+            "table: {}, added: {}, removed: {}, changed: {}, same: {}",
+            table,
+            diff.added.len(),
+            diff.removed.len(),
+            diff.changed.len(),
+            diff.same
+        );
+        for (name, keys) in [
+            ("+", &diff.added),
+            ("-", &diff.removed),
+            ("~", &diff.changed),
+        ] {
+            for key in keys.iter().take(DIFF_PRINT_LIMIT) {
+                println!("  {} {}", name, key);
+            }
+            if keys.len() > DIFF_PRINT_LIMIT {
+                println!("  {} ... {} more", name, keys.len() - DIFF_PRINT_LIMIT);
+            }
+        }
+    }
+    if result.values().all(|v| v.is_same()) {
+        println!("no difference");
+    }
+    Ok(())
+}
+
+///
+/// extract 的过滤条件，为 None 时不过滤
+/// tenant 作用于配置与命名空间，data_id 作用于配置，其它表只受 tables 限制
+#[derive(Debug, Default)]
+pub struct ExtractFilter {
+    pub tables: Option<Vec<String>>,
+    pub tenant: Option<String>,
+    /// 支持 `*` 与 `?` 通配符
+    pub data_id: Option<String>,
+}
+
+impl ExtractFilter {
+    fn is_match(&self, table_name: &str, key: &[u8]) -> bool {
+        if let Some(tables) = &self.tables
+            && !tables.iter().any(|v| v == table_name)
+        {
+            return false;
+        }
+        if table_name == CONFIG_TREE_NAME.as_str() {
+            let config_key = ConfigKey::from(String::from_utf8_lossy(key).as_ref());
+            if let Some(tenant) = &self.tenant
+                && *tenant != config_key.tenant
+            {
+                return false;
+            }
+            if let Some(data_id) = &self.data_id
+                && !glob_match(data_id, &config_key.data_id)
+            {
+                return false;
+            }
+        } else if table_name == NAMESPACE_TREE_NAME.as_str()
+            && let Some(tenant) = &self.tenant
+        {
+            return tenant.as_bytes() == key;
+        }
+        true
+    }
+}
+
+///
+/// 简单的通配符匹配，`*` 匹配任意多个字符，`?` 匹配一个字符
+pub fn glob_match(pattern: &str, s: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+    let (mut pi, mut si) = (0, 0);
+    // 最近一个 * 的位置与当时匹配到的字符位置，失配时回溯
+    let mut star: Option<(usize, usize)> = None;
+    while si < s.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == s[si]) {
+            pi += 1;
+            si += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, si));
+            pi += 1;
+        } else if let Some((star_pi, star_si)) = star {
+            pi = star_pi + 1;
+            si = star_si + 1;
+            star = Some((star_pi, star_si + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+///
+/// 按条件把记录写入新的传输文件，header 与压缩方式沿用原文件，返回写入的记录数
+pub async fn extract(file: &str, out: &str, filter: &ExtractFilter) -> anyhow::Result<u64> {
+    // 输出文件创建时会被清空，不能与输入是同一个文件
+    if let (Ok(a), Ok(b)) = (
+        tokio::fs::canonicalize(file).await,
+        tokio::fs::canonicalize(out).await,
+    ) && a == b
+    {
+        return Err(anyhow::anyhow!(
+            "output file {} is the same as the input file",
+            out
+        ));
+    }
+    let mut reader = TransferFileReader::new(file).await?;
+    let mut writer = TransferFileWriter::create(out, reader.header.clone())?;
+    while let Some(v) = reader.read_record_vec().await? {
+        let record = reader.decode_record(&v)?;
+        if filter.is_match(&record.table_name, &record.key) {
+            writer.write_record(
+                &record.table_name,
+                record.key.to_vec(),
+                record.value.to_vec(),
+            )?;
+        }
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::constant::USER_TREE_NAME;
+    use crate::transfer::model::TransferCompression;
+
+    fn temp_file(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!(
+                "transfer_inspect_{}_{}.data",
+                name,
+                std::process::id()
+            ))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// configs 为 (data_id, tenant, content)
+    fn write_file(path: &str, configs: &[(&str, &str, &str)]) -> anyhow::Result<()> {
+        let mut header = TransferHeaderDto::new(1, 1700000000000, Some("test".to_owned()));
+        header.add_name(CONFIG_TREE_NAME.clone());
+        header.add_name(NAMESPACE_TREE_NAME.clone());
+        header.set_compression(TransferCompression::Zstd);
+        let mut writer = TransferFileWriter::create(path, header)?;
+        for (data_id, tenant, content) in configs {
+            writer.write_record(
+                &CONFIG_TREE_NAME,
+                ConfigKey::new(data_id, "DEFAULT_GROUP", tenant)
+                    .build_key()
+                    .into_bytes(),
+                content.as_bytes().to_vec(),
+            )?;
+        }
+        for tenant in ["dev", "test"] {
+            writer.write_record(&NAMESPACE_TREE_NAME, tenant.as_bytes().to_vec(), vec![])?;
+        }
+        writer.write_record(&USER_TREE_NAME, b"admin".to_vec(), vec![])?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("app*.yaml", "app.yaml"));
+        assert!(glob_match("app*.yaml", "app-dev.yaml"));
+        assert!(!glob_match("app*.yaml", "app.yml"));
+        assert!(glob_match("a?c*", "abcdef"));
+        assert!(!glob_match("a?c", "ac"));
+        assert!(glob_match("*b*b", "abab"));
+        assert!(glob_match("服务?", "服务A"));
+    }
+
+    #[tokio::test]
+    async fn test_stat_diff_extract() -> anyhow::Result<()> {
+        let file = temp_file("a");
+        let other = temp_file("b");
+        let out = temp_file("out");
+        write_file(
+            &file,
+            &[
+                ("app.yaml", "", "a: 1"),
+                ("app.yaml", "dev", "a: 2"),
+                ("db.yaml", "dev", "url: x"),
+                ("app-old.yaml", "dev", "a: 0"),
+            ],
+        )?;
+        write_file(
+            &other,
+            &[
+                ("app.yaml", "", "a: 1"),
+                ("app.yaml", "dev", "a: 3"),
+                ("db.yaml", "dev", "url: x"),
+                ("new.yaml", "test", "n: 1"),
+            ],
+        )?;
+
+        let stat = stat_file(&file).await?;
+        assert_eq!(stat.header.from_sys.as_deref(), Some("test"));
+        assert_eq!(stat.table_count[CONFIG_TREE_NAME.as_str()], 4);
+        assert_eq!(stat.table_count[NAMESPACE_TREE_NAME.as_str()], 2);
+        assert_eq!(stat.table_count[USER_TREE_NAME.as_str()], 1);
+
+        let result = diff_file(&file, &other).await?;
+        assert_eq!(
+            result[CONFIG_TREE_NAME.as_str()],
+            TableDiff {
+                added: vec!["new.yaml|DEFAULT_GROUP|test".to_owned()],
+                removed: vec!["app-old.yaml|DEFAULT_GROUP|dev".to_owned()],
+                changed: vec!["app.yaml|DEFAULT_GROUP|dev".to_owned()],
+                same: 2,
+            }
+        );
+        assert!(result[NAMESPACE_TREE_NAME.as_str()].is_same());
+
+        let filter = ExtractFilter {
+            tenant: Some("dev".to_owned()),
+            data_id: Some("app*".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(extract(&file, &out, &filter).await?, 4);
+        let stat = stat_file(&out).await?;
+        assert_eq!(stat.table_count[CONFIG_TREE_NAME.as_str()], 2);
+        assert_eq!(stat.table_count[NAMESPACE_TREE_NAME.as_str()], 1);
+        assert_eq!(stat.table_count[USER_TREE_NAME.as_str()], 1);
+        assert_eq!(stat.header.compression()?, TransferCompression::Zstd);
+
+        let filter = ExtractFilter {
+            tables: Some(vec![USER_TREE_NAME.to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(extract(&file, &out, &filter).await?, 1);
+
+        // 输出到输入文件时拒绝，输入文件保持不变
+        let data = std::fs::read(&file)?;
+        let same = std::env::temp_dir()
+            .join(".")
+            .join(std::path::Path::new(&file).file_name().unwrap());
+        let same = same.to_string_lossy();
+        let err = extract(&file, &same, &filter).await.err().unwrap();
+        assert!(err.to_string().contains("same as the input file"));
+        assert_eq!(std::fs::read(&file)?, data);
+
+        for path in [file, other, out] {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,10 @@
 pub mod data_to_sqlite;
+pub mod inspect;
 pub mod model;
+pub mod mysql;
+pub mod mysql_to_data;
+pub mod openapi_to_data;
 pub mod reader;
 pub mod sqlite;
+pub mod sqlite_to_data;
+pub mod writer;
@@ -1,9 +1,105 @@
-use crate::common::pb::transfer::TransferHeader;
+use crate::common::pb::transfer::{TableNameMapEntity, TransferHeader, TransferItem};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::io::{Read, Write};
 use std::sync::Arc;
 
+///
+/// 传输文件开头 8 字节的前缀，由 6 字节的 magic 与 2 字节大端的格式版本组成
+/// 之后是 header 与各条记录，每条消息前带 varint 长度
+/// 版本 2 起记录可以按块压缩，文件末尾带上 TransferTrailer
+pub const TRANSFER_MAGIC: &[u8; 6] = b"rnacos";
+pub const TRANSFER_FORMAT_VERSION: u16 = 2;
+
+/// header.extend_info 中记录压缩方式的 key
+pub const TRANSFER_COMPRESSION_KEY: &str = "compression";
+
+///
+/// 记录块的压缩方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TransferCompression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl TransferCompression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    pub fn from_name(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "" | "none" => Ok(Self::None),
+            "gzip" => Ok(Self::Gzip),
+            "zstd" => Ok(Self::Zstd),
+            _ => Err(anyhow::anyhow!("unknown transfer compression: {}", name)),
+        }
+    }
+
+    pub fn compress(&self, v: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::None => Ok(v.to_vec()),
+            Self::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(v)?;
+                Ok(encoder.finish()?)
+            }
+            Self::Zstd => Ok(zstd::encode_all(v, 3)?),
+        }
+    }
+
+    pub fn decompress(&self, v: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::None => Ok(v.to_vec()),
+            Self::Gzip => {
+                let mut buf = Vec::new();
+                flate2::read::GzDecoder::new(v).read_to_end(&mut buf)?;
+                Ok(buf)
+            }
+            Self::Zstd => Ok(zstd::decode_all(v)?),
+        }
+    }
+}
+
+///
+/// 文件末尾固定长度的校验信息: 记录数 | 记录内容(未压缩)的 crc32 | 结束标记
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TransferTrailer {
+    pub record_count: u64,
+    pub checksum: u32,
+}
+
+impl TransferTrailer {
+    pub const LEN: u64 = 16;
+    const END_MAGIC: &'static [u8; 4] = b"rend";
+
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut v = [0u8; 16];
+        v[..8].copy_from_slice(&self.record_count.to_be_bytes());
+        v[8..12].copy_from_slice(&self.checksum.to_be_bytes());
+        v[12..].copy_from_slice(Self::END_MAGIC);
+        v
+    }
+
+    pub fn from_bytes(v: &[u8; 16]) -> Option<Self> {
+        if &v[12..] != Self::END_MAGIC {
+            return None;
+        }
+        Some(Self {
+            record_count: u64::from_be_bytes(v[..8].try_into().ok()?),
+            checksum: u32::from_be_bytes(v[8..12].try_into().ok()?),
+        })
+    }
+}
+
 #[derive(Clone, prost::Message, Serialize, Deserialize)]
 pub struct UserDo {
     #[prost(string, tag = "1")]
@@ -45,7 +141,124 @@ pub struct UserDo {
 
 impl UserDo {
     pub fn to_bytes(&self) -> Vec<u8> {
-        todo!()
+        let mut v = Vec::new();
+        prost::Message::encode(self, &mut v).unwrap();
+        v
+    }
+
+    pub fn from_bytes(v: &[u8]) -> anyhow::Result<Self> {
+        Ok(prost::Message::decode(v)?)
+    }
+}
+
+///
+/// 配置的 key, 传输文件中以 `data_id\x02group[\x02tenant]` 存储
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ConfigKey {
+    pub data_id: String,
+    pub group: String,
+    pub tenant: String,
+}
+
+impl ConfigKey {
+    pub fn new(data_id: &str, group: &str, tenant: &str) -> Self {
+        Self {
+            data_id: data_id.to_owned(),
+            group: group.to_owned(),
+            tenant: tenant.to_owned(),
+        }
+    }
+
+    pub fn build_key(&self) -> String {
+        if self.tenant.is_empty() {
+            return format!("{}\x02{}", self.data_id, self.group);
+        }
+        format!("{}\x02{}\x02{}", self.data_id, self.group, self.tenant)
+    }
+}
+
+impl From<&str> for ConfigKey {
+    fn from(s: &str) -> Self {
+        let mut list = s.split('\x02');
+        let data_id = list.next().unwrap_or_default();
+        let group = list.next().unwrap_or_default();
+        let tenant = list.next().unwrap_or_default();
+        Self::new(data_id, group, tenant)
+    }
+}
+
+#[derive(Clone, prost::Message, Serialize, Deserialize)]
+pub struct ConfigHistoryItemDo {
+    #[prost(string, tag = "1")]
+    pub content: String,
+
+    #[prost(int64, tag = "2")]
+    pub last_time: i64,
+
+    #[prost(string, optional, tag = "3")]
+    pub op_user: Option<String>,
+
+    #[prost(string, optional, tag = "4")]
+    pub config_type: Option<String>,
+
+    #[prost(string, optional, tag = "5")]
+    pub desc: Option<String>,
+}
+
+///
+/// 配置记录的值，带上这个配置的全部历史记录
+#[derive(Clone, prost::Message, Serialize, Deserialize)]
+pub struct ConfigValueDo {
+    #[prost(string, tag = "1")]
+    pub content: String,
+
+    #[prost(message, repeated, tag = "2")]
+    pub histories: Vec<ConfigHistoryItemDo>,
+
+    #[prost(int64, tag = "3")]
+    pub last_time: i64,
+
+    #[prost(string, optional, tag = "4")]
+    pub config_type: Option<String>,
+
+    #[prost(string, optional, tag = "5")]
+    pub desc: Option<String>,
+}
+
+impl ConfigValueDo {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v = Vec::new();
+        prost::Message::encode(self, &mut v).unwrap();
+        v
+    }
+
+    pub fn from_bytes(v: &[u8]) -> anyhow::Result<Self> {
+        Ok(prost::Message::decode(v)?)
+    }
+}
+
+///
+/// 命名空间记录的值，key 为 namespace_id
+#[derive(Clone, prost::Message, Serialize, Deserialize)]
+pub struct NamespaceDo {
+    #[prost(string, tag = "1")]
+    pub namespace_id: String,
+
+    #[prost(string, tag = "2")]
+    pub namespace_name: String,
+
+    #[prost(string, optional, tag = "3")]
+    pub namespace_desc: Option<String>,
+
+    #[prost(int64, optional, tag = "4")]
+    pub create_flag: Option<i64>,
+}
+
+impl NamespaceDo {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v = Vec::new();
+        prost::Message::encode(self, &mut v).unwrap();
+        v
     }
 
     pub fn from_bytes(v: &[u8]) -> anyhow::Result<Self> {
@@ -53,6 +266,7 @@ impl UserDo {
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct TransferHeaderDto {
     pub version: u64,
     pub modify_time: u64,
@@ -63,9 +277,136 @@ pub struct TransferHeaderDto {
     pub extend_info: HashMap<String, String>,
 }
 
+impl TransferHeaderDto {
+    pub fn new(version: u64, modify_time: u64, from_sys: Option<String>) -> Self {
+        Self {
+            version,
+            modify_time,
+            from_sys,
+            name_to_id: HashMap::new(),
+            id_to_name: HashMap::new(),
+            max_id: 0,
+            extend_info: HashMap::new(),
+        }
+    }
+
+    ///
+    /// 登记表名，返回表 id; id 从 1 开始，0 表示记录中直接带表名
+    pub fn add_name(&mut self, name: Arc<String>) -> u32 {
+        if let Some(id) = self.name_to_id.get(&name) {
+            return *id;
+        }
+        self.max_id += 1;
+        self.name_to_id.insert(name.clone(), self.max_id);
+        self.id_to_name.insert(self.max_id, name);
+        self.max_id
+    }
+
+    pub fn set_compression(&mut self, compression: TransferCompression) {
+        if compression == TransferCompression::None {
+            self.extend_info.remove(TRANSFER_COMPRESSION_KEY);
+        } else {
+            self.extend_info.insert(
+                TRANSFER_COMPRESSION_KEY.to_owned(),
+                compression.name().to_owned(),
+            );
+        }
+    }
+
+    pub fn compression(&self) -> anyhow::Result<TransferCompression> {
+        TransferCompression::from_name(
+            self.extend_info
+                .get(TRANSFER_COMPRESSION_KEY)
+                .map(|v| v.as_str())
+                .unwrap_or_default(),
+        )
+    }
+
+    pub fn to_do(&self) -> TransferHeader<'_> {
+        let mut table_name_map_entities = self
+            .id_to_name
+            .iter()
+            .map(|(id, name)| TableNameMapEntity {
+                id: *id,
+                name: Cow::Borrowed(name.as_str()),
+            })
+            .collect::<Vec<_>>();
+        table_name_map_entities.sort_by_key(|e| e.id);
+
+        let extend = if self.extend_info.is_empty() {
+            Vec::new()
+        } else {
+            serde_json::to_vec(&self.extend_info).unwrap_or_default()
+        };
+
+        TransferHeader {
+            version: self.version,
+            modify_time: self.modify_time,
+            from_sys: Cow::Borrowed(self.from_sys.as_deref().unwrap_or_default()),
+            table_name_map_entities,
+            extend: Cow::Owned(extend),
+        }
+    }
+}
+
 impl<'a> From<TransferHeader<'a>> for TransferHeaderDto {
     fn from(t: TransferHeader<'a>) -> Self {
-        todo!()
+        let mut name_to_id = HashMap::new();
+        let mut id_to_name = HashMap::new();
+        let mut max_id = 0;
+        for item in t.table_name_map_entities {
+            let name = Arc::new(item.name.to_string());
+            max_id = max_id.max(item.id);
+            name_to_id.insert(name.clone(), item.id);
+            id_to_name.insert(item.id, name);
+        }
+
+        let from_sys = if t.from_sys.is_empty() {
+            None
+        } else {
+            Some(t.from_sys.to_string())
+        };
+
+        let extend_info = if t.extend.is_empty() {
+            HashMap::new()
+        } else {
+            serde_json::from_slice(&t.extend).unwrap_or_default()
+        };
+
+        Self {
+            version: t.version,
+            modify_time: t.modify_time,
+            from_sys,
+            name_to_id,
+            id_to_name,
+            max_id,
+            extend_info,
+        }
+    }
+}
+
+///
+/// 写入传输文件的记录
+pub struct TransferRecordDto {
+    pub table_name: Option<Arc<String>>,
+    pub table_id: u32,
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+}
+
+impl TransferRecordDto {
+    pub fn to_do(&self) -> TransferItem<'_> {
+        TransferItem {
+            table_name: Cow::Borrowed(
+                self.table_name
+                    .as_ref()
+                    .map(|v| v.as_str())
+                    .unwrap_or_default(),
+            ),
+            table_id: self.table_id,
+            key: Cow::Borrowed(&self.key),
+            value: Cow::Borrowed(&self.value),
+        }
     }
 }
 
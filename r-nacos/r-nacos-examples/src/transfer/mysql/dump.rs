@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+
+///
+/// mysqldump 导出的值，数字保留原始文本
+#[derive(Debug, Clone, PartialEq)]
+pub enum MysqlValue {
+    Null,
+    Text(String),
+    Number(String),
+}
+
+#[derive(Debug, Default)]
+pub struct MysqlTable {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<MysqlValue>>,
+}
+
+pub struct MysqlRow<'a> {
+    columns: &'a [String],
+    values: &'a [MysqlValue],
+}
+
+impl<'a> MysqlRow<'a> {
+    fn value(&self, name: &str) -> Option<&'a MysqlValue> {
+        let i = self
+            .columns
+            .iter()
+            .position(|c| c.eq_ignore_ascii_case(name))?;
+        self.values.get(i)
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&'a str> {
+        match self.value(name)? {
+            MysqlValue::Text(v) | MysqlValue::Number(v) => Some(v.as_str()),
+            MysqlValue::Null => None,
+        }
+    }
+
+    pub fn get_i64(&self, name: &str) -> Option<i64> {
+        self.get_str(name)?.parse().ok()
+    }
+}
+
+///
+/// 解析 mysqldump 导出的 sql 文件，只处理 `CREATE TABLE` 与 `INSERT INTO`，不需要连接 mysql
+/// 没有列名的 insert 语句按建表语句的列顺序取值
+#[derive(Debug, Default)]
+pub struct SqlDump {
+    tables: HashMap<String, MysqlTable>,
+}
+
+impl SqlDump {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(sql: &str) -> anyhow::Result<Self> {
+        let mut dump = Self::default();
+        for stmt in split_statements(sql) {
+            let mut p = Parser::new(&stmt);
+            if p.eat_keywords(&["CREATE", "TABLE"]) {
+                p.eat_keywords(&["IF", "NOT", "EXISTS"]);
+                let name = p.identifier()?;
+                let columns = p.column_defs()?;
+                dump.tables.entry(name).or_default().columns = columns;
+            } else if p.eat_keywords(&["INSERT"]) || p.eat_keywords(&["REPLACE"]) {
+                p.eat_keywords(&["IGNORE"]);
+                p.eat_keywords(&["INTO"]);
+                let name = p.identifier()?;
+                let columns = p.column_list()?;
+                if !p.eat_keywords(&["VALUES"]) {
+                    return Err(anyhow::anyhow!("expect VALUES in insert into {}", name));
+                }
+                let rows = p.value_rows()?;
+                let table = dump.tables.entry(name.clone()).or_default();
+                match columns {
+                    // 列顺序与建表语句不同时，按建表语句的列顺序重排
+                    Some(columns) if !table.columns.is_empty() && columns != table.columns => {
+                        for row in rows {
+                            table.rows.push(reorder(&table.columns, &columns, row));
+                        }
+                    }
+                    Some(columns) => {
+                        table.columns = columns;
+                        table.rows.extend(rows);
+                    }
+                    None if table.columns.is_empty() => {
+                        return Err(anyhow::anyhow!(
+                            "insert into {} without columns before its create table",
+                            name
+                        ));
+                    }
+                    None => table.rows.extend(rows),
+                }
+            }
+        }
+        Ok(dump)
+    }
+
+    pub fn rows(&self, table: &str) -> impl Iterator<Item = MysqlRow<'_>> {
+        self.tables.get(table).into_iter().flat_map(|t| {
+            t.rows.iter().map(|values| MysqlRow {
+                columns: &t.columns,
+                values,
+            })
+        })
+    }
+}
+
+fn reorder(columns: &[String], from: &[String], row: Vec<MysqlValue>) -> Vec<MysqlValue> {
+    columns
+        .iter()
+        .map(|c| {
+            from.iter()
+                .position(|f| f == c)
+                .and_then(|i| row.get(i).cloned())
+                .unwrap_or(MysqlValue::Null)
+        })
+        .collect()
+}
+
+///
+/// 按 `;` 切分语句，跳过注释(包括 `/*!40101 ... */` 这类条件注释)
+fn split_statements(sql: &str) -> Vec<String> {
+    let mut list = Vec::new();
+    let mut stmt = String::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => {
+                stmt.push(c);
+                while let Some(v) = chars.next() {
+                    stmt.push(v);
+                    if v == '\\' && c != '`' {
+                        if let Some(e) = chars.next() {
+                            stmt.push(e);
+                        }
+                    } else if v == c {
+                        // '' 转义
+                        if chars.peek() == Some(&c) {
+                            stmt.push(chars.next().unwrap());
+                        } else {
+                            break;
+                        }
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                for v in chars.by_ref() {
+                    if v == '\n' {
+                        break;
+                    }
+                }
+            }
+            '#' => {
+                for v in chars.by_ref() {
+                    if v == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut pre = ' ';
+                for v in chars.by_ref() {
+                    if pre == '*' && v == '/' {
+                        break;
+                    }
+                    pre = v;
+                }
+            }
+            ';' => {
+                if !stmt.trim().is_empty() {
+                    list.push(stmt.trim().to_owned());
+                }
+                stmt.clear();
+            }
+            _ => stmt.push(c),
+        }
+    }
+    if !stmt.trim().is_empty() {
+        list.push(stmt.trim().to_owned());
+    }
+    list
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Self {
+        Self { s, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_ws();
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, c: char) -> anyhow::Result<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "expect '{}' at: {}",
+                c,
+                self.rest().chars().take(32).collect::<String>()
+            ))
+        }
+    }
+
+    fn word(&mut self) -> &'a str {
+        self.skip_ws();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.' || c == '-' || c == '+'))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    ///
+    /// 依次匹配关键字(忽略大小写)，不匹配时不移动位置
+    fn eat_keywords(&mut self, keywords: &[&str]) -> bool {
+        let start = self.pos;
+        for keyword in keywords {
+            if !self.word().eq_ignore_ascii_case(keyword) {
+                self.pos = start;
+                return false;
+            }
+        }
+        true
+    }
+
+    fn identifier(&mut self) -> anyhow::Result<String> {
+        let name = if self.eat('`') {
+            let rest = self.rest();
+            let end = rest
+                .find('`')
+                .ok_or_else(|| anyhow::anyhow!("unclosed identifier"))?;
+            self.pos += end + 1;
+            rest[..end].to_owned()
+        } else {
+            self.word().to_owned()
+        };
+        // db.table 只保留表名
+        if self.eat('.') {
+            return self.identifier();
+        }
+        if name.is_empty() {
+            return Err(anyhow::anyhow!("expect identifier"));
+        }
+        Ok(name.rsplit('.').next().unwrap_or_default().to_owned())
+    }
+
+    ///
+    /// 建表语句中的列名，跳过 key 与约束
+    fn column_defs(&mut self) -> anyhow::Result<Vec<String>> {
+        self.expect('(')?;
+        let mut columns = Vec::new();
+        let mut depth = 0;
+        let mut at_item_start = true;
+        while let Some(c) = self.peek() {
+            if at_item_start {
+                at_item_start = false;
+                if c == '`' {
+                    columns.push(self.identifier()?);
+                    continue;
+                }
+            }
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 0 => {
+                    self.pos += 1;
+                    return Ok(columns);
+                }
+                ')' => depth -= 1,
+                ',' if depth == 0 => at_item_start = true,
+                '\'' | '"' => {
+                    self.string()?;
+                    continue;
+                }
+                _ => {}
+            }
+            self.pos += c.len_utf8();
+        }
+        Err(anyhow::anyhow!("unclosed create table"))
+    }
+
+    fn column_list(&mut self) -> anyhow::Result<Option<Vec<String>>> {
+        if !self.eat('(') {
+            return Ok(None);
+        }
+        let mut columns = vec![self.identifier()?];
+        while self.eat(',') {
+            columns.push(self.identifier()?);
+        }
+        self.expect(')')?;
+        Ok(Some(columns))
+    }
+
+    fn value_rows(&mut self) -> anyhow::Result<Vec<Vec<MysqlValue>>> {
+        let mut rows = Vec::new();
+        loop {
+            self.expect('(')?;
+            let mut row = vec![self.value()?];
+            while self.eat(',') {
+                row.push(self.value()?);
+            }
+            self.expect(')')?;
+            rows.push(row);
+            if !self.eat(',') {
+                return Ok(rows);
+            }
+        }
+    }
+
+    fn value(&mut self) -> anyhow::Result<MysqlValue> {
+        match self.peek() {
+            Some('\'') | Some('"') => Ok(MysqlValue::Text(self.string()?)),
+            _ => {
+                let word = self.word();
+                if word.eq_ignore_ascii_case("NULL") {
+                    Ok(MysqlValue::Null)
+                } else if word.starts_with('_') && matches!(self.peek(), Some('\'') | Some('"')) {
+                    // 字符集前缀，如 _binary 'abc'
+                    Ok(MysqlValue::Text(self.string()?))
+                } else if word.eq_ignore_ascii_case("X") && self.peek() == Some('\'') {
+                    let hex = self.string()?;
+                    Ok(MysqlValue::Text(decode_hex(&hex)?))
+                } else if let Some(hex) = word.strip_prefix("0x") {
+                    Ok(MysqlValue::Text(decode_hex(hex)?))
+                } else if word.is_empty() {
+                    Err(anyhow::anyhow!(
+                        "unexpected value at: {}",
+                        self.rest().chars().take(32).collect::<String>()
+                    ))
+                } else {
+                    Ok(MysqlValue::Number(word.to_owned()))
+                }
+            }
+        }
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let quote = self.peek().unwrap_or_default();
+        self.pos += 1;
+        let mut v = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            if c == '\\' {
+                let (_, e) = chars
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("unclosed string"))?;
+                v.push(match e {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    '0' => '\0',
+                    'b' => '\u{8}',
+                    'Z' => '\u{1a}',
+                    e => e,
+                });
+            } else if c == quote {
+                if self.rest()[i + 1..].starts_with(quote) {
+                    chars.next();
+                    v.push(quote);
+                } else {
+                    self.pos += i + 1;
+                    return Ok(v);
+                }
+            } else {
+                v.push(c);
+            }
+        }
+        Err(anyhow::anyhow!("unclosed string"))
+    }
+}
+
+fn decode_hex(hex: &str) -> anyhow::Result<String> {
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2).unwrap_or("x"), 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| anyhow::anyhow!("invalid hex value 0x{}", hex))?;
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
@@ -0,0 +1,128 @@
+use crate::transfer::mysql::dump::MysqlRow;
+
+pub mod dump;
+
+///
+/// nacos 的默认命名空间在 mysql 中为空串或 public，r-nacos 中为空串
+pub fn default_tenant(tenant_id: Option<&str>) -> String {
+    match tenant_id {
+        None | Some("public") => String::new(),
+        Some(v) => v.to_owned(),
+    }
+}
+
+///
+/// nacos 的时间字段为服务器本地时间的 datetime，转为毫秒时间戳
+pub fn datetime_to_millis(v: Option<&str>) -> i64 {
+    let Some(v) = v else {
+        return 0;
+    };
+    // 兼容带毫秒的 datetime(3)
+    let Ok(dt) = chrono::NaiveDateTime::parse_from_str(v, "%Y-%m-%d %H:%M:%S%.f") else {
+        return 0;
+    };
+    dt.and_local_timezone(chrono::Local)
+        .earliest()
+        .map(|t| t.timestamp_millis())
+        .unwrap_or_else(|| dt.and_utc().timestamp_millis())
+}
+
+/// nacos 表 config_info
+#[derive(Debug, Default)]
+pub struct ConfigInfoDO {
+    pub id: Option<i64>,
+    pub data_id: String,
+    pub group_id: String,
+    pub tenant_id: String,
+    pub content: String,
+    pub md5: Option<String>,
+    pub gmt_modified: i64,
+    pub config_type: Option<String>,
+    pub config_desc: Option<String>,
+}
+
+impl ConfigInfoDO {
+    pub fn from_row(r: &MysqlRow) -> Self {
+        Self {
+            id: r.get_i64("id"),
+            data_id: r.get_str("data_id").unwrap_or_default().to_owned(),
+            group_id: r.get_str("group_id").unwrap_or_default().to_owned(),
+            tenant_id: default_tenant(r.get_str("tenant_id")),
+            content: r.get_str("content").unwrap_or_default().to_owned(),
+            md5: r.get_str("md5").map(|v| v.to_owned()),
+            gmt_modified: datetime_to_millis(r.get_str("gmt_modified")),
+            config_type: r.get_str("type").map(|v| v.to_owned()),
+            config_desc: r.get_str("c_desc").map(|v| v.to_owned()),
+        }
+    }
+}
+
+/// nacos 表 his_config_info
+#[derive(Debug, Default)]
+pub struct HisConfigInfoDO {
+    pub nid: Option<i64>,
+    pub data_id: String,
+    pub group_id: String,
+    pub tenant_id: String,
+    pub content: String,
+    pub md5: Option<String>,
+    pub gmt_modified: i64,
+    pub src_user: Option<String>,
+    pub op_type: Option<String>,
+}
+
+impl HisConfigInfoDO {
+    pub fn from_row(r: &MysqlRow) -> Self {
+        Self {
+            nid: r.get_i64("nid"),
+            data_id: r.get_str("data_id").unwrap_or_default().to_owned(),
+            group_id: r.get_str("group_id").unwrap_or_default().to_owned(),
+            tenant_id: default_tenant(r.get_str("tenant_id")),
+            content: r.get_str("content").unwrap_or_default().to_owned(),
+            md5: r.get_str("md5").map(|v| v.to_owned()),
+            gmt_modified: datetime_to_millis(r.get_str("gmt_modified")),
+            src_user: r.get_str("src_user").map(|v| v.to_owned()),
+            op_type: r.get_str("op_type").map(|v| v.trim().to_owned()),
+        }
+    }
+}
+
+/// nacos 表 tenant_info
+#[derive(Debug, Default)]
+pub struct TenantInfoDO {
+    pub tenant_id: String,
+    pub tenant_name: Option<String>,
+    pub tenant_desc: Option<String>,
+}
+
+impl TenantInfoDO {
+    pub fn from_row(r: &MysqlRow) -> Self {
+        Self {
+            tenant_id: default_tenant(r.get_str("tenant_id")),
+            tenant_name: r.get_str("tenant_name").map(|v| v.to_owned()),
+            tenant_desc: r.get_str("tenant_desc").map(|v| v.to_owned()),
+        }
+    }
+}
+
+/// nacos 表 users
+#[derive(Debug, Default)]
+pub struct NacosUserDO {
+    pub username: String,
+    pub password: String,
+    pub enabled: bool,
+}
+
+impl NacosUserDO {
+    pub fn from_row(r: &MysqlRow) -> Self {
+        let enabled = match r.get_str("enabled") {
+            Some(v) => v == "1" || v.eq_ignore_ascii_case("true"),
+            None => true,
+        };
+        Self {
+            username: r.get_str("username").unwrap_or_default().to_owned(),
+            password: r.get_str("password").unwrap_or_default().to_owned(),
+            enabled,
+        }
+    }
+}
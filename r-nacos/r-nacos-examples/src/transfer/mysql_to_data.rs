@@ -0,0 +1,384 @@
+use crate::common::constant::{
+    CONFIG_TREE_NAME, NAMESPACE_TREE_NAME, USER_ROLE_DEVELOPER, USER_ROLE_MANAGER,
+    USER_ROLE_VISITOR, USER_TREE_NAME,
+};
+use crate::transfer::model::{
+    ConfigHistoryItemDo, ConfigKey, ConfigValueDo, NamespaceDo, TransferHeaderDto, UserDo,
+};
+use crate::transfer::mysql::dump::SqlDump;
+use crate::transfer::mysql::{ConfigInfoDO, HisConfigInfoDO, NacosUserDO, TenantInfoDO};
+use crate::transfer::writer::TransferFileWriter;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const FROM_SYS: &str = "nacos-mysql";
+const NACOS_ROLE_ADMIN: &str = "ROLE_ADMIN";
+
+#[derive(Debug, Default)]
+pub struct MysqlTransferCount {
+    pub config: usize,
+    pub config_history: usize,
+    pub tenant: usize,
+    pub user: usize,
+    pub md5_mismatch: usize,
+    pub ignore_history: usize,
+}
+
+///
+/// 把 nacos mysql 库(mysqldump 导出的 sql 文件)中的配置、命名空间、用户转为传输文件
+pub async fn mysql_to_data(dump_file: &str, data_file: &str) -> anyhow::Result<()> {
+    let dump = SqlDump::load(dump_file)?;
+    let count = dump_to_data(&dump, data_file)?;
+    log::info!(
+        "transfer from nacos mysql finished, config count:{}, config history count:{}, tenant count:{}, user count:{}, md5 mismatch count:{}, ignore history count:{}",
+        count.config,
+        count.config_history,
+        count.tenant,
+        count.user,
+        count.md5_mismatch,
+        count.ignore_history
+    );
+    Ok(())
+}
+
+pub fn dump_to_data(dump: &SqlDump, data_file: &str) -> anyhow::Result<MysqlTransferCount> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    let mut header = TransferHeaderDto::new(1, now.as_millis() as u64, Some(FROM_SYS.to_owned()));
+    header.add_name(CONFIG_TREE_NAME.clone());
+    header.add_name(NAMESPACE_TREE_NAME.clone());
+    header.add_name(USER_TREE_NAME.clone());
+    let mut writer = TransferFileWriter::create(data_file, header)?;
+
+    let mut count = MysqlTransferCount::default();
+    self::write_configs(&mut writer, dump, &mut count)?;
+    self::write_namespaces(&mut writer, dump, &mut count)?;
+    self::write_users(&mut writer, dump, now.as_secs() as u32, &mut count)?;
+    writer.finish()?;
+    Ok(count)
+}
+
+///
+/// md5 为空时不校验
+fn md5_match(content: &str, md5: Option<&str>) -> bool {
+    match md5 {
+        Some(md5) if !md5.is_empty() => {
+            format!("{:x}", md5::compute(content)).eq_ignore_ascii_case(md5)
+        }
+        _ => true,
+    }
+}
+
+fn write_configs(
+    writer: &mut TransferFileWriter,
+    dump: &SqlDump,
+    count: &mut MysqlTransferCount,
+) -> anyhow::Result<()> {
+    let mut history_map: HashMap<ConfigKey, Vec<HisConfigInfoDO>> = HashMap::new();
+    for row in dump.rows("his_config_info") {
+        let history = HisConfigInfoDO::from_row(&row);
+        let key = ConfigKey::new(&history.data_id, &history.group_id, &history.tenant_id);
+        history_map.entry(key).or_default().push(history);
+    }
+
+    for row in dump.rows("config_info") {
+        let config = ConfigInfoDO::from_row(&row);
+        let key = ConfigKey::new(&config.data_id, &config.group_id, &config.tenant_id);
+        // 内容以 content 为准，md5 不一致时只记录
+        if !md5_match(&config.content, config.md5.as_deref()) {
+            log::warn!("config md5 mismatch, key:{:?}", &key);
+            count.md5_mismatch += 1;
+        }
+
+        let mut histories = history_map.remove(&key).unwrap_or_default();
+        histories.sort_by_key(|v| v.nid);
+        let mut history_items = Vec::with_capacity(histories.len());
+        for history in histories {
+            if !md5_match(&history.content, history.md5.as_deref()) {
+                log::warn!("config history md5 mismatch, key:{:?}", &key);
+                count.md5_mismatch += 1;
+            }
+            history_items.push(ConfigHistoryItemDo {
+                content: history.content,
+                last_time: history.gmt_modified,
+                op_user: history.src_user,
+                config_type: config.config_type.clone(),
+                desc: config.config_desc.clone(),
+            });
+        }
+        count.config_history += history_items.len();
+
+        let value_do = ConfigValueDo {
+            content: config.content,
+            histories: history_items,
+            last_time: config.gmt_modified,
+            config_type: config.config_type,
+            desc: config.config_desc,
+        };
+        writer.write_record(
+            &CONFIG_TREE_NAME,
+            key.build_key().into_bytes(),
+            value_do.to_bytes(),
+        )?;
+        count.config += 1;
+    }
+
+    // 已删除配置的历史记录没有归属
+    count.ignore_history = history_map.values().map(|v| v.len()).sum();
+    Ok(())
+}
+
+fn write_namespaces(
+    writer: &mut TransferFileWriter,
+    dump: &SqlDump,
+    count: &mut MysqlTransferCount,
+) -> anyhow::Result<()> {
+    for row in dump.rows("tenant_info") {
+        let tenant = TenantInfoDO::from_row(&row);
+        // 默认命名空间不需要导出
+        if tenant.tenant_id.is_empty() {
+            continue;
+        }
+        let namespace_name = match tenant.tenant_name {
+            Some(v) if !v.is_empty() => v,
+            _ => tenant.tenant_id.clone(),
+        };
+        let value_do = NamespaceDo {
+            namespace_id: tenant.tenant_id,
+            namespace_name,
+            namespace_desc: tenant.tenant_desc,
+            create_flag: None,
+        };
+        writer.write_record(
+            &NAMESPACE_TREE_NAME,
+            value_do.namespace_id.as_bytes().to_vec(),
+            value_do.to_bytes(),
+        )?;
+        count.tenant += 1;
+    }
+    Ok(())
+}
+
+fn write_users(
+    writer: &mut TransferFileWriter,
+    dump: &SqlDump,
+    now_second: u32,
+    count: &mut MysqlTransferCount,
+) -> anyhow::Result<()> {
+    let mut role_map: HashMap<String, Vec<String>> = HashMap::new();
+    for row in dump.rows("roles") {
+        let (Some(username), Some(role)) = (row.get_str("username"), row.get_str("role")) else {
+            continue;
+        };
+        let role = if role == NACOS_ROLE_ADMIN {
+            USER_ROLE_MANAGER
+        } else {
+            USER_ROLE_DEVELOPER
+        };
+        let roles = role_map.entry(username.to_owned()).or_default();
+        if !roles.iter().any(|v| v == role) {
+            roles.push(role.to_owned());
+        }
+    }
+
+    for row in dump.rows("users") {
+        let user = NacosUserDO::from_row(&row);
+        if user.username.is_empty() {
+            continue;
+        }
+        // 没有角色的用户只能查看
+        let roles = role_map
+            .remove(&user.username)
+            .unwrap_or_else(|| vec![USER_ROLE_VISITOR.to_owned()]);
+        // nacos 与 r-nacos 的密码都是 bcrypt 哈希，可以直接使用
+        let value_do = UserDo {
+            username: user.username.clone(),
+            password: String::new(),
+            nickname: user.username,
+            gmt_create: now_second,
+            gmt_modified: now_second,
+            enable: user.enabled,
+            roles,
+            extend_info: HashMap::new(),
+            password_hash: Some(user.password),
+            namespace_privilege_flags: None,
+            namespace_white_list: vec![],
+            namespace_black_list: vec![],
+        };
+        writer.write_record(
+            &USER_TREE_NAME,
+            value_do.username.as_bytes().to_vec(),
+            value_do.to_bytes(),
+        )?;
+        count.user += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::reader::TransferFileReader;
+
+    const DUMP: &str = r#"-- MySQL dump 10.13  Distrib 8.0.36, for Linux (x86_64)
+/*!40101 SET @OLD_CHARACTER_SET_CLIENT=@@CHARACTER_SET_CLIENT */;
+/*!40101 SET NAMES utf8mb4 */;
+
+DROP TABLE IF EXISTS `config_info`;
+CREATE TABLE `config_info` (
+  `id` bigint NOT NULL AUTO_INCREMENT COMMENT 'id',
+  `data_id` varchar(255) COLLATE utf8_bin NOT NULL COMMENT 'data_id',
+  `group_id` varchar(128) COLLATE utf8_bin DEFAULT NULL,
+  `content` longtext COLLATE utf8_bin NOT NULL COMMENT 'content',
+  `md5` varchar(32) COLLATE utf8_bin DEFAULT NULL COMMENT 'md5',
+  `gmt_create` datetime NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+  `gmt_modified` datetime NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '修改时间',
+  `src_user` text COLLATE utf8_bin COMMENT 'source user',
+  `src_ip` varchar(50) COLLATE utf8_bin DEFAULT NULL COMMENT 'source ip',
+  `app_name` varchar(128) COLLATE utf8_bin DEFAULT NULL,
+  `tenant_id` varchar(128) COLLATE utf8_bin DEFAULT '' COMMENT '租户字段',
+  `c_desc` varchar(256) COLLATE utf8_bin DEFAULT NULL,
+  `c_use` varchar(64) COLLATE utf8_bin DEFAULT NULL,
+  `effect` varchar(64) COLLATE utf8_bin DEFAULT NULL,
+  `type` varchar(64) COLLATE utf8_bin DEFAULT NULL,
+  `c_schema` text COLLATE utf8_bin,
+  `encrypted_data_key` text COLLATE utf8_bin NOT NULL COMMENT '秘钥',
+  PRIMARY KEY (`id`),
+  UNIQUE KEY `uk_configinfo_datagrouptenant` (`data_id`,`group_id`,`tenant_id`)
+) ENGINE=InnoDB AUTO_INCREMENT=4 DEFAULT CHARSET=utf8mb3 COLLATE=utf8_bin COMMENT='config_info';
+
+LOCK TABLES `config_info` WRITE;
+INSERT INTO `config_info` VALUES (1,'app.yaml','DEFAULT_GROUP','a: 1\nb: \'中文\'','MD5_APP','2024-01-02 03:04:05','2024-01-02 03:04:05','nacos','127.0.0.1','','','app config',NULL,NULL,'yaml',NULL,''),(2,'db.properties','DEFAULT_GROUP','url=jdbc:mysql://db;x=1','00000000000000000000000000000000','2024-01-02 03:04:05','2024-01-03 03:04:05',NULL,'127.0.0.1','',NULL,NULL,NULL,NULL,'properties',NULL,''),(3,'app.yaml','DEFAULT_GROUP','a: 2','','2024-01-02 03:04:05','2024-01-02 03:04:05',NULL,'127.0.0.1','','dev',NULL,NULL,NULL,'yaml',NULL,'');
+UNLOCK TABLES;
+
+CREATE TABLE `his_config_info` (
+  `id` bigint unsigned NOT NULL,
+  `nid` bigint unsigned NOT NULL AUTO_INCREMENT,
+  `data_id` varchar(255) COLLATE utf8_bin NOT NULL,
+  `group_id` varchar(128) COLLATE utf8_bin NOT NULL,
+  `app_name` varchar(128) COLLATE utf8_bin DEFAULT NULL COMMENT 'app_name',
+  `content` longtext COLLATE utf8_bin NOT NULL,
+  `md5` varchar(32) COLLATE utf8_bin DEFAULT NULL,
+  `gmt_create` datetime NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `gmt_modified` datetime NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `src_user` text COLLATE utf8_bin,
+  `src_ip` varchar(50) COLLATE utf8_bin DEFAULT NULL,
+  `op_type` char(10) COLLATE utf8_bin DEFAULT NULL,
+  `tenant_id` varchar(128) COLLATE utf8_bin DEFAULT '' COMMENT '租户字段',
+  `encrypted_data_key` text COLLATE utf8_bin NOT NULL COMMENT '秘钥',
+  PRIMARY KEY (`nid`),
+  KEY `idx_gmt_create` (`gmt_create`)
+) ENGINE=InnoDB AUTO_INCREMENT=5 DEFAULT CHARSET=utf8mb3 COLLATE=utf8_bin;
+INSERT INTO `his_config_info` VALUES (1,2,'app.yaml','DEFAULT_GROUP','','a: 0',NULL,'2024-01-01 00:00:00','2024-01-01 00:00:00','admin','127.0.0.1','U','',''),(1,1,'app.yaml','DEFAULT_GROUP','','',NULL,'2023-12-31 00:00:00','2023-12-31 00:00:00',NULL,'127.0.0.1','I','','');
+INSERT INTO `his_config_info` (`id`,`nid`,`data_id`,`group_id`,`content`,`op_type`,`tenant_id`,`gmt_modified`) VALUES (9,4,'removed.yaml','DEFAULT_GROUP','x','D','public','2024-01-01 00:00:00');
+
+CREATE TABLE `tenant_info` (
+  `id` bigint NOT NULL AUTO_INCREMENT COMMENT 'id',
+  `kp` varchar(128) COLLATE utf8_bin NOT NULL COMMENT 'kp',
+  `tenant_id` varchar(128) COLLATE utf8_bin DEFAULT '' COMMENT 'tenant_id',
+  `tenant_name` varchar(128) COLLATE utf8_bin DEFAULT '' COMMENT 'tenant_name',
+  `tenant_desc` varchar(256) COLLATE utf8_bin DEFAULT NULL COMMENT 'tenant_desc',
+  `create_source` varchar(32) COLLATE utf8_bin DEFAULT NULL COMMENT 'create_source',
+  `gmt_create` bigint NOT NULL COMMENT '创建时间',
+  `gmt_modified` bigint NOT NULL COMMENT '修改时间',
+  PRIMARY KEY (`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb3 COLLATE=utf8_bin COMMENT='tenant_info';
+INSERT INTO `tenant_info` VALUES (1,'1','dev','开发环境','dev env','nacos',1704164645000,1704164645000),(2,'1','test','',NULL,'nacos',1704164645000,1704164645000);
+
+CREATE TABLE `users` (
+  `username` varchar(50) NOT NULL PRIMARY KEY,
+  `password` varchar(500) NOT NULL,
+  `enabled` boolean NOT NULL
+);
+INSERT INTO `users` VALUES ('nacos','$2a$10$EuWPZHzz32dJN7jexM34MOeYirDdFAZm2kuWj7VEOJhhZkDrxfvUu',1),('dev','$2a$10$hash',0);
+
+CREATE TABLE `roles` (
+  `username` varchar(50) NOT NULL,
+  `role` varchar(50) NOT NULL,
+  UNIQUE INDEX `idx_user_role` (`username` ASC, `role` ASC) USING BTREE
+);
+INSERT INTO `roles` VALUES ('nacos','ROLE_ADMIN');
+"#;
+
+    #[test]
+    fn test_parse_dump() -> anyhow::Result<()> {
+        let dump = SqlDump::parse(DUMP)?;
+        let configs: Vec<_> = dump
+            .rows("config_info")
+            .map(|r| ConfigInfoDO::from_row(&r))
+            .collect();
+        assert_eq!(configs.len(), 3);
+        assert_eq!(configs[0].content, "a: 1\nb: '中文'");
+        assert_eq!(configs[1].content, "url=jdbc:mysql://db;x=1");
+        assert_eq!(configs[1].config_desc, None);
+        assert_eq!(configs[2].tenant_id, "dev");
+
+        // 带列名的 insert 按建表语句的列顺序取值
+        let history = dump
+            .rows("his_config_info")
+            .map(|r| HisConfigInfoDO::from_row(&r))
+            .find(|v| v.nid == Some(4))
+            .unwrap();
+        assert_eq!(history.data_id, "removed.yaml");
+        assert_eq!(history.tenant_id, "");
+        assert_eq!(history.md5, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mysql_to_data() -> anyhow::Result<()> {
+        // 修正第一条配置的 md5，第二条保持错误
+        let md5 = format!("{:x}", md5::compute("a: 1\nb: '中文'"));
+        let dump = SqlDump::parse(&DUMP.replace("MD5_APP", &md5))?;
+        let data_file = std::env::temp_dir()
+            .join(format!("mysql_to_data_{}.data", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let count = dump_to_data(&dump, &data_file)?;
+        assert_eq!(count.config, 3);
+        assert_eq!(count.config_history, 2);
+        assert_eq!(count.tenant, 2);
+        assert_eq!(count.user, 2);
+        assert_eq!(count.md5_mismatch, 1);
+        assert_eq!(count.ignore_history, 1);
+
+        let mut reader = TransferFileReader::new(&data_file).await?;
+        assert_eq!(reader.header.from_sys.as_deref(), Some(FROM_SYS));
+        let mut configs = HashMap::new();
+        let mut namespaces = HashMap::new();
+        let mut users = HashMap::new();
+        while let Some(v) = reader.read_record_vec().await? {
+            let record = reader.decode_record(&v)?;
+            let key = String::from_utf8(record.key.to_vec())?;
+            if record.table_name == *CONFIG_TREE_NAME {
+                configs.insert(key, ConfigValueDo::from_bytes(&record.value)?);
+            } else if record.table_name == *NAMESPACE_TREE_NAME {
+                namespaces.insert(key, NamespaceDo::from_bytes(&record.value)?);
+            } else if record.table_name == *USER_TREE_NAME {
+                users.insert(key, UserDo::from_bytes(&record.value)?);
+            }
+        }
+        std::fs::remove_file(&data_file)?;
+
+        let app = &configs[&ConfigKey::new("app.yaml", "DEFAULT_GROUP", "").build_key()];
+        assert_eq!(app.content, "a: 1\nb: '中文'");
+        assert_eq!(app.config_type.as_deref(), Some("yaml"));
+        assert_eq!(app.desc.as_deref(), Some("app config"));
+        let histories: Vec<_> = app.histories.iter().map(|v| v.content.as_str()).collect();
+        assert_eq!(histories, vec!["", "a: 0"]);
+        assert_eq!(app.histories[1].op_user.as_deref(), Some("admin"));
+        assert!(app.histories[0].last_time < app.histories[1].last_time);
+        assert!(
+            configs.contains_key(&ConfigKey::new("app.yaml", "DEFAULT_GROUP", "dev").build_key())
+        );
+
+        assert_eq!(namespaces["dev"].namespace_name, "开发环境");
+        assert_eq!(namespaces["test"].namespace_name, "test");
+
+        assert_eq!(users["nacos"].roles, vec![USER_ROLE_MANAGER.to_owned()]);
+        assert!(users["nacos"].enable);
+        assert_eq!(users["dev"].roles, vec![USER_ROLE_VISITOR.to_owned()]);
+        assert!(!users["dev"].enable);
+        assert_eq!(users["dev"].password_hash.as_deref(), Some("$2a$10$hash"));
+        Ok(())
+    }
+}
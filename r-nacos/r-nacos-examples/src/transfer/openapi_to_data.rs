@@ -0,0 +1,636 @@
+use crate::common::constant::{CONFIG_TREE_NAME, NAMESPACE_TREE_NAME};
+use crate::transfer::model::{
+    ConfigKey, ConfigValueDo, NamespaceDo, TransferHeaderDto, TransferTrailer,
+};
+use crate::transfer::writer::TransferFileWriter;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const FROM_SYS: &str = "nacos-openapi";
+
+///
+/// 通过 nacos open api 导出时的分页、限流与重试参数
+#[derive(Debug, Clone)]
+pub struct OpenapiOption {
+    pub page_size: u32,
+    /// 两次请求之间的最小间隔，避免压垮源 nacos
+    pub request_interval: Duration,
+    /// 单个请求失败后的重试次数
+    pub retry: u32,
+}
+
+impl Default for OpenapiOption {
+    fn default() -> Self {
+        Self {
+            page_size: 100,
+            request_interval: Duration::from_millis(50),
+            retry: 3,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OpenapiTransferCount {
+    pub config: usize,
+    pub tenant: usize,
+}
+
+///
+/// 导出进度，每写完一页保存一次，导出完成后删除
+/// 中断后再次执行时从记录的位置继续，文件中未保存进度的记录会被截掉
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Progress {
+    namespaces: Vec<String>,
+    namespace_index: usize,
+    page_no: u32,
+    offset: u64,
+    // offset 处的记录数与校验值，续写时恢复 trailer
+    record_count: u64,
+    checksum: u32,
+    config_count: usize,
+    tenant_count: usize,
+}
+
+impl Progress {
+    fn path(data_file: &str) -> String {
+        format!("{}.progress", data_file)
+    }
+
+    fn load(data_file: &str) -> Option<Self> {
+        if !Path::new(data_file).exists() {
+            return None;
+        }
+        let v = std::fs::read(Self::path(data_file)).ok()?;
+        serde_json::from_slice(&v).ok()
+    }
+
+    ///
+    /// 记录当前写入位置与校验信息并保存
+    fn checkpoint(
+        &mut self,
+        writer: &mut TransferFileWriter,
+        data_file: &str,
+    ) -> anyhow::Result<()> {
+        self.offset = writer.position()?;
+        let trailer = writer.trailer();
+        self.record_count = trailer.record_count;
+        self.checksum = trailer.checksum;
+        self.save(data_file)
+    }
+
+    fn save(&self, data_file: &str) -> anyhow::Result<()> {
+        // 先写临时文件再改名，避免进度文件写一半
+        let path = Self::path(data_file);
+        let tmp = format!("{}.tmp", path);
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LoginResult {
+    access_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NamespaceResult {
+    #[serde(default)]
+    data: Vec<NamespaceInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NamespaceInfo {
+    #[serde(default)]
+    namespace: String,
+    namespace_show_name: Option<String>,
+    namespace_desc: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConfigPage {
+    #[serde(default)]
+    pages_available: u32,
+    #[serde(default)]
+    page_items: Vec<ConfigInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConfigInfo {
+    data_id: String,
+    group: String,
+    content: Option<String>,
+    #[serde(rename = "type")]
+    config_type: Option<String>,
+    desc: Option<String>,
+}
+
+struct RateLimiter {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl RateLimiter {
+    async fn acquire(&mut self) {
+        if let Some(last) = self.last {
+            let next = last + self.interval;
+            let now = Instant::now();
+            if next > now {
+                tokio::time::sleep(next - now).await;
+            }
+        }
+        self.last = Some(Instant::now());
+    }
+}
+
+///
+/// nacos open api 客户端，token 失效(403)时重新登录
+struct NacosOpenapi {
+    client: reqwest::Client,
+    host: String,
+    username: String,
+    password: String,
+    access_token: Option<String>,
+    limiter: RateLimiter,
+    retry: u32,
+}
+
+impl NacosOpenapi {
+    fn new(host: &str, username: &str, password: &str, option: &OpenapiOption) -> Self {
+        let host = host.trim_end_matches('/');
+        let host = if host.starts_with("http://") || host.starts_with("https://") {
+            host.to_owned()
+        } else {
+            format!("http://{}", host)
+        };
+        Self {
+            client: reqwest::Client::new(),
+            host,
+            username: username.to_owned(),
+            password: password.to_owned(),
+            access_token: None,
+            limiter: RateLimiter {
+                interval: option.request_interval,
+                last: None,
+            },
+            retry: option.retry,
+        }
+    }
+
+    ///
+    /// 未设置用户名时认为源 nacos 没有开启鉴权
+    async fn login(&mut self) -> anyhow::Result<()> {
+        if self.username.is_empty() {
+            return Ok(());
+        }
+        self.limiter.acquire().await;
+        let res = self
+            .client
+            .post(format!("{}/nacos/v1/auth/login", self.host))
+            .form(&[
+                ("username", self.username.as_str()),
+                ("password", self.password.as_str()),
+            ])
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "login nacos failed, status:{}",
+                res.status()
+            ));
+        }
+        let result: LoginResult = res.json().await?;
+        self.access_token = Some(result.access_token);
+        Ok(())
+    }
+
+    async fn get(&mut self, path: &str, query: &[(&str, String)]) -> anyhow::Result<String> {
+        let mut error = None;
+        for i in 0..=self.retry {
+            if i > 0 {
+                tokio::time::sleep(self.limiter.interval * i).await;
+            }
+            self.limiter.acquire().await;
+            let mut req = self
+                .client
+                .get(format!("{}{}", self.host, path))
+                .query(query);
+            if let Some(token) = &self.access_token {
+                req = req.query(&[("accessToken", token)]);
+            }
+            match req.send().await {
+                Ok(res) if res.status() == reqwest::StatusCode::FORBIDDEN => {
+                    error = Some(anyhow::anyhow!("request {} forbidden", path));
+                    self.login().await?;
+                }
+                Ok(res) if res.status().is_success() => return Ok(res.text().await?),
+                Ok(res) => {
+                    error = Some(anyhow::anyhow!(
+                        "request {} failed, status:{}",
+                        path,
+                        res.status()
+                    ));
+                }
+                Err(e) => error = Some(e.into()),
+            }
+            log::warn!("request {} failed, retry times:{}", path, i);
+        }
+        Err(error.unwrap_or_else(|| anyhow::anyhow!("request {} failed", path)))
+    }
+
+    async fn get_json<T: DeserializeOwned>(
+        &mut self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> anyhow::Result<T> {
+        let v = self.get(path, query).await?;
+        Ok(serde_json::from_str(&v)?)
+    }
+
+    async fn namespaces(&mut self) -> anyhow::Result<Vec<NamespaceInfo>> {
+        let result: NamespaceResult = self.get_json("/nacos/v1/console/namespaces", &[]).await?;
+        Ok(result.data)
+    }
+
+    async fn config_page(
+        &mut self,
+        tenant: &str,
+        page_no: u32,
+        page_size: u32,
+    ) -> anyhow::Result<ConfigPage> {
+        let query = [
+            ("search", "blur".to_owned()),
+            ("dataId", String::new()),
+            ("group", String::new()),
+            ("tenant", tenant.to_owned()),
+            ("pageNo", page_no.to_string()),
+            ("pageSize", page_size.to_string()),
+        ];
+        self.get_json("/nacos/v1/cs/configs", &query).await
+    }
+
+    async fn config_content(&mut self, key: &ConfigKey) -> anyhow::Result<String> {
+        let query = [
+            ("dataId", key.data_id.to_string()),
+            ("group", key.group.to_string()),
+            ("tenant", key.tenant.to_string()),
+        ];
+        self.get("/nacos/v1/cs/configs", &query).await
+    }
+}
+
+///
+/// 通过 nacos open api 导出命名空间与配置到传输文件
+/// 导出中断后使用相同参数再次执行会从上次保存的进度继续
+pub async fn openapi_to_data(
+    username: &str,
+    password: &str,
+    host: &str,
+    data_file: &str,
+) -> anyhow::Result<()> {
+    let count = openapi_to_data_with_option(
+        username,
+        password,
+        host,
+        data_file,
+        &OpenapiOption::default(),
+    )
+    .await?;
+    log::info!(
+        "transfer from nacos openapi finished, config count:{}, tenant count:{}",
+        count.config,
+        count.tenant
+    );
+    Ok(())
+}
+
+pub async fn openapi_to_data_with_option(
+    username: &str,
+    password: &str,
+    host: &str,
+    data_file: &str,
+    option: &OpenapiOption,
+) -> anyhow::Result<OpenapiTransferCount> {
+    let mut api = NacosOpenapi::new(host, username, password, option);
+    api.login().await?;
+
+    let modify_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+    let mut header = TransferHeaderDto::new(1, modify_time, Some(FROM_SYS.to_owned()));
+    header.add_name(CONFIG_TREE_NAME.clone());
+    header.add_name(NAMESPACE_TREE_NAME.clone());
+
+    let (mut writer, mut progress) = match Progress::load(data_file) {
+        Some(progress) => {
+            log::info!(
+                "resume transfer from namespace index:{}, page:{}",
+                progress.namespace_index,
+                progress.page_no
+            );
+            let writer = TransferFileWriter::resume(
+                data_file,
+                header,
+                progress.offset,
+                TransferTrailer {
+                    record_count: progress.record_count,
+                    checksum: progress.checksum,
+                },
+            )?;
+            (writer, progress)
+        }
+        None => {
+            let mut writer = TransferFileWriter::create(data_file, header)?;
+            let mut progress = Progress {
+                page_no: 1,
+                ..Default::default()
+            };
+            for namespace in api.namespaces().await? {
+                // 默认命名空间只导出配置
+                if namespace.namespace.is_empty() || namespace.namespace == "public" {
+                    progress.namespaces.push(String::new());
+                    continue;
+                }
+                progress.namespaces.push(namespace.namespace.clone());
+                let value_do = NamespaceDo {
+                    namespace_name: namespace
+                        .namespace_show_name
+                        .unwrap_or_else(|| namespace.namespace.clone()),
+                    namespace_id: namespace.namespace,
+                    namespace_desc: namespace.namespace_desc,
+                    create_flag: None,
+                };
+                writer.write_record(
+                    &NAMESPACE_TREE_NAME,
+                    value_do.namespace_id.as_bytes().to_vec(),
+                    value_do.to_bytes(),
+                )?;
+                progress.tenant_count += 1;
+            }
+            progress.checkpoint(&mut writer, data_file)?;
+            (writer, progress)
+        }
+    };
+
+    while progress.namespace_index < progress.namespaces.len() {
+        let tenant = progress.namespaces[progress.namespace_index].clone();
+        let page = api
+            .config_page(&tenant, progress.page_no, option.page_size)
+            .await?;
+        let last_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+        let is_last = page.page_items.is_empty() || progress.page_no >= page.pages_available;
+        for config in page.page_items {
+            let key = ConfigKey::new(&config.data_id, &config.group, &tenant);
+            let content = match config.content {
+                Some(content) => content,
+                None => api.config_content(&key).await?,
+            };
+            let value_do = ConfigValueDo {
+                content,
+                histories: vec![],
+                last_time,
+                config_type: config.config_type.filter(|v| !v.is_empty()),
+                desc: config.desc.filter(|v| !v.is_empty()),
+            };
+            writer.write_record(
+                &CONFIG_TREE_NAME,
+                key.build_key().into_bytes(),
+                value_do.to_bytes(),
+            )?;
+            progress.config_count += 1;
+        }
+        if is_last {
+            progress.namespace_index += 1;
+            progress.page_no = 1;
+        } else {
+            progress.page_no += 1;
+        }
+        progress.checkpoint(&mut writer, data_file)?;
+    }
+    writer.finish()?;
+    std::fs::remove_file(Progress::path(data_file))?;
+
+    Ok(OpenapiTransferCount {
+        config: progress.config_count,
+        tenant: progress.tenant_count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::reader::TransferFileReader;
+    use actix_web::{App, HttpResponse, HttpServer, web};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    const TOKEN: &str = "mock-token";
+
+    /// 模拟的 nacos，fail_pages 中的分页在返回成功前先失败指定次数
+    #[derive(Default)]
+    struct MockNacos {
+        fail_pages: Mutex<HashMap<(String, u32), u32>>,
+        request_times: Mutex<Vec<Instant>>,
+    }
+
+    fn mock_configs(tenant: &str) -> Vec<serde_json::Value> {
+        (0..3)
+            .map(|i| {
+                serde_json::json!({
+                    "id": i.to_string(),
+                    "dataId": format!("app{}.yaml", i),
+                    "group": "DEFAULT_GROUP",
+                    "tenant": tenant,
+                    "type": "yaml",
+                    // dev 命名空间的列表不带内容，需要单独下载
+                    "content": if tenant == "dev" { None } else { Some(format!("a: {}", i)) },
+                })
+            })
+            .collect()
+    }
+
+    /// 记录请求时间并校验 token
+    fn check_token(state: &MockNacos, query: &HashMap<String, String>) -> bool {
+        state.request_times.lock().unwrap().push(Instant::now());
+        query.get("accessToken").map(|v| v.as_str()) == Some(TOKEN)
+    }
+
+    async fn login(form: web::Form<HashMap<String, String>>) -> HttpResponse {
+        if form.get("username").map(|v| v.as_str()) != Some("nacos")
+            || form.get("password").map(|v| v.as_str()) != Some("nacos")
+        {
+            return HttpResponse::Forbidden().finish();
+        }
+        HttpResponse::Ok().json(serde_json::json!({"accessToken": TOKEN, "tokenTtl": 18000}))
+    }
+
+    async fn namespaces(
+        state: web::Data<MockNacos>,
+        query: web::Query<HashMap<String, String>>,
+    ) -> HttpResponse {
+        if !check_token(&state, &query) {
+            return HttpResponse::Forbidden().finish();
+        }
+        HttpResponse::Ok().json(serde_json::json!({"code": 200, "data": [
+            {"namespace": "", "namespaceShowName": "public", "type": 0},
+            {"namespace": "dev", "namespaceShowName": "开发", "namespaceDesc": "dev env", "type": 2},
+        ]}))
+    }
+
+    async fn configs(
+        state: web::Data<MockNacos>,
+        query: web::Query<HashMap<String, String>>,
+    ) -> HttpResponse {
+        if !check_token(&state, &query) {
+            return HttpResponse::Forbidden().finish();
+        }
+        let tenant = query.get("tenant").cloned().unwrap_or_default();
+        let configs = mock_configs(&tenant);
+        let Some(page_no) = query.get("pageNo") else {
+            let data_id = query.get("dataId").cloned().unwrap_or_default();
+            let index = data_id.trim_start_matches("app").trim_end_matches(".yaml");
+            return HttpResponse::Ok().body(format!("{}: {}", tenant, index));
+        };
+        let page_no: u32 = page_no.parse().unwrap();
+        let page_size: usize = query["pageSize"].parse().unwrap();
+        if let Some(times) = state
+            .fail_pages
+            .lock()
+            .unwrap()
+            .get_mut(&(tenant.clone(), page_no))
+            && *times > 0
+        {
+            *times -= 1;
+            return HttpResponse::InternalServerError().finish();
+        }
+        let items: Vec<_> = configs
+            .iter()
+            .skip((page_no as usize - 1) * page_size)
+            .take(page_size)
+            .collect();
+        HttpResponse::Ok().json(serde_json::json!({
+            "totalCount": configs.len(),
+            "pageNumber": page_no,
+            "pagesAvailable": configs.len().div_ceil(page_size),
+            "pageItems": items,
+        }))
+    }
+
+    fn start_mock(state: web::Data<MockNacos>) -> anyhow::Result<String> {
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(state.clone())
+                .route("/nacos/v1/auth/login", web::post().to(login))
+                .route("/nacos/v1/console/namespaces", web::get().to(namespaces))
+                .route("/nacos/v1/cs/configs", web::get().to(configs))
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")?;
+        let addr = server.addrs()[0];
+        actix_rt::spawn(server.run());
+        Ok(format!("127.0.0.1:{}", addr.port()))
+    }
+
+    async fn read_configs(data_file: &str) -> anyhow::Result<Vec<(String, ConfigValueDo)>> {
+        let mut reader = TransferFileReader::new(data_file).await?;
+        let mut configs = Vec::new();
+        while let Some(v) = reader.read_record_vec().await? {
+            let record = reader.decode_record(&v)?;
+            if record.table_name == *CONFIG_TREE_NAME {
+                let key = String::from_utf8(record.key.to_vec())?;
+                configs.push((key, ConfigValueDo::from_bytes(&record.value)?));
+            }
+        }
+        Ok(configs)
+    }
+
+    #[actix_rt::test]
+    async fn test_openapi_to_data_resume() -> anyhow::Result<()> {
+        let state = web::Data::new(MockNacos::default());
+        // dev 第二页持续失败，超过重试次数后中断导出
+        state
+            .fail_pages
+            .lock()
+            .unwrap()
+            .insert(("dev".to_owned(), 2), 3);
+        let host = start_mock(state.clone())?;
+        let data_file = std::env::temp_dir()
+            .join(format!("openapi_to_data_{}.data", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let _ = std::fs::remove_file(Progress::path(&data_file));
+        let option = OpenapiOption {
+            page_size: 2,
+            request_interval: Duration::from_millis(20),
+            retry: 1,
+        };
+
+        let result =
+            openapi_to_data_with_option("nacos", "nacos", &host, &data_file, &option).await;
+        assert!(result.is_err());
+        let progress = Progress::load(&data_file).unwrap();
+        assert_eq!(progress.namespaces, vec!["".to_owned(), "dev".to_owned()]);
+        assert_eq!((progress.namespace_index, progress.page_no), (1, 2));
+        assert_eq!(progress.config_count, 5);
+
+        // 限流: 相邻请求的间隔不小于 request_interval
+        let times = state.request_times.lock().unwrap().clone();
+        assert!(
+            times
+                .windows(2)
+                .all(|v| v[1] - v[0] >= Duration::from_millis(15))
+        );
+
+        let count =
+            openapi_to_data_with_option("nacos", "nacos", &host, &data_file, &option).await?;
+        assert_eq!(
+            count,
+            OpenapiTransferCount {
+                config: 6,
+                tenant: 1
+            }
+        );
+        assert!(Progress::load(&data_file).is_none());
+
+        let configs = read_configs(&data_file).await?;
+        std::fs::remove_file(&data_file)?;
+        let keys: Vec<_> = configs.iter().map(|(k, _)| k.as_str()).collect();
+        let mut expect = Vec::new();
+        for tenant in ["", "dev"] {
+            for i in 0..3 {
+                expect.push(
+                    ConfigKey::new(&format!("app{}.yaml", i), "DEFAULT_GROUP", tenant).build_key(),
+                );
+            }
+        }
+        assert_eq!(keys, expect);
+        assert_eq!(configs[1].1.content, "a: 1");
+        assert_eq!(configs[1].1.config_type.as_deref(), Some("yaml"));
+        assert_eq!(configs[5].1.content, "dev: 2");
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn test_openapi_login_failed() -> anyhow::Result<()> {
+        let host = start_mock(web::Data::new(MockNacos::default()))?;
+        let data_file = std::env::temp_dir()
+            .join(format!("openapi_login_{}.data", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let result = openapi_to_data_with_option(
+            "nacos",
+            "wrong",
+            &format!("http://{}/", host),
+            &data_file,
+            &OpenapiOption::default(),
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(!Path::new(&data_file).exists());
+        Ok(())
+    }
+}
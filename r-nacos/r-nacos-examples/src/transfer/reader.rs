@@ -1,18 +1,112 @@
-use crate::common::pb::transfer::TransferHeader;
+use crate::common::pb::transfer::{TransferHeader, TransferItem};
 use crate::common::protobuf_utils::FileMessageReader;
-use crate::transfer::model::{TransferHeaderDto, TransferRecordRef};
+use crate::transfer::model::{
+    TRANSFER_FORMAT_VERSION, TRANSFER_MAGIC, TransferCompression, TransferHeaderDto,
+    TransferRecordRef, TransferTrailer,
+};
+use anyhow::Context;
 use quick_protobuf::BytesReader;
+use std::io::SeekFrom;
+use std::sync::Arc;
 use tokio::fs::OpenOptions;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 
 pub(crate) fn reader_transfer_record<'a>(
     v: &'a [u8],
     header: &'a TransferHeaderDto,
 ) -> anyhow::Result<TransferRecordRef<'a>> {
-    todo!()
+    let mut reader = BytesReader::from_bytes(v);
+    let record_do: TransferItem = reader
+        .read_message_by_len(v, v.len())
+        .map_err(|e| anyhow::anyhow!("decode transfer record error: {}", e))?;
+
+    // 有表 id 时表名从 header 中取，否则使用记录中的表名
+    let table_name = if record_do.table_id == 0 {
+        if record_do.table_name.is_empty() {
+            return Err(anyhow::anyhow!("transfer record without table name"));
+        }
+        Arc::new(record_do.table_name.to_string())
+    } else if let Some(name) = header.id_to_name.get(&record_do.table_id) {
+        name.clone()
+    } else {
+        return Err(anyhow::anyhow!(
+            "unknown table id {} in transfer record",
+            record_do.table_id
+        ));
+    };
+
+    Ok(TransferRecordRef {
+        table_name,
+        key: record_do.key,
+        value: record_do.value,
+    })
+}
+
+///
+/// 校验文件前缀中的 magic 与格式版本
+fn check_prefix(prefix: &[u8; 8]) -> anyhow::Result<u16> {
+    if &prefix[..6] != TRANSFER_MAGIC {
+        return Err(anyhow::anyhow!("not a transfer file, invalid magic prefix"));
+    }
+    let version = u16::from_be_bytes([prefix[6], prefix[7]]);
+    if version == 0 || version > TRANSFER_FORMAT_VERSION {
+        return Err(anyhow::anyhow!(
+            "unsupported transfer file version {}, max supported version {}",
+            version,
+            TRANSFER_FORMAT_VERSION
+        ));
+    }
+    Ok(version)
+}
+
+///
+/// 从解压后的块中读取一条带 varint 长度的记录
+fn read_block_item(block: &[u8], pos: &mut usize) -> anyhow::Result<Vec<u8>> {
+    let mut len = 0u64;
+    let mut i = 0;
+    loop {
+        let b = *block
+            .get(*pos)
+            .ok_or_else(|| anyhow::anyhow!("read data not enough in block"))?;
+        *pos += 1;
+        len |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            break;
+        }
+        i += 1;
+        if i >= 10 {
+            return Err(anyhow::anyhow!("message len varint overflow"));
+        }
+    }
+    // 长度被破坏时可能远超块大小，相加前先检查溢出
+    let end = usize::try_from(len)
+        .ok()
+        .and_then(|len| pos.checked_add(len))
+        .filter(|end| *end <= block.len())
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("read data not enough in block, expect len:{}", len),
+            )
+        })?;
+    let v = block[*pos..end].to_vec();
+    *pos = end;
+    Ok(v)
 }
 
 pub struct TransferFileReader {
     message_reader: FileMessageReader,
+    // 最近读取的记录在文件中的位置，用于错误信息; 压缩文件中为记录所在块的位置
+    record_offset: u64,
+    // 记录部分的结束位置，之后是 trailer
+    body_end: u64,
+    compression: TransferCompression,
+    block: Vec<u8>,
+    block_pos: usize,
+    // 版本 1 的文件没有 trailer
+    trailer: Option<TransferTrailer>,
+    record_count: u64,
+    hasher: crc32fast::Hasher,
 
     // 可以在当前 crate 的所有模块中访问
     pub(crate) header: TransferHeaderDto,
@@ -20,25 +114,345 @@ pub struct TransferFileReader {
 
 impl TransferFileReader {
     pub async fn new(path: &str) -> anyhow::Result<Self> {
-        let file = OpenOptions::new().read(true).open(path).await?;
+        let mut file = OpenOptions::new().read(true).open(path).await?;
+        let file_len = file.metadata().await?.len();
+        let mut prefix = [0u8; 8];
+        file.read_exact(&mut prefix)
+            .await
+            .map_err(|_| anyhow::anyhow!("transfer file is too short to contain prefix"))?;
+        let version = check_prefix(&prefix)?;
+
+        let (body_end, trailer) = if version >= 2 {
+            let mut v = [0u8; TransferTrailer::LEN as usize];
+            let trailer = if file_len >= 8 + TransferTrailer::LEN {
+                file.seek(SeekFrom::Start(file_len - TransferTrailer::LEN))
+                    .await?;
+                file.read_exact(&mut v).await?;
+                TransferTrailer::from_bytes(&v)
+            } else {
+                None
+            };
+            let trailer = trailer.ok_or_else(|| {
+                anyhow::anyhow!("transfer file trailer is missing, the file may be truncated")
+            })?;
+            (file_len - TransferTrailer::LEN, Some(trailer))
+        } else {
+            (file_len, None)
+        };
+
         let mut message_reader = FileMessageReader::new(file, 8);
+        message_reader.set_end(body_end);
         message_reader.seek_start(8).await?;
 
-        let header = if let Ok(v) = message_reader.read_next().await {
-            let mut reader = BytesReader::from_bytes(&v);
-            let header_do: TransferHeader = reader.read_message(&v)?;
-            header_do.into()
+        let v = message_reader
+            .read_next()
+            .await
+            .context("read header error from transfer file at offset 8")?;
+        let mut reader = BytesReader::from_bytes(&v);
+        let header_do: TransferHeader = reader
+            .read_message_by_len(&v, v.len())
+            .map_err(|e| anyhow::anyhow!("decode transfer header error at offset 8: {}", e))?;
+        let header: TransferHeaderDto = header_do.into();
+        let compression = if version >= 2 {
+            header.compression()?
         } else {
-            return Err(anyhow::anyhow!("read header error from transfer file"));
+            TransferCompression::None
         };
 
         Ok(Self {
+            record_offset: message_reader.position(),
             message_reader,
+            body_end,
+            compression,
+            block: Vec::new(),
+            block_pos: 0,
+            trailer,
+            record_count: 0,
+            hasher: crc32fast::Hasher::new(),
             header,
         })
     }
 
+    ///
+    /// 读取下一条记录的原始数据，读完时返回 None
+    /// 读完时校验 trailer 中的记录数与 crc32
     pub async fn read_record_vec(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
-        todo!()
+        loop {
+            if self.block_pos < self.block.len() {
+                let v = read_block_item(&self.block, &mut self.block_pos).with_context(|| {
+                    format!("truncated transfer record at offset {}", self.record_offset)
+                })?;
+                return Ok(Some(self.on_record(v)));
+            }
+            if self.message_reader.position() >= self.body_end {
+                self.verify()?;
+                return Ok(None);
+            }
+            self.record_offset = self.message_reader.position();
+            let v = self.message_reader.read_next().await.ok().ok_or_else(|| {
+                anyhow::anyhow!("truncated transfer record at offset {}", self.record_offset)
+            })?;
+            if self.compression == TransferCompression::None {
+                return Ok(Some(self.on_record(v)));
+            }
+            self.block = self.compression.decompress(&v).with_context(|| {
+                format!("invalid transfer block at offset {}", self.record_offset)
+            })?;
+            self.block_pos = 0;
+        }
+    }
+
+    fn on_record(&mut self, v: Vec<u8>) -> Vec<u8> {
+        self.record_count += 1;
+        self.hasher.update(&v);
+        v
+    }
+
+    fn verify(&self) -> anyhow::Result<()> {
+        let Some(trailer) = &self.trailer else {
+            return Ok(());
+        };
+        if trailer.record_count != self.record_count {
+            return Err(anyhow::anyhow!(
+                "transfer record count mismatch, expect:{}, actual:{}",
+                trailer.record_count,
+                self.record_count
+            ));
+        }
+        let checksum = self.hasher.clone().finalize();
+        if trailer.checksum != checksum {
+            return Err(anyhow::anyhow!(
+                "transfer file checksum mismatch, expect:{:08x}, actual:{:08x}",
+                trailer.checksum,
+                checksum
+            ));
+        }
+        Ok(())
+    }
+
+    ///
+    /// 解析 read_record_vec 读出的记录，出错时带上记录的位置
+    pub fn decode_record<'a>(&'a self, v: &'a [u8]) -> anyhow::Result<TransferRecordRef<'a>> {
+        reader_transfer_record(v, &self.header)
+            .with_context(|| format!("invalid transfer record at offset {}", self.record_offset))
+    }
+
+    ///
+    /// 最近读取的记录在文件中的位置
+    pub fn record_offset(&self) -> u64 {
+        self.record_offset
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::constant::CONFIG_TREE_NAME;
+    use crate::transfer::writer::TransferFileWriter;
+    use quick_protobuf::Writer;
+    use std::io::Write;
+
+    fn temp_file(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!(
+                "transfer_reader_{}_{}.data",
+                name,
+                std::process::id()
+            ))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn test_header() -> TransferHeaderDto {
+        let mut header = TransferHeaderDto::new(1, 1700000000000, Some("test".to_owned()));
+        header.add_name(CONFIG_TREE_NAME.clone());
+        header
+    }
+
+    /// 写入两条记录，返回第二条记录的位置
+    fn write_file(path: &str) -> anyhow::Result<u64> {
+        let mut writer = TransferFileWriter::create(path, test_header())?;
+        writer.write_record(&CONFIG_TREE_NAME, b"k1".to_vec(), b"v1".to_vec())?;
+        let offset = writer.position()?;
+        writer.write_record(
+            &Arc::new("T_OTHER".to_owned()),
+            b"k2".to_vec(),
+            b"v2".to_vec(),
+        )?;
+        writer.finish()?;
+        Ok(offset)
+    }
+
+    async fn read_all(path: &str) -> anyhow::Result<Vec<Vec<u8>>> {
+        let mut reader = TransferFileReader::new(path).await?;
+        let mut list = Vec::new();
+        while let Some(v) = reader.read_record_vec().await? {
+            list.push(v);
+        }
+        Ok(list)
+    }
+
+    #[tokio::test]
+    async fn test_read_records() -> anyhow::Result<()> {
+        let path = temp_file("ok");
+        write_file(&path)?;
+        let mut reader = TransferFileReader::new(&path).await?;
+        assert_eq!(reader.header.from_sys.as_deref(), Some("test"));
+        assert_eq!(reader.header.id_to_name[&1], *CONFIG_TREE_NAME);
+        assert_eq!(reader.header.name_to_id[&*CONFIG_TREE_NAME], 1);
+
+        let mut records = Vec::new();
+        while let Some(v) = reader.read_record_vec().await? {
+            let record = reader.decode_record(&v)?;
+            records.push((
+                record.table_name.to_string(),
+                record.key.to_vec(),
+                record.value.to_vec(),
+            ));
+        }
+        std::fs::remove_file(&path)?;
+        assert_eq!(
+            records,
+            vec![
+                (CONFIG_TREE_NAME.to_string(), b"k1".to_vec(), b"v1".to_vec()),
+                ("T_OTHER".to_owned(), b"k2".to_vec(), b"v2".to_vec()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_prefix() -> anyhow::Result<()> {
+        let path = temp_file("prefix");
+        std::fs::write(&path, b"nacos\x00\x00\x01")?;
+        let err = TransferFileReader::new(&path).await.err().unwrap();
+        assert!(err.to_string().contains("magic"));
+
+        std::fs::write(&path, b"rnacos\x00\x09")?;
+        let err = TransferFileReader::new(&path).await.err().unwrap();
+        assert!(
+            err.to_string()
+                .contains("unsupported transfer file version 9")
+        );
+
+        std::fs::write(&path, b"rna")?;
+        assert!(TransferFileReader::new(&path).await.is_err());
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_truncated_file() -> anyhow::Result<()> {
+        let path = temp_file("truncated");
+        let offset = write_file(&path)?;
+        let data = std::fs::read(&path)?;
+        let body_end = data.len() - TransferTrailer::LEN as usize;
+
+        // 文件末尾被截断，trailer 不完整
+        std::fs::write(&path, &data[..data.len() - 2])?;
+        let err = TransferFileReader::new(&path).await.err().unwrap();
+        assert!(err.to_string().contains("trailer is missing"));
+
+        // 最后一条记录缺了一部分
+        let mut v = data[..body_end - 2].to_vec();
+        v.extend_from_slice(&data[body_end..]);
+        std::fs::write(&path, &v)?;
+        let err = read_all(&path).await.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("truncated transfer record at offset {}", offset)
+        );
+
+        // 内容被修改，读完时校验失败
+        let mut v = data.clone();
+        v[body_end - 1] ^= 0xff;
+        std::fs::write(&path, &v)?;
+        let err = read_all(&path).await.err().unwrap();
+        assert!(err.to_string().contains("checksum mismatch"));
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_record() -> anyhow::Result<()> {
+        // 版本 1 的文件没有 trailer，直接拼出无法解析的记录
+        let path = temp_file("corrupt");
+        let header = test_header();
+        let mut data = b"rnacos\x00\x01".to_vec();
+        Writer::new(&mut data).write_message(&header.to_do())?;
+        let offset = data.len() as u64;
+        data.write_all(&[1, 0xff])?;
+        // 表 id 不存在
+        data.write_all(&[2, 0x10, 0x09])?;
+        std::fs::write(&path, &data)?;
+
+        let mut reader = TransferFileReader::new(&path).await?;
+        let v = reader.read_record_vec().await?.unwrap();
+        let err = reader.decode_record(&v).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("invalid transfer record at offset {}", offset)
+        );
+        let v = reader.read_record_vec().await?.unwrap();
+        let err = reader.decode_record(&v).err().unwrap();
+        assert_eq!(reader.record_offset(), offset + 2);
+        assert!(format!("{:#}", err).contains("unknown table id 9"));
+        assert!(reader.read_record_vec().await?.is_none());
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_record_len() -> anyhow::Result<()> {
+        let path = temp_file("corrupt_len");
+        let offset = write_file(&path)?;
+        let data = std::fs::read(&path)?;
+        let body_end = data.len() - TransferTrailer::LEN as usize;
+
+        // 第二条记录的长度被改大，延伸到 trailer 中
+        let mut v = data.clone();
+        v[offset as usize] += (body_end - offset as usize) as u8;
+        std::fs::write(&path, &v)?;
+        let err = read_all(&path).await.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("truncated transfer record at offset {}", offset)
+        );
+
+        // 长度远超文件大小，不会按这个长度分配内存
+        let mut v = data[..offset as usize].to_vec();
+        v.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]);
+        v.extend_from_slice(&data[body_end..]);
+        std::fs::write(&path, &v)?;
+        let err = read_all(&path).await.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("truncated transfer record at offset {}", offset)
+        );
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_block_item_len() {
+        let mut pos = 0;
+        assert_eq!(
+            read_block_item(&[2, 1, 2, 0], &mut pos).unwrap(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            read_block_item(&[2, 1, 2, 0], &mut pos).unwrap(),
+            Vec::<u8>::new()
+        );
+        assert_eq!(pos, 4);
+
+        // 长度超过块的剩余部分，以及 pos + len 溢出
+        let truncated: &[u8] = &[3, 1, 2];
+        let oversized: &[u8] = &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+        for block in [truncated, oversized] {
+            let mut pos = 0;
+            let err = read_block_item(block, &mut pos).err().unwrap();
+            let err = err.downcast_ref::<std::io::Error>().unwrap();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        }
     }
 }
@@ -1 +1,72 @@
-pub struct ConfigSql {}
+use crate::common::rusqlite_utils::get_row_value;
+use crate::transfer::sqlite::dao::table::{TableDO, TableDao, TableInfo, TableParam, TableSql};
+use rusqlite::Row;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigDO {
+    pub id: Option<i64>,
+    pub data_id: Option<String>,
+    pub group_id: Option<String>,
+    pub tenant_id: Option<String>,
+    pub content: Option<String>,
+    pub config_type: Option<String>,
+    pub config_desc: Option<String>,
+    pub last_time: Option<i64>,
+}
+
+impl TableDO for ConfigDO {
+    const TABLE: TableInfo = TableInfo {
+        name: "tb_config",
+        columns: &[
+            "id",
+            "data_id",
+            "group_id",
+            "tenant_id",
+            "content",
+            "config_type",
+            "config_desc",
+            "last_time",
+        ],
+        id_column: "id",
+    };
+
+    fn from_row(r: &Row) -> Self {
+        Self {
+            id: get_row_value(r, "id"),
+            data_id: get_row_value(r, "data_id"),
+            group_id: get_row_value(r, "group_id"),
+            tenant_id: get_row_value(r, "tenant_id"),
+            content: get_row_value(r, "content"),
+            config_type: get_row_value(r, "config_type"),
+            config_desc: get_row_value(r, "config_desc"),
+            last_time: get_row_value(r, "last_time"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ConfigParam {
+    pub id: Option<i64>,
+    pub data_id: Option<String>,
+    pub group_id: Option<String>,
+    pub tenant_id: Option<String>,
+    #[serde(skip)]
+    pub limit: Option<i64>,
+    #[serde(skip)]
+    pub offset: Option<i64>,
+}
+
+impl TableParam for ConfigParam {
+    fn limit(&self) -> Option<i64> {
+        self.limit
+    }
+
+    fn offset(&self) -> Option<i64> {
+        self.offset
+    }
+}
+
+pub type ConfigSql = TableSql<ConfigDO>;
+
+pub type ConfigDao<'a> = TableDao<'a, ConfigDO>;
@@ -1,17 +1,81 @@
-use rusqlite::Connection;
+use crate::common::rusqlite_utils::get_row_value;
+use crate::transfer::sqlite::dao::table::{TableDO, TableDao, TableInfo, TableParam, TableSql};
+use rusqlite::Row;
+use serde::{Deserialize, Serialize};
 
-pub struct ConfigHistoryDao<'a> {
-    conn: &'a Connection,
-    inner: ConfigHistorySql,
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigHistoryDO {
+    pub id: Option<i64>,
+    pub data_id: Option<String>,
+    pub group_id: Option<String>,
+    pub tenant_id: Option<String>,
+    pub content: Option<String>,
+    pub config_type: Option<String>,
+    pub config_desc: Option<String>,
+    pub op_user: Option<String>,
+    pub last_time: Option<i64>,
 }
 
-impl<'a> ConfigHistoryDao<'a> {
-    pub fn new(conn: &'a Connection) -> Self {
+impl TableDO for ConfigHistoryDO {
+    const TABLE: TableInfo = TableInfo {
+        name: "tb_config_history",
+        columns: &[
+            "id",
+            "data_id",
+            "group_id",
+            "tenant_id",
+            "content",
+            "config_type",
+            "config_desc",
+            "op_user",
+            "last_time",
+        ],
+        id_column: "id",
+    };
+
+    fn from_row(r: &Row) -> Self {
         Self {
-            conn,
-            inner: ConfigHistorySql {},
+            id: get_row_value(r, "id"),
+            data_id: get_row_value(r, "data_id"),
+            group_id: get_row_value(r, "group_id"),
+            tenant_id: get_row_value(r, "tenant_id"),
+            content: get_row_value(r, "content"),
+            config_type: get_row_value(r, "config_type"),
+            config_desc: get_row_value(r, "config_desc"),
+            op_user: get_row_value(r, "op_user"),
+            last_time: get_row_value(r, "last_time"),
         }
     }
 }
 
-pub struct ConfigHistorySql {}
+#[derive(Debug, Default, Serialize)]
+pub struct ConfigHistoryParam {
+    pub id: Option<i64>,
+    pub data_id: Option<String>,
+    pub group_id: Option<String>,
+    pub tenant_id: Option<String>,
+    #[serde(skip)]
+    pub limit: Option<i64>,
+    #[serde(skip)]
+    pub offset: Option<i64>,
+    #[serde(skip)]
+    pub order_desc: bool,
+}
+
+impl TableParam for ConfigHistoryParam {
+    fn limit(&self) -> Option<i64> {
+        self.limit
+    }
+
+    fn offset(&self) -> Option<i64> {
+        self.offset
+    }
+
+    fn order_desc(&self) -> bool {
+        self.order_desc
+    }
+}
+
+pub type ConfigHistorySql = TableSql<ConfigHistoryDO>;
+
+pub type ConfigHistoryDao<'a> = TableDao<'a, ConfigHistoryDO>;
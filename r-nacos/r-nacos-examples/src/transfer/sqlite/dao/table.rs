@@ -0,0 +1,401 @@
+use crate::common::rusqlite_utils::{convert_json_params, sqlite_execute};
+use rusqlite::{Connection, Row, params_from_iter};
+use serde::Serialize;
+use std::marker::PhantomData;
+
+///
+/// 表结构描述，列名与 DO 的字段名一致
+pub struct TableInfo {
+    pub name: &'static str,
+    pub columns: &'static [&'static str],
+    /// 自增主键，默认排序字段
+    pub id_column: &'static str,
+}
+
+///
+/// 表记录，值为 None 的字段在 insert/update 时忽略
+pub trait TableDO: Serialize + Sized {
+    const TABLE: TableInfo;
+
+    fn from_row(r: &Row) -> Self;
+}
+
+///
+/// 查询参数，值不为 None 的字段作为等值条件
+/// limit/offset 不参与序列化，通过方法提供
+pub trait TableParam: Serialize {
+    fn limit(&self) -> Option<i64>;
+
+    fn offset(&self) -> Option<i64>;
+
+    /// 是否按 id 倒序
+    fn order_desc(&self) -> bool {
+        false
+    }
+}
+
+///
+/// 按表的列顺序取出值不为空的字段，不属于表的字段忽略
+fn not_null_fields<V: Serialize>(
+    table: &TableInfo,
+    v: &V,
+) -> Vec<(&'static str, serde_json::Value)> {
+    let serde_json::Value::Object(mut map) = serde_json::to_value(v).unwrap_or_default() else {
+        return vec![];
+    };
+    table
+        .columns
+        .iter()
+        .filter_map(|c| match map.remove(*c) {
+            Some(serde_json::Value::Null) | None => None,
+            Some(v) => Some((*c, v)),
+        })
+        .collect()
+}
+
+pub struct TableSql<T> {
+    _t: PhantomData<T>,
+}
+
+impl<T> Default for TableSql<T> {
+    fn default() -> Self {
+        Self { _t: PhantomData }
+    }
+}
+
+impl<T: TableDO> TableSql<T> {
+    fn conditions<P: TableParam>(&self, param: &P) -> rsql_builder::B<'static> {
+        let mut whr = rsql_builder::B::new_where();
+        for (column, value) in not_null_fields(&T::TABLE, param) {
+            whr.eq(column, &value);
+        }
+        whr
+    }
+
+    pub fn query_prepare<P: TableParam>(&self, param: &P) -> (String, Vec<serde_json::Value>) {
+        let select = format!(
+            "select {} from {}",
+            T::TABLE.columns.join(", "),
+            T::TABLE.name
+        );
+        let (limit, offset, desc) = (param.limit(), param.offset(), param.order_desc());
+        let mut b = rsql_builder::B::new();
+        b.push_string(select)
+            .push_build(&mut self.conditions(param))
+            .push_fn(move || {
+                let mut b = rsql_builder::B::new();
+                b.order_by(T::TABLE.id_column, desc);
+                // sqlite 的 offset 必须跟在 limit 之后，只有 offset 时用 -1 表示不限制数量
+                match (&limit, &offset) {
+                    (Some(limit), _) => {
+                        b.limit(limit);
+                    }
+                    (None, Some(_)) => {
+                        b.limit(&-1i64);
+                    }
+                    (None, None) => {}
+                }
+                if let Some(offset) = &offset {
+                    b.offset(offset);
+                }
+                b
+            });
+        rsql_builder::B::prepare(&mut b)
+    }
+
+    pub fn query_count_prepare<P: TableParam>(
+        &self,
+        param: &P,
+    ) -> (String, Vec<serde_json::Value>) {
+        let mut b = rsql_builder::B::new();
+        b.push_string(format!("select count(1) from {}", T::TABLE.name))
+            .push_build(&mut self.conditions(param));
+        rsql_builder::B::prepare(&mut b)
+    }
+
+    pub fn insert_prepare(&self, record: &T) -> (String, Vec<serde_json::Value>) {
+        let mut field_builder = rsql_builder::B::new_comma_paren();
+        let mut value_builder = rsql_builder::B::new_comma_paren();
+        for (column, value) in not_null_fields(&T::TABLE, record) {
+            field_builder.push_sql(column);
+            value_builder.push("?", &value);
+        }
+
+        let mut b = rsql_builder::B::new();
+        b.push_string(format!("insert into {}", T::TABLE.name))
+            .push_build(&mut field_builder)
+            .push_sql("values")
+            .push_build(&mut value_builder);
+        rsql_builder::B::prepare(&mut b)
+    }
+
+    ///
+    /// 没有更新条件时返回错误，避免更新整张表
+    pub fn update_prepare<P: TableParam>(
+        &self,
+        record: &T,
+        param: &P,
+    ) -> anyhow::Result<(String, Vec<serde_json::Value>)> {
+        let mut set_builder = rsql_builder::B::new_comma();
+        for (column, value) in not_null_fields(&T::TABLE, record) {
+            set_builder.eq(column, &value);
+        }
+        if set_builder.is_empty() {
+            return Err(anyhow::anyhow!("update {} without fields", T::TABLE.name));
+        }
+
+        let mut whr = self.conditions(param);
+        if whr.is_empty() {
+            return Err(anyhow::anyhow!(
+                "update {} condition is empty",
+                T::TABLE.name
+            ));
+        }
+
+        let mut b = rsql_builder::B::new();
+        b.push_string(format!("update {} set ", T::TABLE.name))
+            .push_build(&mut set_builder)
+            .push_build(&mut whr);
+        Ok(rsql_builder::B::prepare(&mut b))
+    }
+
+    ///
+    /// 没有删除条件时返回错误，避免清空整张表
+    pub fn delete_prepare<P: TableParam>(
+        &self,
+        param: &P,
+    ) -> anyhow::Result<(String, Vec<serde_json::Value>)> {
+        let mut whr = self.conditions(param);
+        if whr.is_empty() {
+            return Err(anyhow::anyhow!(
+                "delete {} condition is empty",
+                T::TABLE.name
+            ));
+        }
+        let mut b = rsql_builder::B::new();
+        b.push_string(format!("delete from {}", T::TABLE.name))
+            .push_build(&mut whr);
+        Ok(rsql_builder::B::prepare(&mut b))
+    }
+}
+
+pub struct TableDao<'a, T> {
+    conn: &'a Connection,
+    inner: TableSql<T>,
+}
+
+impl<'a, T: TableDO> TableDao<'a, T> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self {
+            conn,
+            inner: TableSql::default(),
+        }
+    }
+
+    pub fn execute(&self, sql: &str, args: &[serde_json::Value]) -> anyhow::Result<usize> {
+        sqlite_execute(self.conn, sql, args)
+    }
+
+    pub fn fetch(&self, sql: &str, args: &[serde_json::Value]) -> anyhow::Result<Vec<T>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let list = stmt
+            .query_map(params_from_iter(convert_json_params(args).iter()), |r| {
+                Ok(T::from_row(r))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(list)
+    }
+
+    pub fn fetch_count(&self, sql: &str, args: &[serde_json::Value]) -> anyhow::Result<u64> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let v = stmt.query_row(params_from_iter(convert_json_params(args).iter()), |r| {
+            r.get(0)
+        })?;
+        Ok(v)
+    }
+
+    pub fn query<P: TableParam>(&self, param: &P) -> anyhow::Result<Vec<T>> {
+        let (sql, args) = self.inner.query_prepare(param);
+        self.fetch(&sql, &args)
+    }
+
+    pub fn query_count<P: TableParam>(&self, param: &P) -> anyhow::Result<u64> {
+        let (sql, args) = self.inner.query_count_prepare(param);
+        self.fetch_count(&sql, &args)
+    }
+
+    ///
+    /// 分页查询，返回 (总数, 当前页)
+    pub fn query_page<P: TableParam>(&self, param: &P) -> anyhow::Result<(u64, Vec<T>)> {
+        let count = self.query_count(param)?;
+        if count == 0 {
+            return Ok((0, vec![]));
+        }
+        Ok((count, self.query(param)?))
+    }
+
+    pub fn insert(&self, record: &T) -> anyhow::Result<usize> {
+        let (sql, args) = self.inner.insert_prepare(record);
+        self.execute(&sql, &args)
+    }
+
+    pub fn update<P: TableParam>(&self, record: &T, param: &P) -> anyhow::Result<usize> {
+        let (sql, args) = self.inner.update_prepare(record, param)?;
+        self.execute(&sql, &args)
+    }
+
+    pub fn delete<P: TableParam>(&self, param: &P) -> anyhow::Result<usize> {
+        let (sql, args) = self.inner.delete_prepare(param)?;
+        self.execute(&sql, &args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::sqlite::dao::config::{ConfigDO, ConfigDao, ConfigParam, ConfigSql};
+    use crate::transfer::sqlite::dao::tenant::{TenantDO, TenantDao, TenantParam};
+
+    fn open_db() -> anyhow::Result<Connection> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            r"
+create table tb_config(id integer primary key autoincrement, data_id text, group_id text, tenant_id text, content text, config_type text, config_desc text, last_time long);
+create table tb_tenant(id integer primary key autoincrement, tenant_id text, tenant_name text, tenant_desc text, create_flag integer);
+",
+        )?;
+        Ok(conn)
+    }
+
+    #[test]
+    fn test_prepare_sql() -> anyhow::Result<()> {
+        let sql = ConfigSql::default();
+        let param = ConfigParam {
+            data_id: Some("app.yaml".to_owned()),
+            tenant_id: Some("".to_owned()),
+            limit: Some(10),
+            offset: Some(20),
+            ..Default::default()
+        };
+        let (s, args) = sql.query_prepare(&param);
+        assert_eq!(
+            s.split_whitespace().collect::<Vec<_>>().join(" "),
+            "select id, data_id, group_id, tenant_id, content, config_type, config_desc, last_time from tb_config where data_id=? and tenant_id=? order by id limit ? offset ?"
+        );
+        assert_eq!(
+            args,
+            vec![
+                serde_json::json!("app.yaml"),
+                serde_json::json!(""),
+                serde_json::json!(10),
+                serde_json::json!(20)
+            ]
+        );
+
+        let (s, args) = sql.query_prepare(&ConfigParam {
+            offset: Some(20),
+            ..Default::default()
+        });
+        assert!(
+            s.split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .ends_with("from tb_config order by id limit ? offset ?")
+        );
+        assert_eq!(args, vec![serde_json::json!(-1), serde_json::json!(20)]);
+
+        let (s, args) = sql.insert_prepare(&ConfigDO {
+            data_id: Some("app.yaml".to_owned()),
+            last_time: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(
+            s.split_whitespace().collect::<Vec<_>>().join(" "),
+            "insert into tb_config (data_id , last_time) values (? , ?)"
+        );
+        assert_eq!(args.len(), 2);
+
+        // 没有条件时不允许更新与删除
+        assert!(
+            sql.update_prepare(&ConfigDO::default(), &ConfigParam::default())
+                .is_err()
+        );
+        assert!(sql.delete_prepare(&ConfigParam::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_dao() -> anyhow::Result<()> {
+        let conn = open_db()?;
+        let dao = TenantDao::new(&conn);
+        for i in 0..5 {
+            dao.insert(&TenantDO {
+                tenant_id: Some(format!("ns{}", i)),
+                tenant_name: Some(format!("namespace {}", i)),
+                create_flag: Some(i % 2),
+                ..Default::default()
+            })?;
+        }
+
+        let (count, list) = dao.query_page(&TenantParam {
+            limit: Some(2),
+            offset: Some(2),
+            ..Default::default()
+        })?;
+        assert_eq!(count, 5);
+        let ids: Vec<_> = list
+            .iter()
+            .map(|v| v.tenant_id.as_deref().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["ns2", "ns3"]);
+
+        // 只有 offset 时返回剩余的全部记录
+        let list = dao.query(&TenantParam {
+            offset: Some(3),
+            ..Default::default()
+        })?;
+        let ids: Vec<_> = list
+            .iter()
+            .map(|v| v.tenant_id.as_deref().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["ns3", "ns4"]);
+
+        let param = TenantParam {
+            tenant_id: Some("ns3".to_owned()),
+            ..Default::default()
+        };
+        let updated = dao.update(
+            &TenantDO {
+                tenant_desc: Some("updated".to_owned()),
+                ..Default::default()
+            },
+            &param,
+        )?;
+        assert_eq!(updated, 1);
+        let ns3 = dao.query(&param)?.remove(0);
+        assert_eq!(ns3.tenant_desc.as_deref(), Some("updated"));
+        assert_eq!(ns3.tenant_name.as_deref(), Some("namespace 3"));
+
+        assert_eq!(dao.delete(&param)?, 1);
+        assert_eq!(dao.query_count(&TenantParam::default())?, 4);
+        assert_eq!(dao.query_page(&param)?, (0, vec![]));
+
+        // 不同的表共用同一套实现
+        let config_dao = ConfigDao::new(&conn);
+        config_dao.insert(&ConfigDO {
+            data_id: Some("app.yaml".to_owned()),
+            group_id: Some("DEFAULT_GROUP".to_owned()),
+            tenant_id: Some("".to_owned()),
+            content: Some("a: 1".to_owned()),
+            ..Default::default()
+        })?;
+        let list = config_dao.query(&ConfigParam {
+            tenant_id: Some("".to_owned()),
+            ..Default::default()
+        })?;
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].content.as_deref(), Some("a: 1"));
+        assert_eq!(list[0].id, Some(1));
+        Ok(())
+    }
+}
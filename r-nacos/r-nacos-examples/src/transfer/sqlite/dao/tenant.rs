@@ -1,16 +1,61 @@
-use rusqlite::Connection;
+use crate::common::rusqlite_utils::get_row_value;
+use crate::transfer::sqlite::dao::table::{TableDO, TableDao, TableInfo, TableParam, TableSql};
+use rusqlite::Row;
+use serde::{Deserialize, Serialize};
 
-pub struct TenantDao<'a> {
-    conn: &'a Connection,
-    inner: TenantSql,
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TenantDO {
+    pub id: Option<i64>,
+    pub tenant_id: Option<String>,
+    pub tenant_name: Option<String>,
+    pub tenant_desc: Option<String>,
+    pub create_flag: Option<i64>,
 }
 
-impl<'a> TenantDao<'a> {
-    pub fn new(conn: &'a Connection) -> Self {
+impl TableDO for TenantDO {
+    const TABLE: TableInfo = TableInfo {
+        name: "tb_tenant",
+        columns: &[
+            "id",
+            "tenant_id",
+            "tenant_name",
+            "tenant_desc",
+            "create_flag",
+        ],
+        id_column: "id",
+    };
+
+    fn from_row(r: &Row) -> Self {
         Self {
-            conn,
-            inner: TenantSql {},
+            id: get_row_value(r, "id"),
+            tenant_id: get_row_value(r, "tenant_id"),
+            tenant_name: get_row_value(r, "tenant_name"),
+            tenant_desc: get_row_value(r, "tenant_desc"),
+            create_flag: get_row_value(r, "create_flag"),
         }
     }
 }
-pub struct TenantSql {}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TenantParam {
+    pub id: Option<i64>,
+    pub tenant_id: Option<String>,
+    #[serde(skip)]
+    pub limit: Option<i64>,
+    #[serde(skip)]
+    pub offset: Option<i64>,
+}
+
+impl TableParam for TenantParam {
+    fn limit(&self) -> Option<i64> {
+        self.limit
+    }
+
+    fn offset(&self) -> Option<i64> {
+        self.offset
+    }
+}
+
+pub type TenantSql = TableSql<TenantDO>;
+
+pub type TenantDao<'a> = TableDao<'a, TenantDO>;
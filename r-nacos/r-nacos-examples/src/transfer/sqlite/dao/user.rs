@@ -1,10 +1,9 @@
-use crate::common::rusqlite_utils::{get_row_value, sqlite_execute};
-use crate::transfer::model::TransferRecordRef;
-use rsql_builder::SqlBuilder;
-use rusqlite::{Connection, Row, params};
+use crate::common::rusqlite_utils::get_row_value;
+use crate::transfer::sqlite::dao::table::{TableDO, TableDao, TableInfo, TableParam, TableSql};
+use rusqlite::Row;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UserDO {
     pub id: Option<i64>,
     pub username: Option<String>,
@@ -15,192 +14,70 @@ pub struct UserDO {
     pub enabled: Option<String>,
     pub roles: Option<String>,
     pub extend_info: Option<String>,
+    pub namespace_privilege_flags: Option<i64>,
+    // json 数组
+    pub namespace_white_list: Option<String>,
+    pub namespace_black_list: Option<String>,
 }
 
-impl UserDO {
-    fn from_row(r: &Row) -> Self {
-        let mut user = UserDO::default();
-        user.id = get_row_value(r, "id");
-        user.username = get_row_value(r, "username");
-        user.nickname = get_row_value(r, "nickname");
-        user.password_hash = get_row_value(r, "password_hash");
-        user.gmt_create = get_row_value(r, "gmt_create");
-        user.gmt_modified = get_row_value(r, "gmt_modified");
-        user.enabled = get_row_value(r, "enabled");
-        user.roles = get_row_value(r, "roles");
-        user.extend_info = get_row_value(r, "extend_info");
+impl TableDO for UserDO {
+    const TABLE: TableInfo = TableInfo {
+        name: "tb_user",
+        columns: &[
+            "id",
+            "username",
+            "nickname",
+            "password_hash",
+            "gmt_create",
+            "gmt_modified",
+            "enabled",
+            "roles",
+            "extend_info",
+            "namespace_privilege_flags",
+            "namespace_white_list",
+            "namespace_black_list",
+        ],
+        id_column: "id",
+    };
 
-        user
+    fn from_row(r: &Row) -> Self {
+        Self {
+            id: get_row_value(r, "id"),
+            username: get_row_value(r, "username"),
+            nickname: get_row_value(r, "nickname"),
+            password_hash: get_row_value(r, "password_hash"),
+            gmt_create: get_row_value(r, "gmt_create"),
+            gmt_modified: get_row_value(r, "gmt_modified"),
+            enabled: get_row_value(r, "enabled"),
+            roles: get_row_value(r, "roles"),
+            extend_info: get_row_value(r, "extend_info"),
+            namespace_privilege_flags: get_row_value(r, "namespace_privilege_flags"),
+            namespace_white_list: get_row_value(r, "namespace_white_list"),
+            namespace_black_list: get_row_value(r, "namespace_black_list"),
+        }
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize)]
 pub struct UserParam {
     pub id: Option<i64>,
+    pub username: Option<String>,
+    #[serde(skip)]
     pub limit: Option<i64>,
+    #[serde(skip)]
     pub offset: Option<i64>,
 }
 
-pub struct UserSql {}
-
-impl UserSql {
-    fn conditions(&self, param: &UserParam) -> rsql_builder::B {
-        let mut whr = rsql_builder::B::new_where();
-
-        if let Some(id) = &param.id {
-            whr.eq("id", id);
-        }
-
-        whr
-    }
-
-    pub fn query_prepare(&self, param: &UserParam) -> (String, Vec<serde_json::Value>) {
-        rsql_builder::B::prepare(
-            rsql_builder::B::new_sql("select id, username, nickname, password_hash, gmt_create, gmt_modified, enabled, roles, extend_info from tb_user")
-                .push_build(&mut self.conditions(param))
-        )
-    }
-
-    pub fn insert_prepare(&self, record: &UserDO) -> (String, Vec<serde_json::Value>) {
-        let mut field_builder = rsql_builder::B::new_comma_paren();
-        let mut value_builder = rsql_builder::B::new_where();
-
-        if let Some(id) = &record.id {
-            field_builder.push_sql("id");
-            value_builder.push("?", id);
-        }
-
-        if let Some(username) = &record.username {
-            field_builder.push_sql("username");
-            value_builder.push("?", username);
-        }
-
-        if let Some(nickname) = &record.nickname {
-            field_builder.push_sql("nickname");
-            value_builder.push("?", nickname);
-        }
-
-        if let Some(password_hash) = &record.password_hash {
-            field_builder.push_sql("password_hash");
-            value_builder.push("?", password_hash);
-        }
-
-        if let Some(gmt_create) = &record.gmt_create {
-            field_builder.push_sql("gmt_create");
-            value_builder.push("?", gmt_create);
-        }
-
-        if let Some(gmt_modified) = &record.gmt_modified {
-            field_builder.push_sql("gmt_modified");
-            value_builder.push("?", gmt_modified);
-        }
-
-        if let Some(enabled) = &record.enabled {
-            field_builder.push_sql("enabled");
-            value_builder.push("?", enabled);
-        }
-
-        if let Some(roles) = &record.roles {
-            field_builder.push_sql("roles");
-            value_builder.push("?", roles);
-        }
-
-        if let Some(extend_info) = &record.extend_info {
-            field_builder.push_sql("extend_info");
-            value_builder.push("?", extend_info);
-        }
-
-        rsql_builder::B::prepare(
-            rsql_builder::B::new_sql("insert into tb_user")
-                .push_build(&mut field_builder)
-                .push_sql("values")
-                .push_build(&mut value_builder),
-        )
+impl TableParam for UserParam {
+    fn limit(&self) -> Option<i64> {
+        self.limit
     }
 
-    pub fn update_prepare(
-        &self,
-        record: &UserDO,
-        param: &UserParam,
-    ) -> (String, Vec<serde_json::Value>) {
-        let mut set_builder = rsql_builder::B::new_comma();
-
-        if let Some(id) = &record.id {
-            set_builder.eq("id", id);
-        }
-
-        if let Some(username) = &record.username {
-            set_builder.eq("username", username);
-        }
-
-        if let Some(nickname) = &record.nickname {
-            set_builder.eq("nickname", nickname);
-        }
-
-        if let Some(password_hash) = &record.password_hash {
-            set_builder.eq("password_hash", password_hash);
-        }
-
-        if let Some(gmt_create) = &record.gmt_create {
-            set_builder.eq("gmt_create", gmt_create);
-        }
-
-        if let Some(gmt_modified) = &record.gmt_modified {
-            set_builder.eq("gmt_modified", gmt_modified);
-        }
-
-        if let Some(enabled) = &record.enabled {
-            set_builder.eq("enabled", enabled);
-        }
-
-        if let Some(roles) = &record.roles {
-            set_builder.eq("roles", roles);
-        }
-
-        if let Some(extend_info) = &record.extend_info {
-            set_builder.eq("extend_info", extend_info);
-        }
-
-        let mut whr = self.conditions(param);
-        if whr.is_empty() {
-            panic!("update condition is empty");
-        }
-
-        rsql_builder::B::prepare(
-            rsql_builder::B::new_sql("update tb_user set ")
-                .push_build(&mut set_builder)
-                .push_build(&mut whr),
-        )
+    fn offset(&self) -> Option<i64> {
+        self.offset
     }
 }
 
-pub struct UserDao<'a> {
-    conn: &'a Connection,
-    inner: UserSql,
-}
-
-impl<'a> UserDao<'a> {
-    pub fn new(conn: &'a Connection) -> Self {
-        UserDao {
-            conn,
-            inner: UserSql {},
-        }
-    }
-
-    pub fn execute(&self, sql: &str, args: &[serde_json::Value]) -> anyhow::Result<usize> {
-        sqlite_execute(&self.conn, sql, args)
-    }
-
-    pub fn fetch(&self, sql: &str, args: &[serde_json::Value]) -> anyhow::Result<Vec<UserDO>> {
-        todo!()
-    }
-
-    pub fn fetch_count(&self, sql: &str, args: &[serde_json::Value]) -> anyhow::Result<u64> {
-        todo!()
-    }
+pub type UserSql = TableSql<UserDO>;
 
-    pub fn insert(&self, record: &UserDO) -> anyhow::Result<usize> {
-        let (sql, args) = self.inner.insert_prepare(record);
-        self.execute(&sql, &args)
-    }
-}
+pub type UserDao<'a> = TableDao<'a, UserDO>;
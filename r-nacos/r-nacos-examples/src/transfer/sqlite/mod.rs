@@ -1,5 +1,7 @@
 use crate::common::EMPTY_STR;
-use crate::transfer::model::UserDo;
+use crate::transfer::model::{ConfigHistoryItemDo, ConfigKey, NamespaceDo, UserDo};
+use crate::transfer::sqlite::dao::config_history::ConfigHistoryDO;
+use crate::transfer::sqlite::dao::tenant::TenantDO;
 use crate::transfer::sqlite::dao::user::UserDO;
 use std::collections::HashMap;
 
@@ -14,12 +16,36 @@ pub struct TableSeq {
 }
 
 impl TableSeq {
+    pub fn next_config_id(&mut self) -> i64 {
+        self.config_id += 1;
+        self.config_id
+    }
+
+    pub fn next_config_history_id(&mut self) -> i64 {
+        self.config_history_id += 1;
+        self.config_history_id
+    }
+
+    pub fn next_tenant_id(&mut self) -> i64 {
+        self.tenant_id += 1;
+        self.tenant_id
+    }
+
     pub fn next_user_id(&mut self) -> i64 {
         self.user_id += 1;
         self.user_id
     }
 }
 
+///
+/// 空列表不写入，与未设置相同
+fn json_list(v: &[String]) -> Option<String> {
+    if v.is_empty() {
+        return None;
+    }
+    serde_json::to_string(v).ok()
+}
+
 impl From<UserDo> for UserDO {
     fn from(value: UserDo) -> Self {
         Self {
@@ -32,6 +58,9 @@ impl From<UserDo> for UserDO {
             enabled: Some(value.enable.to_string()),
             roles: serde_json::to_string(&value.roles).ok(),
             extend_info: serde_json::to_string(&value.extend_info).ok(),
+            namespace_privilege_flags: value.namespace_privilege_flags.map(|v| v as i64),
+            namespace_white_list: json_list(&value.namespace_white_list),
+            namespace_black_list: json_list(&value.namespace_black_list),
         }
     }
 }
@@ -66,9 +95,66 @@ impl From<UserDO> for UserDo {
             roles,
             extend_info,
             password_hash: value.password_hash,
-            namespace_privilege_flags: None,
-            namespace_white_list: Default::default(),
-            namespace_black_list: Default::default(),
+            namespace_privilege_flags: value.namespace_privilege_flags.map(|v| v as u32),
+            namespace_white_list: value
+                .namespace_white_list
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default(),
+            namespace_black_list: value
+                .namespace_black_list
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl From<ConfigHistoryDO> for ConfigHistoryItemDo {
+    fn from(value: ConfigHistoryDO) -> Self {
+        Self {
+            content: value.content.unwrap_or_default(),
+            last_time: value.last_time.unwrap_or_default(),
+            op_user: value.op_user,
+            config_type: value.config_type,
+            desc: value.config_desc,
+        }
+    }
+}
+
+impl ConfigHistoryDO {
+    pub fn from_item(key: &ConfigKey, item: ConfigHistoryItemDo) -> Self {
+        Self {
+            id: None,
+            data_id: Some(key.data_id.clone()),
+            group_id: Some(key.group.clone()),
+            tenant_id: Some(key.tenant.clone()),
+            content: Some(item.content),
+            config_type: item.config_type,
+            config_desc: item.desc,
+            op_user: item.op_user,
+            last_time: Some(item.last_time),
+        }
+    }
+}
+
+impl From<TenantDO> for NamespaceDo {
+    fn from(value: TenantDO) -> Self {
+        Self {
+            namespace_id: value.tenant_id.unwrap_or_default(),
+            namespace_name: value.tenant_name.unwrap_or_default(),
+            namespace_desc: value.tenant_desc,
+            create_flag: value.create_flag,
+        }
+    }
+}
+
+impl From<NamespaceDo> for TenantDO {
+    fn from(value: NamespaceDo) -> Self {
+        Self {
+            id: None,
+            tenant_id: Some(value.namespace_id),
+            tenant_name: Some(value.namespace_name),
+            tenant_desc: value.namespace_desc,
+            create_flag: value.create_flag,
         }
     }
 }
@@ -0,0 +1,314 @@
+use crate::common::constant::{CONFIG_TREE_NAME, NAMESPACE_TREE_NAME, USER_TREE_NAME};
+use crate::transfer::model::{ConfigKey, ConfigValueDo, NamespaceDo, TransferHeaderDto, UserDo};
+use crate::transfer::sqlite::dao::ConfigDao;
+use crate::transfer::sqlite::dao::config::ConfigParam;
+use crate::transfer::sqlite::dao::config_history::{ConfigHistoryDao, ConfigHistoryParam};
+use crate::transfer::sqlite::dao::tenant::{TenantDao, TenantParam};
+use crate::transfer::sqlite::dao::user::{UserDao, UserParam};
+use crate::transfer::writer::TransferFileWriter;
+use rusqlite::{Connection, OpenFlags};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PAGE_SIZE: i64 = 500;
+const FROM_SYS: &str = "r-nacos-sqlite";
+
+///
+/// 把 sqlite 中的配置(带历史记录)、命名空间、用户导出为传输文件，是 data_to_sqlite 的逆过程
+pub async fn sqlite_to_data(db_path: &str, data_file: &str) -> anyhow::Result<()> {
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let count = self::export_to_file(&conn, data_file)?;
+
+    log::info!(
+        "transfer from sqlite db finished, config count:{}, tenant count:{}, user count:{}",
+        count.config,
+        count.tenant,
+        count.user
+    );
+
+    Ok(())
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ExportCount {
+    pub config: usize,
+    pub tenant: usize,
+    pub user: usize,
+}
+
+///
+/// 把连接中的全部数据写入传输文件，需要一致的数据时由调用方在事务中调用
+pub fn export_to_file(conn: &Connection, data_file: &str) -> anyhow::Result<ExportCount> {
+    let modify_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+    let mut header = TransferHeaderDto::new(1, modify_time, Some(FROM_SYS.to_owned()));
+    header.add_name(CONFIG_TREE_NAME.clone());
+    header.add_name(NAMESPACE_TREE_NAME.clone());
+    header.add_name(USER_TREE_NAME.clone());
+    let mut writer = TransferFileWriter::create(data_file, header)?;
+
+    let count = ExportCount {
+        config: self::write_configs(
+            &mut writer,
+            &ConfigDao::new(conn),
+            &ConfigHistoryDao::new(conn),
+        )?,
+        tenant: self::write_namespaces(&mut writer, &TenantDao::new(conn))?,
+        user: self::write_users(&mut writer, &UserDao::new(conn))?,
+    };
+    writer.finish()?;
+    Ok(count)
+}
+
+fn write_configs(
+    writer: &mut TransferFileWriter,
+    config_dao: &ConfigDao<'_>,
+    config_history_dao: &ConfigHistoryDao<'_>,
+) -> anyhow::Result<usize> {
+    let mut count = 0;
+    let mut history_count = 0;
+    let mut offset = 0;
+    loop {
+        let param = ConfigParam {
+            limit: Some(PAGE_SIZE),
+            offset: Some(offset),
+            ..Default::default()
+        };
+        let list = config_dao.query(&param)?;
+        for config in &list {
+            let key = ConfigKey::new(
+                config.data_id.as_deref().unwrap_or_default(),
+                config.group_id.as_deref().unwrap_or_default(),
+                config.tenant_id.as_deref().unwrap_or_default(),
+            );
+
+            let history_param = ConfigHistoryParam {
+                data_id: Some(key.data_id.clone()),
+                group_id: Some(key.group.clone()),
+                tenant_id: Some(key.tenant.clone()),
+                ..Default::default()
+            };
+            let histories = config_history_dao.query(&history_param)?;
+            history_count += histories.len();
+
+            let value_do = ConfigValueDo {
+                content: config.content.clone().unwrap_or_default(),
+                histories: histories.into_iter().map(|v| v.into()).collect(),
+                last_time: config.last_time.unwrap_or_default(),
+                config_type: config.config_type.clone(),
+                desc: config.config_desc.clone(),
+            };
+            writer.write_record(
+                &CONFIG_TREE_NAME,
+                key.build_key().into_bytes(),
+                value_do.to_bytes(),
+            )?;
+            count += 1;
+        }
+        if (list.len() as i64) < PAGE_SIZE {
+            break;
+        }
+        offset += PAGE_SIZE;
+    }
+
+    // 历史记录跟随配置导出，已删除配置的历史记录没有归属
+    // 导出期间库可能被并发修改，总数小于已导出数时只记录差异
+    let total = config_history_dao.query_count(&ConfigHistoryParam::default())? as usize;
+    if total > history_count {
+        log::warn!(
+            "ignore {} config history records without config",
+            total - history_count
+        );
+    } else if total < history_count {
+        log::warn!(
+            "config history count changed during export, total:{}, exported:{}",
+            total,
+            history_count
+        );
+    }
+
+    Ok(count)
+}
+
+fn write_namespaces(
+    writer: &mut TransferFileWriter,
+    tenant_dao: &TenantDao<'_>,
+) -> anyhow::Result<usize> {
+    let mut count = 0;
+    let mut offset = 0;
+    loop {
+        let param = TenantParam {
+            limit: Some(PAGE_SIZE),
+            offset: Some(offset),
+            ..Default::default()
+        };
+        let list = tenant_dao.query(&param)?;
+        let len = list.len();
+        for tenant in list {
+            let value_do: NamespaceDo = tenant.into();
+            writer.write_record(
+                &NAMESPACE_TREE_NAME,
+                value_do.namespace_id.as_bytes().to_vec(),
+                value_do.to_bytes(),
+            )?;
+            count += 1;
+        }
+        if (len as i64) < PAGE_SIZE {
+            break;
+        }
+        offset += PAGE_SIZE;
+    }
+    Ok(count)
+}
+
+fn write_users(writer: &mut TransferFileWriter, user_dao: &UserDao<'_>) -> anyhow::Result<usize> {
+    let mut count = 0;
+    let mut offset = 0;
+    loop {
+        let param = UserParam {
+            limit: Some(PAGE_SIZE),
+            offset: Some(offset),
+            ..Default::default()
+        };
+        let list = user_dao.query(&param)?;
+        let len = list.len();
+        for user in list {
+            let value_do: UserDo = user.into();
+            writer.write_record(
+                &USER_TREE_NAME,
+                value_do.username.as_bytes().to_vec(),
+                value_do.to_bytes(),
+            )?;
+            count += 1;
+        }
+        if (len as i64) < PAGE_SIZE {
+            break;
+        }
+        offset += PAGE_SIZE;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::data_to_sqlite::{data_to_sqlite, open_init_db};
+    use crate::transfer::sqlite::dao::config::ConfigDO;
+    use crate::transfer::sqlite::dao::config_history::ConfigHistoryDO;
+    use crate::transfer::sqlite::dao::tenant::TenantDO;
+    use crate::transfer::sqlite::dao::user::UserDO;
+
+    fn config(data_id: &str, tenant_id: &str, content: &str) -> ConfigDO {
+        ConfigDO {
+            data_id: Some(data_id.to_owned()),
+            group_id: Some("DEFAULT_GROUP".to_owned()),
+            tenant_id: Some(tenant_id.to_owned()),
+            content: Some(content.to_owned()),
+            config_type: Some("yaml".to_owned()),
+            config_desc: Some(format!("desc of {}", data_id)),
+            last_time: Some(1700000000000),
+            ..Default::default()
+        }
+    }
+
+    fn history(config: &ConfigDO, content: &str, op_user: &str) -> ConfigHistoryDO {
+        ConfigHistoryDO {
+            data_id: config.data_id.clone(),
+            group_id: config.group_id.clone(),
+            tenant_id: config.tenant_id.clone(),
+            content: Some(content.to_owned()),
+            config_type: config.config_type.clone(),
+            config_desc: config.config_desc.clone(),
+            op_user: Some(op_user.to_owned()),
+            last_time: Some(1600000000000),
+            ..Default::default()
+        }
+    }
+
+    async fn init_source_db(db_path: &str) -> anyhow::Result<()> {
+        let conn = open_init_db(db_path).await?;
+        let config_dao = ConfigDao::new(&conn);
+        let config_history_dao = ConfigHistoryDao::new(&conn);
+        let configs = [
+            config("app.yaml", "", "a: 1\nb: 中文"),
+            config("db.yaml", "", "url: sqlite"),
+            config("app.yaml", "dev", "a: 2"),
+        ];
+        for config in &configs {
+            config_dao.insert(config)?;
+            config_history_dao.insert(&history(config, "a: 0", "admin"))?;
+        }
+        config_history_dao.insert(&history(&configs[0], "a: 1", "test"))?;
+
+        TenantDao::new(&conn).insert(&TenantDO {
+            tenant_id: Some("dev".to_owned()),
+            tenant_name: Some("开发".to_owned()),
+            tenant_desc: Some("dev env".to_owned()),
+            create_flag: Some(1),
+            ..Default::default()
+        })?;
+
+        UserDao::new(&conn).insert(&UserDO {
+            username: Some("admin".to_owned()),
+            nickname: Some("admin".to_owned()),
+            password_hash: Some("$2b$10$hash".to_owned()),
+            gmt_create: Some(1700000000),
+            gmt_modified: Some(1700000001),
+            enabled: Some("true".to_owned()),
+            roles: Some(r#"["0"]"#.to_owned()),
+            extend_info: Some(r#"{"k":"v"}"#.to_owned()),
+            ..Default::default()
+        })?;
+        Ok(())
+    }
+
+    type Rows = (
+        Vec<ConfigDO>,
+        Vec<ConfigHistoryDO>,
+        Vec<TenantDO>,
+        Vec<UserDO>,
+    );
+
+    /// 读出全部记录，去掉自增 id
+    fn query_all(db_path: &str) -> anyhow::Result<Rows> {
+        let conn = Connection::open(db_path)?;
+        let mut configs = ConfigDao::new(&conn).query(&ConfigParam::default())?;
+        configs.iter_mut().for_each(|v| v.id = None);
+        let mut histories = ConfigHistoryDao::new(&conn).query(&ConfigHistoryParam::default())?;
+        histories.iter_mut().for_each(|v| v.id = None);
+        let mut tenants = TenantDao::new(&conn).query(&TenantParam::default())?;
+        tenants.iter_mut().for_each(|v| v.id = None);
+        let mut users = UserDao::new(&conn).query(&UserParam::default())?;
+        users.iter_mut().for_each(|v| v.id = None);
+        Ok((configs, histories, tenants, users))
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_to_data_round_trip() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("sqlite_to_data_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        let source_db = dir.join("source.db").to_string_lossy().to_string();
+        let data_file = dir.join("transfer.data").to_string_lossy().to_string();
+        let target_db = dir.join("target.db").to_string_lossy().to_string();
+
+        init_source_db(&source_db).await?;
+        sqlite_to_data(&source_db, &data_file).await?;
+        data_to_sqlite(&data_file, &target_db).await?;
+
+        let (configs, mut histories, tenants, users) = query_all(&source_db)?;
+        let target = query_all(&target_db)?;
+        assert_eq!(configs.len(), 3);
+        assert_eq!(configs, target.0);
+        // 历史记录按配置分组写回，只比较内容
+        let mut target_histories = target.1;
+        let sort_key = |v: &ConfigHistoryDO| format!("{:?}", v);
+        histories.sort_by_key(sort_key);
+        target_histories.sort_by_key(sort_key);
+        assert_eq!(histories.len(), 4);
+        assert_eq!(histories, target_histories);
+        assert_eq!(tenants, target.2);
+        assert_eq!(users, target.3);
+
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use crate::transfer::model::{
+    TRANSFER_FORMAT_VERSION, TRANSFER_MAGIC, TransferCompression, TransferHeaderDto,
+    TransferRecordDto, TransferTrailer,
+};
+use quick_protobuf::{MessageWrite, Writer};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::sync::Arc;
+
+/// 压缩时每个块未压缩前的大小上限
+const BLOCK_SIZE: usize = 256 * 1024;
+
+///
+/// 传输文件的写入，与 TransferFileReader 对应
+/// 文件格式: 8 字节前缀 | header | record* | trailer，header 与 record 前都带 varint 长度
+/// 开启压缩时 record 按块压缩，每个块前带 varint 长度; header 不压缩
+pub struct TransferFileWriter {
+    file: BufWriter<File>,
+    pub(crate) header: TransferHeaderDto,
+    compression: TransferCompression,
+    block: Vec<u8>,
+    record_count: u64,
+    hasher: crc32fast::Hasher,
+}
+
+impl TransferFileWriter {
+    ///
+    /// 创建文件并写入前缀与 header，header 中需要先登记好表名与压缩方式
+    pub fn create(path: &str, header: TransferHeaderDto) -> anyhow::Result<Self> {
+        let compression = header.compression()?;
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(TRANSFER_MAGIC)?;
+        file.write_all(&TRANSFER_FORMAT_VERSION.to_be_bytes())?;
+        {
+            let mut writer = Writer::new(&mut file);
+            writer.write_message(&header.to_do())?;
+        }
+        Ok(Self {
+            file,
+            header,
+            compression,
+            block: Vec::new(),
+            record_count: 0,
+            hasher: crc32fast::Hasher::new(),
+        })
+    }
+
+    ///
+    /// 从已写入的位置继续写，截掉 offset 之后未完成的记录
+    /// header 需要与创建文件时按相同顺序登记表名，trailer 为 offset 处的校验信息
+    pub fn resume(
+        path: &str,
+        header: TransferHeaderDto,
+        offset: u64,
+        trailer: TransferTrailer,
+    ) -> anyhow::Result<Self> {
+        let compression = header.compression()?;
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        file.set_len(offset)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            file: BufWriter::new(file),
+            header,
+            compression,
+            block: Vec::new(),
+            record_count: trailer.record_count,
+            hasher: crc32fast::Hasher::new_with_initial(trailer.checksum),
+        })
+    }
+
+    ///
+    /// 写完当前块并刷盘后返回写入位置，用于断点续传
+    pub fn position(&mut self) -> anyhow::Result<u64> {
+        self.write_block()?;
+        self.file.flush()?;
+        Ok(self.file.get_mut().stream_position()?)
+    }
+
+    ///
+    /// 已写入记录的校验信息
+    pub fn trailer(&self) -> TransferTrailer {
+        TransferTrailer {
+            record_count: self.record_count,
+            checksum: self.hasher.clone().finalize(),
+        }
+    }
+
+    pub fn write_record(
+        &mut self,
+        table_name: &Arc<String>,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        // 未登记的表名直接写在记录中
+        let record = match self.header.name_to_id.get(table_name) {
+            Some(id) => TransferRecordDto {
+                table_name: None,
+                table_id: *id,
+                key,
+                value,
+            },
+            None => TransferRecordDto {
+                table_name: Some(table_name.clone()),
+                table_id: 0,
+                key,
+                value,
+            },
+        };
+        let item = record.to_do();
+        let mut buf = Vec::with_capacity(item.get_size());
+        item.write_message(&mut Writer::new(&mut buf))?;
+        self.hasher.update(&buf);
+        self.record_count += 1;
+
+        if self.compression == TransferCompression::None {
+            Writer::new(&mut self.file).write_bytes(&buf)?;
+        } else {
+            Writer::new(&mut self.block).write_bytes(&buf)?;
+            if self.block.len() >= BLOCK_SIZE {
+                self.write_block()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self) -> anyhow::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        let v = self.compression.compress(&self.block)?;
+        Writer::new(&mut self.file).write_bytes(&v)?;
+        self.block.clear();
+        Ok(())
+    }
+
+    ///
+    /// 写入剩余的块与末尾的校验信息，返回写入的记录数
+    pub fn finish(mut self) -> anyhow::Result<u64> {
+        self.write_block()?;
+        self.file.write_all(&self.trailer().to_bytes())?;
+        self.file.flush()?;
+        Ok(self.record_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::constant::CONFIG_TREE_NAME;
+    use crate::transfer::reader::TransferFileReader;
+
+    async fn write_and_read(compression: TransferCompression) -> anyhow::Result<u64> {
+        let path = std::env::temp_dir()
+            .join(format!(
+                "transfer_writer_{}_{}.data",
+                compression.name(),
+                std::process::id()
+            ))
+            .to_string_lossy()
+            .to_string();
+        let mut header = TransferHeaderDto::new(1, 1700000000000, Some("test".to_owned()));
+        header.add_name(CONFIG_TREE_NAME.clone());
+        header
+            .extend_info
+            .insert("source".to_owned(), "unit test".to_owned());
+        header.set_compression(compression);
+
+        // 总量超过一个块
+        let value = "config content ".repeat(100).into_bytes();
+        let mut writer = TransferFileWriter::create(&path, header)?;
+        for i in 0..1000 {
+            writer.write_record(
+                &CONFIG_TREE_NAME,
+                format!("key{}", i).into_bytes(),
+                value.clone(),
+            )?;
+        }
+        assert_eq!(writer.finish()?, 1000);
+        let file_len = std::fs::metadata(&path)?.len();
+
+        let mut reader = TransferFileReader::new(&path).await?;
+        assert_eq!(reader.header.compression()?, compression);
+        assert_eq!(reader.header.extend_info["source"], "unit test");
+        let mut i = 0;
+        while let Some(v) = reader.read_record_vec().await? {
+            let record = reader.decode_record(&v)?;
+            assert_eq!(record.table_name, *CONFIG_TREE_NAME);
+            assert_eq!(record.key.as_ref(), format!("key{}", i).as_bytes());
+            assert_eq!(record.value.as_ref(), value.as_slice());
+            i += 1;
+        }
+        assert_eq!(i, 1000);
+        std::fs::remove_file(&path)?;
+        Ok(file_len)
+    }
+
+    #[tokio::test]
+    async fn test_compression() -> anyhow::Result<()> {
+        let plain_len = write_and_read(TransferCompression::None).await?;
+        let gzip_len = write_and_read(TransferCompression::Gzip).await?;
+        let zstd_len = write_and_read(TransferCompression::Zstd).await?;
+        assert!(gzip_len < plain_len / 10);
+        assert!(zstd_len < plain_len / 10);
+        Ok(())
+    }
+}
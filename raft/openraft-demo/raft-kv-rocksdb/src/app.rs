@@ -1 +1,57 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use openraft::async_runtime::WatchReceiver;
+use openraft::{Config, ReadPolicy};
+use crate::store::{ReadConsistency, ReadRequest, ReadResponse};
+use crate::watch::WatchHub;
+use crate::{typ, NodeId};
 
+// Representation of an application state. This struct can be shared around to share
+// instances of raft, store and more.
+pub struct App {
+    pub id: NodeId,
+    pub api_addr: String,
+    pub rpc_addr: String,
+    pub raft: typ::Raft,
+    pub key_values: Arc<RwLock<BTreeMap<String, String>>>,
+    pub watch: Arc<WatchHub>,
+    pub config: Arc<Config>,
+}
+
+impl App {
+    /// Read a key with the consistency the caller asked for.
+    ///
+    /// `Linearizable` and `Lease` have to be served by the leader, a follower answers with a
+    /// `ForwardToLeader` error. `Stale` is always served locally.
+    pub async fn read(&self, req: ReadRequest) -> Result<ReadResponse, typ::RaftError<typ::LinearizableReadError>> {
+        read(&self.raft, &self.key_values, req).await
+    }
+}
+
+/// See [`App::read`], shared with the groups of a multi-raft node.
+pub(crate) async fn read(
+    raft: &typ::Raft,
+    key_values: &RwLock<BTreeMap<String, String>>,
+    req: ReadRequest,
+) -> Result<ReadResponse, typ::RaftError<typ::LinearizableReadError>> {
+    let policy = match req.consistency {
+        ReadConsistency::Linearizable => Some(ReadPolicy::ReadIndex),
+        ReadConsistency::Lease => Some(ReadPolicy::LeaseRead),
+        ReadConsistency::Stale => None,
+    };
+
+    if let Some(policy) = policy {
+        // Blocks until the local state machine has applied up to the read log id.
+        raft.ensure_linearizable(policy).await?;
+    }
+
+    // Take `last_applied` before reading, the value read is at least as new as it.
+    let last_applied_id = raft.metrics().borrow_watched().last_applied;
+    let value = key_values.read().unwrap().get(&req.key).cloned();
+
+    Ok(ReadResponse {
+        value,
+        consistency: req.consistency,
+        last_applied_id,
+    })
+}
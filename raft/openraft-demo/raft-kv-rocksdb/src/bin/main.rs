@@ -1,7 +1,7 @@
 use clap::Parser;
 use serde::{Deserialize, Serialize};
 use tracing::info;
-use raft_kv_rocksdb::start_example_raft_node;
+use raft_kv_rocksdb::{start_example_raft_node, start_multi_raft_node};
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::fmt::Subscriber;
 
@@ -16,6 +16,10 @@ pub struct Opt {
 
     #[clap(long)]
     pub rpc_addr: String,
+
+    /// Host several raft groups, each owning a range of keys, see `/shard` in the README.
+    #[clap(long)]
+    pub multi_raft: bool,
 }
 
 
@@ -42,6 +46,15 @@ async fn main() -> std::io::Result<()> {
 
     info!("options: {:?}", serde_json::to_string(&options));
 
+    if options.multi_raft {
+        return start_multi_raft_node(
+            options.id,
+            format!("{}.db", options.rpc_addr),
+            options.http_addr,
+            options.rpc_addr,
+        ).await;
+    }
+
     start_example_raft_node(
         options.id,
         format!("{}.db", options.rpc_addr),
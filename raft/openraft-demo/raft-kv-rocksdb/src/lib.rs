@@ -3,13 +3,20 @@ use std::io::Cursor;
 use std::sync::Arc;
 use openraft::Config;
 use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::task;
 use tracing::info;
-use crate::store::{Request, Response};
+use crate::app::App;
+use crate::network::{api, management, shard_api, Network};
+use crate::shard::MultiRaft;
+use crate::store::{new_storage, open_db, Request, Response};
 
 pub mod app;
 pub mod client;
 pub mod network;
+pub mod shard;
 pub mod store;
+pub mod watch;
 
 pub type NodeId = u64;
 
@@ -42,24 +49,48 @@ pub mod typ {
     use openraft::error::Infallible;
     use crate::TypeConfig;
 
+    pub type Raft = openraft::Raft<TypeConfig>;
+
+    pub type Vote = openraft::Vote<TypeConfig>;
+    pub type SnapshotMeta = openraft::SnapshotMeta<TypeConfig>;
+    pub type Snapshot = openraft::Snapshot<TypeConfig>;
+
     pub type Entry = openraft::Entry<TypeConfig>;
 
     pub type RaftError<E = Infallible> = openraft::error::RaftError<TypeConfig, E>;
 
     pub type RPCError<E = Infallible> = openraft::error::RPCError<TypeConfig, RaftError<E>>;
 
+    pub type ClientWriteError = openraft::error::ClientWriteError<TypeConfig>;
 
-    // pub type ClientWriteError = openraft::error::ClientWriteError<TypeConfig, openraft::Node>;
-
-    // pub type CheckIsLeaderError = openraft::error::CheckIsLeaderError<TypeConfig>;
+    pub type LinearizableReadError = openraft::error::LinearizableReadError<TypeConfig>;
 
+    pub type ForwardToLeader = openraft::error::ForwardToLeader<TypeConfig>;
 
+    pub type InitializeError = openraft::error::InitializeError<TypeConfig>;
 
+    pub type ClientWriteResponse = openraft::raft::ClientWriteResponse<TypeConfig>;
 
+    pub type VoteRequest = openraft::raft::VoteRequest<TypeConfig>;
+    pub type VoteResponse = openraft::raft::VoteResponse<TypeConfig>;
+    pub type AppendEntriesRequest = openraft::raft::AppendEntriesRequest<TypeConfig>;
+    pub type AppendEntriesResponse = openraft::raft::AppendEntriesResponse<TypeConfig>;
+    pub type SnapshotResponse = openraft::raft::SnapshotResponse<TypeConfig>;
 }
 
 
 
+fn example_config() -> Arc<Config> {
+    let config = Config {
+        heartbeat_interval: 250,
+        election_timeout_min: 299,
+        cluster_name: "raft-kv".to_string(),
+        ..Default::default()
+    };
+
+    Arc::new(config.validate().unwrap())
+}
+
 pub async fn start_example_raft_node<P>(
     node_id: NodeId,
     dir: P,
@@ -69,21 +100,89 @@ pub async fn start_example_raft_node<P>(
 where
     P: AsRef<std::path::Path>,
 {
-    let config = Config {
-        heartbeat_interval: 250,
-        election_timeout_min: 299,
-        cluster_name: "raft-kv".to_string(),
-        ..Default::default()
-    };
+    let config = example_config();
+
+    info!("raft config: {:?}", config);
+
+    let (log_store, state_machine_store) = new_storage(&dir).await;
+
+    let kvs = state_machine_store.data.kvs.clone();
+    let watch = state_machine_store.watch.clone();
+
+    // Create the network layer that will connect and communicate the raft instances and
+    // will be used in conjunction with the store created above.
+    let network = Network {};
 
-    let config = Arc::new(config.validate().unwrap());
+    // Create a local raft instance.
+    let raft = openraft::Raft::new(node_id, config.clone(), network, log_store, state_machine_store)
+        .await
+        .unwrap();
+
+    let app = Arc::new(App {
+        id: node_id,
+        api_addr: http_addr.clone(),
+        rpc_addr: rpc_addr.clone(),
+        raft,
+        key_values: kvs,
+        watch,
+        config,
+    });
+
+    let echo_service = Arc::new(network::raft::Raft::new(app.clone()));
+
+    let server = toy_rpc::Server::builder().register(echo_service).build();
+
+    let listener = TcpListener::bind(rpc_addr).await?;
+    let handle = task::spawn(async move {
+        server.accept_websocket(listener).await.unwrap();
+    });
+
+    let mut app: tide::Server<Arc<App>> = tide::Server::with_state(app);
+
+    management::rest(&mut app);
+    api::rest(&mut app);
+
+    app.listen(http_addr).await?;
+    handle.await?;
+
+    Ok(())
+}
+
+/// Start a node hosting several raft groups, see [`shard`]. The node starts with the root group,
+/// further groups are created by splitting it.
+pub async fn start_multi_raft_node<P>(
+    node_id: NodeId,
+    dir: P,
+    http_addr: String,
+    rpc_addr: String,
+) -> std::io::Result<()>
+where
+    P: AsRef<std::path::Path>,
+{
+    let config = example_config();
 
     info!("raft config: {:?}", config);
 
+    let db = open_db(&dir);
+    let (multi, split_rx) = MultiRaft::open(node_id, http_addr.clone(), rpc_addr.clone(), config, db).await?;
+
+    task::spawn(multi.clone().handle_splits(split_rx));
+
+    let group_service = Arc::new(network::raft::GroupRaft::new(multi.clone()));
 
+    let server = toy_rpc::Server::builder().register(group_service).build();
 
+    let listener = TcpListener::bind(rpc_addr).await?;
+    let handle = task::spawn(async move {
+        server.accept_websocket(listener).await.unwrap();
+    });
 
+    let mut app: tide::Server<Arc<MultiRaft>> = tide::Server::with_state(multi);
 
+    shard_api::rest(&mut app);
+
+    app.listen(http_addr).await?;
+    handle.await?;
 
     Ok(())
-}
\ No newline at end of file
+}
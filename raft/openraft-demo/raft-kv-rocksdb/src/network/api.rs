@@ -1 +1,66 @@
+use std::sync::Arc;
+use tide::{Body, Request, Response, StatusCode};
+use crate::app::App;
+use crate::store::ReadRequest;
+use crate::watch::WatchRequest;
 
+// Application API
+//
+// This is where you place your application, you can use the example below to create your
+// API. The current implementation:
+//
+//  - `POST - /api/write` saves a value in a key and sync the nodes.
+//  - `POST - /api/read` reads a key with the consistency given in the request, linearizable
+//    by default.
+//  - `GET - /api/watch?key=..&prefix=..&start_revision=..` streams the changes of a key or a
+//    prefix as server-sent events, the event id is the revision.
+pub fn rest(app: &mut tide::Server<Arc<App>>) {
+    let mut api = app.at("/api");
+    api.at("/write").post(write);
+    api.at("/read").post(read);
+    api.at("/watch").get(tide::sse::endpoint(watch));
+}
+
+async fn write(mut req: Request<Arc<App>>) -> tide::Result {
+    let body = req.body_json().await?;
+    let res = req.state().raft.client_write(body).await;
+    Ok(Response::builder(StatusCode::Ok).body(Body::from_json(&res)?).build())
+}
+
+async fn read(mut req: Request<Arc<App>>) -> tide::Result {
+    let body: ReadRequest = req.body_json().await?;
+    let res = req.state().read(body).await;
+    Ok(Response::builder(StatusCode::Ok).body(Body::from_json(&res)?).build())
+}
+
+async fn watch(req: Request<Arc<App>>, sender: tide::sse::Sender) -> tide::Result<()> {
+    let mut watch: WatchRequest = req.query()?;
+
+    // A reconnecting EventSource sends back the id of the last event it has received.
+    if watch.start_revision.is_none() {
+        if let Some(last) = req.header("Last-Event-ID").and_then(|v| v.as_str().parse::<u64>().ok()) {
+            watch.start_revision = Some(last + 1);
+        }
+    }
+
+    let mut watcher = match req.state().watch.subscribe(watch) {
+        Ok(w) => w,
+        Err(e) => {
+            sender.send("error", serde_json::to_string(&e)?, None).await?;
+            return Ok(());
+        }
+    };
+
+    loop {
+        match watcher.next().await {
+            Ok(event) => {
+                let id = event.revision.to_string();
+                sender.send("event", serde_json::to_string(&event)?, Some(&id)).await?;
+            }
+            Err(e) => {
+                sender.send("error", serde_json::to_string(&e)?, None).await?;
+                return Ok(());
+            }
+        }
+    }
+}
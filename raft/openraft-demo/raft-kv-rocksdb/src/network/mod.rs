@@ -2,3 +2,6 @@ pub mod api;
 pub mod management;
 pub mod raft;
 pub mod raft_network_impl;
+pub mod shard_api;
+
+pub use raft_network_impl::{GroupNetwork, Network};
@@ -1 +1,93 @@
+use std::sync::Arc;
+use toy_rpc::macros::export_impl;
+use crate::app::App;
+use crate::shard::{Group, GroupId, MultiRaft};
+use crate::typ::*;
 
+// Raft API
+//
+// Served over a toy-rpc websocket by every node, called by `NetworkConnection` of its peers.
+pub struct Raft {
+    app: Arc<App>,
+}
+
+#[export_impl]
+impl Raft {
+    pub fn new(app: Arc<App>) -> Self {
+        Self { app }
+    }
+
+    #[export_method]
+    pub async fn vote(&self, vote: VoteRequest) -> Result<VoteResponse, toy_rpc::Error> {
+        self.app.raft.vote(vote).await.map_err(|e| toy_rpc::Error::Internal(Box::new(e)))
+    }
+
+    #[export_method]
+    pub async fn append(&self, req: AppendEntriesRequest) -> Result<AppendEntriesResponse, toy_rpc::Error> {
+        tracing::debug!("handle append");
+        self.app.raft.append_entries(req).await.map_err(|e| toy_rpc::Error::Internal(Box::new(e)))
+    }
+
+    #[export_method]
+    pub async fn snapshot(&self, req: (Vote, SnapshotMeta, Vec<u8>)) -> Result<SnapshotResponse, toy_rpc::Error> {
+        let (vote, meta, data) = req;
+        let snapshot = Snapshot {
+            meta,
+            snapshot: std::io::Cursor::new(data),
+        };
+        self.app
+            .raft
+            .install_full_snapshot(vote, snapshot)
+            .await
+            .map_err(|e| toy_rpc::Error::Internal(Box::new(e)))
+    }
+}
+
+// Raft API of a multi-raft node
+//
+// Same as `Raft` above, every request carries the id of the group it is sent to.
+pub struct GroupRaft {
+    multi: Arc<MultiRaft>,
+}
+
+#[export_impl]
+impl GroupRaft {
+    pub fn new(multi: Arc<MultiRaft>) -> Self {
+        Self { multi }
+    }
+
+    fn group(&self, group_id: GroupId) -> Result<Arc<Group>, toy_rpc::Error> {
+        self.multi
+            .group(group_id)
+            .ok_or_else(|| toy_rpc::Error::Internal(format!("unknown raft group: {}", group_id).into()))
+    }
+
+    #[export_method]
+    pub async fn vote(&self, req: (GroupId, VoteRequest)) -> Result<VoteResponse, toy_rpc::Error> {
+        let (group_id, vote) = req;
+        self.group(group_id)?.raft.vote(vote).await.map_err(|e| toy_rpc::Error::Internal(Box::new(e)))
+    }
+
+    #[export_method]
+    pub async fn append(&self, req: (GroupId, AppendEntriesRequest)) -> Result<AppendEntriesResponse, toy_rpc::Error> {
+        let (group_id, req) = req;
+        self.group(group_id)?.raft.append_entries(req).await.map_err(|e| toy_rpc::Error::Internal(Box::new(e)))
+    }
+
+    #[export_method]
+    pub async fn snapshot(
+        &self,
+        req: (GroupId, Vote, SnapshotMeta, Vec<u8>),
+    ) -> Result<SnapshotResponse, toy_rpc::Error> {
+        let (group_id, vote, meta, data) = req;
+        let snapshot = Snapshot {
+            meta,
+            snapshot: std::io::Cursor::new(data),
+        };
+        self.group(group_id)?
+            .raft
+            .install_full_snapshot(vote, snapshot)
+            .await
+            .map_err(|e| toy_rpc::Error::Internal(Box::new(e)))
+    }
+}
@@ -1 +1,128 @@
+use std::future::Future;
+use openraft::error::{NetworkError, RPCError, ReplicationClosed, StreamingError, Unreachable};
+use openraft::network::RPCOption;
+use openraft::{RaftNetworkFactory, RaftNetworkV2};
+use toy_rpc::pubsub::AckModeNone;
+use toy_rpc::Client;
+use crate::network::raft::{GroupRaftClientStub, RaftClientStub};
+use crate::shard::GroupId;
+use crate::typ::*;
+use crate::{Node, NodeId, TypeConfig};
 
+pub struct Network {}
+
+// NOTE: This could be implemented also on `Arc<Network>`, but since it's empty, implemented
+// directly.
+impl RaftNetworkFactory<TypeConfig> for Network {
+    type Network = NetworkConnection;
+
+    async fn new_client(&mut self, target: NodeId, node: &Node) -> Self::Network {
+        NetworkConnection::new(node.rpc_addr.clone(), target)
+    }
+}
+
+/// The network of one group of a multi-raft node, the groups of a node share its rpc address.
+pub struct GroupNetwork {
+    pub group_id: GroupId,
+}
+
+impl RaftNetworkFactory<TypeConfig> for GroupNetwork {
+    type Network = NetworkConnection;
+
+    async fn new_client(&mut self, target: NodeId, node: &Node) -> Self::Network {
+        let mut conn = NetworkConnection::new(node.rpc_addr.clone(), target);
+        conn.group = Some(self.group_id);
+        conn
+    }
+}
+
+pub struct NetworkConnection {
+    addr: String,
+    client: Option<Client<AckModeNone>>,
+    target: NodeId,
+
+    /// Set when talking to a group of a multi-raft node.
+    group: Option<GroupId>,
+}
+
+impl NetworkConnection {
+    pub fn new(addr: String, target: NodeId) -> Self {
+        NetworkConnection {
+            addr,
+            client: None,
+            target,
+            group: None,
+        }
+    }
+
+    /// Connect lazily, a dropped connection is re-dialed on the next RPC.
+    async fn c(&mut self) -> Result<&Client<AckModeNone>, RPCError<TypeConfig>> {
+        if self.client.is_none() {
+            let client = Client::dial_websocket(&format!("ws://{}", self.addr))
+                .await
+                .map_err(|e| RPCError::Unreachable(Unreachable::new(&e)))?;
+            self.client = Some(client);
+        }
+
+        Ok(self.client.as_ref().unwrap())
+    }
+
+    fn rpc_error(&mut self, e: toy_rpc::Error) -> RPCError<TypeConfig> {
+        tracing::debug!(target = display(self.target), error = display(&e), "rpc failed");
+        match e {
+            toy_rpc::Error::IoError(e) => {
+                self.client = None;
+                RPCError::Unreachable(Unreachable::new(&e))
+            }
+            e => RPCError::Network(NetworkError::new(&e)),
+        }
+    }
+}
+
+impl RaftNetworkV2<TypeConfig> for NetworkConnection {
+    async fn append_entries(
+        &mut self,
+        req: AppendEntriesRequest,
+        _option: RPCOption,
+    ) -> Result<AppendEntriesResponse, RPCError<TypeConfig>> {
+        tracing::debug!(req = debug(&req), "append_entries");
+
+        let group = self.group;
+        let c = self.c().await?;
+        let res = match group {
+            None => c.raft().append(req).await,
+            Some(group_id) => c.group_raft().append((group_id, req)).await,
+        };
+        res.map_err(|e| self.rpc_error(e))
+    }
+
+    async fn vote(&mut self, req: VoteRequest, _option: RPCOption) -> Result<VoteResponse, RPCError<TypeConfig>> {
+        tracing::debug!(req = debug(&req), "vote");
+
+        let group = self.group;
+        let c = self.c().await?;
+        let res = match group {
+            None => c.raft().vote(req).await,
+            Some(group_id) => c.group_raft().vote((group_id, req)).await,
+        };
+        res.map_err(|e| self.rpc_error(e))
+    }
+
+    async fn full_snapshot(
+        &mut self,
+        vote: Vote,
+        snapshot: Snapshot,
+        _cancel: impl Future<Output = ReplicationClosed> + Send + 'static,
+        _option: RPCOption,
+    ) -> Result<SnapshotResponse, StreamingError<TypeConfig>> {
+        let data = snapshot.snapshot.into_inner();
+
+        let group = self.group;
+        let c = self.c().await?;
+        let res = match group {
+            None => c.raft().snapshot((vote, snapshot.meta, data)).await,
+            Some(group_id) => c.group_raft().snapshot((group_id, vote, snapshot.meta, data)).await,
+        };
+        Ok(res.map_err(|e| self.rpc_error(e))?)
+    }
+}
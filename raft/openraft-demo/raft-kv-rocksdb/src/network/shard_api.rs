@@ -0,0 +1,162 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use openraft::async_runtime::WatchReceiver;
+use openraft::RaftMetrics;
+use serde::{Deserialize, Serialize};
+use tide::{Body, Request, Response, StatusCode};
+use crate::shard::{Group, GroupId, MultiRaft, ROOT_GROUP};
+use crate::store::{ReadRequest, Request as KvRequest};
+use crate::{Node, NodeId, TypeConfig};
+
+// Multi-Raft API
+//
+// Served by a node started with `--multi-raft`:
+//
+//  - `POST - /shard/write` and `POST - /shard/read` route the request to the group owning
+//    its key, a follower answers with `ForwardToLeader` as with `/api`. A write whose key is
+//    moved by a split before it is applied is sent again to the new group, and answered with
+//    `503 Service Unavailable` if the key is still not settled, the client may retry it.
+//  - `POST - /shard/split` splits the group owning a key, `{"key": "m", "group_id": 2}` moves
+//    the keys from "m" on to a new group 2.
+//  - `GET - /shard/routes` lists the range and the leader of every group.
+//  - `/shard/init`, `/shard/add-learner`, `/shard/change-membership` and `/shard/metrics` do
+//    what their `/cluster` counterparts do, for the group in `?group=..`, the root group by
+//    default.
+pub fn rest(app: &mut tide::Server<Arc<MultiRaft>>) {
+    let mut shard = app.at("/shard");
+    shard.at("/write").post(write);
+    shard.at("/read").post(read);
+    shard.at("/split").post(split);
+    shard.at("/routes").get(routes);
+    shard.at("/init").post(init);
+    shard.at("/add-learner").post(add_learner);
+    shard.at("/change-membership").post(change_membership);
+    shard.at("/metrics").get(metrics);
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitRequest {
+    pub key: String,
+    pub group_id: GroupId,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct GroupQuery {
+    group: Option<GroupId>,
+}
+
+fn not_found(msg: String) -> tide::Result {
+    Ok(Response::builder(StatusCode::NotFound).body(msg).build())
+}
+
+fn query_group(req: &Request<Arc<MultiRaft>>) -> tide::Result<Option<Arc<Group>>> {
+    let query: GroupQuery = req.query()?;
+    Ok(req.state().group(query.group.unwrap_or(ROOT_GROUP)))
+}
+
+async fn write(mut req: Request<Arc<MultiRaft>>) -> tide::Result {
+    let body: KvRequest = req.body_json().await?;
+    let key = match &body {
+        KvRequest::Set { key, .. } | KvRequest::Delete { key } => key.clone(),
+        KvRequest::Split { .. } => {
+            return Ok(Response::builder(StatusCode::BadRequest).body("split through /shard/split").build());
+        }
+    };
+
+    let Some(res) = req.state().write(&key, body).await else {
+        return not_found(format!("no group owns key: {}", key));
+    };
+    if matches!(&res, Ok(resp) if resp.data.wrong_group) {
+        return Ok(Response::builder(StatusCode::ServiceUnavailable)
+            .body(format!("key moved by a split, retry: {}", key))
+            .build());
+    }
+    Ok(Response::builder(StatusCode::Ok).body(Body::from_json(&res)?).build())
+}
+
+async fn read(mut req: Request<Arc<MultiRaft>>) -> tide::Result {
+    let body: ReadRequest = req.body_json().await?;
+
+    let Some(group) = req.state().locate(&body.key) else {
+        return not_found(format!("no group owns key: {}", body.key));
+    };
+    let res = group.read(body).await;
+    Ok(Response::builder(StatusCode::Ok).body(Body::from_json(&res)?).build())
+}
+
+async fn split(mut req: Request<Arc<MultiRaft>>) -> tide::Result {
+    let body: SplitRequest = req.body_json().await?;
+
+    // Fail fast on a group this node knows of, the state machine refuses the others.
+    if req.state().group(body.group_id).is_some() {
+        return Ok(Response::builder(StatusCode::BadRequest)
+            .body(format!("group already exists: {}", body.group_id))
+            .build());
+    }
+    let Some(group) = req.state().locate(&body.key) else {
+        return not_found(format!("no group owns key: {}", body.key));
+    };
+
+    let res = group
+        .raft
+        .client_write(KvRequest::Split {
+            key: body.key,
+            group_id: body.group_id,
+        })
+        .await;
+    Ok(Response::builder(StatusCode::Ok).body(Body::from_json(&res)?).build())
+}
+
+async fn routes(req: Request<Arc<MultiRaft>>) -> tide::Result {
+    let table = req.state().route_table();
+    let routes = table.routes().cloned().collect::<Vec<_>>();
+    Ok(Response::builder(StatusCode::Ok).body(Body::from_json(&routes)?).build())
+}
+
+/// Initialize a group with this node as its only voter.
+async fn init(req: Request<Arc<MultiRaft>>) -> tide::Result {
+    let Some(group) = query_group(&req)? else {
+        return not_found("unknown group".to_string());
+    };
+
+    let mut nodes = BTreeMap::new();
+    let node = Node {
+        api_addr: req.state().api_addr.clone(),
+        rpc_addr: req.state().rpc_addr.clone(),
+    };
+    nodes.insert(req.state().id, node);
+
+    let res = group.raft.initialize(nodes).await;
+    Ok(Response::builder(StatusCode::Ok).body(Body::from_json(&res)?).build())
+}
+
+async fn add_learner(mut req: Request<Arc<MultiRaft>>) -> tide::Result {
+    let (node_id, api_addr, rpc_addr): (NodeId, String, String) = req.body_json().await?;
+    let Some(group) = query_group(&req)? else {
+        return not_found("unknown group".to_string());
+    };
+
+    let node = Node { rpc_addr, api_addr };
+    let res = group.raft.add_learner(node_id, node, true).await;
+    Ok(Response::builder(StatusCode::Ok).body(Body::from_json(&res)?).build())
+}
+
+async fn change_membership(mut req: Request<Arc<MultiRaft>>) -> tide::Result {
+    let body: BTreeSet<NodeId> = req.body_json().await?;
+    let Some(group) = query_group(&req)? else {
+        return not_found("unknown group".to_string());
+    };
+
+    let res = group.raft.change_membership(body, false).await;
+    Ok(Response::builder(StatusCode::Ok).body(Body::from_json(&res)?).build())
+}
+
+async fn metrics(req: Request<Arc<MultiRaft>>) -> tide::Result {
+    let Some(group) = query_group(&req)? else {
+        return not_found("unknown group".to_string());
+    };
+
+    let metrics = group.raft.metrics().borrow_watched().clone();
+    let res: Result<RaftMetrics<TypeConfig>, openraft::error::Infallible> = Ok(metrics);
+    Ok(Response::builder(StatusCode::Ok).body(Body::from_json(&res)?).build())
+}
@@ -0,0 +1,66 @@
+//! Multi-Raft: several raft groups in one process, each owning a range of the key space.
+//!
+//! All groups of a node share one rocksdb, every key a group writes is prefixed with its
+//! [`GroupId`]. A node starts with the [`ROOT_GROUP`] owning every key; a `Request::Split`
+//! committed in a group moves the upper half of its range to a new group made of the same
+//! voters. Clients find the group of a key, and its leader, in the [`RouteTable`].
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use openraft::{LogId, Membership};
+use serde::{Deserialize, Serialize};
+use crate::TypeConfig;
+
+pub mod node;
+pub mod route;
+
+pub use node::{Group, MultiRaft};
+pub use route::{Route, RouteTable};
+
+pub type GroupId = u64;
+
+/// The group a multi-raft node is initialized with, it owns the whole key space until split.
+pub const ROOT_GROUP: GroupId = 1;
+
+/// Keys in `[start, end)`, `end` of `None` is unbounded.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeyRange {
+    pub start: String,
+    pub end: Option<String>,
+}
+
+impl KeyRange {
+    pub fn contains(&self, key: &str) -> bool {
+        key >= self.start.as_str() && self.end.as_deref().is_none_or(|end| key < end)
+    }
+}
+
+impl Display for KeyRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.end {
+            Some(end) => write!(f, "[{:?}, {:?})", self.start, end),
+            None => write!(f, "[{:?}, +inf)", self.start),
+        }
+    }
+}
+
+/// Emitted by a state machine when it applies a split, or installs a snapshot that contains
+/// splits it has not applied, the node then creates the new group.
+#[derive(Debug, Clone)]
+pub struct SplitEvent {
+    pub group_id: GroupId,
+
+    /// The range handed over to the new group.
+    pub range: KeyRange,
+
+    pub kvs: BTreeMap<String, String>,
+
+    /// The group ids already used by the parent's lineage, the new group refuses to split into
+    /// any of them.
+    pub groups: BTreeSet<GroupId>,
+
+    /// The membership of the parent group, its voters become the voters of the new group.
+    pub membership: Membership<TypeConfig>,
+
+    pub log_id: LogId<TypeConfig>,
+}
@@ -0,0 +1,342 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use openraft::async_runtime::WatchReceiver;
+use openraft::Config;
+use rocksdb::DB;
+use tokio::sync::mpsc;
+use crate::network::GroupNetwork;
+use crate::shard::{GroupId, KeyRange, Route, RouteTable, SplitEvent, ROOT_GROUP};
+use crate::store::{new_group_storage, save_seed, ReadRequest, ReadResponse, Request};
+use crate::{typ, Node, NodeId};
+
+/// The key in the "store" column family listing the groups hosted by a node.
+const GROUPS_KEY: &[u8] = b"multi_raft_groups";
+
+/// How many times [`MultiRaft::write`] sends a write to another group after a split moved its
+/// key.
+const MAX_REROUTES: u32 = 5;
+
+/// One raft group hosted by a multi-raft node.
+pub struct Group {
+    pub id: GroupId,
+    pub raft: typ::Raft,
+    pub range: Arc<RwLock<KeyRange>>,
+    pub key_values: Arc<RwLock<BTreeMap<String, String>>>,
+}
+
+impl Group {
+    /// See [`crate::app::App::read`].
+    pub async fn read(&self, req: ReadRequest) -> Result<ReadResponse, typ::RaftError<typ::LinearizableReadError>> {
+        crate::app::read(&self.raft, &self.key_values, req).await
+    }
+
+    pub fn route(&self) -> Route {
+        Route {
+            group_id: self.id,
+            range: self.range.read().unwrap().clone(),
+            leader: self.raft.metrics().borrow_watched().current_leader,
+        }
+    }
+}
+
+// Representation of the state of a multi-raft node, shared by the groups, the rpc service
+// and the http api.
+pub struct MultiRaft {
+    pub id: NodeId,
+    pub api_addr: String,
+    pub rpc_addr: String,
+    pub config: Arc<Config>,
+    db: Arc<DB>,
+    groups: RwLock<BTreeMap<GroupId, Arc<Group>>>,
+    split_tx: mpsc::UnboundedSender<SplitEvent>,
+}
+
+impl MultiRaft {
+    /// Open every group this node hosted before it was restarted, or the root group on a
+    /// new node. The returned receiver has to be passed to [`MultiRaft::handle_splits`].
+    pub async fn open(
+        id: NodeId,
+        api_addr: String,
+        rpc_addr: String,
+        config: Arc<Config>,
+        db: Arc<DB>,
+    ) -> io::Result<(Arc<Self>, mpsc::UnboundedReceiver<SplitEvent>)> {
+        let (split_tx, split_rx) = mpsc::unbounded_channel();
+
+        let multi = Arc::new(Self {
+            id,
+            api_addr,
+            rpc_addr,
+            config,
+            db,
+            groups: Default::default(),
+            split_tx,
+        });
+
+        let mut group_ids = multi.load_group_ids()?;
+        group_ids.insert(ROOT_GROUP);
+        for group_id in group_ids {
+            multi.open_group(group_id).await?;
+        }
+
+        Ok((multi, split_rx))
+    }
+
+    pub fn group(&self, group_id: GroupId) -> Option<Arc<Group>> {
+        self.groups.read().unwrap().get(&group_id).cloned()
+    }
+
+    pub fn groups(&self) -> Vec<Arc<Group>> {
+        self.groups.read().unwrap().values().cloned().collect()
+    }
+
+    /// The local group owning `key`.
+    pub fn locate(&self, key: &str) -> Option<Arc<Group>> {
+        let group_id = self.route_table().locate(key)?.group_id;
+        self.group(group_id)
+    }
+
+    /// Write `req` to the local group owning `key`, `None` if no group owns it.
+    ///
+    /// A write applied by a group after a split moved its key away is answered with
+    /// `Response::wrong_group`, it is then sent again to the group owning the key now. The
+    /// response still has `wrong_group` set if the key kept moving, or the new group was not
+    /// created in time.
+    pub async fn write(
+        &self,
+        key: &str,
+        req: Request,
+    ) -> Option<Result<typ::ClientWriteResponse, typ::RaftError<typ::ClientWriteError>>> {
+        let mut last = None;
+        for attempt in 0..MAX_REROUTES {
+            let Some(group) = self.locate(key) else {
+                // The parent has applied the split, but the new group is not created yet.
+                if last.is_none() {
+                    return None;
+                }
+                tokio::time::sleep(Duration::from_millis(20 << attempt)).await;
+                continue;
+            };
+            let res = group.raft.client_write(req.clone()).await;
+            match &res {
+                Ok(resp) if resp.data.wrong_group => {
+                    tracing::debug!(group_id = group.id, key, "key moved by a split, reroute the write");
+                    last = Some(res);
+                }
+                _ => return Some(res),
+            }
+        }
+        last
+    }
+
+    pub fn route_table(&self) -> RouteTable {
+        let mut table = RouteTable::default();
+        for group in self.groups() {
+            table.insert(group.route());
+        }
+        table
+    }
+
+    /// Create the groups split off by the local state machines, until the node shuts down.
+    ///
+    /// Every voter of the parent group applies the split and creates the new group from the
+    /// same keys and with the same voters, so each of them can initialize it. A learner of the
+    /// parent creates the group too, and is added to it as a learner by its leader.
+    pub async fn handle_splits(self: Arc<Self>, mut split_rx: mpsc::UnboundedReceiver<SplitEvent>) {
+        while let Some(event) = split_rx.recv().await {
+            let group_id = event.group_id;
+            if let Err(e) = self.split(event).await {
+                tracing::error!(group_id, error = display(&e), "failed to create split group");
+            }
+        }
+    }
+
+    async fn split(&self, event: SplitEvent) -> io::Result<()> {
+        // The split is emitted again when the parent group replays its logs or reloads its
+        // snapshot after a restart. A group with another range is a different group that
+        // happens to use the same id.
+        if let Some(group) = self.group(event.group_id) {
+            let start = group.range.read().unwrap().start.clone();
+            if start == event.range.start {
+                return Ok(());
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("group {} already exists with a range starting at {:?}", event.group_id, start),
+            ));
+        }
+
+        tracing::info!(group_id = event.group_id, range = display(&event.range), "create split group");
+
+        save_seed(&self.db, &event)?;
+        let group = self.open_group(event.group_id).await?;
+
+        let voters = event.membership.voter_ids().collect::<BTreeSet<_>>();
+        if !voters.contains(&self.id) {
+            // Replicated to once the leader of the new group adds it as a learner.
+            return Ok(());
+        }
+
+        let nodes = event
+            .membership
+            .nodes()
+            .filter(|(id, _)| voters.contains(id))
+            .map(|(id, node)| (*id, node.clone()))
+            .collect::<BTreeMap<NodeId, Node>>();
+
+        // Every voter initializes with the same membership, only the first one succeeds.
+        if let Err(e) = group.raft.initialize(nodes).await {
+            tracing::debug!(group_id = event.group_id, error = display(&e), "split group already initialized");
+        }
+
+        let learners = event
+            .membership
+            .learner_ids()
+            .filter_map(|id| Some((id, event.membership.get_node(&id)?.clone())))
+            .collect::<Vec<_>>();
+        if !learners.is_empty() {
+            tokio::spawn(add_learners(self.id, group, learners));
+        }
+        Ok(())
+    }
+
+    async fn open_group(&self, group_id: GroupId) -> io::Result<Arc<Group>> {
+        let (log_store, state_machine_store) =
+            new_group_storage(self.db.clone(), group_id, Some(self.split_tx.clone())).await;
+
+        let range = state_machine_store.data.range.clone();
+        let key_values = state_machine_store.data.kvs.clone();
+
+        let network = GroupNetwork { group_id };
+        let raft = openraft::Raft::new(self.id, self.config.clone(), network, log_store, state_machine_store)
+            .await
+            .map_err(io::Error::other)?;
+
+        let group = Arc::new(Group {
+            id: group_id,
+            raft,
+            range,
+            key_values,
+        });
+
+        let group_ids = {
+            let mut groups = self.groups.write().unwrap();
+            groups.insert(group_id, group.clone());
+            groups.keys().copied().collect::<BTreeSet<_>>()
+        };
+        self.save_group_ids(&group_ids)?;
+
+        Ok(group)
+    }
+
+    fn load_group_ids(&self) -> io::Result<BTreeSet<GroupId>> {
+        let bytes = self.db.get_cf(self.db.cf_handle("store").unwrap(), GROUPS_KEY).map_err(io::Error::other)?;
+        match bytes {
+            None => Ok(BTreeSet::new()),
+            Some(b) => serde_json::from_slice(&b).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+
+    fn save_group_ids(&self, group_ids: &BTreeSet<GroupId>) -> io::Result<()> {
+        let bytes = serde_json::to_vec(group_ids).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.db.put_cf(self.db.cf_handle("store").unwrap(), GROUPS_KEY, bytes).map_err(io::Error::other)?;
+        self.db.flush_wal(true).map_err(io::Error::other)
+    }
+}
+
+/// Add the learners of the parent group to a group created by a split. Only done by the node
+/// that became the leader of the new group.
+async fn add_learners(id: NodeId, group: Arc<Group>, learners: Vec<(NodeId, Node)>) {
+    let metrics = group
+        .raft
+        .wait(Some(Duration::from_secs(10)))
+        .metrics(|m| m.current_leader.is_some(), "split group has a leader")
+        .await;
+    match metrics {
+        Ok(m) if m.current_leader == Some(id) => {}
+        Ok(_) => return,
+        Err(e) => {
+            tracing::warn!(group_id = group.id, error = display(&e), "no leader to add the learners of the split group");
+            return;
+        }
+    }
+
+    for (learner, node) in learners {
+        if let Err(e) = group.raft.add_learner(learner, node, false).await {
+            tracing::warn!(group_id = group.id, learner, error = display(&e), "failed to add learner to split group");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::open_db;
+    use super::*;
+
+    async fn wait_for_leader(group: &Group) {
+        group.raft.wait(Some(Duration::from_secs(10))).current_leader(1, "leader").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_split_into_taken_group_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Arc::new(Config::default().validate().unwrap());
+        let (multi, split_rx) =
+            MultiRaft::open(1, String::new(), String::new(), config, open_db(dir.path())).await.unwrap();
+        tokio::spawn(multi.clone().handle_splits(split_rx));
+
+        let root = multi.group(ROOT_GROUP).unwrap();
+        root.raft.initialize(BTreeMap::from([(1, Node::default())])).await.unwrap();
+        wait_for_leader(&root).await;
+        for key in ["a", "f", "m", "t"] {
+            let set = Request::Set {
+                key: key.to_string(),
+                value: key.to_string(),
+            };
+            root.raft.client_write(set).await.unwrap();
+        }
+
+        let split = |key: &str, group_id| Request::Split {
+            key: key.to_string(),
+            group_id,
+        };
+        root.raft.client_write(split("m", 2)).await.unwrap();
+        let child = loop {
+            if let Some(group) = multi.group(2) {
+                break group;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        wait_for_leader(&child).await;
+        assert_eq!(child.key_values.read().unwrap().keys().collect::<Vec<_>>(), ["m", "t"]);
+
+        // Neither the existing group, nor the parent, nor the group itself can be split into.
+        root.raft.client_write(split("f", 2)).await.unwrap();
+        child.raft.client_write(split("t", ROOT_GROUP)).await.unwrap();
+        child.raft.client_write(split("t", 2)).await.unwrap();
+
+        assert_eq!(root.key_values.read().unwrap().keys().collect::<Vec<_>>(), ["a", "f"]);
+        assert_eq!(child.key_values.read().unwrap().keys().collect::<Vec<_>>(), ["m", "t"]);
+        assert_eq!(multi.groups().len(), 2);
+        assert_eq!(multi.locate("f").unwrap().id, ROOT_GROUP);
+        assert_eq!(multi.locate("t").unwrap().id, 2);
+
+        // A write reaching the parent after the split is rejected, not dropped as a success,
+        // and the router sends it on to the group owning the key.
+        let set = Request::Set {
+            key: "x".to_string(),
+            value: "x".to_string(),
+        };
+        let res = root.raft.client_write(set.clone()).await.unwrap();
+        assert!(res.data.wrong_group);
+        assert!(!root.key_values.read().unwrap().contains_key("x"));
+        assert!(!child.key_values.read().unwrap().contains_key("x"));
+
+        let res = multi.write("x", set).await.unwrap().unwrap();
+        assert!(!res.data.wrong_group);
+        assert_eq!(res.data.value.as_deref(), Some("x"));
+        assert_eq!(child.key_values.read().unwrap().get("x").map(String::as_str), Some("x"));
+    }
+}
@@ -0,0 +1,42 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::shard::{GroupId, KeyRange};
+use crate::NodeId;
+
+/// Where the keys of one range are served.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Route {
+    pub group_id: GroupId,
+    pub range: KeyRange,
+
+    /// The leader of the group as last seen by this node, `None` during an election.
+    pub leader: Option<NodeId>,
+}
+
+/// Maps keys to groups and to the leaders of the groups.
+///
+/// The ranges of the groups do not overlap, routes are indexed by the start of their range.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RouteTable {
+    routes: BTreeMap<String, Route>,
+}
+
+impl RouteTable {
+    pub fn insert(&mut self, route: Route) {
+        self.routes.insert(route.range.start.clone(), route);
+    }
+
+    /// The route of the group owning `key`.
+    pub fn locate(&self, key: &str) -> Option<&Route> {
+        let (_, route) = self.routes.range(..=key.to_string()).next_back()?;
+        if route.range.contains(key) {
+            Some(route)
+        } else {
+            None
+        }
+    }
+
+    pub fn routes(&self) -> impl Iterator<Item = &Route> {
+        self.routes.values()
+    }
+}
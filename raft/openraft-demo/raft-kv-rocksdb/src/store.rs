@@ -1,19 +1,87 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Debug, Display, Formatter};
+use std::io;
+use std::io::Cursor;
+use std::ops::RangeBounds;
 use std::path::Path;
 use std::sync::{Arc, RwLock};
-use openraft::{LogId, SnapshotMeta, StoredMembership};
-use rocksdb::DB;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use futures::{Stream, TryStreamExt};
+use openraft::storage::{EntryResponder, IOFlushed, LogState, RaftLogReader, RaftLogStorage, RaftSnapshotBuilder, RaftStateMachine, Snapshot};
+use openraft::{Entry, EntryPayload, LogId, OptionalSend, SnapshotMeta, StoredMembership, Vote};
+use openraft::entry::RaftEntry;
+use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, Direction, Options, DB};
 use serde::{Deserialize, Serialize};
-use crate::{NodeId, TypeConfig};
+use tokio::sync::mpsc;
+use crate::shard::{GroupId, KeyRange, SplitEvent, ROOT_GROUP};
+use crate::watch::{EventKind, WatchEvent, WatchHub};
+use crate::{SnapshotData, TypeConfig};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Request {
     Set {key: String, value: String},
+    Delete {key: String},
+
+    /// Hand the keys from `key` on over to a new raft group `group_id`. Only applied by the
+    /// groups of a multi-raft node, see [`crate::shard`].
+    Split {key: String, group_id: GroupId},
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl Display for Request {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Request::Set { key, value } => write!(f, "Set {{ key: {}, value: {} }}", key, value),
+            Request::Delete { key } => write!(f, "Delete {{ key: {} }}", key),
+            Request::Split { key, group_id } => write!(f, "Split {{ key: {}, group_id: {} }}", key, group_id),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Response {
-    pub value: Option<String>
+    pub value: Option<String>,
+
+    /// Set when the key of a `Set` or `Delete` is no longer owned by the group: a split moved
+    /// it away after the request was proposed. Nothing was written, the request has to be sent
+    /// again to the group owning the key now.
+    #[serde(default)]
+    pub wrong_group: bool,
+}
+
+/// How much a client is willing to pay for the freshness of a read.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadConsistency {
+    /// Confirm leadership with a quorum (ReadIndex) and wait until the state machine
+    /// has applied up to the read index before reading.
+    #[default]
+    Linearizable,
+
+    /// Served by the leader without a network round-trip while its lease, renewed by
+    /// heartbeats, has not expired.
+    Lease,
+
+    /// Served from the local state machine of whichever node receives the request.
+    Stale,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReadRequest {
+    pub key: String,
+
+    #[serde(default)]
+    pub consistency: ReadConsistency,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReadResponse {
+    pub value: Option<String>,
+
+    pub consistency: ReadConsistency,
+
+    /// The log id the state machine had applied when the value was read. For a stale
+    /// read this tells the caller how far behind the serving node may be.
+    pub last_applied_id: Option<LogId<TypeConfig>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -23,30 +91,729 @@ pub struct StoredSnapshot {
     pub data: Vec<u8>,
 }
 
+/// What `StoredSnapshot::data` holds.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct SnapshotPayload {
+    range: KeyRange,
+    kvs: BTreeMap<String, String>,
+
+    /// See `StateMachineData::groups`.
+    #[serde(default)]
+    groups: BTreeSet<GroupId>,
+
+    /// See `StateMachineData::splits`.
+    #[serde(default)]
+    splits: BTreeMap<GroupId, SnapshotPayload>,
+}
+
+#[derive(Debug)]
 pub struct StateMachineStore {
     pub data: StateMachineData,
 
     snapshot_idx: u64,
 
     db: Arc<DB>,
+
+    /// The raft group this state machine belongs to.
+    group_id: GroupId,
+
+    /// Prepended to every key this store writes, so that the groups of a multi-raft node can
+    /// share one rocksdb. Empty for a single group.
+    prefix: Vec<u8>,
+
+    /// Receives a put or delete event for every key changed by an applied entry.
+    pub watch: Arc<WatchHub>,
+
+    /// Where an applied `Request::Split` is handed to. `None` when the node runs a single
+    /// group, which then ignores splits.
+    split_tx: Option<mpsc::UnboundedSender<SplitEvent>>,
 }
 
+#[derive(Debug, Clone)]
 pub struct StateMachineData {
-    pub last_applied_id: Option<LogId<NodeId>>,
+    pub last_applied_id: Option<LogId<TypeConfig>>,
 
-    pub last_membership: StoredMembership<TypeConfig, NodeId>,
+    pub last_membership: StoredMembership<TypeConfig>,
+
+    /// The keys this group owns, the whole key space unless the group has been split.
+    pub range: Arc<RwLock<KeyRange>>,
 
     pub kvs: Arc<RwLock<BTreeMap<String, String>>>,
+
+    /// The group ids this group knows to be taken: its own, those of its ancestors and of
+    /// every group split off from them. A split into one of them is refused.
+    pub groups: BTreeSet<GroupId>,
+
+    /// The initial state of every group split off from this one. A node that catches up by
+    /// installing a snapshot never applies the splits folded into it, it creates the groups
+    /// from here instead.
+    splits: BTreeMap<GroupId, SnapshotPayload>,
+}
+
+impl RaftSnapshotBuilder<TypeConfig> for StateMachineStore {
+    async fn build_snapshot(&mut self) -> Result<Snapshot<TypeConfig>, io::Error> {
+        let last_applied_id = self.data.last_applied_id;
+        let last_membership = self.data.last_membership.clone();
+
+        let kv_json = {
+            let payload = SnapshotPayload {
+                range: self.data.range.read().unwrap().clone(),
+                kvs: self.data.kvs.read().unwrap().clone(),
+                groups: self.data.groups.clone(),
+                splits: self.data.splits.clone(),
+            };
+            serde_json::to_vec(&payload).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        };
+
+        let snapshot_id = if let Some(last) = last_applied_id {
+            format!("{}-{}-{}", last.committed_leader_id(), last.index(), self.snapshot_idx)
+        } else {
+            format!("--{}", self.snapshot_idx)
+        };
+
+        let meta = SnapshotMeta {
+            last_log_id: last_applied_id,
+            last_membership,
+            snapshot_id,
+        };
+
+        let snapshot = StoredSnapshot {
+            meta: meta.clone(),
+            data: kv_json.clone(),
+        };
+        self.set_current_snapshot_(snapshot)?;
+
+        Ok(Snapshot {
+            meta,
+            snapshot: Cursor::new(kv_json),
+        })
+    }
+}
+
+impl StateMachineStore {
+    async fn new(
+        db: Arc<DB>,
+        group_id: GroupId,
+        prefix: Vec<u8>,
+        split_tx: Option<mpsc::UnboundedSender<SplitEvent>>,
+    ) -> Result<StateMachineStore, io::Error> {
+        let mut sm = Self {
+            data: StateMachineData {
+                last_applied_id: None,
+                last_membership: Default::default(),
+                range: Arc::new(Default::default()),
+                kvs: Arc::new(Default::default()),
+                groups: BTreeSet::from([group_id]),
+                splits: Default::default(),
+            },
+            snapshot_idx: 0,
+            db,
+            group_id,
+            prefix,
+            watch: Arc::new(WatchHub::default()),
+            split_tx,
+        };
+
+        let snapshot = sm.get_current_snapshot_()?;
+        if let Some(snapshot) = snapshot {
+            sm.update_state_machine_(snapshot)?;
+        } else if let Some(seed) = sm.get_seed_()? {
+            // A group created by a split starts from the keys handed over by its parent.
+            *sm.data.range.write().unwrap() = seed.range;
+            *sm.data.kvs.write().unwrap() = seed.kvs;
+            sm.data.groups.extend(seed.groups);
+        }
+
+        Ok(sm)
+    }
+
+    fn update_state_machine_(&mut self, snapshot: StoredSnapshot) -> Result<(), io::Error> {
+        let payload: SnapshotPayload = serde_json::from_slice(&snapshot.data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        self.data.last_applied_id = snapshot.meta.last_log_id;
+        self.data.last_membership = snapshot.meta.last_membership.clone();
+        *self.data.range.write().unwrap() = payload.range;
+        let old = std::mem::replace(&mut *self.data.kvs.write().unwrap(), payload.kvs);
+        self.data.groups = payload.groups;
+        self.data.groups.insert(self.group_id);
+        let old_splits = std::mem::replace(&mut self.data.splits, payload.splits);
+
+        // Create the groups split off by the entries folded into the snapshot. The node
+        // ignores the ones it has created already.
+        if let (Some(tx), Some(log_id)) = (&self.split_tx, snapshot.meta.last_log_id) {
+            for (group_id, seed) in self.data.splits.iter().filter(|(id, _)| !old_splits.contains_key(*id)) {
+                let _ = tx.send(SplitEvent {
+                    group_id: *group_id,
+                    range: seed.range.clone(),
+                    kvs: seed.kvs.clone(),
+                    groups: seed.groups.clone(),
+                    membership: self.data.last_membership.membership().clone(),
+                    log_id,
+                });
+            }
+        }
+
+        // Tell the watchers about every key the snapshot changed, at the snapshot revision.
+        if let Some(log_id) = snapshot.meta.last_log_id {
+            let kvs = self.data.kvs.read().unwrap();
+            for (key, value) in kvs.iter() {
+                if old.get(key) != Some(value) {
+                    self.watch.publish(WatchEvent {
+                        kind: EventKind::Put,
+                        key: key.clone(),
+                        value: Some(value.clone()),
+                        revision: log_id.index(),
+                    });
+                }
+            }
+            for key in old.keys().filter(|k| !kvs.contains_key(*k)) {
+                self.watch.publish(WatchEvent {
+                    kind: EventKind::Delete,
+                    key: key.clone(),
+                    value: None,
+                    revision: log_id.index(),
+                });
+            }
+
+            // The logs folded into the snapshot are never applied here, so the events
+            // before it can not be replayed to a resuming watcher.
+            self.watch.compact(log_id.index());
+        }
+
+        Ok(())
+    }
+
+    fn get_current_snapshot_(&self) -> Result<Option<StoredSnapshot>, io::Error> {
+        let bytes = self.db.get_cf(self.store(), meta_key(&self.prefix, "snapshot")).map_err(io::Error::other)?;
+        match bytes {
+            None => Ok(None),
+            Some(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+
+    fn get_seed_(&self) -> Result<Option<SnapshotPayload>, io::Error> {
+        let bytes = self.db.get_cf(self.store(), meta_key(&self.prefix, "seed")).map_err(io::Error::other)?;
+        bytes
+            .map(|b| serde_json::from_slice(&b).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+            .transpose()
+    }
+
+    fn set_current_snapshot_(&self, snapshot: StoredSnapshot) -> Result<(), io::Error> {
+        let bytes = serde_json::to_vec(&snapshot).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.db.put_cf(self.store(), meta_key(&self.prefix, "snapshot"), bytes).map_err(io::Error::other)?;
+        self.db.flush_wal(true).map_err(io::Error::other)?;
+        Ok(())
+    }
+
+    fn store(&self) -> &ColumnFamily {
+        self.db.cf_handle("store").unwrap()
+    }
+
+    /// Move the keys from `key` on out of this group. Returns `None` if `key` does not
+    /// split the range into two non-empty halves.
+    fn split_off(&self, key: &str) -> Option<(KeyRange, BTreeMap<String, String>)> {
+        let mut range = self.data.range.write().unwrap();
+        if !range.contains(key) || key == range.start {
+            return None;
+        }
+
+        let right = KeyRange {
+            start: key.to_string(),
+            end: range.end.take(),
+        };
+        range.end = Some(key.to_string());
+
+        let kvs = self.data.kvs.write().unwrap().split_off(key);
+        Some((right, kvs))
+    }
+
+    /// Apply `Request::Split`: move the keys from `key` on to the new group `group_id` and
+    /// return the event that makes the node create it. Returns `None` if the split is refused.
+    fn apply_split(&mut self, key: &str, group_id: GroupId, log_id: LogId<TypeConfig>) -> Option<SplitEvent> {
+        if self.split_tx.is_none() {
+            tracing::warn!("split is only supported by a multi-raft node, ignored");
+            return None;
+        }
+
+        // Every replica decides the same here, a proposal checked only by the node that
+        // received it could still race with another split into the same group.
+        if group_id == self.group_id || self.data.groups.contains(&group_id) {
+            tracing::warn!(group_id, "split into a group id that is already taken, ignored");
+            return None;
+        }
+
+        let (range, kvs) = self.split_off(key)?;
+        self.data.groups.insert(group_id);
+
+        let seed = SnapshotPayload {
+            range,
+            kvs,
+            groups: self.data.groups.clone(),
+            splits: Default::default(),
+        };
+        self.data.splits.insert(group_id, seed.clone());
+
+        Some(SplitEvent {
+            group_id,
+            range: seed.range,
+            kvs: seed.kvs,
+            groups: seed.groups,
+            membership: self.data.last_membership.membership().clone(),
+            log_id,
+        })
+    }
+}
+
+impl RaftStateMachine<TypeConfig> for StateMachineStore {
+    type SnapshotBuilder = Self;
+
+    async fn applied_state(&mut self) -> Result<(Option<LogId<TypeConfig>>, StoredMembership<TypeConfig>), io::Error> {
+        Ok((self.data.last_applied_id, self.data.last_membership.clone()))
+    }
+
+    async fn apply<Strm>(&mut self, mut entries: Strm) -> Result<(), io::Error>
+    where Strm: Stream<Item = Result<EntryResponder<TypeConfig>, io::Error>> + Unpin + OptionalSend {
+        while let Some((entry, responder)) = entries.try_next().await? {
+            tracing::debug!(%entry.log_id, "replicate to sm");
+
+            self.data.last_applied_id = Some(entry.log_id);
+
+            let response = match entry.payload {
+                EntryPayload::Blank => Response::default(),
+                EntryPayload::Normal(Request::Set { ref key, .. } | Request::Delete { ref key })
+                    if !self.data.range.read().unwrap().contains(key) =>
+                {
+                    // Proposed before a split moved the key to another group, the caller retries
+                    // it with the group owning the key now.
+                    tracing::debug!(key = display(key), "key is out of the range of this group, rejected");
+                    Response {
+                        value: None,
+                        wrong_group: true,
+                    }
+                }
+                EntryPayload::Normal(ref req) => match req {
+                    Request::Set { key, value } => {
+                        let prev = self.data.kvs.write().unwrap().insert(key.clone(), value.clone());
+                        if prev.as_ref() != Some(value) {
+                            self.watch.publish(WatchEvent {
+                                kind: EventKind::Put,
+                                key: key.clone(),
+                                value: Some(value.clone()),
+                                revision: entry.log_id.index(),
+                            });
+                        }
+                        Response {
+                            value: Some(value.clone()),
+                            wrong_group: false,
+                        }
+                    }
+                    Request::Delete { key } => {
+                        let prev = self.data.kvs.write().unwrap().remove(key);
+                        if prev.is_some() {
+                            self.watch.publish(WatchEvent {
+                                kind: EventKind::Delete,
+                                key: key.clone(),
+                                value: None,
+                                revision: entry.log_id.index(),
+                            });
+                        }
+                        Response {
+                            value: prev,
+                            wrong_group: false,
+                        }
+                    }
+                    Request::Split { key, group_id } => {
+                        if let Some(event) = self.apply_split(key, *group_id, entry.log_id) {
+                            if let Some(tx) = &self.split_tx {
+                                let _ = tx.send(event);
+                            }
+                        }
+                        Response::default()
+                    }
+                },
+                EntryPayload::Membership(ref mem) => {
+                    self.data.last_membership = StoredMembership::new(Some(entry.log_id), mem.clone());
+                    Response::default()
+                }
+            };
+
+            if let Some(responder) = responder {
+                responder.send(response);
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_snapshot_builder(&mut self) -> Self::SnapshotBuilder {
+        self.snapshot_idx += 1;
+
+        // Take a copy of the kvs, the live map keeps moving while the snapshot is built.
+        let data = StateMachineData {
+            last_applied_id: self.data.last_applied_id,
+            last_membership: self.data.last_membership.clone(),
+            range: Arc::new(RwLock::new(self.data.range.read().unwrap().clone())),
+            kvs: Arc::new(RwLock::new(self.data.kvs.read().unwrap().clone())),
+            groups: self.data.groups.clone(),
+            splits: self.data.splits.clone(),
+        };
+
+        StateMachineStore {
+            data,
+            snapshot_idx: self.snapshot_idx,
+            db: self.db.clone(),
+            group_id: self.group_id,
+            prefix: self.prefix.clone(),
+            watch: self.watch.clone(),
+            split_tx: None,
+        }
+    }
+
+    async fn begin_receiving_snapshot(&mut self) -> Result<SnapshotData, io::Error> {
+        Ok(Cursor::new(Vec::new()))
+    }
+
+    async fn install_snapshot(&mut self, meta: &SnapshotMeta<TypeConfig>, snapshot: SnapshotData) -> Result<(), io::Error> {
+        let new_snapshot = StoredSnapshot {
+            meta: meta.clone(),
+            data: snapshot.into_inner(),
+        };
+
+        self.update_state_machine_(new_snapshot.clone())?;
+        self.set_current_snapshot_(new_snapshot)?;
+
+        Ok(())
+    }
+
+    async fn get_current_snapshot(&mut self) -> Result<Option<Snapshot<TypeConfig>>, io::Error> {
+        let snapshot = self.get_current_snapshot_()?;
+        Ok(snapshot.map(|s| Snapshot {
+            meta: s.meta.clone(),
+            snapshot: Cursor::new(s.data.clone()),
+        }))
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct LogStore {
     db: Arc<DB>,
+
+    /// See `StateMachineStore::prefix`.
+    prefix: Vec<u8>,
+}
+
+/// Log index is encoded big-endian so that rocksdb iterates logs in index order.
+fn id_to_bin(prefix: &[u8], id: u64) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(prefix.len() + 8);
+    buf.extend_from_slice(prefix);
+    buf.write_u64::<BigEndian>(id).unwrap();
+    buf
+}
+
+fn bin_to_id(prefix: &[u8], buf: &[u8]) -> u64 {
+    (&buf[prefix.len()..prefix.len() + 8]).read_u64::<BigEndian>().unwrap()
+}
+
+fn meta_key(prefix: &[u8], name: &str) -> Vec<u8> {
+    let mut key = prefix.to_vec();
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+/// The key prefix of a raft group in a multi-raft node.
+pub(crate) fn group_prefix(group_id: GroupId) -> Vec<u8> {
+    group_id.to_be_bytes().to_vec()
+}
+
+impl LogStore {
+    fn store(&self) -> &ColumnFamily {
+        self.db.cf_handle("store").unwrap()
+    }
+
+    fn logs(&self) -> &ColumnFamily {
+        self.db.cf_handle("logs").unwrap()
+    }
+
+    fn flush(&self) -> Result<(), io::Error> {
+        self.db.flush_wal(true).map_err(io::Error::other)
+    }
+
+    fn get_meta<T: for<'de> Deserialize<'de>>(&self, key: &str) -> Result<Option<T>, io::Error> {
+        let bytes = self.db.get_cf(self.store(), meta_key(&self.prefix, key)).map_err(io::Error::other)?;
+        match bytes {
+            None => Ok(None),
+            Some(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+
+    fn put_meta<T: Serialize>(&self, key: &str, value: &T) -> Result<(), io::Error> {
+        let bytes = serde_json::to_vec(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.db.put_cf(self.store(), meta_key(&self.prefix, key), bytes).map_err(io::Error::other)?;
+        self.flush()
+    }
+}
+
+impl RaftLogReader<TypeConfig> for LogStore {
+    async fn try_get_log_entries<RB: RangeBounds<u64> + Clone + Debug + OptionalSend>(
+        &mut self,
+        range: RB,
+    ) -> Result<Vec<Entry<TypeConfig>>, io::Error> {
+        let start = match range.start_bound() {
+            std::ops::Bound::Included(x) => id_to_bin(&self.prefix, *x),
+            std::ops::Bound::Excluded(x) => id_to_bin(&self.prefix, *x + 1),
+            std::ops::Bound::Unbounded => id_to_bin(&self.prefix, 0),
+        };
+
+        let mut entries = vec![];
+        for kv in self.db.iterator_cf(self.logs(), rocksdb::IteratorMode::From(&start, Direction::Forward)) {
+            let (id, val) = kv.map_err(io::Error::other)?;
+            if !id.starts_with(&self.prefix) {
+                break;
+            }
+            let id = bin_to_id(&self.prefix, &id);
+            if !range.contains(&id) {
+                break;
+            }
+
+            let entry: Entry<TypeConfig> =
+                serde_json::from_slice(&val).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            assert_eq!(id, entry.index());
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+
+    async fn read_vote(&mut self) -> Result<Option<Vote<TypeConfig>>, io::Error> {
+        self.get_meta("vote")
+    }
 }
 
+impl RaftLogStorage<TypeConfig> for LogStore {
+    type LogReader = Self;
+
+    async fn get_log_state(&mut self) -> Result<LogState<TypeConfig>, io::Error> {
+        let end = id_to_bin(&self.prefix, u64::MAX);
+        let last = self
+            .db
+            .iterator_cf(self.logs(), rocksdb::IteratorMode::From(&end, Direction::Reverse))
+            .next()
+            .transpose()
+            .map_err(io::Error::other)?
+            .filter(|(id, _)| id.starts_with(&self.prefix));
+
+        let last_log_id = match last {
+            None => None,
+            Some((_, val)) => {
+                let entry: Entry<TypeConfig> =
+                    serde_json::from_slice(&val).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                Some(entry.log_id())
+            }
+        };
+
+        let last_purged_log_id: Option<LogId<TypeConfig>> = self.get_meta("last_purged_log_id")?;
+
+        let last_log_id = match last_log_id {
+            None => last_purged_log_id,
+            Some(x) => Some(x),
+        };
+
+        Ok(LogState {
+            last_purged_log_id,
+            last_log_id,
+        })
+    }
+
+    async fn get_log_reader(&mut self) -> Self::LogReader {
+        self.clone()
+    }
+
+    async fn save_vote(&mut self, vote: &Vote<TypeConfig>) -> Result<(), io::Error> {
+        self.put_meta("vote", vote)
+    }
+
+    async fn save_committed(&mut self, committed: Option<LogId<TypeConfig>>) -> Result<(), io::Error> {
+        self.put_meta("committed", &committed)
+    }
+
+    async fn read_committed(&mut self) -> Result<Option<LogId<TypeConfig>>, io::Error> {
+        Ok(self.get_meta::<Option<LogId<TypeConfig>>>("committed")?.flatten())
+    }
+
+    async fn append<I>(&mut self, entries: I, callback: IOFlushed<TypeConfig>) -> Result<(), io::Error>
+    where I: IntoIterator<Item = Entry<TypeConfig>> + OptionalSend {
+        for entry in entries {
+            let id = id_to_bin(&self.prefix, entry.index());
+            let bytes = serde_json::to_vec(&entry).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            self.db.put_cf(self.logs(), id, bytes).map_err(io::Error::other)?;
+        }
+
+        // The entries are durable once the WAL is synced, so report completion right away.
+        callback.io_completed(self.flush());
+        Ok(())
+    }
+
+    async fn truncate_after(&mut self, last_log_id: Option<LogId<TypeConfig>>) -> Result<(), io::Error> {
+        tracing::debug!("truncate_after: ({:?}, +oo)", last_log_id);
+
+        let from = match last_log_id {
+            Some(log_id) => log_id.index() + 1,
+            None => 0,
+        };
+        self.db
+            .delete_range_cf(self.logs(), id_to_bin(&self.prefix, from), id_to_bin(&self.prefix, u64::MAX))
+            .map_err(io::Error::other)?;
+        self.flush()
+    }
+
+    async fn purge(&mut self, log_id: LogId<TypeConfig>) -> Result<(), io::Error> {
+        tracing::debug!("purge_log_upto: {:?}", log_id);
+
+        // Record the purged id first, so that a crash in between never leaves a gap
+        // between `last_purged_log_id` and the first log on disk.
+        self.put_meta("last_purged_log_id", &log_id)?;
+        self.db
+            .delete_range_cf(
+                self.logs(),
+                id_to_bin(&self.prefix, 0),
+                id_to_bin(&self.prefix, log_id.index() + 1),
+            )
+            .map_err(io::Error::other)?;
+        self.flush()
+    }
+}
+
+pub(crate) fn open_db<P: AsRef<Path>>(db_path: P) -> Arc<DB> {
+    let mut db_opts = Options::default();
+    db_opts.create_missing_column_families(true);
+    db_opts.create_if_missing(true);
+
+    let store = ColumnFamilyDescriptor::new("store", Options::default());
+    let logs = ColumnFamilyDescriptor::new("logs", Options::default());
+
+    let db = DB::open_cf_descriptors(&db_opts, db_path, vec![store, logs]).unwrap();
+    Arc::new(db)
+}
+
+/// Store the initial state of a group created by a split. It is loaded when the group's state
+/// machine is opened and has no snapshot yet.
+pub(crate) fn save_seed(db: &DB, event: &SplitEvent) -> Result<(), io::Error> {
+    let seed = SnapshotPayload {
+        range: event.range.clone(),
+        kvs: event.kvs.clone(),
+        groups: event.groups.clone(),
+        splits: Default::default(),
+    };
+    let bytes = serde_json::to_vec(&seed).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let store = db.cf_handle("store").unwrap();
+    db.put_cf(store, meta_key(&group_prefix(event.group_id), "seed"), bytes).map_err(io::Error::other)?;
+    db.flush_wal(true).map_err(io::Error::other)
+}
+
+pub(crate) async fn new_group_storage(
+    db: Arc<DB>,
+    group_id: GroupId,
+    split_tx: Option<mpsc::UnboundedSender<SplitEvent>>,
+) -> (LogStore, StateMachineStore) {
+    open_storage(db, group_id, group_prefix(group_id), split_tx).await
+}
+
+async fn open_storage(
+    db: Arc<DB>,
+    group_id: GroupId,
+    prefix: Vec<u8>,
+    split_tx: Option<mpsc::UnboundedSender<SplitEvent>>,
+) -> (LogStore, StateMachineStore) {
+    let log_store = LogStore {
+        db: db.clone(),
+        prefix: prefix.clone(),
+    };
+    let sm_store = StateMachineStore::new(db, group_id, prefix, split_tx).await.unwrap();
+
+    (log_store, sm_store)
+}
+
+pub async fn new_storage<P: AsRef<Path>>(db_path: P) -> (LogStore, StateMachineStore) {
+    open_storage(open_db(db_path), ROOT_GROUP, vec![], None).await
+}
+
+#[cfg(test)]
+mod tests {
+    use openraft::testing::log_id;
+    use super::*;
+
+    async fn group_store(db: &Arc<DB>, group_id: GroupId) -> (StateMachineStore, mpsc::UnboundedReceiver<SplitEvent>) {
+        let (split_tx, split_rx) = mpsc::unbounded_channel();
+        let (_, sm) = new_group_storage(db.clone(), group_id, Some(split_tx)).await;
+        (sm, split_rx)
+    }
+
+    fn fill(sm: &StateMachineStore, keys: &[&str]) {
+        let mut kvs = sm.data.kvs.write().unwrap();
+        for key in keys {
+            kvs.insert(key.to_string(), key.to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_split_refuses_taken_group_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = open_db(dir.path());
+        let (mut sm, _rx) = group_store(&db, ROOT_GROUP).await;
+        fill(&sm, &["a", "f", "m", "t"]);
+
+        assert!(sm.apply_split("m", ROOT_GROUP, log_id::<TypeConfig>(1, 1, 1)).is_none());
+
+        let event = sm.apply_split("m", 2, log_id::<TypeConfig>(1, 1, 2)).unwrap();
+        assert_eq!(event.range, KeyRange {
+            start: "m".to_string(),
+            end: None
+        });
+        assert_eq!(event.kvs.keys().collect::<Vec<_>>(), ["m", "t"]);
+        assert_eq!(event.groups, BTreeSet::from([ROOT_GROUP, 2]));
+
+        // A second split into group 2 leaves the keys where they are.
+        assert!(sm.apply_split("f", 2, log_id::<TypeConfig>(1, 1, 3)).is_none());
+        assert_eq!(sm.data.kvs.read().unwrap().keys().collect::<Vec<_>>(), ["a", "f"]);
+
+        // The new group inherits the taken ids, so it can not split back into its parent.
+        save_seed(&db, &event).unwrap();
+        let (mut child, _rx) = group_store(&db, 2).await;
+        assert!(child.apply_split("t", ROOT_GROUP, log_id::<TypeConfig>(1, 1, 1)).is_none());
+        assert!(child.apply_split("t", 3, log_id::<TypeConfig>(1, 1, 2)).is_some());
+
+        // A single group ignores splits.
+        let (_, mut single) = open_storage(db.clone(), ROOT_GROUP, b"single".to_vec(), None).await;
+        fill(&single, &["a", "m"]);
+        assert!(single.apply_split("m", 2, log_id::<TypeConfig>(1, 1, 1)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_install_snapshot_emits_split() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut leader, _rx) = group_store(&open_db(dir.path().join("leader")), ROOT_GROUP).await;
+        fill(&leader, &["a", "m", "t"]);
+        leader.apply_split("m", 2, log_id::<TypeConfig>(1, 1, 2)).unwrap();
+        leader.data.last_applied_id = Some(log_id::<TypeConfig>(1, 1, 2));
+        let snapshot = leader.get_snapshot_builder().await.build_snapshot().await.unwrap();
 
+        // A replica that catches up from the snapshot never applies the split entry.
+        let (mut follower, mut rx) = group_store(&open_db(dir.path().join("follower")), ROOT_GROUP).await;
+        follower.install_snapshot(&snapshot.meta, snapshot.snapshot).await.unwrap();
 
-pub(crate) async fn new_storage<P: AsRef<Path>>(pd_path: P) -> (LogStore, StateMachineStore) {
+        let event = rx.try_recv().unwrap();
+        assert_eq!(event.group_id, 2);
+        assert_eq!(event.range.start, "m");
+        assert_eq!(event.kvs.keys().collect::<Vec<_>>(), ["m", "t"]);
+        assert_eq!(event.groups, BTreeSet::from([ROOT_GROUP, 2]));
+        assert_eq!(event.log_id, log_id::<TypeConfig>(1, 1, 2));
+        assert!(rx.try_recv().is_err());
 
-    todo!()
+        assert_eq!(follower.data.kvs.read().unwrap().keys().collect::<Vec<_>>(), ["a"]);
+        assert!(follower.apply_split("b", 2, log_id::<TypeConfig>(1, 1, 3)).is_none());
+    }
 }
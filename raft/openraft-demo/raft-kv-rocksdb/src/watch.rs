@@ -0,0 +1,338 @@
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// How many applied events are kept in memory for watchers resuming from an older revision.
+pub const DEFAULT_HISTORY_SIZE: usize = 4096;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Put,
+    Delete,
+}
+
+/// A change of one key, emitted when the state machine applies it.
+///
+/// `revision` is the index of the raft log that made the change, it only grows and is the
+/// same on every node, so a client can resume on any node from the last revision it has seen.
+/// A `Set` that leaves the value as it was is not a change and emits no event.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WatchEvent {
+    pub kind: EventKind,
+    pub key: String,
+    pub value: Option<String>,
+    pub revision: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WatchRequest {
+    pub key: String,
+
+    /// Watch every key starting with `key` instead of `key` alone.
+    #[serde(default)]
+    pub prefix: bool,
+
+    /// Replay the events from this revision on, inclusive, before streaming new ones.
+    /// Without it only the changes applied after the watch is created are sent.
+    /// Revisions start at 1, `0` is taken as `1`: replay all the history still kept.
+    #[serde(default)]
+    pub start_revision: Option<u64>,
+}
+
+impl WatchRequest {
+    pub fn matches(&self, key: &str) -> bool {
+        if self.prefix {
+            key.starts_with(&self.key)
+        } else {
+            key == self.key
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WatchError {
+    /// The events at `start_revision` are no longer kept, the oldest revision that can
+    /// still be resumed from is `compact_revision + 1`.
+    Compacted { compact_revision: u64 },
+
+    /// The watcher fell too far behind the applied events and has been dropped. It can
+    /// resume from `last_revision + 1`.
+    Lagged { last_revision: u64 },
+
+    Closed,
+}
+
+impl Display for WatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchError::Compacted { compact_revision } => {
+                write!(f, "required revision has been compacted, compact revision: {}", compact_revision)
+            }
+            WatchError::Lagged { last_revision } => {
+                write!(f, "watcher lagged behind, last revision sent: {}", last_revision)
+            }
+            WatchError::Closed => write!(f, "watch hub is closed"),
+        }
+    }
+}
+
+impl std::error::Error for WatchError {}
+
+struct History {
+    events: VecDeque<WatchEvent>,
+
+    /// Every event with a revision up to and including this one has been dropped.
+    compact_revision: u64,
+
+    capacity: usize,
+}
+
+/// Fans the applied changes out to the watchers and keeps a bounded history of them.
+///
+/// The history lock is held while publishing and while subscribing, so a new watcher
+/// sees every event exactly once: either in its backlog or from the broadcast channel.
+pub struct WatchHub {
+    tx: broadcast::Sender<WatchEvent>,
+    history: Mutex<History>,
+}
+
+impl Debug for WatchHub {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchHub")
+            .field("watchers", &self.tx.receiver_count())
+            .field("compact_revision", &self.compact_revision())
+            .finish()
+    }
+}
+
+impl Default for WatchHub {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_SIZE)
+    }
+}
+
+impl WatchHub {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        Self {
+            tx,
+            history: Mutex::new(History {
+                events: VecDeque::with_capacity(capacity),
+                compact_revision: 0,
+                capacity,
+            }),
+        }
+    }
+
+    pub(crate) fn publish(&self, event: WatchEvent) {
+        let mut history = self.history.lock().unwrap();
+
+        if history.events.len() == history.capacity {
+            if let Some(dropped) = history.events.pop_front() {
+                history.compact_revision = dropped.revision;
+            }
+        }
+        history.events.push_back(event.clone());
+
+        // No receiver is not an error, nobody is watching yet.
+        let _ = self.tx.send(event);
+    }
+
+    /// Forget the history up to `revision`, e.g. when the state machine is replaced by a
+    /// snapshot and the logs in between are never applied locally.
+    pub(crate) fn compact(&self, revision: u64) {
+        let mut history = self.history.lock().unwrap();
+
+        while history.events.front().is_some_and(|e| e.revision <= revision) {
+            history.events.pop_front();
+        }
+        history.compact_revision = history.compact_revision.max(revision);
+    }
+
+    pub fn compact_revision(&self) -> u64 {
+        self.history.lock().unwrap().compact_revision
+    }
+
+    pub fn subscribe(&self, mut req: WatchRequest) -> Result<Watcher, WatchError> {
+        req.start_revision = req.start_revision.map(|r| r.max(1));
+        let history = self.history.lock().unwrap();
+
+        let mut backlog = VecDeque::new();
+        if let Some(start) = req.start_revision {
+            if start <= history.compact_revision {
+                return Err(WatchError::Compacted {
+                    compact_revision: history.compact_revision,
+                });
+            }
+            backlog.extend(
+                history.events.iter().filter(|e| e.revision >= start && req.matches(&e.key)).cloned(),
+            );
+        }
+
+        let rx = self.tx.subscribe();
+        let last_revision = req.start_revision.map(|r| r - 1).unwrap_or(0);
+
+        Ok(Watcher {
+            req,
+            backlog,
+            rx,
+            last_revision,
+        })
+    }
+}
+
+pub struct Watcher {
+    req: WatchRequest,
+    backlog: VecDeque<WatchEvent>,
+    rx: broadcast::Receiver<WatchEvent>,
+    last_revision: u64,
+}
+
+impl Watcher {
+    /// Wait for the next event matching the watched key or prefix.
+    pub async fn next(&mut self) -> Result<WatchEvent, WatchError> {
+        if let Some(event) = self.backlog.pop_front() {
+            self.last_revision = event.revision;
+            return Ok(event);
+        }
+
+        loop {
+            match self.rx.recv().await {
+                Ok(event) => {
+                    if self.req.matches(&event.key) {
+                        self.last_revision = event.revision;
+                        return Ok(event);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    return Err(WatchError::Lagged {
+                        last_revision: self.last_revision,
+                    });
+                }
+                Err(broadcast::error::RecvError::Closed) => return Err(WatchError::Closed),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(key: &str, value: &str, revision: u64) -> WatchEvent {
+        WatchEvent {
+            kind: EventKind::Put,
+            key: key.to_string(),
+            value: Some(value.to_string()),
+            revision,
+        }
+    }
+
+    fn watch(key: &str, prefix: bool, start_revision: Option<u64>) -> WatchRequest {
+        WatchRequest {
+            key: key.to_string(),
+            prefix,
+            start_revision,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_history() {
+        let hub = WatchHub::new(16);
+        for rev in 1..=3 {
+            hub.publish(put("k", &rev.to_string(), rev));
+        }
+
+        // Replays from the start revision on, then goes on with the live events.
+        let mut watcher = hub.subscribe(watch("k", false, Some(2))).unwrap();
+        hub.publish(put("k", "4", 4));
+        for rev in 2..=4 {
+            assert_eq!(watcher.next().await.unwrap(), put("k", &rev.to_string(), rev));
+        }
+
+        // Revision 0 replays everything kept, there is no event at 0.
+        let mut watcher = hub.subscribe(watch("k", false, Some(0))).unwrap();
+        assert_eq!(watcher.next().await.unwrap().revision, 1);
+
+        // Without a start revision only new events are sent.
+        let mut watcher = hub.subscribe(watch("k", false, None)).unwrap();
+        hub.publish(put("k", "5", 5));
+        assert_eq!(watcher.next().await.unwrap().revision, 5);
+    }
+
+    #[tokio::test]
+    async fn test_compaction() {
+        let hub = WatchHub::new(2);
+        for rev in 1..=3 {
+            hub.publish(put("k", &rev.to_string(), rev));
+        }
+        // The history is full, the oldest event has been dropped.
+        assert_eq!(hub.compact_revision(), 1);
+        assert_eq!(
+            hub.subscribe(watch("k", false, Some(1))).err(),
+            Some(WatchError::Compacted { compact_revision: 1 })
+        );
+        assert_eq!(
+            hub.subscribe(watch("k", false, Some(0))).err(),
+            Some(WatchError::Compacted { compact_revision: 1 })
+        );
+        let mut watcher = hub.subscribe(watch("k", false, Some(2))).unwrap();
+        assert_eq!(watcher.next().await.unwrap().revision, 2);
+
+        // A snapshot installed at 10 drops the events before it, even if they are still kept.
+        hub.compact(10);
+        assert_eq!(hub.compact_revision(), 10);
+        assert_eq!(
+            hub.subscribe(watch("k", false, Some(3))).err(),
+            Some(WatchError::Compacted { compact_revision: 10 })
+        );
+        hub.publish(put("k", "11", 11));
+        let mut watcher = hub.subscribe(watch("k", false, Some(11))).unwrap();
+        assert_eq!(watcher.next().await.unwrap().revision, 11);
+
+        // Compacting to an older revision does not move it back.
+        hub.compact(5);
+        assert_eq!(hub.compact_revision(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_prefix_filter() {
+        let hub = WatchHub::new(16);
+        hub.publish(put("app/a", "1", 1));
+        hub.publish(put("other", "2", 2));
+        hub.publish(put("app/b", "3", 3));
+
+        let mut prefix = hub.subscribe(watch("app/", true, Some(1))).unwrap();
+        let mut key = hub.subscribe(watch("app/b", false, Some(1))).unwrap();
+        hub.publish(put("app", "4", 4));
+        hub.publish(WatchEvent {
+            kind: EventKind::Delete,
+            key: "app/a".to_string(),
+            value: None,
+            revision: 5,
+        });
+
+        let revisions = [
+            prefix.next().await.unwrap().revision,
+            prefix.next().await.unwrap().revision,
+            prefix.next().await.unwrap().revision,
+        ];
+        assert_eq!(revisions, [1, 3, 5]);
+        assert_eq!(key.next().await.unwrap().revision, 3);
+    }
+
+    #[tokio::test]
+    async fn test_lagged() {
+        let hub = WatchHub::new(2);
+        let mut watcher = hub.subscribe(watch("k", false, None)).unwrap();
+        hub.publish(put("k", "1", 1));
+        assert_eq!(watcher.next().await.unwrap().revision, 1);
+        for rev in 2..=4 {
+            hub.publish(put("k", &rev.to_string(), rev));
+        }
+        assert_eq!(watcher.next().await.err(), Some(WatchError::Lagged { last_revision: 1 }));
+    }
+}
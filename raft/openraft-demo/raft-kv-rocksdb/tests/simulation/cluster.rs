@@ -0,0 +1,307 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use openraft::async_runtime::WatchReceiver;
+use openraft::{Config, ReadPolicy, ServerState};
+use raft_kv_rocksdb::app::App;
+use raft_kv_rocksdb::store::{new_storage, Request};
+use raft_kv_rocksdb::watch::WatchHub;
+use raft_kv_rocksdb::{typ, Node, NodeId};
+use tempfile::TempDir;
+use crate::linearizability::{History, Op, Outcome};
+use crate::network::{Crashed, Router, SimNetwork};
+
+/// How long a client waits for a write or a read before giving up on it.
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub struct SimNode {
+    pub raft: typ::Raft,
+    pub kvs: Arc<RwLock<BTreeMap<String, String>>>,
+    pub watch: Arc<WatchHub>,
+    crashed: Crashed,
+}
+
+/// N raft-kv-rocksdb nodes in one process, connected by a [`Router`].
+///
+/// Each node keeps its rocksdb in its own directory of a temp dir, so that a crashed node can
+/// be restarted from what it has persisted. Every restart opens a copy taken at the crash, in
+/// a directory of its own.
+pub struct SimCluster {
+    pub config: Arc<Config>,
+    pub router: Router,
+    pub history: Arc<History>,
+    dir: TempDir,
+    nodes: BTreeMap<NodeId, SimNode>,
+
+    /// How many times each node has crashed, it names the directory the node runs from.
+    crashes: BTreeMap<NodeId, u32>,
+}
+
+impl SimCluster {
+    pub async fn new(node_ids: impl IntoIterator<Item = NodeId>, seed: u64, config: Config) -> Self {
+        let mut cluster = Self {
+            config: Arc::new(config.validate().unwrap()),
+            router: Router::new(seed),
+            history: Arc::new(History::default()),
+            dir: tempfile::tempdir().unwrap(),
+            nodes: BTreeMap::new(),
+            crashes: BTreeMap::new(),
+        };
+        for id in node_ids {
+            cluster.start(id).await;
+        }
+        cluster
+    }
+
+    pub fn default_config() -> Config {
+        Config {
+            heartbeat_interval: 50,
+            election_timeout_min: 150,
+            election_timeout_max: 300,
+            cluster_name: "raft-kv-sim".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Start a node, or restart a crashed one from its storage.
+    pub async fn start(&mut self, id: NodeId) {
+        assert!(!self.nodes.contains_key(&id), "node {} is already running", id);
+
+        let (log_store, state_machine_store) = new_storage(self.node_dir(id)).await;
+        let kvs = state_machine_store.data.kvs.clone();
+        let watch = state_machine_store.watch.clone();
+
+        let crashed = Crashed::default();
+        let network = SimNetwork {
+            router: self.router.clone(),
+            source: id,
+            crashed: crashed.clone(),
+        };
+        let raft = openraft::Raft::new(id, self.config.clone(), network, log_store, state_machine_store)
+            .await
+            .unwrap();
+
+        self.router.register(id, raft.clone(), crashed.clone());
+        self.nodes.insert(id, SimNode {
+            raft,
+            kvs,
+            watch,
+            crashed,
+        });
+    }
+
+    fn node_dir(&self, id: NodeId) -> PathBuf {
+        let crashes = self.crashes.get(&id).copied().unwrap_or(0);
+        self.dir.path().join(format!("node-{}-{}", id, crashes))
+    }
+
+    /// Crash a node abruptly, [`SimCluster::start`] restarts it from what it had written to
+    /// its storage at that moment.
+    ///
+    /// The raft is not shut down: nothing is flushed and the writes in flight do not finish.
+    /// It is cut off from the network and abandoned, still holding the lock of its rocksdb,
+    /// so the files as they are now are copied aside for the restart to open. The test runs on
+    /// a single thread, no task writes to them while they are copied.
+    pub async fn crash(&mut self, id: NodeId) {
+        let node = self.nodes.remove(&id).unwrap();
+        node.crashed.store(true, Ordering::Relaxed);
+        self.router.unregister(id);
+
+        let from = self.node_dir(id);
+        *self.crashes.entry(id).or_default() += 1;
+        copy_dir(&from, &self.node_dir(id));
+    }
+
+    pub fn node(&self, id: NodeId) -> &SimNode {
+        &self.nodes[&id]
+    }
+
+    /// The [`App`] of a running node, for calling the same methods as the HTTP API does.
+    pub fn app(&self, id: NodeId) -> App {
+        let node = self.node(id);
+        App {
+            id,
+            api_addr: String::new(),
+            rpc_addr: String::new(),
+            raft: node.raft.clone(),
+            key_values: node.kvs.clone(),
+            watch: node.watch.clone(),
+            config: self.config.clone(),
+        }
+    }
+
+    pub fn running(&self) -> Vec<NodeId> {
+        self.nodes.keys().copied().collect()
+    }
+
+    /// Initialize the cluster with `voters`, which must all be running.
+    pub async fn initialize(&self, voters: &BTreeSet<NodeId>) {
+        let members = voters.iter().map(|id| (*id, Node::default())).collect::<BTreeMap<_, _>>();
+        self.node(*voters.first().unwrap()).raft.initialize(members).await.unwrap();
+        self.wait_for_leader(None).await;
+    }
+
+    /// Wait until one of the running nodes, other than `not`, is leader, and return it.
+    pub async fn wait_for_leader(&self, not: Option<NodeId>) -> NodeId {
+        for _ in 0..200 {
+            for (id, node) in &self.nodes {
+                if Some(*id) == not {
+                    continue;
+                }
+                if node.raft.metrics().borrow_watched().state == ServerState::Leader {
+                    return *id;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("no leader elected");
+    }
+
+    /// Wait until every running node has applied up to `index`.
+    pub async fn wait_for_applied(&self, index: u64) {
+        for node in self.nodes.values() {
+            node.raft
+                .wait(Some(Duration::from_secs(10)))
+                .applied_index_at_least(Some(index), "applied")
+                .await
+                .unwrap();
+        }
+    }
+
+    /// The last log index applied by the leader.
+    pub async fn leader_applied(&self) -> u64 {
+        let leader = self.wait_for_leader(None).await;
+        self.node(leader).raft.metrics().borrow_watched().last_applied.map(|l| l.index).unwrap_or(0)
+    }
+
+    pub fn client(&self, client_id: u64) -> SimClient {
+        self.client_of(client_id, &self.running().into_iter().collect())
+    }
+
+    /// A client that can only reach `nodes`, e.g. one on a side of a partition.
+    pub fn client_of(&self, client_id: u64, nodes: &BTreeSet<NodeId>) -> SimClient {
+        let reachable = self.nodes.iter().filter(|(id, _)| nodes.contains(id));
+        SimClient {
+            id: client_id,
+            rafts: reachable.clone().map(|(id, n)| (*id, n.raft.clone())).collect(),
+            kvs: reachable.map(|(id, n)| (*id, n.kvs.clone())).collect(),
+            history: self.history.clone(),
+            leader: None,
+        }
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    std::fs::create_dir_all(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            std::fs::copy(entry.path(), target).unwrap();
+        }
+    }
+}
+
+/// A client of the cluster, recording every operation in the history of the cluster.
+///
+/// It talks to the node it believes is the leader and follows `ForwardToLeader`. A client
+/// created before a node crashes keeps its handle, calls to it fail as a real client's would.
+pub struct SimClient {
+    pub id: u64,
+    rafts: BTreeMap<NodeId, typ::Raft>,
+    kvs: BTreeMap<NodeId, Arc<RwLock<BTreeMap<String, String>>>>,
+    history: Arc<History>,
+    leader: Option<NodeId>,
+}
+
+impl SimClient {
+    fn target(&self) -> NodeId {
+        self.leader.unwrap_or_else(|| *self.rafts.keys().next().unwrap())
+    }
+
+    fn follow(&mut self, e: &typ::ForwardToLeader) {
+        match e.leader_id {
+            Some(leader) if self.rafts.contains_key(&leader) => self.leader = Some(leader),
+            _ => self.try_next(),
+        }
+    }
+
+    /// Give up on the current node, e.g. a leader cut off from its followers, and try the next.
+    fn try_next(&mut self) {
+        let current = self.target();
+        self.leader = self.rafts.keys().copied().find(|id| *id > current).or(self.rafts.keys().next().copied());
+    }
+
+    pub async fn write(&mut self, key: &str, value: &str) -> Outcome {
+        let op = Op::Write {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        let call = self.history.call(self.id, op);
+
+        let req = Request::Set {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        let raft = &self.rafts[&self.target()];
+        let outcome = match tokio::time::timeout(CLIENT_TIMEOUT, raft.client_write(req)).await {
+            Ok(Ok(_)) => Outcome::Ok(None),
+            Ok(Err(e)) => match e.forward_to_leader() {
+                Some(fwd) => {
+                    let fwd = fwd.clone();
+                    self.follow(&fwd);
+                    Outcome::Failed
+                }
+                // Shut down or fatal, the entry may still have been replicated.
+                None => Outcome::Unknown,
+            },
+            Err(_) => {
+                self.try_next();
+                Outcome::Unknown
+            }
+        };
+
+        self.history.ret(call, outcome.clone());
+        outcome
+    }
+
+    pub async fn read(&mut self, key: &str) -> Outcome {
+        let call = self.history.call(self.id, Op::Read { key: key.to_string() });
+
+        let target = self.target();
+        let raft = &self.rafts[&target];
+        let outcome = match tokio::time::timeout(CLIENT_TIMEOUT, raft.ensure_linearizable(ReadPolicy::ReadIndex)).await
+        {
+            Ok(Ok(_)) => Outcome::Ok(self.kvs[&target].read().unwrap().get(key).cloned()),
+            Ok(Err(e)) => {
+                if let Some(fwd) = e.forward_to_leader() {
+                    let fwd = fwd.clone();
+                    self.follow(&fwd);
+                }
+                Outcome::Failed
+            }
+            Err(_) => {
+                self.try_next();
+                Outcome::Failed
+            }
+        };
+
+        self.history.ret(call, outcome.clone());
+        outcome
+    }
+
+    /// Retry a write until it succeeds, for setting up a test.
+    pub async fn must_write(&mut self, key: &str, value: &str) {
+        for _ in 0..50 {
+            if let Outcome::Ok(_) = self.write(key, value).await {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("write {}={} never succeeded", key, value);
+    }
+}
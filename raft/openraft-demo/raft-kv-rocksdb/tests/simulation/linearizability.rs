@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    Write { key: String, value: String },
+    Read { key: String },
+}
+
+impl Op {
+    fn key(&self) -> &str {
+        match self {
+            Op::Write { key, .. } | Op::Read { key } => key,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The operation took effect, with the value read for a read.
+    Ok(Option<String>),
+
+    /// The operation certainly did not take effect, e.g. it was rejected by a follower.
+    Failed,
+
+    /// The client gave up waiting, the operation may or may not have taken effect.
+    Unknown,
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub client: u64,
+    pub op: Op,
+    pub call: u64,
+    pub ret: u64,
+    pub outcome: Outcome,
+}
+
+/// The operations issued by the clients of a test, with the order in which they were called
+/// and returned.
+#[derive(Debug, Default)]
+pub struct History {
+    clock: AtomicU64,
+    events: Mutex<Vec<Event>>,
+}
+
+/// An operation in flight, pass it back to [`History::ret`] once it returns.
+pub struct Call {
+    client: u64,
+    op: Op,
+    call: u64,
+}
+
+impl History {
+    pub fn call(&self, client: u64, op: Op) -> Call {
+        Call {
+            client,
+            op,
+            call: self.clock.fetch_add(1, Ordering::SeqCst),
+        }
+    }
+
+    pub fn ret(&self, call: Call, outcome: Outcome) {
+        let ret = self.clock.fetch_add(1, Ordering::SeqCst);
+        self.events.lock().unwrap().push(Event {
+            client: call.client,
+            op: call.op,
+            call: call.call,
+            ret,
+            outcome,
+        });
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+/// Check that a history of reads and writes of a key-value store is linearizable: every
+/// operation can be placed at one point between its call and its return, so that each read
+/// returns the last value written before it.
+///
+/// A key-value store is linearizable if every key alone is, so the keys are checked one by
+/// one, each with a depth first search over the orders allowed by the real time order of the
+/// operations. A write with an `Unknown` outcome may be left out or placed anywhere after its
+/// call; `Failed` operations and reads with an `Unknown` outcome are ignored.
+pub fn check(events: &[Event]) -> Result<(), String> {
+    let mut by_key: BTreeMap<&str, Vec<&Event>> = BTreeMap::new();
+    for e in events {
+        let ignored = matches!((&e.op, &e.outcome), (_, Outcome::Failed) | (Op::Read { .. }, Outcome::Unknown));
+        if !ignored {
+            by_key.entry(e.op.key()).or_default().push(e);
+        }
+    }
+
+    for (key, ops) in by_key {
+        if !Checker::new(ops.clone()).check() {
+            let lines = ops
+                .iter()
+                .map(|e| format!("  [{}, {}] client {}: {:?} -> {:?}", e.call, e.ret, e.client, e.op, e.outcome))
+                .collect::<Vec<_>>();
+            return Err(format!("history of key {:?} is not linearizable:\n{}", key, lines.join("\n")));
+        }
+    }
+    Ok(())
+}
+
+struct Checker<'a> {
+    ops: Vec<&'a Event>,
+
+    /// `(linearized ops, register value)` states already explored without success.
+    visited: HashSet<(Vec<bool>, Option<String>)>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(mut ops: Vec<&'a Event>) -> Self {
+        ops.sort_by_key(|e| e.call);
+        Self {
+            ops,
+            visited: HashSet::new(),
+        }
+    }
+
+    fn ret(&self, i: usize) -> u64 {
+        match self.ops[i].outcome {
+            Outcome::Unknown => u64::MAX,
+            _ => self.ops[i].ret,
+        }
+    }
+
+    fn check(&mut self) -> bool {
+        let done = vec![false; self.ops.len()];
+        self.search(done, None)
+    }
+
+    fn search(&mut self, done: Vec<bool>, value: Option<String>) -> bool {
+        // Only writes of unknown outcome left, they may all have been lost.
+        if (0..self.ops.len()).all(|i| done[i] || self.ops[i].outcome == Outcome::Unknown) {
+            return true;
+        }
+
+        let state = (done, value);
+        if self.visited.contains(&state) {
+            return false;
+        }
+        let (done, value) = state.clone();
+
+        // An operation can go next only if it was called before every pending one returned.
+        let first_ret = (0..self.ops.len()).filter(|i| !done[*i]).map(|i| self.ret(i)).min().unwrap();
+
+        for i in 0..self.ops.len() {
+            if done[i] || self.ops[i].call > first_ret {
+                continue;
+            }
+
+            let next_value = match (&self.ops[i].op, &self.ops[i].outcome) {
+                (Op::Write { value: v, .. }, _) => Some(v.clone()),
+                (Op::Read { .. }, Outcome::Ok(read)) => {
+                    if *read != value {
+                        continue;
+                    }
+                    value.clone()
+                }
+                (Op::Read { .. }, _) => unreachable!("filtered out"),
+            };
+
+            let mut next_done = done.clone();
+            next_done[i] = true;
+            if self.search(next_done, next_value) {
+                return true;
+            }
+        }
+
+        self.visited.insert(state);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn w(client: u64, value: &str, call: u64, ret: u64, outcome: Outcome) -> Event {
+        Event {
+            client,
+            op: Op::Write {
+                key: "k".to_string(),
+                value: value.to_string(),
+            },
+            call,
+            ret,
+            outcome,
+        }
+    }
+
+    fn r(client: u64, value: Option<&str>, call: u64, ret: u64) -> Event {
+        Event {
+            client,
+            op: Op::Read { key: "k".to_string() },
+            call,
+            ret,
+            outcome: Outcome::Ok(value.map(|v| v.to_string())),
+        }
+    }
+
+    fn ok() -> Outcome {
+        Outcome::Ok(None)
+    }
+
+    #[test]
+    fn test_sequential_history() {
+        let h = vec![r(1, None, 0, 1), w(1, "a", 2, 3, ok()), r(1, Some("a"), 4, 5)];
+        assert!(check(&h).is_ok());
+    }
+
+    #[test]
+    fn test_stale_read_after_write_returned() {
+        let h = vec![w(1, "a", 0, 1, ok()), w(1, "b", 2, 3, ok()), r(2, Some("a"), 4, 5)];
+        assert!(check(&h).is_err());
+    }
+
+    #[test]
+    fn test_concurrent_writes_any_order() {
+        let h = vec![
+            w(1, "a", 0, 3, ok()),
+            w(2, "b", 1, 4, ok()),
+            r(3, Some("a"), 5, 6),
+            r(3, Some("a"), 7, 8),
+        ];
+        assert!(check(&h).is_ok());
+
+        // Once `a` is read after `b`, reading `b` again needs another write of `b`.
+        let h = vec![
+            w(1, "a", 0, 3, ok()),
+            w(2, "b", 1, 4, ok()),
+            r(3, Some("a"), 5, 6),
+            r(3, Some("b"), 7, 8),
+        ];
+        assert!(check(&h).is_err());
+    }
+
+    #[test]
+    fn test_unknown_write_may_or_may_not_apply() {
+        let h = vec![w(1, "a", 0, 1, Outcome::Unknown), r(2, None, 2, 3), r(2, Some("a"), 4, 5)];
+        assert!(check(&h).is_ok());
+
+        let h = vec![w(1, "a", 0, 1, Outcome::Unknown), r(2, None, 2, 3)];
+        assert!(check(&h).is_ok());
+
+        // Once seen, an unknown write can not be undone.
+        let h = vec![w(1, "a", 0, 1, Outcome::Unknown), r(2, Some("a"), 2, 3), r(2, None, 4, 5)];
+        assert!(check(&h).is_err());
+    }
+
+    #[test]
+    fn test_failed_write_is_ignored() {
+        let h = vec![w(1, "a", 0, 1, Outcome::Failed), r(2, Some("a"), 2, 3)];
+        assert!(check(&h).is_err());
+    }
+}
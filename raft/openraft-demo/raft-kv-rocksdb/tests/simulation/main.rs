@@ -0,0 +1,14 @@
+//! Deterministic cluster simulation.
+//!
+//! Runs raft-kv-rocksdb nodes in one process, connected by an in-memory network that can
+//! lose, delay and reorder messages and be partitioned, with nodes crashed and restarted from
+//! their rocksdb in a temp dir. Tests run on tokio's paused clock and draw every fault from a
+//! seeded generator, so a run needs no sockets and replays the same way from its seed. The
+//! history recorded by the clients is checked for linearizability at the end of each test.
+
+mod cluster;
+mod linearizability;
+mod network;
+mod test_faults;
+mod test_read;
+mod test_watch;
@@ -0,0 +1,267 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use openraft::error::{NetworkError, RPCError, ReplicationClosed, StreamingError, Unreachable};
+use openraft::network::RPCOption;
+use openraft::{RaftNetworkFactory, RaftNetworkV2};
+use raft_kv_rocksdb::typ::*;
+use raft_kv_rocksdb::{Node, NodeId, TypeConfig};
+
+/// A seeded pseudo random generator, so that a failing run can be replayed from its seed.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    // splitmix64
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A value in `[0, n)`.
+    pub fn below(&mut self, n: u64) -> u64 {
+        if n == 0 {
+            0
+        } else {
+            self.next_u64() % n
+        }
+    }
+
+    /// True with a probability of `percent` / 100.
+    pub fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+}
+
+/// The faults applied to every message, a request and its response are delayed and dropped
+/// independently.
+#[derive(Debug, Clone, Default)]
+pub struct Faults {
+    /// Percentage of the messages that are lost.
+    pub loss: u64,
+
+    /// Every message is delayed by a random duration up to this one. Two messages sent one
+    /// after the other may then arrive in the other order.
+    pub max_delay: Duration,
+}
+
+struct RouterState {
+    rng: Rng,
+    faults: Faults,
+    nodes: BTreeMap<NodeId, (Raft, Crashed)>,
+
+    /// The side of the partition every node is on, nodes not listed share side 0.
+    sides: BTreeMap<NodeId, usize>,
+}
+
+/// Set when a node crashes. The raft of a crashed node is abandoned, not shut down, the flag
+/// keeps it from sending or answering anything, also once the node has been restarted under
+/// the same id.
+pub type Crashed = Arc<AtomicBool>;
+
+fn unreachable_if_crashed(crashed: &Crashed, msg: impl FnOnce() -> String) -> Result<(), Unreachable<TypeConfig>> {
+    if crashed.load(Ordering::Relaxed) {
+        return Err(Unreachable::from_string(msg()));
+    }
+    Ok(())
+}
+
+/// Delivers the RPCs between the raft nodes of one process, in place of a real network.
+#[derive(Clone)]
+pub struct Router {
+    state: Arc<Mutex<RouterState>>,
+}
+
+enum Fate {
+    Deliver(Duration),
+    Drop,
+}
+
+impl Router {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(RouterState {
+                rng: Rng::new(seed),
+                faults: Faults::default(),
+                nodes: BTreeMap::new(),
+                sides: BTreeMap::new(),
+            })),
+        }
+    }
+
+    pub fn set_faults(&self, faults: Faults) {
+        self.state.lock().unwrap().faults = faults;
+    }
+
+    pub fn register(&self, id: NodeId, raft: Raft, crashed: Crashed) {
+        self.state.lock().unwrap().nodes.insert(id, (raft, crashed));
+    }
+
+    /// Make a node unreachable, e.g. because it crashed.
+    pub fn unregister(&self, id: NodeId) {
+        self.state.lock().unwrap().nodes.remove(&id);
+    }
+
+    /// Cut the network into the given sets of nodes and the set of the nodes not listed,
+    /// messages only flow within a set.
+    pub fn partition(&self, sets: &[BTreeSet<NodeId>]) {
+        let mut state = self.state.lock().unwrap();
+        state.sides.clear();
+        for (side, set) in sets.iter().enumerate() {
+            for id in set {
+                state.sides.insert(*id, side + 1);
+            }
+        }
+    }
+
+    pub fn heal(&self) {
+        self.state.lock().unwrap().sides.clear();
+    }
+
+    pub fn rng(&self) -> Rng {
+        let mut state = self.state.lock().unwrap();
+        Rng::new(state.rng.next_u64())
+    }
+
+    fn fate(&self) -> Fate {
+        let mut state = self.state.lock().unwrap();
+        let faults = state.faults.clone();
+        if state.rng.chance(faults.loss) {
+            return Fate::Drop;
+        }
+        let delay = state.rng.below(faults.max_delay.as_millis() as u64 + 1);
+        Fate::Deliver(Duration::from_millis(delay))
+    }
+
+    /// The target raft, if it is up and on the same side of a partition as the source.
+    fn target(&self, source: NodeId, target: NodeId) -> Result<(Raft, Crashed), Unreachable<TypeConfig>> {
+        let state = self.state.lock().unwrap();
+
+        let side = |id| state.sides.get(&id).copied().unwrap_or(0);
+        if side(source) != side(target) {
+            return Err(Unreachable::from_string(format!("{} -> {}: partitioned", source, target)));
+        }
+
+        state
+            .nodes
+            .get(&target)
+            .cloned()
+            .ok_or_else(|| Unreachable::from_string(format!("{} -> {}: node is down", source, target)))
+    }
+
+    /// Send a request and wait for its response through the faulty network.
+    async fn send<Resp, E, Fu>(
+        &self,
+        source: NodeId,
+        source_crashed: &Crashed,
+        target: NodeId,
+        call: impl FnOnce(Raft) -> Fu,
+    ) -> Result<Resp, RPCError<TypeConfig>>
+    where
+        Fu: Future<Output = Result<Resp, E>>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        unreachable_if_crashed(source_crashed, || format!("{} -> {}: source crashed", source, target))
+            .map_err(RPCError::Unreachable)?;
+        let (raft, target_crashed) = self.target(source, target).map_err(RPCError::Unreachable)?;
+
+        match self.fate() {
+            Fate::Drop => {
+                return Err(RPCError::Network(NetworkError::from_string(format!(
+                    "{} -> {}: request lost",
+                    source, target
+                ))));
+            }
+            Fate::Deliver(delay) => tokio::time::sleep(delay).await,
+        }
+
+        let resp = call(raft).await.map_err(|e| RPCError::Network(NetworkError::new(&e)))?;
+
+        // The target may have been cut off, or have crashed, while handling the request. A
+        // response of a crashed node is lost even if the node has been restarted since.
+        unreachable_if_crashed(&target_crashed, || format!("{} -> {}: node crashed", target, source))
+            .map_err(RPCError::Unreachable)?;
+        self.target(target, source).map_err(RPCError::Unreachable)?;
+        unreachable_if_crashed(source_crashed, || format!("{} -> {}: source crashed", target, source))
+            .map_err(RPCError::Unreachable)?;
+
+        match self.fate() {
+            Fate::Drop => Err(RPCError::Network(NetworkError::from_string(format!(
+                "{} -> {}: response lost",
+                target, source
+            )))),
+            Fate::Deliver(delay) => {
+                tokio::time::sleep(delay).await;
+                Ok(resp)
+            }
+        }
+    }
+}
+
+/// The network factory of one node, every connection it creates sends from `source`.
+pub struct SimNetwork {
+    pub router: Router,
+    pub source: NodeId,
+    pub crashed: Crashed,
+}
+
+impl RaftNetworkFactory<TypeConfig> for SimNetwork {
+    type Network = SimConnection;
+
+    async fn new_client(&mut self, target: NodeId, _node: &Node) -> Self::Network {
+        SimConnection {
+            router: self.router.clone(),
+            source: self.source,
+            crashed: self.crashed.clone(),
+            target,
+        }
+    }
+}
+
+pub struct SimConnection {
+    router: Router,
+    source: NodeId,
+    crashed: Crashed,
+    target: NodeId,
+}
+
+impl RaftNetworkV2<TypeConfig> for SimConnection {
+    async fn append_entries(
+        &mut self,
+        req: AppendEntriesRequest,
+        _option: RPCOption,
+    ) -> Result<AppendEntriesResponse, RPCError<TypeConfig>> {
+        self.router
+            .send(self.source, &self.crashed, self.target, |raft| async move { raft.append_entries(req).await })
+            .await
+    }
+
+    async fn vote(&mut self, req: VoteRequest, _option: RPCOption) -> Result<VoteResponse, RPCError<TypeConfig>> {
+        self.router.send(self.source, &self.crashed, self.target, |raft| async move { raft.vote(req).await }).await
+    }
+
+    async fn full_snapshot(
+        &mut self,
+        vote: Vote,
+        snapshot: Snapshot,
+        _cancel: impl Future<Output = ReplicationClosed> + Send + 'static,
+        _option: RPCOption,
+    ) -> Result<SnapshotResponse, StreamingError<TypeConfig>> {
+        let res = self
+            .router
+            .send(self.source, &self.crashed, self.target, |raft| async move {
+                raft.install_full_snapshot(vote, snapshot).await
+            })
+            .await;
+        Ok(res?)
+    }
+}
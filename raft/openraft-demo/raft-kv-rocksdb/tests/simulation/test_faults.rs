@@ -0,0 +1,184 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+use maplit::btreeset;
+use openraft::async_runtime::WatchReceiver;
+use openraft::{ServerState, SnapshotPolicy};
+use crate::cluster::SimCluster;
+use crate::linearizability::{check, Outcome};
+use crate::network::Faults;
+
+/// The leader crashes while clients are writing; the writes in flight may or may not survive,
+/// but the history must stay linearizable and the restarted node must catch up.
+#[tokio::test(start_paused = true)]
+async fn test_leader_crash_mid_write() {
+    let mut cluster = SimCluster::new(1..=3, 1, SimCluster::default_config()).await;
+    cluster.initialize(&btreeset! {1, 2, 3}).await;
+    let leader = cluster.wait_for_leader(None).await;
+
+    let mut writers = vec![];
+    for c in 0..3 {
+        let mut client = cluster.client(c);
+        writers.push(tokio::spawn(async move {
+            for i in 0..20 {
+                client.write("k", &format!("{}-{}", c, i)).await;
+                client.read("k").await;
+            }
+        }));
+    }
+
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    cluster.crash(leader).await;
+
+    for w in writers {
+        w.await.unwrap();
+    }
+
+    let new_leader = cluster.wait_for_leader(Some(leader)).await;
+    assert_ne!(leader, new_leader);
+
+    cluster.start(leader).await;
+    let mut client = cluster.client(10);
+    client.must_write("k", "last").await;
+    assert_eq!(client.read("k").await, Outcome::Ok(Some("last".to_string())));
+
+    cluster.wait_for_applied(cluster.leader_applied().await).await;
+    assert_eq!(cluster.node(leader).kvs.read().unwrap().get("k"), Some(&"last".to_string()));
+
+    check(&cluster.history.events()).unwrap();
+}
+
+/// The old leader is cut off with a minority: it can neither commit nor serve linearizable
+/// reads, while the majority elects a new leader and goes on. After healing, the old leader
+/// steps down and its uncommitted writes are discarded.
+#[tokio::test(start_paused = true)]
+async fn test_split_brain() {
+    let cluster = SimCluster::new(1..=5, 2, SimCluster::default_config()).await;
+    cluster.initialize(&btreeset! {1, 2, 3, 4, 5}).await;
+    let old_leader = cluster.wait_for_leader(None).await;
+
+    let mut client = cluster.client(0);
+    client.must_write("k", "before").await;
+
+    let minority: BTreeSet<_> = [old_leader, old_leader % 5 + 1].into();
+    let majority: BTreeSet<_> = cluster.running().into_iter().filter(|id| !minority.contains(id)).collect();
+    cluster.router.partition(&[minority.clone(), majority.clone()]);
+
+    // Writes and reads through the old leader can not reach a quorum.
+    let mut stale = cluster.client_of(1, &btreeset! {old_leader});
+    assert_eq!(stale.write("k", "minority").await, Outcome::Unknown);
+    assert_eq!(stale.read("k").await, Outcome::Failed);
+
+    let new_leader = cluster.wait_for_leader(Some(old_leader)).await;
+    assert!(majority.contains(&new_leader));
+
+    let mut client = cluster.client_of(2, &majority);
+    client.must_write("k", "majority").await;
+
+    cluster.router.heal();
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let metrics = cluster.node(old_leader).raft.metrics().borrow_watched().clone();
+    assert_ne!(metrics.state, ServerState::Leader);
+
+    cluster.wait_for_applied(cluster.leader_applied().await).await;
+    for id in cluster.running() {
+        assert_eq!(cluster.node(id).kvs.read().unwrap().get("k"), Some(&"majority".to_string()));
+    }
+
+    check(&cluster.history.events()).unwrap();
+}
+
+/// A node down while the leader compacts its logs can only catch up by installing a snapshot.
+#[tokio::test(start_paused = true)]
+async fn test_snapshot_install() {
+    let config = openraft::Config {
+        snapshot_policy: SnapshotPolicy::LogsSinceLast(10),
+        max_in_snapshot_log_to_keep: 0,
+        purge_batch_size: 1,
+        ..SimCluster::default_config()
+    };
+    let mut cluster = SimCluster::new(1..=3, 3, config).await;
+    cluster.initialize(&btreeset! {1, 2, 3}).await;
+    let leader = cluster.wait_for_leader(None).await;
+    let lagging = if leader == 3 { 2 } else { 3 };
+
+    cluster.crash(lagging).await;
+
+    let mut client = cluster.client(0);
+    for i in 0..30 {
+        client.must_write(&format!("k{}", i), &format!("v{}", i)).await;
+    }
+
+    let applied = cluster.leader_applied().await;
+    let leader = cluster.wait_for_leader(None).await;
+    cluster
+        .node(leader)
+        .raft
+        .wait(Some(Duration::from_secs(10)))
+        .metrics(|m| m.purged.is_some(), "leader purged logs")
+        .await
+        .unwrap();
+
+    cluster.start(lagging).await;
+    cluster.wait_for_applied(applied).await;
+
+    let metrics = cluster.node(lagging).raft.metrics().borrow_watched().clone();
+    assert!(metrics.snapshot.is_some(), "lagging node should have installed a snapshot");
+    assert_eq!(
+        *cluster.node(lagging).kvs.read().unwrap(),
+        *cluster.node(leader).kvs.read().unwrap()
+    );
+
+    check(&cluster.history.events()).unwrap();
+}
+
+/// Clients run over a network losing, delaying and reordering messages, while the nodes are
+/// partitioned and crashed at random. The fault schedule is derived from the seed.
+#[tokio::test(start_paused = true)]
+async fn test_random_faults() {
+    for seed in 0..3 {
+        run_random_faults(seed).await;
+    }
+}
+
+async fn run_random_faults(seed: u64) {
+    let mut cluster = SimCluster::new(1..=3, seed, SimCluster::default_config()).await;
+    cluster.initialize(&btreeset! {1, 2, 3}).await;
+    cluster.router.set_faults(Faults {
+        loss: 5,
+        max_delay: Duration::from_millis(20),
+    });
+
+    let mut rng = cluster.router.rng();
+    for round in 0..5 {
+        let mut clients = vec![];
+        for c in 0..3 {
+            let mut client = cluster.client(round * 10 + c);
+            let key = format!("k{}", c % 2);
+            clients.push(tokio::spawn(async move {
+                for i in 0..5 {
+                    client.write(&key, &format!("{}-{}-{}", round, c, i)).await;
+                    client.read(&key).await;
+                }
+            }));
+        }
+
+        let victim = rng.below(3) + 1;
+        match rng.below(3) {
+            0 => cluster.router.partition(&[btreeset! {victim}]),
+            1 => cluster.crash(victim).await,
+            _ => {}
+        }
+
+        for c in clients {
+            c.await.unwrap();
+        }
+
+        cluster.router.heal();
+        if !cluster.running().contains(&victim) {
+            cluster.start(victim).await;
+        }
+    }
+
+    check(&cluster.history.events()).unwrap_or_else(|e| panic!("seed {}: {}", seed, e));
+}
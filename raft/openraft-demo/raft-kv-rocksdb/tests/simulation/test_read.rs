@@ -0,0 +1,68 @@
+use maplit::btreeset;
+use openraft::async_runtime::WatchReceiver;
+use raft_kv_rocksdb::store::{ReadConsistency, ReadRequest};
+
+use crate::cluster::SimCluster;
+
+fn read_req(key: &str, consistency: ReadConsistency) -> ReadRequest {
+    ReadRequest {
+        key: key.to_string(),
+        consistency,
+    }
+}
+
+/// `App::read` through each consistency level: the leader serves all of them, a follower
+/// forwards `Linearizable` and `Lease` to the leader and serves `Stale` from its own state
+/// machine, which may be behind.
+#[tokio::test(start_paused = true)]
+async fn test_read_consistency() {
+    let cluster = SimCluster::new(1..=3, 5, SimCluster::default_config()).await;
+    cluster.initialize(&btreeset! {1, 2, 3}).await;
+    let leader = cluster.wait_for_leader(None).await;
+    let follower = cluster.running().into_iter().find(|id| *id != leader).unwrap();
+
+    let mut client = cluster.client(0);
+    client.must_write("k", "v1").await;
+    let applied = cluster.leader_applied().await;
+    cluster.wait_for_applied(applied).await;
+
+    let app = cluster.app(leader);
+    for consistency in [ReadConsistency::Linearizable, ReadConsistency::Lease, ReadConsistency::Stale] {
+        let res = app.read(read_req("k", consistency)).await.unwrap();
+        assert_eq!(res.value.as_deref(), Some("v1"), "{:?}", consistency);
+        assert_eq!(res.consistency, consistency);
+        assert!(res.last_applied_id.unwrap().index >= applied);
+    }
+    let res = app.read(read_req("missing", ReadConsistency::Linearizable)).await.unwrap();
+    assert_eq!(res.value, None);
+
+    let app = cluster.app(follower);
+    for consistency in [ReadConsistency::Linearizable, ReadConsistency::Lease] {
+        let err = app.read(read_req("k", consistency)).await.unwrap_err();
+        let fwd = err.forward_to_leader().unwrap_or_else(|| panic!("{:?}: {}", consistency, err));
+        assert_eq!(fwd.leader_id, Some(leader));
+    }
+    let res = app.read(read_req("k", ReadConsistency::Stale)).await.unwrap();
+    assert_eq!(res.value.as_deref(), Some("v1"));
+
+    // Cut the follower off: the majority goes on, its stale reads keep the old value and
+    // report the older log id they were served at.
+    let majority = cluster.running().into_iter().filter(|id| *id != follower).collect();
+    cluster.router.partition(&[btreeset! {follower}, majority]);
+    client.must_write("k", "v2").await;
+
+    let stale = app.read(read_req("k", ReadConsistency::Stale)).await.unwrap();
+    assert_eq!(stale.value.as_deref(), Some("v1"));
+    let fresh = cluster.app(leader).read(read_req("k", ReadConsistency::Linearizable)).await.unwrap();
+    assert_eq!(fresh.value.as_deref(), Some("v2"));
+    assert!(stale.last_applied_id < fresh.last_applied_id);
+
+    cluster.router.heal();
+    cluster.wait_for_applied(cluster.leader_applied().await).await;
+    let res = app.read(read_req("k", ReadConsistency::Stale)).await.unwrap();
+    assert_eq!(res.value.as_deref(), Some("v2"));
+    assert_eq!(
+        res.last_applied_id,
+        cluster.node(follower).raft.metrics().borrow_watched().last_applied
+    );
+}
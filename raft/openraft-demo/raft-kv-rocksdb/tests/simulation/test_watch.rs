@@ -0,0 +1,33 @@
+use maplit::btreeset;
+use raft_kv_rocksdb::watch::{WatchEvent, WatchRequest};
+
+use crate::cluster::SimCluster;
+
+/// A `Set` that writes the value the key already has is applied but emits no watch event,
+/// on every node.
+#[tokio::test(start_paused = true)]
+async fn test_watch_skips_unchanged_set() {
+    let cluster = SimCluster::new(1..=3, 6, SimCluster::default_config()).await;
+    cluster.initialize(&btreeset! {1, 2, 3}).await;
+
+    let mut client = cluster.client(0);
+    client.must_write("k", "v1").await;
+    client.must_write("k", "v1").await;
+    client.must_write("k", "v2").await;
+    cluster.wait_for_applied(cluster.leader_applied().await).await;
+
+    for id in cluster.running() {
+        let req = WatchRequest {
+            key: "k".to_string(),
+            prefix: false,
+            start_revision: Some(0),
+        };
+        let mut watcher = cluster.node(id).watch.subscribe(req).unwrap();
+        let first: WatchEvent = watcher.next().await.unwrap();
+        let second = watcher.next().await.unwrap();
+        assert_eq!(first.value.as_deref(), Some("v1"), "node {}", id);
+        assert_eq!(second.value.as_deref(), Some("v2"), "node {}", id);
+        // The unchanged write still took a log index in between.
+        assert!(second.revision > first.revision + 1, "node {}", id);
+    }
+}
@@ -5,16 +5,23 @@
 #![allow(clippy::field_reassign_with_default)]
 
 use slog::Drain;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError};
 use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
-use std::{str, thread};
+use std::{fs, thread};
 
 use protobuf::Message as PbMessage;
-use raft::storage::MemStorage;
-use raft::{prelude::*, StateRole};
-use regex::Regex;
+use raft::{prelude::*, StateRole, Storage, INVALID_ID};
+use raft_rs_demo::command::{
+    decode_kv_pairs, encode_kv_pairs, Command, MembershipChange, Response,
+};
+use raft_rs_demo::kv;
+use raft_rs_demo::storage::DiskStorage;
+use raft_rs_demo::transport::{PeerConfig, Transport};
 
 use slog::{error, info, o};
 
@@ -28,11 +35,190 @@ fn main() {
         .fuse();
     let logger = slog::Logger::root(drain, o!());
 
-    const NUM_NODES: u32 = 5;
-    // Create 5 mailboxes to send/receive messages. Every node holds a `Receiver` to receive
-    // messages from others, and uses the respective `Sender` to send messages to others.
+    // `five_mem_node --id <id> --config <peers file> [--data-dir <dir>]` runs one node in this
+    // process, talking to the others over TCP. Without arguments, all 5 nodes run in this
+    // process and talk through channels.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        run_in_process(&logger);
+    } else {
+        run_single_node(&args, &logger);
+    }
+}
+
+fn run_in_process(logger: &slog::Logger) {
+    // Every node keeps its raft log and state under `<tmp>/five_mem_node/node_<id>`.
+    let data_dir = std::env::temp_dir().join("five_mem_node");
+    let _ = fs::remove_dir_all(&data_dir);
+
+    // A global pending proposals queue. New proposals will be pushed back into the queue, and
+    // after it's committed by the raft cluster, it will be poped from the queue.
+    let proposals = Arc::new(Mutex::new(VecDeque::<Proposal>::new()));
+
+    let (stop_signal_sender, handles) = start_nodes(&data_dir, NUM_NODES, &proposals, logger);
+
+    // Propose some conf changes so that followers can be initialized.
+    add_all_followers(proposals.as_ref(), 2..=NUM_NODES);
+
+    // Put 100 key-value pairs.
+    info!(
+        logger,
+        "We get a 5 nodes Raft cluster now, now propose 100 proposals"
+    );
+    put_all(&proposals, 0..100);
+
+    info!(logger, "Propose 100 proposals success!");
+
+    stop_nodes(&stop_signal_sender, handles);
+
+    // Restart every node from its storage. They recover their logs, elect a leader again and
+    // rebuild their key-value pairs from their last snapshot and the committed logs after it.
+    info!(logger, "All nodes stopped, restart them from their storage");
+    let (stop_signal_sender, handles) = start_nodes(&data_dir, NUM_NODES, &proposals, logger);
+
+    put_all(&proposals, 100..110);
+
+    info!(logger, "Propose 10 proposals after restart success!");
+
+    let kv_counts = stop_nodes(&stop_signal_sender, handles);
+    info!(
+        logger,
+        "key-value pairs applied by every node: {:?}", kv_counts
+    );
+}
+
+const USAGE: &str = "usage: five_mem_node [--id <id> --config <peers file> [--data-dir <dir>]]";
+
+// Run one node of a cluster whose nodes are listed in a peers config file, each in its own
+// process. The first node of the file bootstraps the cluster and adds the others, the others
+// wait for it. A node restarted with the same data dir recovers from its storage, so processes
+// can be killed and restarted at will.
+fn run_single_node(args: &[String], logger: &slog::Logger) {
+    let (mut id, mut config_path, mut data_dir) = (None, None, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| exit_with_usage(arg));
+        match arg.as_str() {
+            "--id" => {
+                id = Some(
+                    value
+                        .parse::<u64>()
+                        .unwrap_or_else(|_| exit_with_usage(arg)),
+                )
+            }
+            "--config" => config_path = Some(PathBuf::from(value)),
+            "--data-dir" => data_dir = Some(PathBuf::from(value)),
+            _ => exit_with_usage(arg),
+        }
+    }
+    let id = id.unwrap_or_else(|| exit_with_usage("--id"));
+    let config_path = config_path.unwrap_or_else(|| exit_with_usage("--config"));
+    let data_dir = data_dir.unwrap_or_else(|| {
+        std::env::temp_dir()
+            .join("five_mem_node")
+            .join(format!("node_{}", id))
+    });
+
+    let config = PeerConfig::load(&config_path).unwrap_or_else(|e| {
+        error!(logger, "load peers config {:?} fail: {}", config_path, e);
+        std::process::exit(1);
+    });
+    let (sender, receiver) = mpsc::channel();
+    let transport = Transport::start(id, &config, sender, logger).unwrap_or_else(|e| {
+        error!(logger, "start raft transport fail: {}", e);
+        std::process::exit(1);
+    });
+
+    let proposals = Arc::new(Mutex::new(VecDeque::<Proposal>::new()));
+    let bootstrap = *config.peers.keys().next().unwrap();
+    let node = if id == bootstrap {
+        let node = Node::create_raft_leader(id, data_dir, receiver, transport.mailboxes(), logger);
+        // Add the other peers unless they have been added before the node restarted.
+        let voters = node.storage.initial_state().unwrap().conf_state.voters;
+        if voters == vec![id] {
+            let proposals = Arc::clone(&proposals);
+            let followers: Vec<u64> = config.peers.keys().copied().filter(|p| *p != id).collect();
+            thread::spawn(move || add_all_followers(&proposals, followers));
+        }
+        node
+    } else {
+        Node::create_raft_follower(id, data_dir, receiver, transport.mailboxes(), logger)
+    };
+
+    // Serve the key-value API. Commands are queued as proposals, the node handles them only
+    // while it's the leader.
+    if let Some(kv_addr) = config.kv_addr(id) {
+        let leader = Arc::clone(&node.leader);
+        let proposals = Arc::clone(&proposals);
+        let peers = config.clone();
+        let handler = move |cmd: Command| {
+            let leader = leader.load(Ordering::Acquire);
+            if leader != id {
+                return Response::NotLeader { leader };
+            }
+            if let Command::ChangeMembership { changes } = &cmd {
+                let unknown = unknown_peers(changes, &peers);
+                if !unknown.is_empty() {
+                    let msg = format!("nodes {:?} are not in the peers config", unknown);
+                    return Response::Error(msg);
+                }
+            }
+            let (proposal, rx) = Proposal::command(cmd);
+            proposals.lock().unwrap().push_back(proposal);
+            rx.recv_timeout(KV_TIMEOUT)
+                .unwrap_or_else(|_| Response::Error("timeout".to_owned()))
+        };
+        if let Err(e) = kv::serve(kv_addr, handler, logger) {
+            error!(logger, "start kv service fail: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    info!(logger, "node {} started, peers: {:?}", id, config.peers);
+    // The node runs until the process is killed.
+    let (_stop_signal_sender, stop_signal_receiver) = mpsc::channel();
+    run_node(node, &proposals, &Mutex::new(stop_signal_receiver), logger);
+}
+
+// The nodes a membership change adds which have no address in the peers config, the cluster
+// could never reach them.
+fn unknown_peers(changes: &[MembershipChange], config: &PeerConfig) -> Vec<u64> {
+    changes
+        .iter()
+        .filter_map(|change| match *change {
+            MembershipChange::AddVoter(id) | MembershipChange::AddLearner(id) => Some(id),
+            MembershipChange::Remove(_) => None,
+        })
+        .filter(|id| config.addr(*id).is_none())
+        .collect()
+}
+
+// How long the key-value API waits for a command to be applied.
+const KV_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn exit_with_usage(arg: &str) -> ! {
+    eprintln!("invalid or missing argument {}\n{}", arg, USAGE);
+    std::process::exit(2);
+}
+
+const NUM_NODES: u64 = 5;
+
+// Once this many entries are applied after the last snapshot, a node creates a new one and
+// compacts its log.
+const SNAPSHOT_INTERVAL: u64 = 50;
+
+// Spawn `num_nodes` nodes, each on its own thread, opening their storage in `data_dir`. Returns
+// the sender to stop them and the handles to join them.
+fn start_nodes(
+    data_dir: &Path,
+    num_nodes: u64,
+    proposals: &Arc<Mutex<VecDeque<Proposal>>>,
+    logger: &slog::Logger,
+) -> (Sender<Signal>, Vec<JoinHandle<usize>>) {
+    // Create a mailbox per node to send/receive messages. Every node holds a `Receiver` to
+    // receive messages from others, and uses the respective `Sender` to send messages to others.
     let (mut sender_vec, mut receiver_vec) = (Vec::new(), Vec::new());
-    for _ in 0..NUM_NODES {
+    for _ in 0..num_nodes {
         let (sender, receiver) = mpsc::channel();
         sender_vec.push(sender);
         receiver_vec.push(receiver);
@@ -41,114 +227,245 @@ fn main() {
     let (stop_signal_sender, stop_signal_receiver) = mpsc::channel();
     let stop_signal_receiver = Arc::new(Mutex::new(stop_signal_receiver));
 
-    // A global pending proposals queue. New proposals will be pushed back into the queue, and
-    // after it's committed by the raft cluster, it will be poped from the queue.
-    let proposals = Arc::new(Mutex::new(VecDeque::<Proposal>::new()));
-
     let mut handles = Vec::new();
     for (i, rx) in receiver_vec.into_iter().enumerate() {
-        // A map[peer_id -> sender]. In the example we create 5 nodes, with ids in [1, 5].
-        let mailboxes = (1..6u64).zip(sender_vec.iter().cloned()).collect();
-        let mut node = match i {
+        let id = i as u64 + 1;
+        let dir = data_dir.join(format!("node_{}", id));
+        // A map[peer_id -> sender]. The ids of the nodes are in [1, num_nodes].
+        let mailboxes = (1..).zip(sender_vec.iter().cloned()).collect();
+        let node = match i {
             // Peer 1 is the leader.
-            0 => Node::create_raft_leader(1, rx, mailboxes, &logger),
+            0 => Node::create_raft_leader(id, dir, rx, mailboxes, logger),
             // Other peers are followers.
-            _ => Node::create_raft_follower(rx, mailboxes),
+            _ => Node::create_raft_follower(id, dir, rx, mailboxes, logger),
         };
-        let proposals = Arc::clone(&proposals);
-
-        // Tick the raft node per 100ms. So use an `Instant` to trace it.
-        let mut t = Instant::now();
-
-        // Clone the stop receiver
-        let stop_signal_receiver_clone = Arc::clone(&stop_signal_receiver);
+        let proposals = Arc::clone(proposals);
+        let stop_signal_receiver = Arc::clone(&stop_signal_receiver);
         let logger = logger.clone();
         // Here we spawn the node on a new thread and keep a handle so we can join on them later.
-        let handle = thread::spawn(move || loop {
-            thread::sleep(Duration::from_millis(10));
-            loop {
-                // Step raft messages.
-                match node.my_mailbox.try_recv() {
-                    Ok(msg) => node.step(msg, &logger),
-                    Err(TryRecvError::Empty) => break,
-                    Err(TryRecvError::Disconnected) => return,
-                }
+        let handle =
+            thread::spawn(move || run_node(node, &proposals, &stop_signal_receiver, &logger));
+        handles.push(handle);
+    }
+
+    (stop_signal_sender, handles)
+}
+
+// Send terminate signals to every node and wait for their threads to finish. Returns the
+// number of key-value pairs of every node.
+fn stop_nodes(stop_signal_sender: &Sender<Signal>, handles: Vec<JoinHandle<usize>>) -> Vec<usize> {
+    for _ in 0..handles.len() {
+        stop_signal_sender.send(Signal::Terminate).unwrap();
+    }
+
+    handles.into_iter().map(|th| th.join().unwrap()).collect()
+}
+
+// Drive a node until it's told to stop: step the messages it receives, tick it, propose the
+// pending proposals if it's the leader and handle its readies. Returns the number of key-value
+// pairs the node has applied.
+fn run_node(
+    mut node: Node,
+    proposals: &Mutex<VecDeque<Proposal>>,
+    stop_signal_receiver: &Mutex<Receiver<Signal>>,
+    logger: &slog::Logger,
+) -> usize {
+    // Tick the raft node per 100ms. So use an `Instant` to trace it.
+    let mut t = Instant::now();
+    loop {
+        thread::sleep(Duration::from_millis(10));
+        loop {
+            // Step raft messages.
+            match node.my_mailbox.try_recv() {
+                Ok(msg) => node.step(msg, logger),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return node.kv_pairs.len(),
             }
+        }
+        if node.tombstoned {
+            info!(logger, "told it's removed from the cluster, shut down");
+            return node.kv_pairs.len();
+        }
 
-            let raft_group = match node.raft_group {
-                Some(ref mut r) => r,
-                // When Node::raft_group is `None` it means the node is not initialized.
-                _ => continue,
-            };
+        let raft_group = match node.raft_group {
+            Some(ref mut r) => r,
+            // When Node::raft_group is `None` it means the node is not initialized.
+            _ => continue,
+        };
+
+        if t.elapsed() >= Duration::from_millis(100) {
+            // Tick the raft.
+            raft_group.tick();
+            t = Instant::now();
+        }
+
+        node.leader
+            .store(raft_group.raft.leader_id, Ordering::Release);
 
-            if t.elapsed() >= Duration::from_millis(100) {
-                // Tick the raft.
-                raft_group.tick();
-                t = Instant::now();
+        // Let the leader pick pending proposals from the global queue.
+        if raft_group.raft.state == StateRole::Leader {
+            let mut proposals = proposals.lock().unwrap();
+            // Reads don't go through the log, they wait for a read index instead.
+            let (reads, rest) = proposals.drain(..).partition(|p| p.is_read());
+            *proposals = rest;
+            for p in reads {
+                node.next_read_ctx += 1;
+                let ctx = node.next_read_ctx.to_be_bytes().to_vec();
+                raft_group.read_index(ctx.clone());
+                node.pending_reads.insert(ctx, p);
             }
 
-            // Let the leader pick pending proposals from the global queue.
-            if raft_group.raft.state == StateRole::Leader {
-                // Handle new proposals.
-                let mut proposals = proposals.lock().unwrap();
-                for p in proposals.iter_mut().skip_while(|p| p.proposed > 0) {
-                    propose(raft_group, p);
+            // A leader transfer has no entry either, it's done once the transferee is leader.
+            let (transfers, rest) = proposals
+                .drain(..)
+                .partition(|p| p.transfer_leader.is_some());
+            *proposals = rest;
+            for p in transfers {
+                let to = p.transfer_leader.unwrap();
+                if to == raft_group.raft.id || !raft_group.raft.prs().conf().voters().contains(to) {
+                    let msg = format!("can't transfer leadership to {}", to);
+                    let _ = p.propose_success.send(Response::Error(msg));
+                    continue;
+                }
+                raft_group.transfer_leader(to);
+                if let Some((_, previous)) = node.transferring.replace((to, p)) {
+                    let msg = "superseded by another leader transfer".to_owned();
+                    let _ = previous.propose_success.send(Response::Error(msg));
                 }
             }
 
-            // Handle readies from the raft.
-            on_ready(
-                raft_group,
-                &mut node.kv_pairs,
-                &node.mailboxes,
-                &proposals,
-                &logger,
-            );
+            // Handle new proposals, dropping the rejected ones.
+            proposals.retain_mut(|p| p.proposed > 0 || propose(raft_group, p));
+        } else {
+            // The reads of a leader which has stepped down will never get a read index.
+            let leader = raft_group.raft.leader_id;
+            let pending = node.pending_reads.drain().map(|(_, p)| p);
+            for p in pending.chain(node.ready_reads.drain(..).map(|(_, p)| p)) {
+                let _ = p.propose_success.send(Response::NotLeader { leader });
+            }
+        }
 
-            // Check control signals from the main thread.
-            if check_signals(&stop_signal_receiver_clone) {
-                return;
+        if let Some((to, _)) = node.transferring {
+            let raft = &raft_group.raft;
+            let outcome = if raft.leader_id == to {
+                Some(Response::Ok(None))
+            } else if raft.state == StateRole::Leader && raft.lead_transferee.is_none() {
+                // Aborted, e.g. `to` didn't catch up within an election timeout.
+                Some(Response::Error(format!("leader transfer to {} failed", to)))
+            } else if raft.state != StateRole::Leader && raft.leader_id != INVALID_ID {
+                Some(Response::Error(format!(
+                    "{} is leader instead",
+                    raft.leader_id
+                )))
+            } else {
+                None
             };
-        });
-        handles.push(handle);
-    }
+            if let Some(resp) = outcome {
+                let (_, p) = node.transferring.take().unwrap();
+                let _ = p.propose_success.send(resp);
+            }
+        }
 
-    // Propose some conf changes so that followers can be initialized.
-    add_all_followers(proposals.as_ref());
+        // Handle readies from the raft.
+        let mut read_states = Vec::new();
+        on_ready(
+            raft_group,
+            &mut node.kv_pairs,
+            &node.mailboxes,
+            proposals,
+            &mut read_states,
+            logger,
+        );
 
-    // Put 100 key-value pairs.
-    info!(
-        logger,
-        "We get a 5 nodes Raft cluster now, now propose 100 proposals"
-    );
-    (0..100u16)
-        .filter(|i| {
-            let (proposal, rx) = Proposal::normal(*i, "hello, world".to_owned());
-            proposals.lock().unwrap().push_back(proposal);
-            // After we got a response from `rx`, we can assume the put succeeded and following
-            // `get` operations can find the key-value pair.
-            rx.recv().unwrap()
-        })
-        .count();
+        // Answer the reads once the state machine has caught up with their read index.
+        for rs in read_states {
+            if let Some(p) = node.pending_reads.remove(&rs.request_ctx) {
+                node.ready_reads.push((rs.index, p));
+            }
+        }
+        let applied = raft_group.raft.raft_log.applied;
+        let kv_pairs = &node.kv_pairs;
+        node.ready_reads.retain(|(index, p)| {
+            if *index > applied {
+                return true;
+            }
+            if let Some(Command::Get { ref key }) = p.command {
+                let _ = p
+                    .propose_success
+                    .send(Response::Ok(kv_pairs.get(key).cloned()));
+            }
+            false
+        });
 
-    info!(logger, "Propose 100 proposals success!");
+        // A node removed from the cluster gets no more messages, shut it down. A node which
+        // hasn't got its first snapshot yet has an empty configuration.
+        let id = raft_group.raft.id;
+        let cs = raft_group.raft.prs().conf().to_conf_state();
+        let members: HashSet<u64> = [
+            &cs.voters,
+            &cs.voters_outgoing,
+            &cs.learners,
+            &cs.learners_next,
+        ]
+        .into_iter()
+        .flatten()
+        .copied()
+        .collect();
+        if !cs.voters.is_empty() && !members.contains(&id) {
+            info!(logger, "removed from the cluster, shut down"; "id" => id);
+            return node.kv_pairs.len();
+        }
+        // Tell the removed nodes to shut down, they may have missed the commit of their
+        // removal, or not even be initialized yet. Remember them to answer them again later.
+        if members != node.members {
+            for &removed in node.members.difference(&members) {
+                if let Some(mailbox) = node.mailboxes.get(&removed) {
+                    let _ = mailbox.send(tombstone(id, removed));
+                }
+                node.removed.insert(removed);
+            }
+            node.removed.retain(|id| !members.contains(id));
+            node.members = members;
+        }
 
-    // Send terminate signals
-    for _ in 0..NUM_NODES {
-        stop_signal_sender.send(Signal::Terminate).unwrap();
-    }
+        // Snapshot the state machine once enough entries are applied after the last snapshot,
+        // or when the raft needs a newer one for a follower.
+        let (snapshot_index, requested) = {
+            let store = node.storage.rl();
+            let index = store.last_snapshot().get_metadata().index;
+            (index, store.snapshot_requested())
+        };
+        if applied >= snapshot_index + SNAPSHOT_INTERVAL || (requested && applied > snapshot_index)
+        {
+            node.compact_log(applied, logger);
+        }
 
-    // Wait for the thread to finish
-    for th in handles {
-        th.join().unwrap();
+        // Check control signals from the main thread.
+        if check_signals(stop_signal_receiver) {
+            return node.kv_pairs.len();
+        };
     }
 }
 
+fn put_all(proposals: &Mutex<VecDeque<Proposal>>, keys: std::ops::Range<u32>) {
+    keys.filter(|i| {
+        let (proposal, rx) = Proposal::command(Command::Put {
+            key: i.to_string(),
+            value: "hello, world".to_owned(),
+        });
+        proposals.lock().unwrap().push_back(proposal);
+        // After we got a response from `rx`, we can assume the put succeeded and following
+        // `get` operations can find the key-value pair.
+        rx.recv().unwrap() == Response::Ok(None)
+    })
+    .count();
+}
+
 enum Signal {
     Terminate,
 }
 
-fn check_signals(receiver: &Arc<Mutex<mpsc::Receiver<Signal>>>) -> bool {
+fn check_signals(receiver: &Mutex<Receiver<Signal>>) -> bool {
     match receiver.lock().unwrap().try_recv() {
         Ok(Signal::Terminate) => true,
         Err(TryRecvError::Empty) => false,
@@ -158,54 +475,104 @@ fn check_signals(receiver: &Arc<Mutex<mpsc::Receiver<Signal>>>) -> bool {
 
 struct Node {
     // None if the raft is not initialized.
-    raft_group: Option<RawNode<MemStorage>>,
+    raft_group: Option<RawNode<DiskStorage>>,
+    storage: DiskStorage,
     my_mailbox: Receiver<Message>,
     mailboxes: HashMap<u64, Sender<Message>>,
-    // Key-value pairs after applied. `MemStorage` only contains raft logs,
+    // Key-value pairs after applied. `DiskStorage` only contains raft logs,
     // so we need an additional storage engine.
-    kv_pairs: HashMap<u16, String>,
+    kv_pairs: HashMap<String, String>,
+    // The leader known by the node, 0 if none, published for the key-value API.
+    leader: Arc<AtomicU64>,
+    // Reads waiting for their read index, by the context passed to `read_index`.
+    pending_reads: HashMap<Vec<u8>, Proposal>,
+    // Reads with their read index, waiting for the index to be applied.
+    ready_reads: Vec<(u64, Proposal)>,
+    next_read_ctx: u64,
+    // The leader transfer in progress, with its transferee.
+    transferring: Option<(u64, Proposal)>,
+    // The nodes of the current configuration, and the ones removed from it. Node ids are
+    // never reused once removed.
+    members: HashSet<u64>,
+    removed: HashSet<u64>,
+    // The node got a tombstone, it has been removed from the cluster.
+    tombstoned: bool,
 }
 
 impl Node {
+    // The key-value pairs start from the last snapshot, the raft applies the entries after it.
+    fn new(
+        storage: DiskStorage,
+        my_mailbox: Receiver<Message>,
+        mailboxes: HashMap<u64, Sender<Message>>,
+    ) -> Self {
+        let kv_pairs = decode_kv_pairs(storage.rl().last_snapshot().get_data()).unwrap();
+        Node {
+            raft_group: None,
+            storage,
+            my_mailbox,
+            mailboxes,
+            kv_pairs,
+            leader: Default::default(),
+            pending_reads: Default::default(),
+            ready_reads: Vec::new(),
+            next_read_ctx: 0,
+            transferring: None,
+            members: HashSet::new(),
+            removed: HashSet::new(),
+            tombstoned: false,
+        }
+    }
+
     // Create a raft leader only with itself in its configuration.
     fn create_raft_leader(
         id: u64,
+        dir: PathBuf,
         my_mailbox: Receiver<Message>,
         mailboxes: HashMap<u64, Sender<Message>>,
         logger: &slog::Logger,
     ) -> Self {
-        let mut cfg = example_config();
-        cfg.id = id;
-        let logger = logger.new(o!("tag" => format!("peer_{}", id)));
-        let mut s = Snapshot::default();
-        // Because we don't use the same configuration to initialize every node, so we use
-        // a non-zero index to force new followers catch up logs by snapshot first, which will
-        // bring all nodes to the same initial state.
-        s.mut_metadata().index = 1;
-        s.mut_metadata().term = 1;
-        s.mut_metadata().mut_conf_state().voters = vec![1];
-        let storage = MemStorage::new();
-        storage.wl().apply_snapshot(s).unwrap();
-        let raft_group = Some(RawNode::new(&cfg, storage, &logger).unwrap());
-        Node {
-            raft_group,
-            my_mailbox,
-            mailboxes,
-            kv_pairs: Default::default(),
+        let storage = DiskStorage::open(dir).unwrap();
+        if !storage.initial_state().unwrap().initialized() {
+            let mut s = Snapshot::default();
+            // Because we don't use the same configuration to initialize every node, so we use
+            // a non-zero index to force new followers catch up logs by snapshot first, which will
+            // bring all nodes to the same initial state.
+            s.mut_metadata().index = 1;
+            s.mut_metadata().term = 1;
+            s.mut_metadata().mut_conf_state().voters = vec![id];
+            storage.wl().apply_snapshot(s).unwrap();
         }
+        let mut node = Node::new(storage, my_mailbox, mailboxes);
+        node.initialize_raft(id, logger);
+        node
     }
 
-    // Create a raft follower.
+    // Create a raft follower. A follower restarted with an initialized storage creates its
+    // raft at once, a new one waits for the first message from the leader.
     fn create_raft_follower(
+        id: u64,
+        dir: PathBuf,
         my_mailbox: Receiver<Message>,
         mailboxes: HashMap<u64, Sender<Message>>,
+        logger: &slog::Logger,
     ) -> Self {
-        Node {
-            raft_group: None,
-            my_mailbox,
-            mailboxes,
-            kv_pairs: Default::default(),
+        let storage = DiskStorage::open(dir).unwrap();
+        let initialized = storage.initial_state().unwrap().initialized();
+        let mut node = Node::new(storage, my_mailbox, mailboxes);
+        if initialized {
+            node.initialize_raft(id, logger);
         }
+        node
+    }
+
+    fn initialize_raft(&mut self, id: u64, logger: &slog::Logger) {
+        let mut cfg = example_config();
+        cfg.id = id;
+        // Entries up to the last snapshot are already in `kv_pairs`.
+        cfg.applied = self.storage.rl().last_snapshot().get_metadata().index;
+        let logger = logger.new(o!("tag" => format!("peer_{}", id)));
+        self.raft_group = Some(RawNode::new(&cfg, self.storage.clone(), &logger).unwrap());
     }
 
     // Initialize raft for followers.
@@ -213,15 +580,23 @@ impl Node {
         if !is_initial_msg(msg) {
             return;
         }
-        let mut cfg = example_config();
-        cfg.id = msg.to;
-        let logger = logger.new(o!("tag" => format!("peer_{}", msg.to)));
-        let storage = MemStorage::new();
-        self.raft_group = Some(RawNode::new(&cfg, storage, &logger).unwrap());
+        self.initialize_raft(msg.to, logger);
     }
 
     // Step a raft message, initialize the raft if need.
     fn step(&mut self, msg: Message, logger: &slog::Logger) {
+        if is_tombstone(&msg) {
+            self.tombstoned = true;
+            return;
+        }
+        if self.removed.contains(&msg.from) {
+            // A removed node which missed the commit of its removal keeps campaigning, tell it
+            // to shut down instead of letting it disrupt the cluster.
+            if let Some(mailbox) = self.mailboxes.get(&msg.from) {
+                let _ = mailbox.send(tombstone(msg.to, msg.from));
+            }
+            return;
+        }
         if self.raft_group.is_none() {
             if is_initial_msg(&msg) {
                 self.initialize_raft_from_message(&msg, logger);
@@ -232,13 +607,29 @@ impl Node {
         let raft_group = self.raft_group.as_mut().unwrap();
         let _ = raft_group.step(msg);
     }
+
+    // Snapshot the key-value pairs at the applied index, and compact the log up to it.
+    // Followers missing the compacted entries catch up with the snapshot.
+    fn compact_log(&mut self, applied: u64, logger: &slog::Logger) {
+        let data = encode_kv_pairs(&self.kv_pairs);
+        let mut store = self.storage.wl();
+        if let Err(e) = store
+            .create_snapshot(applied, data)
+            .and_then(|_| store.compact(applied + 1))
+        {
+            error!(logger, "create snapshot at {} fail: {:?}", applied, e);
+            return;
+        }
+        info!(logger, "snapshot created and log compacted"; "index" => applied);
+    }
 }
 
 fn on_ready(
-    raft_group: &mut RawNode<MemStorage>,
-    kv_pairs: &mut HashMap<u16, String>,
+    raft_group: &mut RawNode<DiskStorage>,
+    kv_pairs: &mut HashMap<String, String>,
     mailboxes: &HashMap<u64, Sender<Message>>,
     proposals: &Mutex<VecDeque<Proposal>>,
+    read_states: &mut Vec<ReadState>,
     logger: &slog::Logger,
 ) {
     if !raft_group.has_ready() {
@@ -249,14 +640,26 @@ fn on_ready(
     // Get the `Ready` with `RawNode::ready` interface.
     let mut ready = raft_group.ready();
 
-    let handle_messages = |msgs: Vec<Message>| {
+    // The followers a snapshot is sent to.
+    let mut snapshot_sent = Vec::new();
+    let mut handle_messages = |msgs: Vec<Message>| {
         for msg in msgs {
             let to = msg.to;
-            if mailboxes[&to].send(msg).is_err() {
-                error!(
-                    logger,
-                    "send raft message to {} fail, let Raft retry it", to
-                );
+            if msg.get_msg_type() == MessageType::MsgSnapshot {
+                snapshot_sent.push(to);
+            }
+            match mailboxes.get(&to) {
+                Some(mailbox) => {
+                    if mailbox.send(msg).is_err() {
+                        error!(
+                            logger,
+                            "send raft message to {} fail, let Raft retry it", to
+                        );
+                    }
+                }
+                // A member without an address, e.g. added before it was removed from the
+                // peers config. Raft keeps retrying, there's nothing better to do with it.
+                None => error!(logger, "no mailbox for raft peer {}, drop the message", to),
             }
         }
     };
@@ -266,9 +669,22 @@ fn on_ready(
         handle_messages(ready.take_messages());
     }
 
+    // Collect the read indexes, they are answered once the state machine has applied them.
+    read_states.extend(ready.take_read_states());
+
     // Apply the snapshot. It's necessary because in `RawNode::advance` we stabilize the snapshot.
     if *ready.snapshot() != Snapshot::default() {
         let s = ready.snapshot().clone();
+        let snapshot_kv_pairs = match decode_kv_pairs(s.get_data()) {
+            Ok(kvs) => kvs,
+            Err(e) => {
+                error!(
+                    logger,
+                    "decode snapshot fail: {:?}, need to retry or panic", e
+                );
+                return;
+            }
+        };
         if let Err(e) = store.wl().apply_snapshot(s) {
             error!(
                 logger,
@@ -276,35 +692,82 @@ fn on_ready(
             );
             return;
         }
+        *kv_pairs = snapshot_kv_pairs;
     }
 
     let mut handle_committed_entries =
-        |rn: &mut RawNode<MemStorage>, committed_entries: Vec<Entry>| {
+        |rn: &mut RawNode<DiskStorage>, committed_entries: Vec<Entry>| {
             for entry in committed_entries {
-                if entry.data.is_empty() {
-                    // From new elected leaders.
-                    continue;
-                }
-                if let EntryType::EntryConfChange = entry.get_entry_type() {
-                    // For conf change messages, make them effective.
-                    let mut cc = ConfChange::default();
-                    cc.merge_from_bytes(&entry.data).unwrap();
-                    let cs = rn.apply_conf_change(&cc).unwrap();
-                    store.wl().set_conf_state(cs);
-                } else {
-                    // For normal proposals, extract the key-value pair and then
-                    // insert them into the kv engine.
-                    let data = str::from_utf8(&entry.data).unwrap();
-                    let reg = Regex::new("put ([0-9]+) (.+)").unwrap();
-                    if let Some(caps) = reg.captures(data) {
-                        kv_pairs.insert(caps[1].parse().unwrap(), caps[2].to_string());
+                let resp = match entry.get_entry_type() {
+                    EntryType::EntryConfChange | EntryType::EntryConfChangeV2 => {
+                        // For conf change messages, make them effective. An empty
+                        // `ConfChangeV2` leaves the joint configuration.
+                        let res = if entry.get_entry_type() == EntryType::EntryConfChange {
+                            let mut cc = ConfChange::default();
+                            cc.merge_from_bytes(&entry.data).unwrap();
+                            rn.apply_conf_change(&cc)
+                        } else {
+                            let mut cc = ConfChangeV2::default();
+                            cc.merge_from_bytes(&entry.data).unwrap();
+                            rn.apply_conf_change(&cc)
+                        };
+                        match res {
+                            Ok(cs) => {
+                                info!(logger, "conf change applied"; "conf_state" => ?cs);
+                                store.wl().set_conf_state(cs).unwrap();
+                                Response::Ok(None)
+                            }
+                            // Every node fails the same way, skip it.
+                            Err(e) => {
+                                error!(
+                                    logger,
+                                    "skip invalid conf change at {}: {}", entry.index, e
+                                );
+                                Response::Error(e.to_string())
+                            }
+                        }
                     }
-                }
+                    // From new elected leaders, or a conf change ignored by the leader, e.g.
+                    // while the cluster is still in a joint configuration.
+                    _ if entry.data.is_empty() => Response::Error("proposal ignored".to_owned()),
+                    _ => {
+                        // For normal proposals, decode the command and then apply it to the kv
+                        // engine.
+                        match Command::decode(&entry.data) {
+                            Ok(Command::Put { key, value }) => {
+                                kv_pairs.insert(key, value);
+                            }
+                            Ok(Command::Delete { key }) => {
+                                kv_pairs.remove(&key);
+                            }
+                            Ok(_) => {}
+                            Err(e) => {
+                                error!(logger, "skip invalid command at {}: {}", entry.index, e)
+                            }
+                        }
+                        Response::Ok(None)
+                    }
+                };
                 if rn.raft.state == StateRole::Leader {
                     // The leader should response to the clients, tell them if their proposals
-                    // succeeded or not.
-                    let proposal = proposals.lock().unwrap().pop_front().unwrap();
-                    proposal.propose_success.send(true).unwrap();
+                    // succeeded or not. Entries applied again after a restart have no proposal
+                    // waiting for them.
+                    let mut proposals = proposals.lock().unwrap();
+                    // Proposals before this entry have been overwritten, e.g. after the node
+                    // lost its leadership in between.
+                    while proposals
+                        .front()
+                        .is_some_and(|p| p.proposed > 0 && p.proposed < entry.index)
+                    {
+                        let proposal = proposals.pop_front().unwrap();
+                        let _ = proposal
+                            .propose_success
+                            .send(Response::Error("proposal dropped".to_owned()));
+                    }
+                    if proposals.front().is_some_and(|p| p.proposed == entry.index) {
+                        let proposal = proposals.pop_front().unwrap();
+                        let _ = proposal.propose_success.send(resp);
+                    }
                 }
             }
         };
@@ -323,7 +786,7 @@ fn on_ready(
 
     if let Some(hs) = ready.hs() {
         // Raft HardState changed, and we need to persist it.
-        store.wl().set_hardstate(hs.clone());
+        store.wl().set_hardstate(hs.clone()).unwrap();
     }
 
     if !ready.persisted_messages().is_empty() {
@@ -335,7 +798,7 @@ fn on_ready(
     let mut light_rd = raft_group.advance(ready);
     // Update commit index.
     if let Some(commit) = light_rd.commit_index() {
-        store.wl().mut_hard_state().set_commit(commit);
+        store.wl().set_commit(commit).unwrap();
     }
     // Send out the messages.
     handle_messages(light_rd.take_messages());
@@ -343,6 +806,12 @@ fn on_ready(
     handle_committed_entries(raft_group, light_rd.take_committed_entries());
     // Advance the apply index.
     raft_group.advance_apply();
+
+    // Snapshots are sent at once, let raft go on replicating the log after them. A follower
+    // which didn't get its snapshot rejects the next entries, and then gets a new one.
+    for to in snapshot_sent {
+        raft_group.report_snapshot(to, SnapshotStatus::Finish);
+    }
 }
 
 fn example_config() -> Config {
@@ -353,6 +822,22 @@ fn example_config() -> Config {
     }
 }
 
+// Marks the heartbeat telling a node it has been removed from the cluster.
+const TOMBSTONE: &[u8] = b"tombstone";
+
+fn tombstone(from: u64, to: u64) -> Message {
+    let mut msg = Message::default();
+    msg.set_msg_type(MessageType::MsgHeartbeat);
+    msg.from = from;
+    msg.to = to;
+    msg.set_context(TOMBSTONE.to_vec().into());
+    msg
+}
+
+fn is_tombstone(msg: &Message) -> bool {
+    msg.get_msg_type() == MessageType::MsgHeartbeat && msg.get_context() == TOMBSTONE
+}
+
 // The message can be used to initialize a raft node or not.
 fn is_initial_msg(msg: &Message) -> bool {
     let msg_type = msg.get_msg_type();
@@ -362,19 +847,19 @@ fn is_initial_msg(msg: &Message) -> bool {
 }
 
 struct Proposal {
-    normal: Option<(u16, String)>, // key is an u16 integer, and value is a string.
-    conf_change: Option<ConfChange>, // conf change.
+    command: Option<Command>,          // put, delete or get.
+    conf_change: Option<ConfChangeV2>, // conf change.
     transfer_leader: Option<u64>,
     // If it's proposed, it will be set to the index of the entry.
     proposed: u64,
-    propose_success: SyncSender<bool>,
+    propose_success: SyncSender<Response>,
 }
 
 impl Proposal {
-    fn conf_change(cc: &ConfChange) -> (Self, Receiver<bool>) {
+    fn conf_change(cc: &ConfChangeV2) -> (Self, Receiver<Response>) {
         let (tx, rx) = mpsc::sync_channel(1);
         let proposal = Proposal {
-            normal: None,
+            command: None,
             conf_change: Some(cc.clone()),
             transfer_leader: None,
             proposed: 0,
@@ -383,10 +868,31 @@ impl Proposal {
         (proposal, rx)
     }
 
-    fn normal(key: u16, value: String) -> (Self, Receiver<bool>) {
+    fn transfer_leader(to: u64) -> (Self, Receiver<Response>) {
+        let (tx, rx) = mpsc::sync_channel(1);
+        let proposal = Proposal {
+            command: None,
+            conf_change: None,
+            transfer_leader: Some(to),
+            proposed: 0,
+            propose_success: tx,
+        };
+        (proposal, rx)
+    }
+
+    // The proposal of a command from a client, admin commands become conf changes and leader
+    // transfers.
+    fn command(cmd: Command) -> (Self, Receiver<Response>) {
+        match cmd {
+            Command::ChangeMembership { changes } => {
+                return Proposal::conf_change(&conf_change(&changes))
+            }
+            Command::TransferLeader { to } => return Proposal::transfer_leader(to),
+            _ => {}
+        }
         let (tx, rx) = mpsc::sync_channel(1);
         let proposal = Proposal {
-            normal: Some((key, value)),
+            command: Some(cmd),
             conf_change: None,
             transfer_leader: None,
             proposed: 0,
@@ -394,42 +900,215 @@ impl Proposal {
         };
         (proposal, rx)
     }
+
+    fn is_read(&self) -> bool {
+        self.command.as_ref().is_some_and(|c| c.is_read())
+    }
 }
 
-fn propose(raft_group: &mut RawNode<MemStorage>, proposal: &mut Proposal) {
+// Propose to the raft, returns false if the proposal is rejected, e.g. during a leader
+// transfer.
+fn propose(raft_group: &mut RawNode<DiskStorage>, proposal: &mut Proposal) -> bool {
     let last_index1 = raft_group.raft.raft_log.last_index() + 1;
-    if let Some((ref key, ref value)) = proposal.normal {
-        let data = format!("put {} {}", key, value).into_bytes();
-        let _ = raft_group.propose(vec![], data);
+    if let Some(ref cmd) = proposal.command {
+        let _ = raft_group.propose(vec![], cmd.encode());
     } else if let Some(ref cc) = proposal.conf_change {
         let _ = raft_group.propose_conf_change(vec![], cc.clone());
-    } else if let Some(_transferee) = proposal.transfer_leader {
-        // TODO: implement transfer leader.
-        unimplemented!();
     }
 
     let last_index2 = raft_group.raft.raft_log.last_index() + 1;
     if last_index2 == last_index1 {
         // Propose failed, don't forget to respond to the client.
-        proposal.propose_success.send(false).unwrap();
+        let _ = proposal
+            .propose_success
+            .send(Response::Error("proposal rejected".to_owned()));
+        false
     } else {
         proposal.proposed = last_index1;
+        true
     }
 }
 
-// Proposes some conf change for peers [2, 5].
-fn add_all_followers(proposals: &Mutex<VecDeque<Proposal>>) {
-    for i in 2..6u64 {
-        let mut conf_change = ConfChange::default();
-        conf_change.node_id = i;
-        conf_change.set_change_type(ConfChangeType::AddNode);
-        loop {
-            let (proposal, rx) = Proposal::conf_change(&conf_change);
+// A conf change applying all `changes` at once. With more than one change, the cluster goes
+// through a joint configuration, which it leaves on its own once the change is applied.
+fn conf_change(changes: &[MembershipChange]) -> ConfChangeV2 {
+    let changes = changes.iter().map(|change| {
+        let (change_type, node_id) = match *change {
+            MembershipChange::AddVoter(id) => (ConfChangeType::AddNode, id),
+            MembershipChange::AddLearner(id) => (ConfChangeType::AddLearnerNode, id),
+            MembershipChange::Remove(id) => (ConfChangeType::RemoveNode, id),
+        };
+        let mut single = ConfChangeSingle::default();
+        single.set_change_type(change_type);
+        single.node_id = node_id;
+        single
+    });
+    let mut cc = ConfChangeV2::default();
+    cc.set_changes(changes.collect::<Vec<_>>().into());
+    cc.set_transition(ConfChangeTransition::Auto);
+    cc
+}
+
+// Proposes a membership change until it's applied. Returns the response of the last attempt.
+fn change_membership(
+    proposals: &Mutex<VecDeque<Proposal>>,
+    changes: &[MembershipChange],
+) -> Response {
+    let mut resp = Response::Ok(None);
+    for _ in 0..50 {
+        let (proposal, rx) = Proposal::conf_change(&conf_change(changes));
+        proposals.lock().unwrap().push_back(proposal);
+        resp = rx.recv().unwrap();
+        if resp == Response::Ok(None) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    resp
+}
+
+// Proposes some conf change for the given peers.
+fn add_all_followers(proposals: &Mutex<VecDeque<Proposal>>, peers: impl IntoIterator<Item = u64>) {
+    for i in peers {
+        while change_membership(proposals, &[MembershipChange::AddVoter(i)]) != Response::Ok(None) {
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_node_after_compaction() {
+        let logger = slog::Logger::root(slog::Discard, o!());
+        let data_dir =
+            std::env::temp_dir().join(format!("five_mem_node_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&data_dir);
+
+        // Start 6 nodes but only add 5 of them, the 6th waits for the leader.
+        let proposals = Arc::new(Mutex::new(VecDeque::<Proposal>::new()));
+        let (stop_signal_sender, handles) = start_nodes(&data_dir, 6, &proposals, &logger);
+        add_all_followers(proposals.as_ref(), 2..=5);
+        put_all(&proposals, 0..3 * SNAPSHOT_INTERVAL as u32);
+
+        // The log of the leader has been compacted, the new node can only catch up with a
+        // snapshot.
+        add_all_followers(proposals.as_ref(), 6..=6);
+        put_all(&proposals, 1000..1010);
+
+        // Let the followers apply the last entries.
+        thread::sleep(Duration::from_secs(2));
+        let kv_counts = stop_nodes(&stop_signal_sender, handles);
+        assert_eq!(kv_counts, vec![3 * SNAPSHOT_INTERVAL as usize + 10; 6]);
+
+        for id in 1..=6 {
+            let storage = DiskStorage::open(data_dir.join(format!("node_{}", id))).unwrap();
+            let snapshot_index = storage.rl().last_snapshot().get_metadata().index;
+            assert!(snapshot_index >= SNAPSHOT_INTERVAL, "node {}", id);
+            assert_eq!(storage.first_index().unwrap(), snapshot_index + 1);
+        }
+
+        // Restarted nodes rebuild their key-value pairs from their snapshot and log.
+        let (stop_signal_sender, handles) = start_nodes(&data_dir, 6, &proposals, &logger);
+        put_all(&proposals, 2000..2001);
+        thread::sleep(Duration::from_secs(2));
+        let kv_counts = stop_nodes(&stop_signal_sender, handles);
+        assert_eq!(kv_counts, vec![3 * SNAPSHOT_INTERVAL as usize + 11; 6]);
+
+        let _ = fs::remove_dir_all(&data_dir);
+    }
+
+    // Put every key, retrying until it succeeds.
+    fn must_put_all(proposals: &Mutex<VecDeque<Proposal>>, keys: std::ops::Range<u32>) {
+        for i in keys {
+            loop {
+                let (proposal, rx) = Proposal::command(Command::Put {
+                    key: i.to_string(),
+                    value: "hello, world".to_owned(),
+                });
+                proposals.lock().unwrap().push_back(proposal);
+                if rx.recv().unwrap() == Response::Ok(None) {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    #[test]
+    fn test_unknown_peers() {
+        let config = PeerConfig::parse("1 127.0.0.1:6001\n2 127.0.0.1:6002\n").unwrap();
+        let changes = [
+            MembershipChange::AddVoter(2),
+            MembershipChange::AddLearner(3),
+            MembershipChange::AddVoter(4),
+            MembershipChange::Remove(5),
+        ];
+        assert_eq!(unknown_peers(&changes, &config), vec![3, 4]);
+        assert!(unknown_peers(&changes[..1], &config).is_empty());
+    }
+
+    #[test]
+    fn test_membership_changes_under_proposals() {
+        let logger = slog::Logger::root(slog::Discard, o!());
+        let data_dir =
+            std::env::temp_dir().join(format!("five_mem_node_test_cc_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&data_dir);
+
+        // Nodes 6 and 7 wait to be added.
+        let proposals = Arc::new(Mutex::new(VecDeque::<Proposal>::new()));
+        let (stop_signal_sender, handles) = start_nodes(&data_dir, 7, &proposals, &logger);
+        add_all_followers(proposals.as_ref(), 2..=5);
+
+        let writer = {
+            let proposals = Arc::clone(&proposals);
+            thread::spawn(move || must_put_all(&proposals, 0..300))
+        };
+
+        // Replace node 5 by node 6 through a joint configuration.
+        let changes = [MembershipChange::AddVoter(6), MembershipChange::Remove(5)];
+        assert_eq!(change_membership(&proposals, &changes), Response::Ok(None));
+
+        // Add node 7 as a learner, then promote it.
+        let changes = [MembershipChange::AddLearner(7)];
+        assert_eq!(change_membership(&proposals, &changes), Response::Ok(None));
+        let changes = [MembershipChange::AddVoter(7)];
+        assert_eq!(change_membership(&proposals, &changes), Response::Ok(None));
+
+        // Hand the leadership over to node 6 and remove node 1, the first leader.
+        let mut resp = Response::Ok(None);
+        for _ in 0..10 {
+            let (proposal, rx) = Proposal::transfer_leader(6);
             proposals.lock().unwrap().push_back(proposal);
-            if rx.recv().unwrap() {
+            resp = rx.recv().unwrap();
+            if resp == Response::Ok(None) {
+                break;
+            }
+        }
+        assert_eq!(resp, Response::Ok(None));
+        let changes = [MembershipChange::Remove(1)];
+        assert_eq!(change_membership(&proposals, &changes), Response::Ok(None));
+
+        writer.join().unwrap();
+        thread::sleep(Duration::from_secs(2));
+
+        // The removed nodes have shut down on their own. Node 5 may have missed the commit of
+        // its removal, it's then told to shut down once it campaigns.
+        for _ in 0..50 {
+            if handles[0].is_finished() && handles[4].is_finished() {
                 break;
             }
             thread::sleep(Duration::from_millis(100));
         }
+        assert!(handles[0].is_finished());
+        assert!(handles[4].is_finished());
+
+        let kv_counts = stop_nodes(&stop_signal_sender, handles);
+        for id in [2, 3, 4, 6, 7] {
+            assert_eq!(kv_counts[id - 1], 300, "node {}", id);
+        }
+
+        let _ = fs::remove_dir_all(&data_dir);
     }
 }
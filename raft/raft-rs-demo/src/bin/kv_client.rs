@@ -0,0 +1,73 @@
+//! A command line client of the key-value API of `five_mem_node`.
+//!
+//! ```text
+//! kv_client --config five_nodes.conf put <key> <value>
+//! kv_client --config five_nodes.conf get <key>
+//! kv_client --config five_nodes.conf delete <key>
+//! kv_client --config five_nodes.conf change add-voter:<id> add-learner:<id> remove:<id> ...
+//! kv_client --config five_nodes.conf transfer-leader <id>
+//! ```
+//!
+//! The changes passed to `change` are applied at once, through a joint configuration if there
+//! are more than one.
+
+use std::process;
+
+use raft_rs_demo::command::MembershipChange;
+use raft_rs_demo::kv::KvClient;
+use raft_rs_demo::transport::PeerConfig;
+
+const USAGE: &str = "usage: kv_client --config <peers file> (put <key> <value> | get <key> | \
+    delete <key> | change (add-voter|add-learner|remove):<id>... | transfer-leader <id>)";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (config_path, cmd) = match args.as_slice() {
+        [flag, path, cmd @ ..] if flag == "--config" => (path, cmd),
+        _ => exit_with_usage(),
+    };
+    let config = PeerConfig::load(config_path).unwrap_or_else(|e| {
+        eprintln!("load peers config {:?} fail: {}", config_path, e);
+        process::exit(1);
+    });
+    let mut client = KvClient::new(config.kv_addrs);
+
+    let res = match cmd {
+        [op, key, value] if op == "put" => client.put(key, value).map(|_| None),
+        [op, key] if op == "get" => client.get(key),
+        [op, key] if op == "delete" => client.delete(key).map(|_| None),
+        [op, changes @ ..] if op == "change" && !changes.is_empty() => {
+            let changes = changes.iter().map(|c| parse_change(c)).collect();
+            client.change_membership(changes).map(|_| None)
+        }
+        [op, id] if op == "transfer-leader" => {
+            let id = id.parse().unwrap_or_else(|_| exit_with_usage());
+            client.transfer_leader(id).map(|_| None)
+        }
+        _ => exit_with_usage(),
+    };
+    match res {
+        Ok(Some(value)) => println!("{}", value),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn parse_change(s: &str) -> MembershipChange {
+    let (kind, id) = s.split_once(':').unwrap_or_else(|| exit_with_usage());
+    let id = id.parse().unwrap_or_else(|_| exit_with_usage());
+    match kind {
+        "add-voter" => MembershipChange::AddVoter(id),
+        "add-learner" => MembershipChange::AddLearner(id),
+        "remove" => MembershipChange::Remove(id),
+        _ => exit_with_usage(),
+    }
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
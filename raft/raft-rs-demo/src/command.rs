@@ -0,0 +1,398 @@
+//! The commands of the key-value state machine and their binary encoding.
+//!
+//! A command is encoded as a one byte tag followed by its fields, every string as a varint
+//! length and its UTF-8 bytes. `Put` and `Delete` are written to the raft log as the data of
+//! normal entries, `Get` is served by a read index and never enters the log. The admin
+//! commands, `ChangeMembership` and `TransferLeader`, are turned into raft conf changes and
+//! leader transfers.
+//!
+//! A snapshot of the state machine is the number of key-value pairs followed by the pairs,
+//! encoded the same way.
+
+use std::collections::HashMap;
+use std::io;
+
+const TAG_PUT: u8 = 1;
+const TAG_DELETE: u8 = 2;
+const TAG_GET: u8 = 3;
+const TAG_CHANGE_MEMBERSHIP: u8 = 4;
+const TAG_TRANSFER_LEADER: u8 = 5;
+
+const TAG_ADD_VOTER: u8 = 1;
+const TAG_ADD_LEARNER: u8 = 2;
+const TAG_REMOVE: u8 = 3;
+
+const TAG_OK_NONE: u8 = 1;
+const TAG_OK_SOME: u8 = 2;
+const TAG_NOT_LEADER: u8 = 3;
+const TAG_ERROR: u8 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Put {
+        key: String,
+        value: String,
+    },
+    Delete {
+        key: String,
+    },
+    Get {
+        key: String,
+    },
+    /// Apply all the changes at once. More than one change goes through a joint configuration.
+    ChangeMembership {
+        changes: Vec<MembershipChange>,
+    },
+    TransferLeader {
+        to: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MembershipChange {
+    /// Add a voter, or promote a learner.
+    AddVoter(u64),
+    /// Add a learner, or demote a voter.
+    AddLearner(u64),
+    Remove(u64),
+}
+
+/// The result of a command, returned to the client once the command is applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// The command is applied. Carries the value of the key for `Get`.
+    Ok(Option<String>),
+    /// The node is not the leader, `leader` is the leader it knows, or 0 if it knows none.
+    NotLeader {
+        leader: u64,
+    },
+    Error(String),
+}
+
+impl Command {
+    /// A `Get` doesn't change the state machine, it's not written to the log.
+    pub fn is_read(&self) -> bool {
+        matches!(self, Command::Get { .. })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match self {
+            Command::Put { key, value } => {
+                buf.push(TAG_PUT);
+                put_str(&mut buf, key);
+                put_str(&mut buf, value);
+            }
+            Command::Delete { key } => {
+                buf.push(TAG_DELETE);
+                put_str(&mut buf, key);
+            }
+            Command::Get { key } => {
+                buf.push(TAG_GET);
+                put_str(&mut buf, key);
+            }
+            Command::ChangeMembership { changes } => {
+                buf.push(TAG_CHANGE_MEMBERSHIP);
+                put_varint(&mut buf, changes.len() as u64);
+                for change in changes {
+                    let (tag, id) = match change {
+                        MembershipChange::AddVoter(id) => (TAG_ADD_VOTER, id),
+                        MembershipChange::AddLearner(id) => (TAG_ADD_LEARNER, id),
+                        MembershipChange::Remove(id) => (TAG_REMOVE, id),
+                    };
+                    buf.push(tag);
+                    put_varint(&mut buf, *id);
+                }
+            }
+            Command::TransferLeader { to } => {
+                buf.push(TAG_TRANSFER_LEADER);
+                put_varint(&mut buf, *to);
+            }
+        }
+        buf
+    }
+
+    pub fn decode(mut data: &[u8]) -> io::Result<Command> {
+        let buf = &mut data;
+        let cmd = match get_u8(buf)? {
+            TAG_PUT => Command::Put {
+                key: get_str(buf)?,
+                value: get_str(buf)?,
+            },
+            TAG_DELETE => Command::Delete { key: get_str(buf)? },
+            TAG_GET => Command::Get { key: get_str(buf)? },
+            TAG_CHANGE_MEMBERSHIP => {
+                let count = get_varint(buf)?;
+                let mut changes = Vec::new();
+                for _ in 0..count {
+                    let change = match (get_u8(buf)?, get_varint(buf)?) {
+                        (TAG_ADD_VOTER, id) => MembershipChange::AddVoter(id),
+                        (TAG_ADD_LEARNER, id) => MembershipChange::AddLearner(id),
+                        (TAG_REMOVE, id) => MembershipChange::Remove(id),
+                        (tag, _) => {
+                            return Err(invalid(format!("unknown membership change tag {}", tag)))
+                        }
+                    };
+                    changes.push(change);
+                }
+                Command::ChangeMembership { changes }
+            }
+            TAG_TRANSFER_LEADER => Command::TransferLeader {
+                to: get_varint(buf)?,
+            },
+            tag => return Err(invalid(format!("unknown command tag {}", tag))),
+        };
+        finish(buf)?;
+        Ok(cmd)
+    }
+}
+
+impl Response {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match self {
+            Response::Ok(None) => buf.push(TAG_OK_NONE),
+            Response::Ok(Some(value)) => {
+                buf.push(TAG_OK_SOME);
+                put_str(&mut buf, value);
+            }
+            Response::NotLeader { leader } => {
+                buf.push(TAG_NOT_LEADER);
+                put_varint(&mut buf, *leader);
+            }
+            Response::Error(msg) => {
+                buf.push(TAG_ERROR);
+                put_str(&mut buf, msg);
+            }
+        }
+        buf
+    }
+
+    pub fn decode(mut data: &[u8]) -> io::Result<Response> {
+        let buf = &mut data;
+        let resp = match get_u8(buf)? {
+            TAG_OK_NONE => Response::Ok(None),
+            TAG_OK_SOME => Response::Ok(Some(get_str(buf)?)),
+            TAG_NOT_LEADER => Response::NotLeader {
+                leader: get_varint(buf)?,
+            },
+            TAG_ERROR => Response::Error(get_str(buf)?),
+            tag => return Err(invalid(format!("unknown response tag {}", tag))),
+        };
+        finish(buf)?;
+        Ok(resp)
+    }
+}
+
+/// Encodes the key-value pairs of the state machine as the data of a snapshot.
+pub fn encode_kv_pairs(kv_pairs: &HashMap<String, String>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    put_varint(&mut buf, kv_pairs.len() as u64);
+    for (key, value) in kv_pairs {
+        put_str(&mut buf, key);
+        put_str(&mut buf, value);
+    }
+    buf
+}
+
+/// Decodes the data of a snapshot, an empty one holds no key-value pair.
+pub fn decode_kv_pairs(mut data: &[u8]) -> io::Result<HashMap<String, String>> {
+    if data.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let buf = &mut data;
+    let count = get_varint(buf)?;
+    let mut kv_pairs = HashMap::new();
+    for _ in 0..count {
+        let key = get_str(buf)?;
+        kv_pairs.insert(key, get_str(buf)?);
+    }
+    finish(buf)?;
+    Ok(kv_pairs)
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn finish(buf: &[u8]) -> io::Result<()> {
+    if buf.is_empty() {
+        Ok(())
+    } else {
+        Err(invalid(format!("{} trailing bytes", buf.len())))
+    }
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    put_varint(buf, s.len() as u64);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn get_u8(buf: &mut &[u8]) -> io::Result<u8> {
+    let (&b, rest) = buf
+        .split_first()
+        .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+    *buf = rest;
+    Ok(b)
+}
+
+fn get_varint(buf: &mut &[u8]) -> io::Result<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = get_u8(buf)?;
+        // The 10th byte only has room for the highest bit of a u64.
+        if shift == 63 && b > 1 {
+            return Err(invalid("varint overflows u64".to_string()));
+        }
+        v |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(invalid("varint overflows u64".to_string()))
+}
+
+fn get_str(buf: &mut &[u8]) -> io::Result<String> {
+    let len = get_varint(buf)? as usize;
+    if buf.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let (s, rest) = buf.split_at(len);
+    *buf = rest;
+    String::from_utf8(s.to_vec()).map_err(|e| invalid(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_round_trip() {
+        let long = "x".repeat(300);
+        let cmds = vec![
+            Command::Put {
+                key: "k".to_owned(),
+                value: "v".to_owned(),
+            },
+            Command::Put {
+                key: String::new(),
+                value: long.clone(),
+            },
+            Command::Delete {
+                key: "k".to_owned(),
+            },
+            Command::Get {
+                key: "键".to_owned(),
+            },
+            Command::ChangeMembership { changes: vec![] },
+            Command::ChangeMembership {
+                changes: vec![
+                    MembershipChange::AddVoter(6),
+                    MembershipChange::AddLearner(u64::MAX),
+                    MembershipChange::Remove(128),
+                ],
+            },
+            Command::TransferLeader { to: 3 },
+        ];
+        for cmd in cmds {
+            let data = cmd.encode();
+            assert_eq!(Command::decode(&data).unwrap(), cmd);
+
+            // Every prefix of an encoded command is incomplete.
+            for end in 0..data.len() {
+                assert!(
+                    Command::decode(&data[..end]).is_err(),
+                    "{:?} cut at {}",
+                    cmd,
+                    end
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_response_round_trip() {
+        let resps = vec![
+            Response::Ok(None),
+            Response::Ok(Some(String::new())),
+            Response::Ok(Some("v".to_owned())),
+            Response::NotLeader { leader: 0 },
+            Response::NotLeader { leader: 5 },
+            Response::Error("leader transfer to 3 failed".to_owned()),
+        ];
+        for resp in resps {
+            assert_eq!(Response::decode(&resp.encode()).unwrap(), resp);
+        }
+    }
+
+    #[test]
+    fn test_kv_pairs_round_trip() {
+        let kv_pairs = (0..200)
+            .map(|i| (format!("key {}", i), format!("value {}", i)))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(
+            decode_kv_pairs(&encode_kv_pairs(&kv_pairs)).unwrap(),
+            kv_pairs
+        );
+        assert!(decode_kv_pairs(&[]).unwrap().is_empty());
+        assert!(decode_kv_pairs(&encode_kv_pairs(&HashMap::new()))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        // Unknown tags.
+        assert!(Command::decode(&[0]).is_err());
+        assert!(Command::decode(&[TAG_CHANGE_MEMBERSHIP, 1, 9, 1]).is_err());
+        assert!(Response::decode(&[9]).is_err());
+
+        // Trailing bytes.
+        let mut data = Command::TransferLeader { to: 1 }.encode();
+        data.push(0);
+        assert!(Command::decode(&data).is_err());
+
+        // A varint longer than 64 bits, and a string that isn't UTF-8.
+        assert!(Command::decode(&[
+            TAG_TRANSFER_LEADER,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0x01
+        ])
+        .is_err());
+        assert!(Command::decode(&[TAG_DELETE, 2, 0xc3, 0x28]).is_err());
+    }
+
+    #[test]
+    fn test_varint_bounds() {
+        let max = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+        assert_eq!(get_varint(&mut &max[..]).unwrap(), u64::MAX);
+
+        // A 10th byte carrying bits beyond 64, with or without an 11th byte.
+        let mut data = max;
+        data[9] = 0x02;
+        let err = get_varint(&mut &data[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mut data = max.to_vec();
+        data[9] = 0x81;
+        data.push(0x01);
+        let err = get_varint(&mut &data[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,262 @@
+//! A small network API of the key-value store.
+//!
+//! A client sends encoded [`Command`]s over a TCP connection and gets an encoded [`Response`]
+//! back for each of them, once the command is applied. Both are framed by a `u32` big-endian
+//! length. Only the leader serves commands, the other nodes answer `NotLeader` and the
+//! [`KvClient`] retries on the leader.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use slog::{debug, info, warn, Logger};
+
+use crate::command::{Command, MembershipChange, Response};
+
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Serve the key-value API on `addr`. `handler` is called for every command, on the thread of
+/// the connection it comes from, and blocks until the command is applied.
+pub fn serve<A, F>(addr: A, handler: F, logger: &Logger) -> io::Result<()>
+where
+    A: ToSocketAddrs,
+    F: Fn(Command) -> Response + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind(addr)?;
+    info!(logger, "kv service listening"; "addr" => ?listener.local_addr()?);
+
+    let handler = Arc::new(handler);
+    let logger = logger.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!(logger, "accept kv connection fail: {:?}", e);
+                    continue;
+                }
+            };
+            let handler = Arc::clone(&handler);
+            let logger = logger.clone();
+            thread::spawn(move || {
+                if let Err(e) = serve_connection(stream, handler.as_ref()) {
+                    debug!(logger, "kv connection closed: {:?}", e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn serve_connection<F>(stream: TcpStream, handler: &F) -> io::Result<()>
+where
+    F: Fn(Command) -> Response,
+{
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    loop {
+        let resp = match Command::decode(&read_frame(&mut reader)?) {
+            Ok(cmd) => handler(cmd),
+            Err(e) => Response::Error(format!("invalid command: {}", e)),
+        };
+        write_frame(&mut writer, &resp.encode())?;
+        writer.flush()?;
+    }
+}
+
+/// A client of the key-value API, which follows the leader of the cluster.
+pub struct KvClient {
+    addrs: BTreeMap<u64, String>,
+    leader: Option<u64>,
+    conn: Option<(BufReader<TcpStream>, BufWriter<TcpStream>)>,
+    timeout: Duration,
+}
+
+impl KvClient {
+    /// A client of the nodes with the key-value API addresses `addrs`, a map[node_id -> addr].
+    pub fn new(addrs: BTreeMap<u64, String>) -> KvClient {
+        KvClient {
+            addrs,
+            leader: None,
+            conn: None,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    pub fn put(&mut self, key: &str, value: &str) -> io::Result<()> {
+        self.call(Command::Put {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+        .map(|_| ())
+    }
+
+    pub fn delete(&mut self, key: &str) -> io::Result<()> {
+        self.call(Command::Delete {
+            key: key.to_string(),
+        })
+        .map(|_| ())
+    }
+
+    /// A linearizable read of `key`, served by the leader with a read index.
+    pub fn get(&mut self, key: &str) -> io::Result<Option<String>> {
+        self.call(Command::Get {
+            key: key.to_string(),
+        })
+    }
+
+    /// Change the membership of the cluster, returns once the change is applied by the leader.
+    pub fn change_membership(&mut self, changes: Vec<MembershipChange>) -> io::Result<()> {
+        self.call(Command::ChangeMembership { changes }).map(|_| ())
+    }
+
+    /// Ask the leader to hand its leadership over to `to`. Returns once `to` is the leader, or
+    /// with an error if the transfer fails, e.g. if `to` doesn't catch up with the leader
+    /// within an election timeout or another node wins the election.
+    pub fn transfer_leader(&mut self, to: u64) -> io::Result<()> {
+        self.call(Command::TransferLeader { to }).map(|_| ())
+    }
+
+    /// Send `cmd` to the leader, trying every node until one of them is or knows the leader.
+    ///
+    /// A command that fails to be sent is sent to the next node. Once a command that changes
+    /// the state machine has been sent, a failure to read its response is returned instead: the
+    /// node may have applied it, sending it again could apply it twice.
+    pub fn call(&mut self, cmd: Command) -> io::Result<Option<String>> {
+        let data = cmd.encode();
+        let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no kv address");
+        for _ in 0..self.addrs.len() * 3 {
+            let id = self.target();
+            let resp = match self.send(id, &data) {
+                Ok(()) => self.recv(),
+                Err(e) => {
+                    self.conn = None;
+                    self.try_next(id);
+                    last_err = e;
+                    continue;
+                }
+            };
+            match resp {
+                Ok(Response::Ok(value)) => return Ok(value),
+                Ok(Response::NotLeader { leader }) => {
+                    self.conn = None;
+                    last_err = io::Error::other(format!("node {} is not the leader", id));
+                    if leader != 0 && leader != id && self.addrs.contains_key(&leader) {
+                        self.leader = Some(leader);
+                    } else {
+                        // An election may be going on.
+                        self.try_next(id);
+                        thread::sleep(Duration::from_millis(300));
+                    }
+                }
+                Ok(Response::Error(msg)) => return Err(io::Error::other(msg)),
+                Err(e) if !cmd.is_read() => {
+                    self.conn = None;
+                    return Err(io::Error::new(
+                        e.kind(),
+                        format!(
+                            "no response from node {}, the command may have been applied: {}",
+                            id, e
+                        ),
+                    ));
+                }
+                Err(e) => {
+                    self.conn = None;
+                    self.try_next(id);
+                    last_err = e;
+                }
+            }
+        }
+        Err(last_err)
+    }
+
+    fn target(&self) -> u64 {
+        self.leader
+            .unwrap_or_else(|| *self.addrs.keys().next().unwrap())
+    }
+
+    fn try_next(&mut self, current: u64) {
+        let next = self.addrs.keys().copied().find(|id| *id > current);
+        self.leader = next.or_else(|| self.addrs.keys().next().copied());
+    }
+
+    /// Connect to node `id` if not connected yet and write a command to it.
+    fn send(&mut self, id: u64, data: &[u8]) -> io::Result<()> {
+        if self.conn.is_none() {
+            let stream = TcpStream::connect(&self.addrs[&id])?;
+            stream.set_read_timeout(Some(self.timeout))?;
+            stream.set_nodelay(true)?;
+            self.conn = Some((BufReader::new(stream.try_clone()?), BufWriter::new(stream)));
+        }
+        let (_, writer) = self.conn.as_mut().unwrap();
+        write_frame(writer, data)?;
+        writer.flush()
+    }
+
+    fn recv(&mut self) -> io::Result<Response> {
+        let (reader, _) = self.conn.as_mut().unwrap();
+        Response::decode(&read_frame(reader)?)
+    }
+}
+
+fn write_frame<W: Write>(w: &mut W, data: &[u8]) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(data)
+}
+
+fn read_frame<R: Read>(r: &mut R) -> io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    r.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes is too large", len),
+        ));
+    }
+    let mut data = vec![0; len];
+    r.read_exact(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A node that reads every command and closes the connection without answering.
+    fn silent_node(received: Arc<AtomicUsize>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                if read_frame(&mut stream).is_ok() {
+                    received.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_no_resend_after_sent() {
+        let received = Arc::new(AtomicUsize::new(0));
+        let addrs: BTreeMap<_, _> = (1..=2)
+            .map(|id| (id, silent_node(received.clone())))
+            .collect();
+
+        // A put may have been applied by the node that received it, it's not sent again.
+        let mut client = KvClient::new(addrs);
+        let err = client.put("k", "v").unwrap_err();
+        assert!(err.to_string().contains("may have been applied"), "{}", err);
+        assert_eq!(received.load(Ordering::SeqCst), 1);
+
+        // A read is sent to every node in turn.
+        assert!(client.get("k").is_err());
+        assert_eq!(received.load(Ordering::SeqCst), 1 + 2 * 3);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod command;
+pub mod kv;
+pub mod storage;
+pub mod transport;
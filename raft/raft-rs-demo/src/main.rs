@@ -7,7 +7,7 @@ use std::thread;
 use std::time::{Duration, Instant};
 
 use raft::prelude::*;
-use raft::storage::MemStorage;
+use raft_rs_demo::storage::DiskStorage;
 
 use slog::{info, o};
 
@@ -26,10 +26,12 @@ enum Msg {
 
 // A simple example about how to use the Raft library in Rust.
 fn main() {
-    // Create a storage for Raft, and here we just use a simple memory storage.
-    // You need to build your own persistent storage in your production.
-    // Please check the Storage trait in src/storage.rs to see how to implement one.
-    let storage = MemStorage::new_with_conf_state(ConfState::from((vec![1], vec![])));
+    // Create a storage for Raft. It keeps the raft log, the hard state and the snapshots in a
+    // directory, so running the example again recovers the node from it.
+    // Please check src/storage.rs to see how it implements the Storage trait.
+    let dir = std::env::temp_dir().join("raft-rs-demo");
+    let storage =
+        DiskStorage::open_with_conf_state(&dir, ConfState::from((vec![1], vec![]))).unwrap();
 
     let decorator = slog_term::TermDecorator::new().build();
     let drain = slog_term::FullFormat::new(decorator).build().fuse();
@@ -108,7 +110,10 @@ fn main() {
     }
 }
 
-fn on_ready(raft_group: &mut RawNode<MemStorage>, callback_map: &mut HashMap<u8, ProposeCallback>) {
+fn on_ready(
+    raft_group: &mut RawNode<DiskStorage>,
+    callback_map: &mut HashMap<u8, ProposeCallback>,
+) {
     if !raft_group.has_ready() {
         return;
     }
@@ -138,7 +143,7 @@ fn on_ready(raft_group: &mut RawNode<MemStorage>, callback_map: &mut HashMap<u8,
     let mut handle_committed_entries = |committed_entries: Vec<Entry>| {
         for entry in committed_entries {
             // Mostly, you need to save the last apply index to resume applying
-            // after restart. Here we just ignore this because we use a Memory storage.
+            // after restart. Here we just ignore this and apply the committed log again.
             _last_apply_index = entry.index;
 
             if entry.data.is_empty() {
@@ -167,7 +172,7 @@ fn on_ready(raft_group: &mut RawNode<MemStorage>, callback_map: &mut HashMap<u8,
 
     if let Some(hs) = ready.hs() {
         // Raft HardState changed, and we need to persist it.
-        store.wl().set_hardstate(hs.clone());
+        store.wl().set_hardstate(hs.clone()).unwrap();
     }
 
     if !ready.persisted_messages().is_empty() {
@@ -179,7 +184,7 @@ fn on_ready(raft_group: &mut RawNode<MemStorage>, callback_map: &mut HashMap<u8,
     let mut light_rd = raft_group.advance(ready);
     // Update commit index.
     if let Some(commit) = light_rd.commit_index() {
-        store.wl().mut_hard_state().set_commit(commit);
+        store.wl().set_commit(commit).unwrap();
     }
     // Send out the messages.
     handle_messages(light_rd.take_messages());
@@ -0,0 +1,853 @@
+//! A disk backed implementation of the raft `Storage` trait.
+//!
+//! The storage directory holds:
+//!
+//! - `raft_state`: the `HardState`, the `ConfState` and the index and term of the last entry
+//!   dropped from the log, rewritten as a whole on every change.
+//! - `snapshot`: the last snapshot applied or created by the application, the one sent to
+//!   followers which can't catch up with the log.
+//! - `log/<first index>.log`: the raft log, split into append-only segments. Each entry is a
+//!   record of `len: u32 | crc32: u32 | Entry`, a torn record at the tail of the last segment is
+//!   cut off when the storage is opened.
+//!
+//! Files are replaced by writing a temporary file, fsync-ing it and renaming it over the old
+//! one; appended entries are fsync-ed before `append` returns. All entries are also kept in
+//! memory so reads never touch the disk.
+
+use std::cmp;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use protobuf::Message as PbMessage;
+use raft::prelude::*;
+use raft::util::limit_size;
+use raft::{Error, GetEntriesContext, RaftState, Result, Storage, StorageError};
+
+/// A new segment is started once the active one grows past this size.
+pub const DEFAULT_SEGMENT_SIZE: u64 = 64 * 1024 * 1024;
+
+const RAFT_STATE_FILE: &str = "raft_state";
+const SNAPSHOT_FILE: &str = "snapshot";
+const LOG_DIR: &str = "log";
+const RECORD_HEADER_SIZE: u64 = 8;
+
+struct Segment {
+    first_index: u64,
+    path: PathBuf,
+    file: File,
+    // offsets[i] is where the record of entry `first_index + i` starts.
+    offsets: Vec<u64>,
+    len: u64,
+}
+
+impl Segment {
+    fn last_index(&self) -> u64 {
+        self.first_index + self.offsets.len() as u64 - 1
+    }
+}
+
+/// The state of a `DiskStorage`. To access it, use the `rl` and `wl` functions of
+/// `DiskStorage`.
+pub struct DiskStorageCore {
+    dir: PathBuf,
+    raft_state: RaftState,
+    // The last entry dropped from the log, by compaction or by a snapshot.
+    truncated_index: u64,
+    truncated_term: u64,
+    // entries[i] has raft log position i + truncated_index + 1.
+    entries: Vec<Entry>,
+    segments: Vec<Segment>,
+    segment_size: u64,
+    snapshot: Snapshot,
+    // Raft asked for a snapshot the last one can't serve.
+    snapshot_requested: bool,
+}
+
+impl DiskStorageCore {
+    fn open(dir: &Path, segment_size: u64) -> Result<DiskStorageCore> {
+        fs::create_dir_all(dir.join(LOG_DIR))?;
+
+        let mut core = DiskStorageCore {
+            dir: dir.to_path_buf(),
+            raft_state: RaftState::default(),
+            truncated_index: 0,
+            truncated_term: 0,
+            entries: vec![],
+            segments: vec![],
+            segment_size,
+            snapshot: Snapshot::default(),
+            snapshot_requested: false,
+        };
+
+        if let Some(data) = read_file(&dir.join(RAFT_STATE_FILE))? {
+            core.decode_raft_state(&data)?;
+        }
+        if let Some(data) = read_file(&dir.join(SNAPSHOT_FILE))? {
+            core.snapshot.merge_from_bytes(&data)?;
+        }
+        core.recover_log()?;
+
+        Ok(core)
+    }
+
+    /// Load the log segments, dropping the segments left behind by a compaction that was cut
+    /// short, and the torn record a crash may have left at the tail of the last segment.
+    fn recover_log(&mut self) -> Result<()> {
+        let log_dir = self.dir.join(LOG_DIR);
+        let mut first_indexes = vec![];
+        for dir_entry in fs::read_dir(&log_dir)? {
+            let path = dir_entry?.path();
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            if let Some(index) = name
+                .strip_suffix(".log")
+                .and_then(|i| i.parse::<u64>().ok())
+            {
+                first_indexes.push(index);
+            }
+        }
+        first_indexes.sort_unstable();
+
+        let count = first_indexes.len();
+        for (i, first_index) in first_indexes.into_iter().enumerate() {
+            let path = segment_path(&log_dir, first_index);
+            let is_last = i + 1 == count;
+            let (segment, entries) = load_segment(&path, first_index, is_last)?;
+
+            if segment.offsets.is_empty() || segment.last_index() <= self.truncated_index {
+                fs::remove_file(&path)?;
+                continue;
+            }
+            if let Some(prev) = self.segments.last() {
+                if prev.last_index() + 1 != first_index {
+                    return Err(corrupted(format!(
+                        "log segments are not continuous, {} is followed by {}",
+                        prev.last_index(),
+                        first_index
+                    )));
+                }
+            }
+
+            self.entries.extend(
+                entries
+                    .into_iter()
+                    .filter(|e| e.index > self.truncated_index),
+            );
+            self.segments.push(segment);
+        }
+
+        if let Some(e) = self.entries.first() {
+            if e.index != self.truncated_index + 1 {
+                return Err(corrupted(format!(
+                    "log starts at {} but the last truncated entry is {}",
+                    e.index, self.truncated_index
+                )));
+            }
+        }
+        sync_dir(&log_dir)?;
+        Ok(())
+    }
+
+    /// Saves the current HardState.
+    pub fn set_hardstate(&mut self, hs: HardState) -> Result<()> {
+        self.raft_state.hard_state = hs;
+        self.persist_raft_state()
+    }
+
+    /// Get the hard state.
+    pub fn hard_state(&self) -> &HardState {
+        &self.raft_state.hard_state
+    }
+
+    /// Saves the commit index of the current HardState.
+    pub fn set_commit(&mut self, commit: u64) -> Result<()> {
+        self.raft_state.hard_state.commit = commit;
+        self.persist_raft_state()
+    }
+
+    /// Saves the current conf state.
+    pub fn set_conf_state(&mut self, cs: ConfState) -> Result<()> {
+        self.raft_state.conf_state = cs;
+        self.persist_raft_state()
+    }
+
+    fn first_index(&self) -> u64 {
+        self.truncated_index + 1
+    }
+
+    fn last_index(&self) -> u64 {
+        match self.entries.last() {
+            Some(e) => e.index,
+            None => self.truncated_index,
+        }
+    }
+
+    /// Overwrites the contents of this Storage object with those of the given snapshot, and
+    /// drops the whole log.
+    pub fn apply_snapshot(&mut self, snapshot: Snapshot) -> Result<()> {
+        let meta = snapshot.get_metadata();
+        let index = meta.index;
+
+        if self.first_index() > index {
+            return Err(Error::Store(StorageError::SnapshotOutOfDate));
+        }
+
+        write_file_atomic(&self.dir, SNAPSHOT_FILE, &snapshot.write_to_bytes()?)?;
+
+        self.truncated_index = index;
+        self.truncated_term = meta.term;
+        self.raft_state.hard_state.term = cmp::max(self.raft_state.hard_state.term, meta.term);
+        self.raft_state.hard_state.commit = index;
+        self.raft_state.conf_state = meta.get_conf_state().clone();
+        self.snapshot = snapshot;
+        self.persist_raft_state()?;
+
+        self.entries.clear();
+        for segment in self.segments.drain(..) {
+            fs::remove_file(&segment.path)?;
+        }
+        sync_dir(&self.dir.join(LOG_DIR))?;
+        Ok(())
+    }
+
+    /// The last snapshot applied or created.
+    pub fn last_snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    /// Whether raft needs a snapshot newer than the last one, e.g. for a follower added after
+    /// it. The application should create one with [`DiskStorageCore::create_snapshot`].
+    pub fn snapshot_requested(&self) -> bool {
+        self.snapshot_requested
+    }
+
+    /// Creates a snapshot of the state machine at the applied index `index`, which becomes the
+    /// snapshot sent to followers that can't catch up with the log. The log isn't compacted,
+    /// see [`DiskStorageCore::compact`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not in the log.
+    pub fn create_snapshot(&mut self, index: u64, data: Vec<u8>) -> Result<()> {
+        if index <= self.snapshot.get_metadata().index {
+            return Err(Error::Store(StorageError::SnapshotOutOfDate));
+        }
+        if index < self.first_index() || index > self.last_index() {
+            panic!(
+                "snapshot index {} is out of the log [{}, {}]",
+                index,
+                self.first_index(),
+                self.last_index()
+            );
+        }
+
+        let mut snapshot = Snapshot::default();
+        snapshot.set_data(data.into());
+        let meta = snapshot.mut_metadata();
+        meta.index = index;
+        meta.term = self.entries[(index - self.first_index()) as usize].term;
+        meta.set_conf_state(self.raft_state.conf_state.clone());
+
+        write_file_atomic(&self.dir, SNAPSHOT_FILE, &snapshot.write_to_bytes()?)?;
+        self.snapshot = snapshot;
+        self.snapshot_requested = false;
+        Ok(())
+    }
+
+    /// Discards all log entries prior to compact_index.
+    /// It is the application's responsibility to not attempt to compact an index
+    /// greater than RaftLog.applied.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `compact_index` is higher than `Storage::last_index(&self) + 1`.
+    pub fn compact(&mut self, compact_index: u64) -> Result<()> {
+        if compact_index <= self.first_index() {
+            // Don't need to treat this case as an error.
+            return Ok(());
+        }
+
+        if compact_index > self.last_index() + 1 {
+            panic!(
+                "compact not received raft logs: {}, last index: {}",
+                compact_index,
+                self.last_index()
+            );
+        }
+
+        let offset = compact_index - self.first_index();
+        self.truncated_term = self.entries[offset as usize - 1].term;
+        self.truncated_index = compact_index - 1;
+        self.entries.drain(..offset as usize);
+
+        // Persist the new first index before dropping anything, segments left behind by a crash
+        // in between are removed when the storage is opened again.
+        self.persist_raft_state()?;
+
+        let mut removed = false;
+        while self
+            .segments
+            .first()
+            .is_some_and(|s| s.last_index() < compact_index)
+        {
+            let segment = self.segments.remove(0);
+            fs::remove_file(&segment.path)?;
+            removed = true;
+        }
+        if removed {
+            sync_dir(&self.dir.join(LOG_DIR))?;
+        }
+        Ok(())
+    }
+
+    /// Append the new entries to storage, and fsync them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ents` contains compacted entries, or there's a gap between `ents` and the last
+    /// received entry in the storage.
+    pub fn append(&mut self, ents: &[Entry]) -> Result<()> {
+        if ents.is_empty() {
+            return Ok(());
+        }
+        if self.first_index() > ents[0].index {
+            panic!(
+                "overwrite compacted raft logs, compacted: {}, append: {}",
+                self.first_index() - 1,
+                ents[0].index,
+            );
+        }
+        if self.last_index() + 1 < ents[0].index {
+            panic!(
+                "raft logs should be continuous, last index: {}, new appended: {}",
+                self.last_index(),
+                ents[0].index,
+            );
+        }
+
+        // Remove all entries overwritten by `ents`.
+        if ents[0].index <= self.last_index() {
+            self.truncate_from(ents[0].index)?;
+        }
+
+        let mut dir_changed = false;
+        for ent in ents {
+            let rolled = self
+                .segments
+                .last()
+                .is_none_or(|s| s.len >= self.segment_size);
+            if rolled {
+                if let Some(s) = self.segments.last() {
+                    s.file.sync_data()?;
+                }
+                self.segments
+                    .push(create_segment(&self.dir.join(LOG_DIR), ent.index)?);
+                dir_changed = true;
+            }
+
+            let payload = ent.write_to_bytes()?;
+            let mut record = Vec::with_capacity(RECORD_HEADER_SIZE as usize + payload.len());
+            record.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            record.extend_from_slice(&crc32fast::hash(&payload).to_be_bytes());
+            record.extend_from_slice(&payload);
+
+            let segment = self.segments.last_mut().unwrap();
+            segment.file.write_all(&record)?;
+            segment.offsets.push(segment.len);
+            segment.len += record.len() as u64;
+        }
+
+        self.segments.last().unwrap().file.sync_data()?;
+        if dir_changed {
+            sync_dir(&self.dir.join(LOG_DIR))?;
+        }
+
+        self.entries.extend_from_slice(ents);
+        Ok(())
+    }
+
+    /// Remove the entries from `index` on, from the disk and from memory.
+    fn truncate_from(&mut self, index: u64) -> Result<()> {
+        let mut removed = false;
+        while self.segments.last().is_some_and(|s| s.first_index >= index) {
+            let segment = self.segments.pop().unwrap();
+            fs::remove_file(&segment.path)?;
+            removed = true;
+        }
+        if removed {
+            sync_dir(&self.dir.join(LOG_DIR))?;
+        }
+
+        if let Some(segment) = self.segments.last_mut() {
+            if segment.last_index() >= index {
+                let keep = (index - segment.first_index) as usize;
+                segment.len = segment.offsets[keep];
+                segment.offsets.truncate(keep);
+                segment.file.set_len(segment.len)?;
+                segment.file.sync_all()?;
+            }
+        }
+
+        let keep = (index - self.first_index()) as usize;
+        self.entries.truncate(keep);
+        Ok(())
+    }
+
+    fn persist_raft_state(&self) -> Result<()> {
+        let hs = self.raft_state.hard_state.write_to_bytes()?;
+        let cs = self.raft_state.conf_state.write_to_bytes()?;
+
+        let mut data = vec![];
+        data.extend_from_slice(&self.truncated_index.to_be_bytes());
+        data.extend_from_slice(&self.truncated_term.to_be_bytes());
+        data.extend_from_slice(&(hs.len() as u32).to_be_bytes());
+        data.extend_from_slice(&hs);
+        data.extend_from_slice(&(cs.len() as u32).to_be_bytes());
+        data.extend_from_slice(&cs);
+        let crc = crc32fast::hash(&data);
+        data.extend_from_slice(&crc.to_be_bytes());
+
+        write_file_atomic(&self.dir, RAFT_STATE_FILE, &data)
+    }
+
+    fn decode_raft_state(&mut self, data: &[u8]) -> Result<()> {
+        if data.len() < 4 {
+            return Err(corrupted("raft state is too short".to_string()));
+        }
+        let (body, crc) = data.split_at(data.len() - 4);
+        if crc32fast::hash(body).to_be_bytes() != crc {
+            return Err(corrupted("raft state checksum mismatch".to_string()));
+        }
+
+        let mut r = body;
+        self.truncated_index = u64::from_be_bytes(take(&mut r, 8)?.try_into().unwrap());
+        self.truncated_term = u64::from_be_bytes(take(&mut r, 8)?.try_into().unwrap());
+        let hs_len = u32::from_be_bytes(take(&mut r, 4)?.try_into().unwrap()) as usize;
+        self.raft_state
+            .hard_state
+            .merge_from_bytes(take(&mut r, hs_len)?)?;
+        let cs_len = u32::from_be_bytes(take(&mut r, 4)?.try_into().unwrap()) as usize;
+        self.raft_state
+            .conf_state
+            .merge_from_bytes(take(&mut r, cs_len)?)?;
+        Ok(())
+    }
+}
+
+/// `DiskStorage` is a thread-safe implementation of `Storage` that persists the raft log, the
+/// hard state, the conf state and the snapshots in a directory, see the module documentation.
+///
+/// Like `MemStorage` it only holds raft data, the applied data is kept by the application.
+#[derive(Clone)]
+pub struct DiskStorage {
+    core: Arc<RwLock<DiskStorageCore>>,
+}
+
+impl DiskStorage {
+    /// Opens the storage in `dir`, creating it if it does not exist yet.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<DiskStorage> {
+        Self::open_with_segment_size(dir, DEFAULT_SEGMENT_SIZE)
+    }
+
+    pub fn open_with_segment_size<P: AsRef<Path>>(
+        dir: P,
+        segment_size: u64,
+    ) -> Result<DiskStorage> {
+        let core = DiskStorageCore::open(dir.as_ref(), segment_size)?;
+        Ok(DiskStorage {
+            core: Arc::new(RwLock::new(core)),
+        })
+    }
+
+    /// Opens the storage in `dir`, and initializes it with `conf_state` if it is new.
+    ///
+    /// You should use the same input to initialize all nodes.
+    pub fn open_with_conf_state<P, T>(dir: P, conf_state: T) -> Result<DiskStorage>
+    where
+        P: AsRef<Path>,
+        ConfState: From<T>,
+    {
+        let store = DiskStorage::open(dir)?;
+        if !store.initial_state()?.initialized() {
+            store.wl().set_conf_state(ConfState::from(conf_state))?;
+        }
+        Ok(store)
+    }
+
+    /// Opens up a read lock on the storage and returns a guard handle. Use this
+    /// with functions that don't require mutation.
+    pub fn rl(&self) -> RwLockReadGuard<'_, DiskStorageCore> {
+        self.core.read().unwrap()
+    }
+
+    /// Opens up a write lock on the storage and returns guard handle. Use this
+    /// with functions that take a mutable reference to self.
+    pub fn wl(&self) -> RwLockWriteGuard<'_, DiskStorageCore> {
+        self.core.write().unwrap()
+    }
+}
+
+impl Storage for DiskStorage {
+    fn initial_state(&self) -> Result<RaftState> {
+        Ok(self.rl().raft_state.clone())
+    }
+
+    fn entries(
+        &self,
+        low: u64,
+        high: u64,
+        max_size: impl Into<Option<u64>>,
+        _context: GetEntriesContext,
+    ) -> Result<Vec<Entry>> {
+        let core = self.rl();
+        if low < core.first_index() {
+            return Err(Error::Store(StorageError::Compacted));
+        }
+
+        if high > core.last_index() + 1 {
+            panic!(
+                "index out of bound (last: {}, high: {})",
+                core.last_index() + 1,
+                high
+            );
+        }
+
+        let offset = core.first_index();
+        let lo = (low - offset) as usize;
+        let hi = (high - offset) as usize;
+        let mut ents = core.entries[lo..hi].to_vec();
+        limit_size(&mut ents, max_size.into());
+        Ok(ents)
+    }
+
+    fn term(&self, idx: u64) -> Result<u64> {
+        let core = self.rl();
+        if idx == core.truncated_index {
+            return Ok(core.truncated_term);
+        }
+
+        if idx < core.first_index() {
+            return Err(Error::Store(StorageError::Compacted));
+        }
+
+        if idx > core.last_index() {
+            return Err(Error::Store(StorageError::Unavailable));
+        }
+
+        Ok(core.entries[(idx - core.first_index()) as usize].term)
+    }
+
+    fn first_index(&self) -> Result<u64> {
+        Ok(self.rl().first_index())
+    }
+
+    fn last_index(&self) -> Result<u64> {
+        Ok(self.rl().last_index())
+    }
+
+    fn snapshot(&self, request_index: u64, to: u64) -> Result<Snapshot> {
+        let mut core = self.wl();
+        let meta = core.snapshot.get_metadata();
+        // A peer ignores a snapshot whose configuration doesn't include it.
+        let cs = meta.get_conf_state();
+        let includes_to = [
+            &cs.voters,
+            &cs.learners,
+            &cs.voters_outgoing,
+            &cs.learners_next,
+        ]
+        .iter()
+        .any(|ids| ids.contains(&to));
+        if meta.index < request_index || !includes_to {
+            // Raft asks again later, once the application has created a newer snapshot.
+            core.snapshot_requested = true;
+            return Err(Error::Store(StorageError::SnapshotTemporarilyUnavailable));
+        }
+        Ok(core.snapshot.clone())
+    }
+}
+
+fn corrupted(msg: String) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+fn take<'a>(r: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+    if r.len() < n {
+        return Err(corrupted("raft state is truncated".to_string()));
+    }
+    let (head, tail) = r.split_at(n);
+    *r = tail;
+    Ok(head)
+}
+
+fn segment_path(log_dir: &Path, first_index: u64) -> PathBuf {
+    log_dir.join(format!("{:020}.log", first_index))
+}
+
+fn create_segment(log_dir: &Path, first_index: u64) -> Result<Segment> {
+    let path = segment_path(log_dir, first_index);
+    // Opened for appending like a loaded segment, so that the writes after `truncate_from`
+    // shortens the file land at its new end rather than at the old write position.
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+    file.set_len(0)?;
+    Ok(Segment {
+        first_index,
+        path,
+        file,
+        offsets: vec![],
+        len: 0,
+    })
+}
+
+/// Read the entries of a segment. A torn or corrupted record is only tolerated at the end of
+/// the last segment, where a crash in the middle of an append leaves it; it is cut off.
+fn load_segment(path: &Path, first_index: u64, is_last: bool) -> Result<(Segment, Vec<Entry>)> {
+    let mut data = vec![];
+    File::open(path)?.read_to_end(&mut data)?;
+
+    let mut offsets = vec![];
+    let mut entries = vec![];
+    let mut pos = 0usize;
+    let valid_len = loop {
+        if pos == data.len() {
+            break pos;
+        }
+
+        let record = parse_record(&data[pos..]);
+        let (payload, size) = match record {
+            Some(r) => r,
+            None if is_last => break pos,
+            None => return Err(corrupted(format!("corrupted record in {}", path.display()))),
+        };
+
+        let mut entry = Entry::default();
+        entry.merge_from_bytes(payload)?;
+        let expected = first_index + offsets.len() as u64;
+        if entry.index != expected {
+            return Err(corrupted(format!(
+                "{} has entry {} where {} is expected",
+                path.display(),
+                entry.index,
+                expected
+            )));
+        }
+
+        offsets.push(pos as u64);
+        entries.push(entry);
+        pos += size;
+    };
+
+    let file = OpenOptions::new().append(true).open(path)?;
+    if valid_len < data.len() {
+        file.set_len(valid_len as u64)?;
+        file.sync_all()?;
+    }
+
+    let segment = Segment {
+        first_index,
+        path: path.to_path_buf(),
+        file,
+        offsets,
+        len: valid_len as u64,
+    };
+    Ok((segment, entries))
+}
+
+/// The payload of the record at the start of `data` and the size of the whole record, `None`
+/// if it is incomplete or fails its checksum.
+fn parse_record(data: &[u8]) -> Option<(&[u8], usize)> {
+    if data.len() < RECORD_HEADER_SIZE as usize {
+        return None;
+    }
+    let len = u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize;
+    let crc = u32::from_be_bytes(data[4..8].try_into().unwrap());
+    let payload = data.get(RECORD_HEADER_SIZE as usize..RECORD_HEADER_SIZE as usize + len)?;
+    if crc32fast::hash(payload) != crc {
+        return None;
+    }
+    Some((payload, RECORD_HEADER_SIZE as usize + len))
+}
+
+fn read_file(path: &Path) -> Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Replace `dir/name` with `data`, so that after a crash the file holds either the old or the
+/// new content.
+fn write_file_atomic(dir: &Path, name: &str, data: &[u8]) -> Result<()> {
+    let tmp = dir.join(format!("{}.tmp", name));
+    let mut file = File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&tmp, dir.join(name))?;
+    sync_dir(dir)
+}
+
+/// Make the creation, removal or renaming of files in `dir` durable.
+fn sync_dir(dir: &Path) -> Result<()> {
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_entry(index: u64, term: u64) -> Entry {
+        Entry {
+            index,
+            term,
+            data: format!("entry {}", index).into_bytes().into(),
+            ..Default::default()
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("disk_storage_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn all_entries(storage: &DiskStorage) -> Vec<(u64, u64)> {
+        let first = storage.first_index().unwrap();
+        let last = storage.last_index().unwrap();
+        storage
+            .entries(first, last + 1, None, GetEntriesContext::empty(false))
+            .unwrap()
+            .iter()
+            .map(|e| (e.index, e.term))
+            .collect()
+    }
+
+    fn segment_count(dir: &Path) -> usize {
+        fs::read_dir(dir.join(LOG_DIR)).unwrap().count()
+    }
+
+    #[test]
+    fn test_append_and_reopen() {
+        let dir = temp_dir("append");
+        // Small segments, every few entries start a new one.
+        let storage = DiskStorage::open_with_segment_size(&dir, 64).unwrap();
+        let ents = (1..=10).map(|i| new_entry(i, 1)).collect::<Vec<_>>();
+        storage.wl().append(&ents[..4]).unwrap();
+        storage.wl().append(&ents[4..]).unwrap();
+        let hs = HardState {
+            term: 1,
+            vote: 2,
+            commit: 7,
+            ..Default::default()
+        };
+        storage.wl().set_hardstate(hs.clone()).unwrap();
+        assert!(segment_count(&dir) > 1);
+        drop(storage);
+
+        let storage = DiskStorage::open_with_segment_size(&dir, 64).unwrap();
+        assert_eq!(
+            all_entries(&storage),
+            (1..=10).map(|i| (i, 1)).collect::<Vec<_>>()
+        );
+        assert_eq!(storage.initial_state().unwrap().hard_state, hs);
+        assert_eq!(
+            storage
+                .entries(3, 5, None, GetEntriesContext::empty(false))
+                .unwrap(),
+            ents[2..4].to_vec()
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_truncate_and_reopen() {
+        for segment_size in [DEFAULT_SEGMENT_SIZE, 64] {
+            let dir = temp_dir(&format!("truncate_{}", segment_size));
+            let storage = DiskStorage::open_with_segment_size(&dir, segment_size).unwrap();
+            let ents = (1..=10).map(|i| new_entry(i, 1)).collect::<Vec<_>>();
+            storage.wl().append(&ents).unwrap();
+
+            // A new leader overwrites the tail in the middle of a segment, then appends more.
+            let ents = (6..=8).map(|i| new_entry(i, 2)).collect::<Vec<_>>();
+            storage.wl().append(&ents).unwrap();
+            storage.wl().append(&[new_entry(9, 2)]).unwrap();
+
+            let want = (1..=5)
+                .map(|i| (i, 1))
+                .chain((6..=9).map(|i| (i, 2)))
+                .collect::<Vec<_>>();
+            assert_eq!(all_entries(&storage), want, "segment size {}", segment_size);
+            drop(storage);
+
+            // The file holds no hole or stale record the reopened log would trip over.
+            let storage = DiskStorage::open_with_segment_size(&dir, segment_size).unwrap();
+            assert_eq!(all_entries(&storage), want, "segment size {}", segment_size);
+
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+
+    #[test]
+    fn test_torn_tail_is_cut_off() {
+        let dir = temp_dir("torn");
+        let storage = DiskStorage::open(&dir).unwrap();
+        storage
+            .wl()
+            .append(&(1..=3).map(|i| new_entry(i, 1)).collect::<Vec<_>>())
+            .unwrap();
+        drop(storage);
+
+        // A crash in the middle of an append leaves half a record behind.
+        let path = segment_path(&dir.join(LOG_DIR), 1);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0, 0, 0, 100, 1, 2]).unwrap();
+        drop(file);
+
+        let storage = DiskStorage::open(&dir).unwrap();
+        assert_eq!(storage.last_index().unwrap(), 3);
+        storage.wl().append(&[new_entry(4, 1)]).unwrap();
+        drop(storage);
+        let storage = DiskStorage::open(&dir).unwrap();
+        assert_eq!(
+            all_entries(&storage),
+            (1..=4).map(|i| (i, 1)).collect::<Vec<_>>()
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_compact_and_reopen() {
+        let dir = temp_dir("compact");
+        let storage = DiskStorage::open_with_segment_size(&dir, 64).unwrap();
+        storage
+            .wl()
+            .append(&(1..=10).map(|i| new_entry(i, i)).collect::<Vec<_>>())
+            .unwrap();
+        let segments = segment_count(&dir);
+
+        storage.wl().compact(7).unwrap();
+        assert_eq!(storage.first_index().unwrap(), 7);
+        assert_eq!(storage.term(6).unwrap(), 6);
+        assert_eq!(storage.term(5), Err(Error::Store(StorageError::Compacted)));
+        assert!(segment_count(&dir) < segments);
+        drop(storage);
+
+        let storage = DiskStorage::open_with_segment_size(&dir, 64).unwrap();
+        assert_eq!(storage.first_index().unwrap(), 7);
+        assert_eq!(storage.term(6).unwrap(), 6);
+        assert_eq!(
+            all_entries(&storage),
+            (7..=10).map(|i| (i, i)).collect::<Vec<_>>()
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
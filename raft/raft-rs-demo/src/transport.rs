@@ -0,0 +1,386 @@
+//! A TCP transport for raft messages, so that every node can run in its own process.
+//!
+//! Every node listens on its address from the peers config file. Outgoing messages are queued
+//! per peer and sent by one thread per peer over a single long-lived connection, which is
+//! re-established with a backoff when it breaks. The messages queued while a batch is being
+//! written are sent together in the next one.
+//!
+//! A batch is framed as `frame_len: u32 | count: u32 | (len: u32 | Message)*`, all integers
+//! big-endian and every `Message` protobuf encoded.
+//!
+//! Messages which can not be delivered are dropped: when sending a batch fails, the batch and
+//! every message queued for the peer until the backoff is over are discarded and logged. Raft
+//! does not rely on delivery, the leader sends the entries again once the follower answers a
+//! heartbeat, a follower that missed a snapshot rejects the entries after it and is sent a new
+//! one, and a lost vote is cast again in the next election.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use protobuf::Message as PbMessage;
+use raft::prelude::Message;
+use slog::{debug, info, warn, Logger};
+
+/// At most this many messages are sent in one batch.
+pub const MAX_BATCH_SIZE: usize = 256;
+
+const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/// The addresses of the nodes of a cluster, loaded from a file with one
+/// `<id> <raft host:port> [<kv host:port>]` per line, the second address being the one the node
+/// serves the key-value API on. Empty lines and lines starting with `#` are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct PeerConfig {
+    pub peers: BTreeMap<u64, String>,
+    pub kv_addrs: BTreeMap<u64, String>,
+}
+
+impl PeerConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<PeerConfig> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(s: &str) -> io::Result<PeerConfig> {
+        let mut config = PeerConfig::default();
+        for (n, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |msg: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}: {:?}", n + 1, msg, line),
+                )
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (id, addr, kv_addr) = match fields[..] {
+                [id, addr] => (id, addr, None),
+                [id, addr, kv_addr] => (id, addr, Some(kv_addr)),
+                _ => return Err(invalid("expect `<id> <host:port> [<host:port>]`")),
+            };
+            let id = id.parse::<u64>().map_err(|_| invalid("invalid node id"))?;
+            if config.peers.insert(id, addr.to_string()).is_some() {
+                return Err(invalid("duplicated node id"));
+            }
+            if let Some(kv_addr) = kv_addr {
+                config.kv_addrs.insert(id, kv_addr.to_string());
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn addr(&self, id: u64) -> Option<&str> {
+        self.peers.get(&id).map(|s| s.as_str())
+    }
+
+    pub fn kv_addr(&self, id: u64) -> Option<&str> {
+        self.kv_addrs.get(&id).map(|s| s.as_str())
+    }
+}
+
+/// The TCP transport of one node.
+pub struct Transport {
+    mailboxes: HashMap<u64, Sender<Message>>,
+}
+
+impl Transport {
+    /// Listen on the address of node `id` and deliver the received messages to `inbox`, and
+    /// start a sender for every other peer.
+    pub fn start(
+        id: u64,
+        config: &PeerConfig,
+        inbox: Sender<Message>,
+        logger: &Logger,
+    ) -> io::Result<Transport> {
+        let addr = config.addr(id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("node {} is not in the peers config", id),
+            )
+        })?;
+        let listener = TcpListener::bind(addr)?;
+        info!(logger, "raft transport listening"; "addr" => addr);
+
+        let accept_logger = logger.clone();
+        thread::spawn(move || accept_loop(listener, inbox, accept_logger));
+
+        let mut mailboxes = HashMap::new();
+        for (peer, addr) in &config.peers {
+            if *peer == id {
+                continue;
+            }
+            let (tx, rx) = mpsc::channel();
+            let sender = PeerSender {
+                peer: *peer,
+                addr: addr.clone(),
+                queue: rx,
+                conn: None,
+                logger: logger.clone(),
+            };
+            thread::spawn(move || sender.run());
+            mailboxes.insert(*peer, tx);
+        }
+
+        Ok(Transport { mailboxes })
+    }
+
+    /// A map[peer_id -> sender]. A message sent to one of them is delivered to the peer over
+    /// TCP.
+    pub fn mailboxes(&self) -> HashMap<u64, Sender<Message>> {
+        self.mailboxes.clone()
+    }
+}
+
+fn accept_loop(listener: TcpListener, inbox: Sender<Message>, logger: Logger) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                warn!(logger, "accept raft connection fail: {:?}", e);
+                continue;
+            }
+        };
+        let peer_addr = stream.peer_addr().ok();
+        let inbox = inbox.clone();
+        let logger = logger.clone();
+        thread::spawn(move || {
+            if let Err(e) = receive_loop(stream, &inbox) {
+                debug!(
+                    logger,
+                    "raft connection from {:?} closed: {:?}", peer_addr, e
+                );
+            }
+        });
+    }
+}
+
+fn receive_loop(stream: TcpStream, inbox: &Sender<Message>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    loop {
+        for msg in read_batch(&mut reader)? {
+            if inbox.send(msg).is_err() {
+                // The node has stopped.
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Sends the messages queued for one peer, reconnecting when the connection breaks. See the
+/// module documentation for the messages dropped meanwhile.
+struct PeerSender {
+    peer: u64,
+    addr: String,
+    queue: Receiver<Message>,
+    conn: Option<BufWriter<TcpStream>>,
+    logger: Logger,
+}
+
+impl PeerSender {
+    fn run(mut self) {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            let first = match self.queue.recv_timeout(Duration::from_secs(1)) {
+                Ok(msg) => msg,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            let mut batch = vec![first];
+            while batch.len() < MAX_BATCH_SIZE {
+                match self.queue.try_recv() {
+                    Ok(msg) => batch.push(msg),
+                    Err(_) => break,
+                }
+            }
+
+            match self.send(&batch) {
+                Ok(()) => backoff = MIN_BACKOFF,
+                Err(e) => {
+                    self.conn = None;
+
+                    // Don't let messages pile up while the peer is down, raft will send
+                    // them again.
+                    thread::sleep(backoff);
+                    let mut dropped = batch.len();
+                    while self.queue.try_recv().is_ok() {
+                        dropped += 1;
+                    }
+                    warn!(self.logger, "send raft messages fail, drop the queued ones";
+                        "to" => self.peer, "dropped" => dropped, "backoff" => ?backoff, "err" => ?e);
+                    backoff = next_backoff(backoff);
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, batch: &[Message]) -> io::Result<()> {
+        if self.conn.is_none() {
+            let stream = connect(&self.addr)?;
+            stream.set_nodelay(true)?;
+            info!(self.logger, "connected to raft peer"; "to" => self.peer, "addr" => &self.addr);
+            self.conn = Some(BufWriter::new(stream));
+        }
+
+        let conn = self.conn.as_mut().unwrap();
+        write_batch(conn, batch)?;
+        conn.flush()
+    }
+}
+
+fn next_backoff(backoff: Duration) -> Duration {
+    (backoff * 2).min(MAX_BACKOFF)
+}
+
+fn connect(addr: &str) -> io::Result<TcpStream> {
+    let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, format!("no address for {}", addr));
+    for a in addrs {
+        match TcpStream::connect_timeout(&a, CONNECT_TIMEOUT) {
+            Ok(s) => return Ok(s),
+            Err(e) => last_err = e,
+        }
+    }
+    Err(last_err)
+}
+
+pub fn write_batch<W: Write>(w: &mut W, batch: &[Message]) -> io::Result<()> {
+    let mut frame = Vec::new();
+    frame.extend_from_slice(&(batch.len() as u32).to_be_bytes());
+    for msg in batch {
+        let data = msg.write_to_bytes().map_err(io::Error::other)?;
+        frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&data);
+    }
+
+    w.write_all(&(frame.len() as u32).to_be_bytes())?;
+    w.write_all(&frame)
+}
+
+pub fn read_batch<R: Read>(r: &mut R) -> io::Result<Vec<Message>> {
+    let frame_len = read_u32(r)? as usize;
+    if frame_len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes is too large", frame_len),
+        ));
+    }
+    let mut frame = vec![0; frame_len];
+    r.read_exact(&mut frame)?;
+
+    let mut frame = frame.as_slice();
+    let count = read_u32(&mut frame)?;
+    let mut batch = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let len = read_u32(&mut frame)? as usize;
+        if frame.len() < len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let (data, rest) = frame.split_at(len);
+        let mut msg = Message::default();
+        msg.merge_from_bytes(data).map_err(io::Error::other)?;
+        batch.push(msg);
+        frame = rest;
+    }
+    Ok(batch)
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use slog::o;
+
+    use super::*;
+
+    fn message(commit: u64) -> Message {
+        let mut msg = Message::default();
+        msg.set_commit(commit);
+        msg
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = MIN_BACKOFF;
+        let mut sleeps = vec![];
+        for _ in 0..8 {
+            sleeps.push(backoff);
+            backoff = next_backoff(backoff);
+        }
+        assert_eq!(sleeps[1], MIN_BACKOFF * 2);
+        assert!(sleeps.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(*sleeps.last().unwrap(), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_reconnect_when_peer_comes_up() {
+        let logger = Logger::root(slog::Discard, o!());
+        // Take a free port, nothing listens on it until the peer comes up.
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+
+        let (tx, rx) = mpsc::channel();
+        let sender = PeerSender {
+            peer: 2,
+            addr: addr.clone(),
+            queue: rx,
+            conn: None,
+            logger: logger.clone(),
+        };
+        thread::spawn(move || sender.run());
+
+        // Sent while the peer is down, the message is dropped.
+        tx.send(message(1)).unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        let listener = TcpListener::bind(&addr).unwrap();
+        let (inbox_tx, inbox) = mpsc::channel();
+        thread::spawn(move || accept_loop(listener, inbox_tx, logger));
+
+        // Like raft, keep sending until the peer gets a message.
+        let received = (2..50).find_map(|commit| {
+            tx.send(message(commit)).unwrap();
+            inbox.recv_timeout(Duration::from_millis(200)).ok()
+        });
+        let received = received.expect("no message delivered after the peer came up");
+        assert!(received.commit > 1);
+
+        // The connection is kept for the next messages.
+        tx.send(message(100)).unwrap();
+        loop {
+            let msg = inbox.recv_timeout(Duration::from_secs(2)).unwrap();
+            if msg.commit == 100 {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_batch_round_trip() {
+        let batch = (1..=3).map(message).collect::<Vec<_>>();
+        let mut buf = vec![];
+        write_batch(&mut buf, &batch).unwrap();
+        assert_eq!(read_batch(&mut buf.as_slice()).unwrap(), batch);
+
+        // A truncated frame is an error, not a shorter batch.
+        buf.truncate(buf.len() - 1);
+        assert!(read_batch(&mut buf.as_slice()).is_err());
+    }
+}